@@ -4,6 +4,7 @@ use std::path::{Path, PathBuf};
 use std::env;
 use std::fs::{self, File};
 use std::io::{Cursor, Read, Write};
+use std::process::Command;
 use std::thread;
 use std::sync::mpsc::channel;
 
@@ -114,9 +115,39 @@ fn extract_tifu_dataset_archive(archive_buf: &[u8]) -> Vec<u8> {
     tifu_dataset
 }
 
+/// `git_commit` returns the commit hash of the repository HEAD, if available.
+fn git_commit() -> Option<String> {
+    Command::new("git")
+        .args(&["rev-parse", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map(|commit| commit.trim().to_string())
+}
+
+/// `git_head_ref` returns the path of the ref file HEAD points to, if HEAD is not detached.
+fn git_head_ref() -> Option<PathBuf> {
+    let head = fs::read_to_string(".git/HEAD").ok()?;
+    head.trim().strip_prefix("ref: ").map(|r| Path::new(".git").join(r))
+}
+
 fn main() {
     println!("build.rs starting...");
 
+    println!("checking for the git commit of the build...");
+    if let Some(commit) = git_commit() {
+        println!("cargo:rustc-env=MMN_GIT_COMMIT={}", commit);
+    }
+
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/packed-refs");
+    if let Some(head_ref) = git_head_ref() {
+        println!("cargo:rerun-if-changed={}", head_ref.display());
+    }
+    println!("cargo:rerun-if-env-changed=DATA_DIR");
+
     println!("checking for $DATA_DIR variables, defaulting to $DEFAULT_DATA_DIR if absent...");
     let data_dir = data_dir_from_env();
     let data_dir_path = data_dir_path(&data_dir);
@@ -124,6 +155,7 @@ fn main() {
   
     println!("data directory path set at '{}'", data_dir_path.display());
     println!("tifu dataset file path set at '{}'", tifu_dataset_file_path.display());
+    println!("cargo:rerun-if-changed={}", tifu_dataset_file_path.display());
 
     println!("checking if the data directory already exists...");
     if dir_exists(&data_dir_path) {
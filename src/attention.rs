@@ -0,0 +1,249 @@
+use serde::{Serialize, Deserialize};
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+use crate::result::Result;
+use crate::summarizer::Hypothesis;
+
+/// `AttentionLevel` are the attention weights of a memory level. `weights[i][j]` is the
+/// weight of the source token `j` when generating the summary token `i`.
+#[derive(Clone, Default, PartialEq, Debug, Serialize, Deserialize)]
+pub struct AttentionLevel {
+    pub dilation: usize,
+    pub weights: Vec<Vec<f32>>,
+}
+
+/// `AttentionMap` are the attention weights of every memory level for a summary of an entry.
+#[derive(Clone, Default, PartialEq, Debug, Serialize, Deserialize)]
+pub struct AttentionMap {
+    pub id: String,
+    pub source_tokenized: Vec<String>,
+    pub summary_tokenized: Vec<String>,
+    pub levels: Vec<AttentionLevel>,
+}
+
+impl AttentionMap {
+    /// `new` creates a new `AttentionMap`.
+    pub fn new() -> AttentionMap {
+        AttentionMap::default()
+    }
+
+    /// `from_hypothesis` creates a new `AttentionMap` from the attention weights of every
+    /// memory level recorded in the `hypothesis` decoded from `source`. The source tokens are
+    /// the ones the summarizer attended to, `source` may be longer.
+    pub fn from_hypothesis(id: &str, source: &[String], hypothesis: &Hypothesis) -> Result<AttentionMap> {
+        if hypothesis.attention.is_empty() && !hypothesis.tokens.is_empty() {
+            return Err(format!("missing attention weights: {}", id));
+        }
+
+        let columns = hypothesis
+            .attention
+            .iter()
+            .flat_map(|level| level.weights.first())
+            .map(Vec::len)
+            .next()
+            .unwrap_or(0);
+        if columns > source.len() {
+            return Err(format!("invalid attention columns: expected at most {}, found {}", source.len(), columns));
+        }
+
+        let attention = AttentionMap {
+            id: id.to_owned(),
+            source_tokenized: source[..columns].to_vec(),
+            summary_tokenized: hypothesis.tokens.clone(),
+            levels: hypothesis.attention.clone(),
+        };
+
+        attention.validate()?;
+        Ok(attention)
+    }
+
+    /// `file_path` returns the path of the file of the `AttentionMap` with `extension` in the
+    /// directory `dir`, named after the id with the characters other than alphanumerics, '-'
+    /// and '_' replaced by '_'.
+    pub fn file_path<P: AsRef<Path>>(&self, dir: P, extension: &str) -> PathBuf {
+        let name: String = self
+            .id
+            .chars()
+            .map(|c| if c.is_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+            .collect();
+
+        dir.as_ref().join(format!("{}.{}", name, extension))
+    }
+
+    /// `write` writes the `AttentionMap` as json and as HTML in the directory `dir`.
+    pub fn write<P: AsRef<Path>>(&self, dir: P) -> Result<()> {
+        fs::create_dir_all(&dir).map_err(|e| format!("{}", e))?;
+        fs::write(self.file_path(&dir, "json"), self.to_json_string()?).map_err(|e| format!("{}", e))?;
+        fs::write(self.file_path(&dir, "html"), self.to_html()?).map_err(|e| format!("{}", e))
+    }
+
+    /// `validate` returns an error if the weights do not match the source and summary tokens.
+    pub fn validate(&self) -> Result<()> {
+        for level in self.levels.iter() {
+            if level.weights.len() != self.summary_tokenized.len() {
+                return Err(format!("invalid attention rows at dilation {}: expected {}, found {}",
+                    level.dilation, self.summary_tokenized.len(), level.weights.len()));
+            }
+
+            for row in level.weights.iter() {
+                if row.len() != self.source_tokenized.len() {
+                    return Err(format!("invalid attention columns at dilation {}: expected {}, found {}",
+                        level.dilation, self.source_tokenized.len(), row.len()));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// `to_json_string` returns the `AttentionMap` as a json string.
+    pub fn to_json_string(&self) -> Result<String> {
+        serde_json::to_string_pretty(self).map_err(|e| format!("{}", e))
+    }
+
+    /// `to_html` renders the `AttentionMap` as an HTML page with a heatmap table per
+    /// memory level. The weights of every row are scaled to the row maximum.
+    pub fn to_html(&self) -> Result<String> {
+        self.validate()?;
+
+        let mut html = String::new();
+        let id = escape_html(&self.id);
+
+        write_html(&mut html, format_args!(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>attention {}</title>\n\
+             <style>table {{ border-collapse: collapse; }} td, th {{ padding: 2px 4px; font: 12px monospace; }} \
+             th.source {{ writing-mode: vertical-rl; }}</style>\n</head>\n<body>\n<h1>{}</h1>\n",
+            id, id))?;
+
+        for level in self.levels.iter() {
+            write_html(&mut html, format_args!("<h2>dilation {}</h2>\n<table>\n<tr><th></th>", level.dilation))?;
+
+            for token in self.source_tokenized.iter() {
+                write_html(&mut html, format_args!("<th class=\"source\">{}</th>", escape_html(token)))?;
+            }
+            html.push_str("</tr>\n");
+
+            for (token, row) in self.summary_tokenized.iter().zip(level.weights.iter()) {
+                let max = row.iter().cloned().fold(0.0f32, f32::max);
+                write_html(&mut html, format_args!("<tr><th>{}</th>", escape_html(token)))?;
+
+                for weight in row.iter() {
+                    let alpha = if max > 0.0 { weight / max } else { 0.0 };
+                    write_html(&mut html, format_args!(
+                        "<td style=\"background: rgba(214, 39, 40, {:.3})\" title=\"{:.4}\"></td>",
+                        alpha, weight))?;
+                }
+                html.push_str("</tr>\n");
+            }
+
+            html.push_str("</table>\n");
+        }
+
+        html.push_str("</body>\n</html>\n");
+        Ok(html)
+    }
+}
+
+/// `write_html` appends the formatted `args` to `html`.
+fn write_html(html: &mut String, args: std::fmt::Arguments) -> Result<()> {
+    html.write_fmt(args).map_err(|e| format!("{}", e))
+}
+
+/// `escape_html` escapes the HTML special characters of `s`.
+fn escape_html(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+#[cfg(test)]
+mod test {
+    use super::{AttentionLevel, AttentionMap};
+    use crate::summarizer::Hypothesis;
+    use std::env;
+    use std::fs;
+
+    fn attention_map() -> AttentionMap {
+        AttentionMap {
+            id: "a<b".to_string(),
+            source_tokenized: vec!["i".to_string(), "<3".to_string(), "rust".to_string()],
+            summary_tokenized: vec!["love".to_string(), "rust".to_string()],
+            levels: vec![
+                AttentionLevel {
+                    dilation: 1,
+                    weights: vec![vec![0.1, 0.8, 0.1], vec![0.0, 0.0, 1.0]],
+                },
+                AttentionLevel {
+                    dilation: 2,
+                    weights: vec![vec![0.5, 0.5, 0.0], vec![0.0, 0.0, 0.0]],
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn test_attention_map_validate() {
+        let mut attention = attention_map();
+        assert!(attention.validate().is_ok());
+
+        attention.levels[1].weights[0].pop();
+        assert!(attention.validate().is_err());
+
+        attention.levels[1].weights.pop();
+        assert!(attention.validate().is_err());
+    }
+
+    #[test]
+    fn test_attention_map_render() {
+        let attention = attention_map();
+
+        let json = attention.to_json_string().unwrap();
+        assert_eq!(serde_json::from_str::<AttentionMap>(&json).unwrap(), attention);
+
+        let html = attention.to_html().unwrap();
+        assert!(html.contains("<h1>a&lt;b</h1>"));
+        assert!(html.contains("<th class=\"source\">&lt;3</th>"));
+        assert!(html.contains("<h2>dilation 2</h2>"));
+        assert!(html.contains("rgba(214, 39, 40, 1.000)"));
+        assert_eq!(html.matches("<td ").count(), 12);
+    }
+
+    #[test]
+    fn test_attention_map_from_hypothesis() {
+        let source: Vec<String> = vec!["i".to_string(), "<3".to_string(), "rust".to_string(), "a".to_string()];
+        let mut hypothesis = Hypothesis {
+            tokens: vec!["love".to_string(), "rust".to_string()],
+            score: 0.0,
+            attention: attention_map().levels,
+        };
+
+        let attention = AttentionMap::from_hypothesis("a<b", &source, &hypothesis).unwrap();
+        assert_eq!(attention.levels, attention_map().levels);
+        assert_eq!(attention.source_tokenized, attention_map().source_tokenized);
+
+        let mut dir = env::temp_dir();
+        dir.push("mmn_test_attention_map_from_hypothesis");
+        attention.write(&dir).unwrap();
+        assert_eq!(attention.file_path(&dir, "html"), dir.join("a_b.html"));
+        let json = fs::read_to_string(attention.file_path(&dir, "json")).unwrap();
+        assert_eq!(serde_json::from_str::<AttentionMap>(&json).unwrap(), attention);
+        fs::remove_dir_all(&dir).unwrap();
+
+        hypothesis.attention[1].weights.pop();
+        assert!(AttentionMap::from_hypothesis("a", &source, &hypothesis).is_err());
+        hypothesis.attention.clear();
+        assert!(AttentionMap::from_hypothesis("a", &source, &hypothesis).is_err());
+    }
+}
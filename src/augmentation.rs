@@ -0,0 +1,354 @@
+use serde::{Serialize, Deserialize};
+use rand::Rng;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use crate::result::Result;
+use crate::hash::Hasher;
+use crate::seed::{derive_seed, Seeds};
+use crate::sentence::{sentence_spans, split_sentences};
+use crate::long_data_entry::LongDataEntry;
+use crate::long_data_entries::LongDataEntries;
+
+/// `Technique` is a data augmentation technique.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Technique {
+    /// `TokenDropout` drops every token with the technique probability.
+    TokenDropout,
+    /// `TokenSwap` swaps every token with the next one with the technique probability.
+    TokenSwap,
+    /// `SentenceShuffle` shuffles the sentences of the source with the technique probability.
+    SentenceShuffle,
+    /// `Synonym` replaces every token with one of its neighbours with the technique probability.
+    Synonym,
+}
+
+impl Technique {
+    /// `name` returns the name of the `Technique`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Technique::TokenDropout => "token_dropout",
+            Technique::TokenSwap => "token_swap",
+            Technique::SentenceShuffle => "sentence_shuffle",
+            Technique::Synonym => "synonym",
+        }
+    }
+}
+
+/// `TechniqueOptions` are the options of an augmentation `Technique`.
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub struct TechniqueOptions {
+    pub technique: Technique,
+    pub probability: f64,
+    pub seed: u64,
+    /// `copies` is the number of augmented entries generated from every entry.
+    pub copies: usize,
+}
+
+impl TechniqueOptions {
+    /// `new` creates a new `TechniqueOptions` of `technique`, generating a copy with `probability`.
+    pub fn new(technique: Technique, probability: f64, seed: u64) -> TechniqueOptions {
+        TechniqueOptions {
+            technique,
+            probability,
+            seed,
+            copies: 1,
+        }
+    }
+
+    /// `validate` returns an error if the `TechniqueOptions` are invalid.
+    pub fn validate(&self) -> Result<()> {
+        if !(0.0..=1.0).contains(&self.probability) {
+            return Err(format!("invalid {} probability: {}", self.technique.name(), self.probability));
+        }
+
+        Ok(())
+    }
+}
+
+/// `AugmentationOptions` are the augmentation options of a training run: the train split is
+/// extended with the entries generated by `techniques`, the other splits are left untouched.
+#[derive(Clone, Default, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AugmentationOptions {
+    pub techniques: Vec<TechniqueOptions>,
+    /// `neighbours` is the path of the `NeighbourTable` file of the synonym substitution.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub neighbours: Option<PathBuf>,
+}
+
+impl AugmentationOptions {
+    /// `validate` returns an error if the `AugmentationOptions` are invalid.
+    pub fn validate(&self) -> Result<()> {
+        if self.techniques.is_empty() {
+            return Err("invalid techniques: must not be empty".to_string());
+        }
+
+        for technique in self.techniques.iter() {
+            technique.validate()?;
+        }
+
+        let synonym = self.techniques.iter().any(|t| t.technique == Technique::Synonym);
+        if synonym && self.neighbours.is_none() {
+            return Err("invalid synonym technique: missing neighbours file".to_string());
+        }
+
+        Ok(())
+    }
+
+    /// `augmenter` returns the `Augmenter` of the options, reading the neighbour table file.
+    pub fn augmenter(&self) -> Result<Augmenter> {
+        let neighbours = match self.neighbours {
+            Some(ref path) => NeighbourTable::from_file(path)?,
+            None => NeighbourTable::new(),
+        };
+
+        Augmenter::new(self.techniques.clone(), neighbours)
+    }
+}
+
+/// `NeighbourTable` maps the tokens to their neighbours in an embedding space.
+#[derive(Clone, Default, PartialEq, Debug, Serialize, Deserialize)]
+pub struct NeighbourTable {
+    pub neighbours: HashMap<String, Vec<String>>,
+}
+
+impl NeighbourTable {
+    /// `new` creates a new empty `NeighbourTable`.
+    pub fn new() -> NeighbourTable {
+        NeighbourTable::default()
+    }
+
+    /// `from_file` reads a `NeighbourTable` from the file at `path`, where every line holds
+    /// a token followed by its neighbours, separated by whitespace.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<NeighbourTable> {
+        let file = File::open(path).map_err(|e| format!("{}", e))?;
+        let mut table = NeighbourTable::new();
+
+        for (idx, line) in BufReader::new(file).lines().enumerate() {
+            let line = line.map_err(|e| format!("{} at line: {}", e, idx + 1))?;
+            let mut tokens = line.split_whitespace().map(ToOwned::to_owned);
+
+            if let Some(token) = tokens.next() {
+                table.neighbours.insert(token, tokens.collect());
+            }
+        }
+
+        Ok(table)
+    }
+
+    /// `neighbours` returns the neighbours of `token`.
+    pub fn neighbours(&self, token: &str) -> &[String] {
+        self.neighbours.get(token).map(Vec::as_slice).unwrap_or(&[])
+    }
+}
+
+/// `sentences` splits `tokens` in sentences.
+pub fn sentences(tokens: &[String]) -> Vec<&[String]> {
+    sentence_spans(tokens, &[])
+        .into_iter()
+        .map(|span| &tokens[span])
+        .collect()
+}
+
+/// `Augmenter` generates extra training entries from the `LongDataEntry`s with a summary.
+pub struct Augmenter {
+    techniques: Vec<TechniqueOptions>,
+    neighbours: NeighbourTable,
+}
+
+impl Augmenter {
+    /// `new` creates a new `Augmenter` applying `techniques`, with `neighbours` for the
+    /// synonym substitution.
+    pub fn new(techniques: Vec<TechniqueOptions>, neighbours: NeighbourTable) -> Result<Augmenter> {
+        for technique in techniques.iter() {
+            technique.validate()?;
+        }
+
+        Ok(Augmenter { techniques, neighbours })
+    }
+
+    /// `rng` returns the rng of the copy `copy` of the entry `id` with `options`, which does
+    /// not depend on the order the entries are augmented in.
+    fn rng(options: &TechniqueOptions, id: &str, copy: usize) -> StdRng {
+        let mut hasher = Hasher::new();
+        hasher.update(id.as_bytes());
+
+        Seeds::rng(derive_seed(derive_seed(options.seed, hasher.finish_u64()), copy as u64))
+    }
+
+    /// `apply` returns `tokens` transformed by the technique of `options`.
+    fn apply(&self, options: &TechniqueOptions, tokens: &[String], rng: &mut StdRng) -> Vec<String> {
+        let p = options.probability;
+
+        match options.technique {
+            Technique::TokenDropout => {
+                let kept: Vec<String> = tokens.iter().filter(|_| !rng.gen_bool(p)).cloned().collect();
+                if kept.is_empty() { tokens.to_vec() } else { kept }
+            },
+            Technique::TokenSwap => {
+                let mut tokens = tokens.to_vec();
+                let mut idx = 0;
+
+                while idx + 1 < tokens.len() {
+                    if rng.gen_bool(p) {
+                        tokens.swap(idx, idx + 1);
+                        idx += 1;
+                    }
+                    idx += 1;
+                }
+
+                tokens
+            },
+            Technique::SentenceShuffle => {
+                let mut sentences = sentences(tokens);
+                if rng.gen_bool(p) {
+                    sentences.shuffle(rng);
+                }
+
+                sentences.concat()
+            },
+            Technique::Synonym => {
+                tokens
+                    .iter()
+                    .map(|token| {
+                        let neighbours = self.neighbours.neighbours(token);
+                        if neighbours.is_empty() || !rng.gen_bool(p) {
+                            return token.to_owned();
+                        }
+
+                        neighbours.choose(rng).cloned().unwrap_or_else(|| token.to_owned())
+                    })
+                    .collect()
+            },
+        }
+    }
+
+    /// `augment_entry` returns the augmented entries of `entry`, which have the id of `entry`
+    /// in `augmented_from`. Entries without a summary and unchanged copies are skipped.
+    pub fn augment_entry(&self, entry: &LongDataEntry) -> Vec<LongDataEntry> {
+        if entry.summary.is_none() || entry.augmented_from.is_some() {
+            return Vec::new();
+        }
+
+        let mut augmented = Vec::new();
+
+        for options in self.techniques.iter() {
+            for copy in 0..options.copies {
+                let mut rng = Augmenter::rng(options, &entry.id, copy);
+                let source_tokenized = self.apply(options, &entry.source_tokenized, &mut rng);
+
+                if source_tokenized == entry.source_tokenized {
+                    continue;
+                }
+
+                let source = source_tokenized.join(" ");
+                let source_sentences = if entry.source_sentences.is_empty() {
+                    Vec::new()
+                } else {
+                    split_sentences(&source, &source_tokenized)
+                };
+
+                augmented.push(LongDataEntry {
+                    id: format!("{}#{}-{}", entry.id, options.technique.name(), copy),
+                    summary: entry.summary.clone(),
+                    summary_tokenized: entry.summary_tokenized.clone(),
+                    source_sentences,
+                    source,
+                    source_tokenized,
+                    augmented_from: Some(entry.id.to_owned()),
+                    metadata: entry.metadata.clone(),
+                    provenance: entry.provenance.clone(),
+                });
+            }
+        }
+
+        augmented
+    }
+
+    /// `augment` returns the augmented entries of `entries`.
+    pub fn augment(&self, entries: &LongDataEntries) -> LongDataEntries {
+        entries.iter().flat_map(|entry| self.augment_entry(entry)).collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{sentences, Augmenter, NeighbourTable, Technique, TechniqueOptions};
+    use crate::long_data_entry::LongDataEntry;
+    use crate::long_data_entries::LongDataEntries;
+    use std::env;
+    use std::fs;
+
+    fn tokens(s: &str) -> Vec<String> {
+        s.split_whitespace().map(ToOwned::to_owned).collect()
+    }
+
+    fn entry(id: &str, summary: bool) -> LongDataEntry {
+        let mut entry = LongDataEntry::new();
+        entry.id = id.to_string();
+        entry.source_tokenized = tokens("today i broke the build . my boss was mad ! then i fixed it .");
+        entry.source = entry.source_tokenized.join(" ");
+        if summary {
+            entry.summary = Some("broke the build".to_string());
+            entry.summary_tokenized = Some(tokens("broke the build"));
+        }
+        entry
+    }
+
+    #[test]
+    fn test_augmentation_sentences() {
+        let source = tokens("a b . c ! d");
+        let sentences = sentences(&source);
+        assert_eq!(sentences, vec![&source[0..3], &source[3..5], &source[5..6]]);
+    }
+
+    #[test]
+    fn test_augmentation_techniques() {
+        let mut path = env::temp_dir();
+        path.push("mmn_test_augmentation_neighbours.txt");
+        fs::write(&path, "boss manager supervisor\nmad angry\n").unwrap();
+        let neighbours = NeighbourTable::from_file(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(neighbours.neighbours("mad"), &tokens("angry")[..]);
+
+        let techniques = vec![
+            TechniqueOptions::new(Technique::TokenDropout, 0.3, 1),
+            TechniqueOptions::new(Technique::TokenSwap, 0.3, 2),
+            TechniqueOptions::new(Technique::SentenceShuffle, 1.0, 3),
+            TechniqueOptions::new(Technique::Synonym, 1.0, 4),
+        ];
+        let augmenter = Augmenter::new(techniques, neighbours).unwrap();
+
+        let original = entry("a", true);
+        let augmented = augmenter.augment_entry(&original);
+        assert!(!augmented.is_empty());
+        assert_eq!(augmented, augmenter.augment_entry(&original));
+
+        for entry in augmented.iter() {
+            assert_eq!(entry.augmented_from, Some("a".to_string()));
+            assert_eq!(entry.summary, original.summary);
+            assert_ne!(entry.source_tokenized, original.source_tokenized);
+        }
+
+        let synonym = augmented.iter().find(|e| e.id == "a#synonym-0").unwrap();
+        assert_eq!(synonym.source, "today i broke the build . my manager was angry ! then i fixed it .");
+
+        let shuffled = augmented.iter().find(|e| e.id == "a#sentence_shuffle-0").unwrap();
+        let mut sorted = shuffled.source_tokenized.clone();
+        sorted.sort();
+        let mut expected = original.source_tokenized.clone();
+        expected.sort();
+        assert_eq!(sorted, expected);
+
+        let entries: LongDataEntries = vec![original, entry("b", false)].into();
+        assert_eq!(augmenter.augment(&entries).len(), augmented.len());
+        assert!(augmenter.augment_entry(&augmented[0]).is_empty());
+
+        assert!(Augmenter::new(vec![TechniqueOptions::new(Technique::TokenSwap, 1.5, 0)], NeighbourTable::new()).is_err());
+    }
+}
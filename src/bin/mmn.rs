@@ -1 +1,469 @@
-fn main() {}
+use mmn_lib::result::Result;
+use mmn_lib::path::tifu_training_data_path;
+use mmn_lib::parse_options::ParseOptions;
+use mmn_lib::config::{Config, PRESETS};
+use mmn_lib::validation::validate_dataset_file;
+use mmn_lib::model::Model;
+use mmn_lib::summarizer::{evaluate, DecodeOptions, SummarizeResponse, Summarizer};
+use mmn_lib::predict::{input_batch, predict_file, read_inputs, PredictInput, PredictOptions};
+use mmn_lib::attention::AttentionMap;
+use mmn_lib::export::{export, SIGNATURES_FILE};
+use mmn_lib::server::{summarize, Server, ServerOptions};
+use mmn_lib::data_entries::DataEntries;
+use mmn_lib::data_entry::DataEntry;
+use mmn_lib::dataset_reader::{read_dataset_file, ReadOptions};
+use mmn_lib::short_data_entry::ShortDataEntry;
+use mmn_lib::long_data_entry::LongDataEntry;
+use mmn_lib::raw_data_entry::RawDataEntry;
+use mmn_lib::multi_task::{MultiTaskEntries, TaskEntry};
+use mmn_lib::summarizer::{Batch, SummaryMode};
+use mmn_lib::train::{TrainOptions, Trainer, MODEL_DIR};
+use mmn_lib::split::{Split, SplitOptions, SplitSizes, Splits};
+use mmn_lib::manifest::RunManifest;
+use mmn_lib::seed::Seeds;
+use mmn_lib::rouge::RougeScore;
+use std::env;
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::process;
+
+/// `USAGE` is the usage of the `mmn` command.
+const USAGE: &str = "usage:
+    mmn dataset validate [--lenient] [--errors <file>] [<dataset file>]
+    mmn config dump [--json] [<preset or config file>]
+    mmn train --config <preset or config file> --output <dir> [--dataset <dataset file>] [--limit <n>]
+        [--seed <n>] [--checkpoint-every <steps>] [--resume <checkpoint dir>]
+    mmn evaluate --model <dir> --output <dir> [--dataset <dataset file>] [--split <train|validation|test|all>]
+        [--mode <short|long>] [--seed <n>] [--limit <n>] [--batch-size <n>] [--beam-size <n>]
+    mmn inspect --model <dir> --input <inputs file> --output <dir> [--beam-size <n>]
+    mmn predict --model <dir> --input <inputs file> --output <predictions file> [--batch-size <n>]
+        [--workers <n>] [--beam-size <n>] [--overwrite]
+    mmn export --model <dir> --output <dir>
+    mmn serve --model <dir> [--port <port>] [--host <host>] [--max-batch-size <n>] [--beam-size <n>]";
+
+/// `INSPECT_BATCH_SIZE` is the number of inputs decoded together by `mmn inspect`.
+const INSPECT_BATCH_SIZE: usize = 32;
+
+/// `EVALUATE_BATCH_SIZE` is the default number of entries decoded together by `mmn evaluate`.
+const EVALUATE_BATCH_SIZE: usize = 32;
+
+/// `EVALUATION_FILE` is the name of the scores file in an evaluation run directory.
+const EVALUATION_FILE: &str = "evaluation.json";
+
+/// `value` returns the value following the option `name` in `args`.
+fn value<'a, I: Iterator<Item = &'a &'a str>>(args: &mut I, name: &str) -> Result<&'a str> {
+    args.next().cloned().ok_or_else(|| format!("missing {} value", name))
+}
+
+/// `number` returns the number following the option `name` in `args`.
+fn number<'a, I: Iterator<Item = &'a &'a str>>(args: &mut I, name: &str) -> Result<usize> {
+    value(args, name)?.parse().map_err(|e| format!("invalid {} value: {}", name, e))
+}
+
+/// `dataset_validate` runs `mmn dataset validate`.
+fn dataset_validate(args: &[&str]) -> Result<()> {
+    let mut options = ParseOptions::strict();
+    let mut errors_path = None;
+    let mut path = tifu_training_data_path();
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        match *arg {
+            "--lenient" => options = ParseOptions::lenient(),
+            "--errors" => errors_path = Some(args.next().ok_or("missing --errors file")?),
+            p if !p.starts_with("--") => path = PathBuf::from(p),
+            _ => return Err(format!("invalid argument: {}\n{}", arg, USAGE)),
+        }
+    }
+
+    let report = validate_dataset_file(&path, &options)?;
+    print!("{}", report);
+
+    if let Some(errors_path) = errors_path {
+        let file = File::create(errors_path).map_err(|e| format!("{}", e))?;
+        report.write_errors(BufWriter::new(file))?;
+    }
+
+    if report.is_valid() {
+        Ok(())
+    } else {
+        Err(format!("invalid dataset file: {}", path.display()))
+    }
+}
+
+/// `config_dump` runs `mmn config dump`.
+fn config_dump(args: &[&str]) -> Result<()> {
+    let mut json = false;
+    let mut config = Config::default();
+
+    for arg in args {
+        match *arg {
+            "--json" => json = true,
+            p if PRESETS.contains(&p) => config = Config::preset(p)?,
+            p if !p.starts_with("--") => config = Config::from_file(p)?,
+            _ => return Err(format!("invalid argument: {}\n{}", arg, USAGE)),
+        }
+    }
+
+    if json {
+        println!("{}", config.to_json_string()?);
+    } else {
+        print!("{}", config.to_toml_string()?);
+    }
+
+    Ok(())
+}
+
+/// `load_config` returns the preset or the config file named `name`.
+fn load_config(name: &str) -> Result<Config> {
+    if PRESETS.contains(&name) {
+        Config::preset(name)
+    } else {
+        Config::from_file(name)
+    }
+}
+
+/// `train` runs `mmn train`, training on the entries with the summaries of the config mode, or
+/// on both summary kinds with the multi-task training.
+fn train(args: &[&str]) -> Result<()> {
+    let (mut config, mut output, mut limit, mut resume, mut seed) = (None, None, None, None, None);
+    let mut options = TrainOptions::default();
+    let mut dataset = tifu_training_data_path();
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        match *arg {
+            "--config" => config = Some(load_config(value(&mut args, arg)?)?),
+            "--output" => output = Some(PathBuf::from(value(&mut args, arg)?)),
+            "--dataset" => dataset = PathBuf::from(value(&mut args, arg)?),
+            "--limit" => limit = Some(number(&mut args, arg)?),
+            "--seed" => seed = Some(number(&mut args, arg)? as u64),
+            "--checkpoint-every" => options.checkpoint_every = number(&mut args, arg)? as u64,
+            "--resume" => resume = Some(PathBuf::from(value(&mut args, arg)?)),
+            _ => return Err(format!("invalid argument: {}\n{}", arg, USAGE)),
+        }
+    }
+
+    let mut config = config.ok_or("missing --config")?;
+    let output = output.ok_or("missing --output")?;
+
+    if let Some(seed) = seed {
+        config.train.seed = seed;
+    }
+
+    if config.train.multi_task.is_some() {
+        let raw: DataEntries<RawDataEntry> = read_entries(&dataset, limit)?;
+        let entries = MultiTaskEntries::from_raw(&raw).into_tasks();
+        return train_entries::<TaskEntry>(config, options, entries, &dataset, resume.as_deref(), &output);
+    }
+
+    match config.train.mode {
+        SummaryMode::Short => train_entries::<ShortDataEntry>(config, options, read_entries(&dataset, limit)?, &dataset, resume.as_deref(), &output),
+        SummaryMode::Long => train_entries::<LongDataEntry>(config, options, read_entries(&dataset, limit)?, &dataset, resume.as_deref(), &output),
+    }
+}
+
+/// `read_entries` returns the first `limit` entries of type `T` with a summary of the dataset
+/// file at `dataset`.
+fn read_entries<T: DataEntry + Send>(dataset: &Path, limit: Option<usize>) -> Result<DataEntries<T>> {
+    let read_options = ReadOptions { skip_without_summary: true, ..ReadOptions::default() };
+    let entries: DataEntries<T> = read_dataset_file(dataset, &read_options, |_| {})?;
+
+    Ok(match limit {
+        Some(limit) => entries.into_iter().take(limit).collect(),
+        None => entries,
+    })
+}
+
+/// `train_entries` trains a model of `config` with `options` on the `entries` of the dataset
+/// file at `dataset`, in the run directory `output`, resuming from the checkpoint directory
+/// `resume` if given. The run manifest is written first, and a resumption is recorded in the
+/// manifest of the run instead of replacing it.
+fn train_entries<T>(config: Config, options: TrainOptions, entries: DataEntries<T>, dataset: &Path, resume: Option<&Path>, output: &Path) -> Result<()>
+    where T: DataEntry + Send + Sync + 'static,
+          for<'a> Batch: From<&'a [T]>
+{
+    let mut trainer = Trainer::new(config, entries, options)?;
+
+    let mut manifest = trainer.manifest()?;
+    manifest.set_dataset_file(dataset)?;
+
+    match resume {
+        Some(resume) => {
+            trainer.resume(resume)?;
+            manifest.write_resume(output, resume, trainer.global_step)?;
+            println!("resumed from {} at step {}", resume.display(), trainer.global_step);
+        },
+        None => manifest.write(output)?,
+    }
+
+    let summary = trainer.train(output)?;
+
+    println!("{} steps, {} epochs, loss: {:.4}, model written to {}",
+        summary.global_step, summary.epoch, summary.loss, output.join(MODEL_DIR).display());
+
+    if let Some(best) = summary.best_checkpoint {
+        let reason = if summary.stopped { "stopped early" } else { "completed" };
+        println!("{}, best checkpoint: {}", reason, best.display());
+    }
+    Ok(())
+}
+
+/// `evaluate_model` runs `mmn evaluate`, writing the ROUGE-L of a model on a split of the
+/// dataset entries and the run manifest in the run directory. The split is assigned with the
+/// seed of the model config, as in training, and the entries have the summaries of the config
+/// mode, unless `--seed` or `--mode` is given.
+fn evaluate_model(args: &[&str]) -> Result<()> {
+    let (mut model_dir, mut output, mut limit, mut mode, mut seed) = (None, None, None, None, None);
+    let mut split = Some(Split::Test);
+    let mut dataset = tifu_training_data_path();
+    let mut batch_size = EVALUATE_BATCH_SIZE;
+    let mut decode = DecodeOptions::default();
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        match *arg {
+            "--model" => model_dir = Some(value(&mut args, arg)?),
+            "--output" => output = Some(PathBuf::from(value(&mut args, arg)?)),
+            "--dataset" => dataset = PathBuf::from(value(&mut args, arg)?),
+            "--split" => split = match value(&mut args, arg)? {
+                "train" => Some(Split::Train),
+                "validation" => Some(Split::Validation),
+                "test" => Some(Split::Test),
+                "all" => None,
+                s => return Err(format!("invalid --split value: {}", s)),
+            },
+            "--mode" => mode = match value(&mut args, arg)? {
+                "short" => Some(SummaryMode::Short),
+                "long" => Some(SummaryMode::Long),
+                s => return Err(format!("invalid --mode value: {}", s)),
+            },
+            "--seed" => seed = Some(number(&mut args, arg)? as u64),
+            "--limit" => limit = Some(number(&mut args, arg)?),
+            "--batch-size" => batch_size = number(&mut args, arg)?,
+            "--beam-size" => decode.beam_size = number(&mut args, arg)?,
+            _ => return Err(format!("invalid argument: {}\n{}", arg, USAGE)),
+        }
+    }
+
+    if batch_size == 0 {
+        return Err("invalid --batch-size value: 0".to_string());
+    }
+
+    let model = Model::load(Path::new(model_dir.ok_or("missing --model")?))?;
+    let output = output.ok_or("missing --output")?;
+    let mut config = model.seq2seq().map(|model| model.config.clone()).unwrap_or_default();
+    config.train.seed = seed.unwrap_or(config.train.seed);
+    config.train.mode = mode.unwrap_or(config.train.mode);
+
+    let (score, count, split_sizes) = match config.train.mode {
+        SummaryMode::Short => evaluate_entries::<ShortDataEntry>(&model, &dataset, split, config.train.seed, limit, batch_size, &decode)?,
+        SummaryMode::Long => evaluate_entries::<LongDataEntry>(&model, &dataset, split, config.train.seed, limit, batch_size, &decode)?,
+    };
+
+    let mut manifest = RunManifest::new("evaluate", config.train.seed);
+    manifest.set_config(&config)?;
+    manifest.set_dataset_file(&dataset)?;
+    manifest.vocabulary_hash = model.seq2seq().map(|model| model.vocabulary.hash());
+    manifest.split_sizes = split_sizes;
+    manifest.write(&output)?;
+
+    let contents = serde_json::to_vec_pretty(&score).map_err(|e| format!("{}", e))?;
+    fs::write(output.join(EVALUATION_FILE), contents).map_err(|e| format!("{}", e))?;
+
+    println!("{} on {} entries, rouge-l precision: {:.4}, recall: {:.4}, f1: {:.4}, written to {}",
+        model.name(), count, score.precision, score.recall, score.f1, output.display());
+    Ok(())
+}
+
+/// `evaluate_entries` returns the ROUGE-L of `model` on the first `limit` entries of type `T`
+/// with a summary of the `split` of the dataset file at `dataset`, all the entries without a
+/// split, the number of entries evaluated and the split sizes.
+fn evaluate_entries<T>(model: &Model,
+                       dataset: &Path,
+                       split: Option<Split>,
+                       seed: u64,
+                       limit: Option<usize>,
+                       batch_size: usize,
+                       decode: &DecodeOptions) -> Result<(RougeScore, usize, SplitSizes)>
+    where T: DataEntry + Send + 'static,
+          for<'a> Batch: From<&'a [T]>
+{
+    let read_options = ReadOptions { skip_without_summary: true, ..ReadOptions::default() };
+    let entries: DataEntries<T> = read_dataset_file(dataset, &read_options, |_| {})?;
+
+    let splits = Splits::new(entries, &SplitOptions::default(), Seeds::new(seed).split)?;
+    let split_sizes = splits.sizes();
+
+    let mut entries = match split {
+        Some(Split::Train) => splits.train,
+        Some(Split::Validation) => splits.validation,
+        Some(Split::Test) => splits.test,
+        None => {
+            let mut entries = splits.train;
+            entries.extend(splits.validation);
+            entries.extend(splits.test);
+            entries
+        },
+    };
+
+    if let Some(limit) = limit {
+        entries = entries.into_iter().take(limit).collect();
+    }
+
+    let batches: Vec<Batch> = entries.chunks(batch_size).map(Batch::from).collect();
+    Ok((evaluate(model, batches.iter(), decode)?, entries.len(), split_sizes))
+}
+
+/// `inspect` runs `mmn inspect`, writing the attention map of the summary of every input.
+fn inspect(args: &[&str]) -> Result<()> {
+    let (mut model_dir, mut input, mut output) = (None, None, None);
+    let mut options = DecodeOptions { attention: true, ..DecodeOptions::default() };
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        match *arg {
+            "--model" => model_dir = Some(value(&mut args, arg)?),
+            "--input" => input = Some(value(&mut args, arg)?),
+            "--output" => output = Some(value(&mut args, arg)?),
+            "--beam-size" => options.beam_size = number(&mut args, arg)?,
+            _ => return Err(format!("invalid argument: {}\n{}", arg, USAGE)),
+        }
+    }
+
+    let model = Model::load(Path::new(model_dir.ok_or("missing --model")?))?;
+    let mut inputs = read_inputs(input.ok_or("missing --input")?)?;
+    let output = output.ok_or("missing --output")?;
+    let mut count = 0;
+
+    loop {
+        let chunk = inputs.by_ref().take(INSPECT_BATCH_SIZE).collect::<Result<Vec<PredictInput>>>()?;
+        if chunk.is_empty() {
+            break;
+        }
+
+        count += chunk.len();
+        let batch = input_batch(&chunk);
+        let hypotheses = model.decode(&batch, &options)?;
+
+        for ((id, source), hypothesis) in batch.ids.iter().zip(batch.sources.iter()).zip(hypotheses.iter()) {
+            AttentionMap::from_hypothesis(id, source, hypothesis)?.write(output)?;
+        }
+    }
+
+    println!("{} attention maps written to {}", count, output);
+    Ok(())
+}
+
+/// `predict` runs `mmn predict`, writing the summary of every input in order, resuming a
+/// previous run unless `--overwrite` is given.
+fn predict(args: &[&str]) -> Result<()> {
+    let (mut model_dir, mut input, mut output) = (None, None, None);
+    let mut options = PredictOptions::default();
+    let mut decode = DecodeOptions::default();
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        match *arg {
+            "--model" => model_dir = Some(value(&mut args, arg)?),
+            "--input" => input = Some(value(&mut args, arg)?),
+            "--output" => output = Some(value(&mut args, arg)?),
+            "--batch-size" => options.batch_size = number(&mut args, arg)?,
+            "--workers" => options.workers = number(&mut args, arg)?,
+            "--beam-size" => decode.beam_size = number(&mut args, arg)?,
+            "--overwrite" => options.resume = false,
+            _ => return Err(format!("invalid argument: {}\n{}", arg, USAGE)),
+        }
+    }
+
+    let model = Model::load(Path::new(model_dir.ok_or("missing --model")?))?;
+    let input = input.ok_or("missing --input")?;
+    let output = output.ok_or("missing --output")?;
+
+    let summary = predict_file(input, output, &options, |inputs: &[PredictInput]| {
+        let hypotheses = model.decode(&input_batch(inputs), &decode)?;
+
+        Ok(hypotheses
+            .into_iter()
+            .map(|hypothesis| SummarizeResponse {
+                summary: hypothesis.tokens.join(" "),
+                score: hypothesis.score,
+            })
+            .collect())
+    })?;
+
+    println!("{} inputs, {} already predicted, {} predicted, written to {}",
+        summary.inputs, summary.skipped, summary.predicted, output);
+    Ok(())
+}
+
+/// `export_model` runs `mmn export`, writing the model files needed to serve it and its
+/// serving signatures in a new directory.
+fn export_model(args: &[&str]) -> Result<()> {
+    let (mut model_dir, mut output) = (None, None);
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        match *arg {
+            "--model" => model_dir = Some(value(&mut args, arg)?),
+            "--output" => output = Some(value(&mut args, arg)?),
+            _ => return Err(format!("invalid argument: {}\n{}", arg, USAGE)),
+        }
+    }
+
+    let model = Model::load(Path::new(model_dir.ok_or("missing --model")?))?;
+    let output = Path::new(output.ok_or("missing --output")?);
+    export(&model, output)?;
+
+    println!("{} exported to {}, signatures in {}", model.name(), output.display(), SIGNATURES_FILE);
+    Ok(())
+}
+
+/// `serve` runs `mmn serve`, answering the summarization requests with a model until killed.
+fn serve(args: &[&str]) -> Result<()> {
+    let (mut model_dir, mut host, mut port) = (None, "127.0.0.1", 8080);
+    let mut options = ServerOptions::default();
+    let mut decode = DecodeOptions::default();
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        match *arg {
+            "--model" => model_dir = Some(value(&mut args, arg)?),
+            "--host" => host = value(&mut args, arg)?,
+            "--port" => port = value(&mut args, arg)?.parse().map_err(|e| format!("invalid --port value: {}", e))?,
+            "--max-batch-size" => options.max_batch_size = number(&mut args, arg)?,
+            "--beam-size" => decode.beam_size = number(&mut args, arg)?,
+            _ => return Err(format!("invalid argument: {}\n{}", arg, USAGE)),
+        }
+    }
+
+    let model = Model::load(Path::new(model_dir.ok_or("missing --model")?))?;
+    let name = model.name().to_owned();
+    let server = Server::bind((host, port), options, move |requests| summarize(&model, requests, &decode))?;
+
+    println!("serving {} on http://{}", name, server.local_addr()?);
+    server.run(|e| eprintln!("{}", e))
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+
+    let res = match args.as_slice() {
+        ["dataset", "validate", rest @ ..] => dataset_validate(rest),
+        ["config", "dump", rest @ ..] => config_dump(rest),
+        ["train", rest @ ..] => train(rest),
+        ["evaluate", rest @ ..] => evaluate_model(rest),
+        ["inspect", rest @ ..] => inspect(rest),
+        ["predict", rest @ ..] => predict(rest),
+        ["export", rest @ ..] => export_model(rest),
+        ["serve", rest @ ..] => serve(rest),
+        _ => Err(USAGE.to_string()),
+    };
+
+    if let Err(e) = res {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+}
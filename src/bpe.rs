@@ -0,0 +1,286 @@
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use crate::result::Result;
+
+/// `END_OF_WORD` is the suffix of the last subword of a word.
+pub const END_OF_WORD: &str = "</w>";
+
+/// `BPE_FILE` is the name of the merge table file in a model directory.
+pub const BPE_FILE: &str = "bpe.json";
+
+/// `Bpe` is a byte-pair-encoding subword tokenizer. It splits the words in characters and
+/// applies the learned merges in order, marking the end of the words so that the subwords
+/// decode back to the original words.
+#[derive(Clone, Default, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(from = "BpeMerges", into = "BpeMerges")]
+pub struct Bpe {
+    merges: Vec<(String, String)>,
+    /// `ranks` are the ranks of the merges by left and right symbol, so that the pairs of a
+    /// word are looked up without allocating.
+    ranks: HashMap<String, HashMap<String, usize>>,
+}
+
+/// `BpeMerges` is the serialized form of a `Bpe`.
+#[derive(Clone, Default, PartialEq, Debug, Serialize, Deserialize)]
+struct BpeMerges {
+    merges: Vec<(String, String)>,
+}
+
+impl From<BpeMerges> for Bpe {
+    fn from(v: BpeMerges) -> Bpe {
+        Bpe::from_merges(v.merges)
+    }
+}
+
+impl From<Bpe> for BpeMerges {
+    fn from(v: Bpe) -> BpeMerges {
+        BpeMerges { merges: v.merges }
+    }
+}
+
+/// `symbols` splits `word` in characters, the last one ending with `END_OF_WORD`. The empty
+/// word is the single `END_OF_WORD` symbol, so that it decodes back to the empty word.
+fn symbols(word: &str) -> Vec<String> {
+    let mut symbols: Vec<String> = word.chars().map(|c| c.to_string()).collect();
+
+    match symbols.last_mut() {
+        Some(last) => last.push_str(END_OF_WORD),
+        None => symbols.push(END_OF_WORD.to_owned()),
+    }
+
+    symbols
+}
+
+/// `word_lengths` returns the number of subwords of every word of `subwords`, grouped as
+/// `Bpe::decode` does.
+pub fn word_lengths(subwords: &[String]) -> Vec<usize> {
+    let mut lengths = Vec::new();
+    let mut len = 0;
+
+    for subword in subwords.iter() {
+        len += 1;
+        if subword.ends_with(END_OF_WORD) {
+            lengths.push(len);
+            len = 0;
+        }
+    }
+
+    if len > 0 {
+        lengths.push(len);
+    }
+
+    lengths
+}
+
+/// `merge_pair` merges the adjacent occurrences of `pair` in `symbols`.
+fn merge_pair(symbols: &mut Vec<String>, pair: &(String, String)) {
+    let mut idx = 0;
+
+    while idx + 1 < symbols.len() {
+        if symbols[idx] == pair.0 && symbols[idx + 1] == pair.1 {
+            let right = symbols.remove(idx + 1);
+            symbols[idx].push_str(&right);
+        }
+        idx += 1;
+    }
+}
+
+impl Bpe {
+    /// `new` creates a new `Bpe` without merges, which splits the words in characters.
+    pub fn new() -> Bpe {
+        Bpe::default()
+    }
+
+    /// `from_merges` creates a new `Bpe` applying `merges` in order.
+    pub fn from_merges(merges: Vec<(String, String)>) -> Bpe {
+        let mut ranks: HashMap<String, HashMap<String, usize>> = HashMap::new();
+
+        for (rank, (left, right)) in merges.iter().enumerate() {
+            ranks
+                .entry(left.to_owned())
+                .or_default()
+                .entry(right.to_owned())
+                .or_insert(rank);
+        }
+
+        Bpe { merges, ranks }
+    }
+
+    /// `rank` returns the rank of the merge of `left` and `right`, if any.
+    fn rank(&self, left: &str, right: &str) -> Option<usize> {
+        self.ranks.get(left).and_then(|ranks| ranks.get(right)).cloned()
+    }
+
+    /// `train` learns up to `num_merges` merges from the words of `texts`, merging at each
+    /// step the most frequent adjacent pair, if it appears at least `min_frequency` times.
+    pub fn train<'a, I>(texts: I, num_merges: usize, min_frequency: usize) -> Bpe
+        where I: IntoIterator<Item = &'a [String]>
+    {
+        let mut counts: HashMap<&str, usize> = HashMap::new();
+
+        for text in texts {
+            for word in text.iter() {
+                *counts.entry(word.as_str()).or_insert(0) += 1;
+            }
+        }
+
+        let mut words: Vec<(Vec<String>, usize)> = counts
+            .into_iter()
+            .map(|(word, count)| (symbols(word), count))
+            .collect();
+        words.sort();
+
+        let mut merges = Vec::new();
+
+        for _ in 0..num_merges {
+            let mut pairs: HashMap<(&str, &str), usize> = HashMap::new();
+
+            for (symbols, count) in words.iter() {
+                for pair in symbols.windows(2) {
+                    *pairs.entry((&pair[0], &pair[1])).or_insert(0) += count;
+                }
+            }
+
+            let best = pairs
+                .into_iter()
+                .filter(|(_, count)| *count >= min_frequency.max(1))
+                .max_by(|a, b| a.1.cmp(&b.1).then(b.0.cmp(&a.0)));
+
+            let pair = match best {
+                Some(((left, right), _)) => (left.to_owned(), right.to_owned()),
+                None => break,
+            };
+
+            for (symbols, _) in words.iter_mut() {
+                merge_pair(symbols, &pair);
+            }
+
+            merges.push(pair);
+        }
+
+        Bpe::from_merges(merges)
+    }
+
+    /// `len` returns the number of merges of the `Bpe`.
+    pub fn len(&self) -> usize {
+        self.merges.len()
+    }
+
+    /// `is_empty` returns if the `Bpe` has no merges.
+    pub fn is_empty(&self) -> bool {
+        self.merges.is_empty()
+    }
+
+    /// `encode_word` returns the subwords of `word`.
+    pub fn encode_word(&self, word: &str) -> Vec<String> {
+        let mut symbols = symbols(word);
+
+        loop {
+            let best = symbols
+                .windows(2)
+                .filter_map(|pair| self.rank(&pair[0], &pair[1]))
+                .min();
+
+            match best {
+                Some(rank) => merge_pair(&mut symbols, &self.merges[rank]),
+                None => break,
+            }
+        }
+
+        symbols
+    }
+
+    /// `encode` returns the subwords of the words `tokens`.
+    pub fn encode(&self, tokens: &[String]) -> Vec<String> {
+        tokens.iter().flat_map(|word| self.encode_word(word)).collect()
+    }
+
+    /// `decode` returns the words of `subwords`. A trailing incomplete word is kept as is.
+    pub fn decode(&self, subwords: &[String]) -> Vec<String> {
+        let mut words = Vec::new();
+        let mut word = String::new();
+
+        for subword in subwords.iter() {
+            match subword.strip_suffix(END_OF_WORD) {
+                Some(subword) => {
+                    word.push_str(subword);
+                    words.push(word.split_off(0));
+                },
+                None => word.push_str(subword),
+            }
+        }
+
+        if !word.is_empty() {
+            words.push(word);
+        }
+
+        words
+    }
+
+    /// `save` saves the merge table of the `Bpe` in the directory `dir`.
+    pub fn save<P: AsRef<Path>>(&self, dir: P) -> Result<()> {
+        fs::create_dir_all(&dir).map_err(|e| format!("{}", e))?;
+        let contents = serde_json::to_vec(self).map_err(|e| format!("{}", e))?;
+        fs::write(dir.as_ref().join(BPE_FILE), contents).map_err(|e| format!("{}", e))
+    }
+
+    /// `load` loads a `Bpe` from the directory `dir`.
+    pub fn load<P: AsRef<Path>>(dir: P) -> Result<Bpe> {
+        let contents = fs::read(dir.as_ref().join(BPE_FILE)).map_err(|e| format!("{}", e))?;
+        serde_json::from_slice(&contents).map_err(|e| format!("{}", e))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{word_lengths, Bpe};
+    use std::env;
+    use std::fs;
+
+    fn tokens(s: &str) -> Vec<String> {
+        s.split_whitespace().map(ToOwned::to_owned).collect()
+    }
+
+    #[test]
+    fn test_bpe_train() {
+        let texts = [tokens("low low low lower lowest newest newest widest")];
+        let bpe = Bpe::train(texts.iter().map(Vec::as_slice), 10, 2);
+
+        assert_eq!(bpe.len(), 7);
+        assert_eq!(bpe.encode_word("low"), tokens("low</w>"));
+        assert_eq!(bpe.encode_word("widest"), tokens("w i d est</w>"));
+        assert_eq!(bpe.encode_word("lowest"), tokens("lo west</w>"));
+        assert_eq!(bpe.encode_word("slow"), tokens("s low</w>"));
+
+        let words = tokens("the lowest newer 🦀 lowly");
+        let subwords = bpe.encode(&words);
+        assert!(subwords.len() > words.len());
+        assert_eq!(bpe.decode(&subwords), words);
+        assert_eq!(bpe.decode(&subwords[..subwords.len() - 1]), tokens("the lowest newer 🦀 lowl"));
+        assert_eq!(word_lengths(&subwords).len(), words.len());
+
+        let words = vec!["low".to_string(), String::new(), "lower".to_string()];
+        let subwords = bpe.encode(&words);
+        assert_eq!(subwords[1], "</w>");
+        assert_eq!(bpe.decode(&subwords), words);
+        assert_eq!(word_lengths(&subwords[..subwords.len() - 1]), vec![1, 1, subwords.len() - 3]);
+    }
+
+    #[test]
+    fn test_bpe_save_load() {
+        let texts = [tokens("aaa aab abb")];
+        let bpe = Bpe::train(texts.iter().map(Vec::as_slice), 3, 1);
+
+        let mut dir = env::temp_dir();
+        dir.push("mmn_test_bpe_save_load");
+        bpe.save(&dir).unwrap();
+
+        let loaded = Bpe::load(&dir).unwrap();
+        assert_eq!(loaded, bpe);
+        assert_eq!(loaded.encode_word("aab"), bpe.encode_word("aab"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
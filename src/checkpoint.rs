@@ -0,0 +1,152 @@
+use serde::{Serialize, Deserialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use crate::result::Result;
+use crate::lr_schedule::LearningRateSchedule;
+use crate::early_stopping::EarlyStopping;
+
+/// `STATE_FILE` is the name of the training state file in a checkpoint directory.
+pub const STATE_FILE: &str = "state.json";
+
+/// `CHECKPOINTS_DIR` is the name of the checkpoints directory in a run directory.
+pub const CHECKPOINTS_DIR: &str = "checkpoints";
+
+/// `LAST_CHECKPOINT_DIR` is the name of the directory of the latest periodic checkpoint in the
+/// checkpoints directory.
+pub const LAST_CHECKPOINT_DIR: &str = "last";
+
+/// `checkpoint_path` returns the path of the checkpoint directory of `step` in the run
+/// directory `dir`.
+pub fn checkpoint_path<P: AsRef<Path>>(dir: P, step: u64) -> PathBuf {
+    dir.as_ref().join(CHECKPOINTS_DIR).join(format!("step-{:09}", step))
+}
+
+/// `last_checkpoint_path` returns the path of the latest periodic checkpoint directory in the
+/// run directory `dir`.
+pub fn last_checkpoint_path<P: AsRef<Path>>(dir: P) -> PathBuf {
+    dir.as_ref().join(CHECKPOINTS_DIR).join(LAST_CHECKPOINT_DIR)
+}
+
+/// `TrainState` is the training state saved in a checkpoint directory alongside the model variables.
+/// It holds everything needed to resume a run where it stopped.
+#[derive(Clone, Default, PartialEq, Debug, Serialize, Deserialize)]
+pub struct TrainState {
+    /// `global_step` is the number of optimizer steps run.
+    pub global_step: u64,
+    /// `epoch` is the current epoch.
+    pub epoch: u64,
+    /// `epoch_position` is the number of entries of the current epoch already consumed.
+    pub epoch_position: usize,
+    /// `seed` is the seed of the run.
+    pub seed: u64,
+    /// `vocabulary_hash` is the hash of the vocabulary the model was trained with.
+    pub vocabulary_hash: String,
+    /// `config_hash` is the hash of the configuration the model was trained with.
+    pub config_hash: String,
+    /// `learning_rate_schedule` is the learning rate schedule of the run.
+    #[serde(default)]
+    pub learning_rate_schedule: LearningRateSchedule,
+    /// `early_stopping` are the validations recorded so far, with early stopping.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub early_stopping: Option<EarlyStopping>,
+}
+
+impl TrainState {
+    /// `new` creates a new `TrainState`.
+    pub fn new() -> TrainState {
+        TrainState::default()
+    }
+
+    /// `state_path` returns the path of the training state file in the checkpoint directory `dir`.
+    pub fn state_path<P: AsRef<Path>>(dir: P) -> PathBuf {
+        let mut path = PathBuf::new();
+        path.push(dir);
+        path.push(STATE_FILE);
+        path
+    }
+
+    /// `save` writes the `TrainState` in the checkpoint directory `dir`, creating it if missing.
+    /// The file is written atomically, so a killed run never leaves a truncated state.
+    pub fn save<P: AsRef<Path>>(&self, dir: P) -> Result<()> {
+        fs::create_dir_all(&dir).map_err(|e| format!("{}", e))?;
+
+        let path = TrainState::state_path(&dir);
+        let tmp_path = path.with_extension("json.tmp");
+        let contents = serde_json::to_vec_pretty(self).map_err(|e| format!("{}", e))?;
+
+        fs::write(&tmp_path, contents).map_err(|e| format!("{}", e))?;
+        fs::rename(&tmp_path, &path).map_err(|e| format!("{}", e))
+    }
+
+    /// `load` reads the `TrainState` from the checkpoint directory `dir`.
+    pub fn load<P: AsRef<Path>>(dir: P) -> Result<TrainState> {
+        let contents = fs::read(TrainState::state_path(dir)).map_err(|e| format!("{}", e))?;
+        serde_json::from_slice(&contents).map_err(|e| format!("{}", e))
+    }
+
+    /// `check_resume` returns an error if the run cannot be resumed with the vocabulary
+    /// and configuration of hashes `vocabulary_hash` and `config_hash`.
+    pub fn check_resume(&self, vocabulary_hash: &str, config_hash: &str) -> Result<()> {
+        if self.vocabulary_hash != vocabulary_hash {
+            return Err(format!("vocabulary changed: checkpoint hash {}, current hash {}", self.vocabulary_hash, vocabulary_hash));
+        }
+
+        if self.config_hash != config_hash {
+            return Err(format!("config changed: checkpoint hash {}, current hash {}", self.config_hash, config_hash));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::TrainState;
+    use crate::early_stopping::{EarlyStopping, EarlyStoppingOptions, Validation};
+    use crate::lr_schedule::LearningRateSchedule;
+    use std::path::PathBuf;
+    use std::env;
+    use std::fs;
+
+    #[test]
+    fn test_train_state_save_load() {
+        let mut dir = env::temp_dir();
+        dir.push("mmn_test_train_state_save_load");
+
+        let mut state = TrainState::new();
+        state.global_step = 1200;
+        state.epoch = 3;
+        state.epoch_position = 512;
+        state.seed = 42;
+        state.vocabulary_hash = "aaaa".to_string();
+        state.config_hash = "bbbb".to_string();
+        state.learning_rate_schedule = LearningRateSchedule::WarmupInverseSqrt { learning_rate: 0.001, warmup_steps: 4000 };
+
+        let mut early_stopping = EarlyStopping::new(EarlyStoppingOptions::new());
+        early_stopping.update(Validation { step: 1000, loss: 2.5, rouge_l: 0.2, multi_task: None }, PathBuf::from("step-1000"));
+        state.early_stopping = Some(early_stopping);
+
+        assert!(state.save(&dir).is_ok());
+        let res = TrainState::load(&dir);
+        assert!(res.is_ok());
+        assert_eq!(res.unwrap(), state);
+
+        state.global_step += 1;
+        assert!(state.save(&dir).is_ok());
+        assert_eq!(TrainState::load(&dir).unwrap().global_step, 1201);
+
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(TrainState::load(&dir).is_err());
+    }
+
+    #[test]
+    fn test_train_state_check_resume() {
+        let mut state = TrainState::new();
+        state.vocabulary_hash = "aaaa".to_string();
+        state.config_hash = "bbbb".to_string();
+
+        assert!(state.check_resume("aaaa", "bbbb").is_ok());
+        assert!(state.check_resume("aaab", "bbbb").is_err());
+        assert!(state.check_resume("aaaa", "bbbc").is_err());
+    }
+}
@@ -0,0 +1,564 @@
+use serde::{Serialize, Deserialize};
+use serde_json::Value;
+use std::fs;
+use std::path::Path;
+use crate::result::Result;
+use crate::hash::hash_bytes;
+use crate::lr_schedule::LearningRateSchedule;
+use crate::curriculum::{Curriculum, LengthBuckets};
+use crate::metadata::MetadataOptions;
+use crate::multi_task::{MultiTaskOptions, TargetConditioning};
+use crate::summarizer::SummaryMode;
+use crate::early_stopping::EarlyStoppingOptions;
+use crate::augmentation::AugmentationOptions;
+
+/// `PRESETS` are the names of the named configurations.
+pub const PRESETS: [&str; 2] = ["tifu-short", "tifu-long"];
+
+/// `PRESET_KEY` is the key of a config file naming the preset it extends.
+const PRESET_KEY: &str = "preset";
+
+/// `CONFIG_FILE` is the name of the config file in a model directory.
+pub const CONFIG_FILE: &str = "config.json";
+
+/// `Architecture` is the summarizer built from a `ModelConfig`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Architecture {
+    /// `Rnn` is the attentional RNN encoder-decoder, with `embedding_size` hidden units. The
+    /// convolution and memory fields of the `ModelConfig` are not used by it.
+    Rnn,
+    /// `Mmn` is the multi-level memory network: a stack of `layers` residual dilated
+    /// convolutions of `kernel_size` with the `dilation_rates` encodes the source, and the
+    /// decoder attends over the outputs of the top `memory_levels` layers.
+    Mmn,
+    /// `Lead` is the lead-N baseline.
+    Lead,
+}
+
+impl Default for Architecture {
+    fn default() -> Architecture {
+        Architecture::Rnn
+    }
+}
+
+/// `ModelConfig` are the hyperparameters of the model.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ModelConfig {
+    #[serde(default)]
+    pub architecture: Architecture,
+    pub embedding_size: usize,
+    /// `layers` is the number of dilated convolution layers of the `Mmn` encoder.
+    pub layers: usize,
+    /// `dilation_rates` are the dilation rates of the `Mmn` layers, one per layer.
+    pub dilation_rates: Vec<usize>,
+    /// `memory_levels` is the number of top `Mmn` layers attended by the decoder.
+    pub memory_levels: usize,
+    /// `kernel_size` is the width of the kernels of the `Mmn` layers.
+    pub kernel_size: usize,
+    pub dropout: f64,
+    /// `vocabulary_size` is the maximum number of tokens in the vocabulary.
+    pub vocabulary_size: usize,
+    /// `min_token_count` is the minimum count of a token to enter the vocabulary.
+    pub min_token_count: usize,
+    pub max_source_len: usize,
+    pub max_summary_len: usize,
+    /// `copy` enables the copy mechanism of the out-of-vocabulary source tokens.
+    pub copy: bool,
+    pub tokenization: Tokenization,
+    /// `metadata` conditions the summaries on the post metadata, if set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<MetadataOptions>,
+    /// `target_conditioning` conditions the summaries on their kind, to train a model on the
+    /// title and tl;dr targets jointly, if set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target_conditioning: Option<TargetConditioning>,
+}
+
+impl ModelConfig {
+    /// `validate` returns an error if the `ModelConfig` is invalid.
+    pub fn validate(&self) -> Result<()> {
+        let positive = [
+            ("embedding_size", self.embedding_size),
+            ("layers", self.layers),
+            ("memory_levels", self.memory_levels),
+            ("kernel_size", self.kernel_size),
+            ("vocabulary_size", self.vocabulary_size),
+            ("max_source_len", self.max_source_len),
+            ("max_summary_len", self.max_summary_len),
+        ];
+
+        for (name, value) in positive.iter() {
+            if *value == 0 {
+                return Err(format!("invalid model.{}: must be positive", name));
+            }
+        }
+
+        if self.dilation_rates.len() != self.layers {
+            return Err(format!("invalid model.dilation_rates: expected {} rates, found {}",
+                self.layers, self.dilation_rates.len()));
+        }
+
+        if self.dilation_rates.contains(&0) {
+            return Err("invalid model.dilation_rates: must be positive".to_string());
+        }
+
+        if self.memory_levels > self.layers {
+            return Err(format!("invalid model.memory_levels: expected at most {} levels, found {}",
+                self.layers, self.memory_levels));
+        }
+
+        if !(0.0..1.0).contains(&self.dropout) {
+            return Err(format!("invalid model.dropout: {}", self.dropout));
+        }
+
+        if self.tokenization == (Tokenization::Bpe { merges: 0 }) {
+            return Err("invalid model.tokenization.merges: must be positive".to_string());
+        }
+
+        if let Some(ref metadata) = self.metadata {
+            metadata.validate().map_err(|e| format!("invalid model.metadata: {}", e))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// `Tokenization` is how the tokens of the dataset are mapped to the model inputs.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Tokenization {
+    /// `Word` uses the dataset tokens as they are.
+    Word,
+    /// `Bpe` splits the dataset tokens in subwords with `merges` byte-pair-encoding merges.
+    Bpe {
+        merges: usize,
+    },
+}
+
+impl Default for Tokenization {
+    fn default() -> Tokenization {
+        Tokenization::Word
+    }
+}
+
+/// `Optimizer` is the optimizer of the training.
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Optimizer {
+    Adam {
+        beta1: f64,
+        beta2: f64,
+        epsilon: f64,
+    },
+    Sgd {
+        momentum: f64,
+    },
+}
+
+impl Default for Optimizer {
+    fn default() -> Optimizer {
+        Optimizer::Adam {
+            beta1: 0.9,
+            beta2: 0.999,
+            epsilon: 1e-8,
+        }
+    }
+}
+
+impl Optimizer {
+    /// `validate` returns an error if the parameters of the `Optimizer` are invalid.
+    pub fn validate(&self) -> Result<()> {
+        match *self {
+            Optimizer::Adam { beta1, beta2, epsilon } => {
+                if !(0.0..1.0).contains(&beta1) || !(0.0..1.0).contains(&beta2) || epsilon <= 0.0 {
+                    return Err("invalid adam optimizer parameters".to_string());
+                }
+            },
+            Optimizer::Sgd { momentum } => {
+                if !(0.0..1.0).contains(&momentum) {
+                    return Err("invalid sgd optimizer momentum".to_string());
+                }
+            },
+        }
+
+        Ok(())
+    }
+}
+
+/// `TrainConfig` are the hyperparameters of the training.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TrainConfig {
+    /// `mode` is the kind of summaries the model is trained on.
+    #[serde(default)]
+    pub mode: SummaryMode,
+    pub batch_size: usize,
+    pub epochs: u64,
+    /// `max_grad_norm` is the global norm gradients are clipped to. Zero disables clipping.
+    pub max_grad_norm: f64,
+    pub seed: u64,
+    /// `length_buckets` are the source length boundaries of the batches. Empty disables bucketing.
+    pub length_buckets: Vec<usize>,
+    pub optimizer: Optimizer,
+    pub learning_rate_schedule: LearningRateSchedule,
+    /// `curriculum` admits the longer sources gradually, if set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub curriculum: Option<Curriculum>,
+    /// `early_stopping` validates the model periodically, keeping the best checkpoints and
+    /// stopping when the validation metric stops improving, if set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub early_stopping: Option<EarlyStoppingOptions>,
+    /// `augmentation` extends the train split with augmented entries, if set. It needs the
+    /// long mode or the multi-task training, as only the tl;dr entries are augmented.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub augmentation: Option<AugmentationOptions>,
+    /// `multi_task` trains on the title and tl;dr targets jointly, mixing them in the batches
+    /// at its ratio, if set. It replaces `mode` and the length buckets in training, and needs
+    /// `model.target_conditioning`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub multi_task: Option<MultiTaskOptions>,
+}
+
+impl TrainConfig {
+    /// `validate` returns an error if the `TrainConfig` is invalid.
+    pub fn validate(&self) -> Result<()> {
+        if self.batch_size == 0 {
+            return Err("invalid train.batch_size: must be positive".to_string());
+        }
+
+        if self.epochs == 0 {
+            return Err("invalid train.epochs: must be positive".to_string());
+        }
+
+        if self.max_grad_norm < 0.0 {
+            return Err(format!("invalid train.max_grad_norm: {}", self.max_grad_norm));
+        }
+
+        LengthBuckets::new(self.length_buckets.clone()).map_err(|e| format!("train.length_buckets: {}", e))?;
+
+        if let Some(ref curriculum) = self.curriculum {
+            curriculum.validate().map_err(|e| format!("train.curriculum: {}", e))?;
+        }
+
+        if let Some(ref early_stopping) = self.early_stopping {
+            early_stopping.validate().map_err(|e| format!("train.early_stopping: {}", e))?;
+        }
+
+        if let Some(ref multi_task) = self.multi_task {
+            multi_task.validate().map_err(|e| format!("train.multi_task: {}", e))?;
+        }
+
+        if let Some(ref augmentation) = self.augmentation {
+            if self.mode != SummaryMode::Long && self.multi_task.is_none() {
+                return Err("invalid train.augmentation: needs the long mode or train.multi_task".to_string());
+            }
+
+            augmentation.validate().map_err(|e| format!("train.augmentation: {}", e))?;
+        }
+
+        self.optimizer.validate().map_err(|e| format!("train.optimizer: {}", e))?;
+        self.learning_rate_schedule.validate().map_err(|e| format!("train.learning_rate_schedule: {}", e))
+    }
+}
+
+/// `Config` is the configuration of the model and of its training.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    pub model: ModelConfig,
+    pub train: TrainConfig,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config::tifu_short()
+    }
+}
+
+impl Config {
+    /// `new` creates a new `Config`.
+    pub fn new() -> Config {
+        Config::default()
+    }
+
+    /// `tifu_short` returns the preset for the TIFU title summaries.
+    pub fn tifu_short() -> Config {
+        Config {
+            model: ModelConfig {
+                architecture: Architecture::Mmn,
+                embedding_size: 300,
+                layers: 4,
+                dilation_rates: vec![1, 2, 4, 8],
+                memory_levels: 3,
+                kernel_size: 3,
+                dropout: 0.2,
+                vocabulary_size: 15_000,
+                min_token_count: 5,
+                max_source_len: 500,
+                max_summary_len: 20,
+                copy: false,
+                tokenization: Tokenization::Word,
+                metadata: None,
+                target_conditioning: None,
+            },
+            train: TrainConfig {
+                mode: SummaryMode::Short,
+                batch_size: 32,
+                epochs: 20,
+                max_grad_norm: 5.0,
+                seed: 0,
+                length_buckets: vec![100, 200, 400],
+                optimizer: Optimizer::default(),
+                learning_rate_schedule: LearningRateSchedule::WarmupInverseSqrt {
+                    learning_rate: 1e-3,
+                    warmup_steps: 4_000,
+                },
+                curriculum: None,
+                early_stopping: None,
+                augmentation: None,
+                multi_task: None,
+            },
+        }
+    }
+
+    /// `tifu_long` returns the preset for the TIFU tl;dr summaries.
+    pub fn tifu_long() -> Config {
+        let mut config = Config::tifu_short();
+        config.model.layers = 5;
+        config.model.dilation_rates = vec![1, 2, 4, 8, 16];
+        config.model.memory_levels = 4;
+        config.model.max_summary_len = 100;
+        config.train.mode = SummaryMode::Long;
+        config.train.batch_size = 16;
+        config
+    }
+
+    /// `preset` returns the preset named `name`.
+    pub fn preset(name: &str) -> Result<Config> {
+        match name {
+            "tifu-short" => Ok(Config::tifu_short()),
+            "tifu-long" => Ok(Config::tifu_long()),
+            _ => Err(format!("invalid preset: {}, expected one of: {}", name, PRESETS.join(", "))),
+        }
+    }
+
+    /// `validate` returns an error if the `Config` is invalid.
+    pub fn validate(&self) -> Result<()> {
+        self.model.validate()?;
+        self.train.validate()?;
+
+        if self.train.multi_task.is_some() && self.model.target_conditioning.is_none() {
+            return Err("invalid train.multi_task: needs model.target_conditioning".to_string());
+        }
+
+        Ok(())
+    }
+
+    /// `from_json_value` creates a new `Config` from a json value. The value only needs the
+    /// fields that differ from the preset named by its `preset` key, `tifu-short` by default.
+    pub fn from_json_value(v: &Value) -> Result<Config> {
+        let mut v = v.clone();
+
+        let preset = match v.as_object_mut().and_then(|obj| obj.remove(PRESET_KEY)) {
+            Some(Value::String(preset)) => Config::preset(&preset)?,
+            Some(_) => return Err("invalid preset: expected a string".to_string()),
+            None => Config::default(),
+        };
+
+        let mut resolved = serde_json::to_value(&preset).map_err(|e| format!("{}", e))?;
+        merge(&mut resolved, v);
+
+        let config: Config = serde_json::from_value(resolved).map_err(|e| format!("{}", e))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// `from_json_string` creates a new `Config` from a json string.
+    pub fn from_json_string(s: &str) -> Result<Config> {
+        let v: Value = serde_json::from_str(s).map_err(|e| format!("{}", e))?;
+        Config::from_json_value(&v)
+    }
+
+    /// `from_toml_string` creates a new `Config` from a toml string.
+    pub fn from_toml_string(s: &str) -> Result<Config> {
+        let v: Value = toml::from_str(s).map_err(|e| format!("{}", e))?;
+        Config::from_json_value(&v)
+    }
+
+    /// `from_file` creates a new `Config` from the file at `path`, read as json if its
+    /// extension is `json` and as toml otherwise.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Config> {
+        let path = path.as_ref();
+        let contents = fs::read_to_string(path).map_err(|e| format!("{}", e))?;
+
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("json") => Config::from_json_string(&contents),
+            _ => Config::from_toml_string(&contents),
+        }
+    }
+
+    /// `to_json_string` returns the `Config` as a json string.
+    pub fn to_json_string(&self) -> Result<String> {
+        serde_json::to_string_pretty(self).map_err(|e| format!("{}", e))
+    }
+
+    /// `to_toml_string` returns the `Config` as a toml string.
+    pub fn to_toml_string(&self) -> Result<String> {
+        toml::to_string(self).map_err(|e| format!("{}", e))
+    }
+
+    /// `save` saves the `Config` in the directory `dir`.
+    pub fn save<P: AsRef<Path>>(&self, dir: P) -> Result<()> {
+        fs::create_dir_all(&dir).map_err(|e| format!("{}", e))?;
+        fs::write(dir.as_ref().join(CONFIG_FILE), self.to_json_string()?).map_err(|e| format!("{}", e))
+    }
+
+    /// `load` loads a `Config` from the directory `dir`.
+    pub fn load<P: AsRef<Path>>(dir: P) -> Result<Config> {
+        Config::from_file(dir.as_ref().join(CONFIG_FILE))
+    }
+
+    /// `hash` returns the hash of the `Config`, as stored in the checkpoints.
+    pub fn hash(&self) -> Result<String> {
+        let json = serde_json::to_string(self).map_err(|e| format!("{}", e))?;
+        Ok(hash_bytes(json.as_bytes()))
+    }
+}
+
+/// `merge` overrides the fields of `base` with the ones of `v`. Objects are merged
+/// recursively unless their `type` tags differ, in which case they are replaced.
+fn merge(base: &mut Value, v: Value) {
+    match (base, v) {
+        (Value::Object(base), Value::Object(v)) => {
+            let same_type = match (base.get("type"), v.get("type")) {
+                (Some(a), Some(b)) => a == b,
+                _ => true,
+            };
+
+            if !same_type {
+                *base = v;
+                return;
+            }
+
+            for (k, v) in v {
+                match base.get_mut(&k) {
+                    Some(field) => merge(field, v),
+                    None => {
+                        base.insert(k, v);
+                    },
+                }
+            }
+        },
+        (base, v) => *base = v,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Architecture, Config, Optimizer, Tokenization, PRESETS};
+    use crate::early_stopping::Metric;
+    use crate::lr_schedule::LearningRateSchedule;
+
+    #[test]
+    fn test_config_presets() {
+        for name in PRESETS.iter() {
+            let config = Config::preset(name).unwrap();
+            assert!(config.validate().is_ok());
+            assert_eq!(config.model.architecture, Architecture::Mmn);
+
+            let toml = config.to_toml_string().unwrap();
+            assert_eq!(Config::from_toml_string(&toml).unwrap(), config);
+
+            let json = config.to_json_string().unwrap();
+            assert_eq!(Config::from_json_string(&json).unwrap(), config);
+        }
+
+        assert!(Config::preset("tifu").is_err());
+        assert_ne!(Config::tifu_short().hash().unwrap(), Config::tifu_long().hash().unwrap());
+    }
+
+    #[test]
+    fn test_config_from_toml_string() {
+        let config = Config::from_toml_string(r#"
+            preset = "tifu-long"
+
+            [model]
+            dropout = 0.3
+
+            [model.tokenization]
+            type = "bpe"
+            merges = 8000
+
+            [model.metadata]
+            features = ["score", "hour"]
+
+            [train.optimizer]
+            type = "sgd"
+            momentum = 0.9
+
+            [train.learning_rate_schedule]
+            learning_rate = 0.01
+
+            [train.early_stopping]
+            validate_every = 500
+            metric = "loss"
+            keep_best = 2
+            patience = 3
+
+            [train.curriculum]
+            unit = "epoch"
+
+            [train.curriculum.schedule]
+            type = "stages"
+            stages = [{ from = 0, max_len = 200 }, { from = 3, max_len = 1000 }]
+        "#).unwrap();
+
+        assert_eq!(config.model.dropout, 0.3);
+        assert_eq!(config.model.max_summary_len, 100);
+        assert_eq!(config.model.tokenization, Tokenization::Bpe { merges: 8000 });
+        assert_eq!(config.model.metadata.as_ref().map(|m| m.features.len()), Some(2));
+        assert_eq!(config.train.optimizer, Optimizer::Sgd { momentum: 0.9 });
+        assert_eq!(config.train.curriculum.as_ref().map(|c| c.max_len(0, 4)), Some(1000));
+        assert_eq!(config.train.early_stopping.map(|e| e.metric), Some(Metric::Loss));
+
+        let toml = config.to_toml_string().unwrap();
+        assert_eq!(Config::from_toml_string(&toml).unwrap(), config);
+        assert_eq!(config.train.learning_rate_schedule, LearningRateSchedule::WarmupInverseSqrt {
+            learning_rate: 0.01,
+            warmup_steps: 4_000,
+        });
+    }
+
+    #[test]
+    fn test_config_validate() {
+        let invalid = [
+            "[model]\nlayers = 3",
+            "[model]\ndilation_rates = [1, 2, 0, 4]",
+            "[model]\nmemory_levels = 5",
+            "[model]\ndropout = 1.0",
+            "[model]\nembedding_size = 0",
+            "[model]\nembeding_size = 300",
+            "[model.tokenization]\ntype = \"bpe\"\nmerges = 0",
+            "[model.metadata]\nfeatures = [\"score\", \"score\"]",
+            "[train]\nbatch_size = 0",
+            "[train]\nlength_buckets = [200, 100]",
+            "[train.optimizer]\nbeta1 = 1.5",
+            "[train.early_stopping]\nvalidate_every = 0\nmetric = \"loss\"\nkeep_best = 1\npatience = 1",
+            "[train.learning_rate_schedule]\nlearning_rate = -1.0",
+            "[[train.augmentation.techniques]]\ntechnique = \"token_swap\"\nprobability = 0.5\nseed = 0\ncopies = 1",
+            "[train.multi_task]\nshort_ratio = 0.5",
+            "[model]\ntarget_conditioning = \"token\"\n[train.multi_task]\nshort_ratio = 1.5",
+            "preset = \"tifu-long\"\n[[train.augmentation.techniques]]\ntechnique = \"synonym\"\nprobability = 0.5\nseed = 0\ncopies = 1",
+            "preset = \"tifu\"",
+        ];
+
+        for s in invalid.iter() {
+            assert!(Config::from_toml_string(s).is_err(), "{}", s);
+        }
+
+        let err = Config::from_toml_string("[model]\nlayers = 3").unwrap_err();
+        assert_eq!(err, "invalid model.dilation_rates: expected 3 rates, found 4");
+    }
+}
@@ -0,0 +1,169 @@
+use serde::{Serialize, Deserialize};
+use crate::result::Result;
+use crate::vocabulary::{Vocabulary, BOS_ID, EOS_ID, PAD_ID, UNK_ID, UNK_TOKEN};
+
+/// `CopyExample` is a source encoded with its per-example extended vocabulary: the
+/// out-of-vocabulary source tokens get the ids following the ones of the `Vocabulary`,
+/// so that the decoder can copy them.
+#[derive(Clone, Default, PartialEq, Debug, Serialize, Deserialize)]
+pub struct CopyExample {
+    /// `source_ids` are the ids of the source, with `UNK_ID` for the out-of-vocabulary tokens.
+    pub source_ids: Vec<usize>,
+    /// `source_extended_ids` are the ids of the source in the extended vocabulary.
+    pub source_extended_ids: Vec<usize>,
+    /// `oovs` are the out-of-vocabulary source tokens, in extended id order.
+    pub oovs: Vec<String>,
+}
+
+impl CopyExample {
+    /// `new` creates a new `CopyExample` of `source` with `vocabulary`.
+    pub fn new(vocabulary: &Vocabulary, source: &[String]) -> CopyExample {
+        let mut example = CopyExample::default();
+
+        for token in source.iter() {
+            if vocabulary.contains(token) {
+                let id = vocabulary.id(token);
+                example.source_ids.push(id);
+                example.source_extended_ids.push(id);
+                continue;
+            }
+
+            let idx = match example.oovs.iter().position(|oov| oov == token) {
+                Some(idx) => idx,
+                None => {
+                    example.oovs.push(token.to_owned());
+                    example.oovs.len() - 1
+                },
+            };
+
+            example.source_ids.push(UNK_ID);
+            example.source_extended_ids.push(vocabulary.len() + idx);
+        }
+
+        example
+    }
+
+    /// `extended_len` returns the size of the extended vocabulary.
+    pub fn extended_len(&self, vocabulary: &Vocabulary) -> usize {
+        vocabulary.len() + self.oovs.len()
+    }
+
+    /// `encode_summary` returns the ids of `summary` in the extended vocabulary. The
+    /// out-of-vocabulary tokens not found in the source are `UNK_ID`.
+    pub fn encode_summary(&self, vocabulary: &Vocabulary, summary: &[String]) -> Vec<usize> {
+        summary
+            .iter()
+            .map(|token| {
+                if vocabulary.contains(token) {
+                    return vocabulary.id(token);
+                }
+
+                self.oovs
+                    .iter()
+                    .position(|oov| oov == token)
+                    .map(|idx| vocabulary.len() + idx)
+                    .unwrap_or(UNK_ID)
+            })
+            .collect()
+    }
+
+    /// `decode` returns the tokens of the extended `ids`, mapping the copied ids back to
+    /// the original source tokens.
+    pub fn decode(&self, vocabulary: &Vocabulary, ids: &[usize]) -> Vec<String> {
+        ids.iter()
+            .take_while(|id| **id != EOS_ID)
+            .filter(|id| **id != PAD_ID && **id != BOS_ID)
+            .map(|id| match id.checked_sub(vocabulary.len()) {
+                Some(idx) => self.oovs.get(idx).map(String::as_str).unwrap_or(UNK_TOKEN),
+                None => vocabulary.token(*id).unwrap_or(UNK_TOKEN),
+            })
+            .map(ToOwned::to_owned)
+            .collect()
+    }
+}
+
+/// `copy_distribution` mixes the generation distribution `vocabulary_distribution` with
+/// the copy distribution given by the `attention` over the source positions:
+/// `p(w) = p_gen * p_vocabulary(w) + (1 - p_gen) * sum of the attention on the positions of w`.
+/// The result is over the extended vocabulary of `extended_len` ids.
+pub fn copy_distribution(p_gen: f32,
+                         vocabulary_distribution: &[f32],
+                         attention: &[f32],
+                         source_extended_ids: &[usize],
+                         extended_len: usize) -> Result<Vec<f32>>
+{
+    if !(0.0..=1.0).contains(&p_gen) {
+        return Err(format!("invalid p_gen: {}", p_gen));
+    }
+
+    if attention.len() != source_extended_ids.len() {
+        return Err(format!("invalid attention: expected {} positions, found {}", source_extended_ids.len(), attention.len()));
+    }
+
+    if vocabulary_distribution.len() > extended_len {
+        return Err(format!("invalid extended_len: {}", extended_len));
+    }
+
+    let mut distribution = vec![0.0; extended_len];
+
+    for (p, q) in distribution.iter_mut().zip(vocabulary_distribution.iter()) {
+        *p = p_gen * q;
+    }
+
+    for (a, id) in attention.iter().zip(source_extended_ids.iter()) {
+        let p = distribution
+            .get_mut(*id)
+            .ok_or_else(|| format!("invalid source id: {}", id))?;
+        *p += (1.0 - p_gen) * a;
+    }
+
+    Ok(distribution)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{copy_distribution, CopyExample};
+    use crate::vocabulary::{Vocabulary, BOS_ID, EOS_ID, UNK_ID};
+
+    fn tokens(s: &str) -> Vec<String> {
+        s.split_whitespace().map(ToOwned::to_owned).collect()
+    }
+
+    #[test]
+    fn test_copy_example() {
+        let vocabulary = Vocabulary::from_tokens(tokens("my cat ate"));
+        let example = CopyExample::new(&vocabulary, &tokens("my cat gustavo ate zucchini gustavo"));
+
+        assert_eq!(example.oovs, tokens("gustavo zucchini"));
+        assert_eq!(example.source_ids, vec![4, 5, UNK_ID, 6, UNK_ID, UNK_ID]);
+        assert_eq!(example.source_extended_ids, vec![4, 5, 7, 6, 8, 7]);
+        assert_eq!(example.extended_len(&vocabulary), 9);
+
+        let summary = example.encode_summary(&vocabulary, &tokens("gustavo ate my homework"));
+        assert_eq!(summary, vec![7, 6, 4, UNK_ID]);
+
+        let mut ids = vec![BOS_ID];
+        ids.extend_from_slice(&summary);
+        ids.push(EOS_ID);
+        assert_eq!(example.decode(&vocabulary, &ids), tokens("gustavo ate my <unk>"));
+    }
+
+    #[test]
+    fn test_copy_distribution() {
+        let vocabulary_distribution = [0.0, 0.1, 0.0, 0.1, 0.8];
+        let attention = [0.25, 0.5, 0.25];
+        let source_extended_ids = [4, 5, 5];
+
+        let distribution = copy_distribution(0.6, &vocabulary_distribution, &attention, &source_extended_ids, 6).unwrap();
+        let expected = [0.0, 0.06, 0.0, 0.06, 0.48 + 0.1, 0.3];
+
+        for (p, q) in distribution.iter().zip(expected.iter()) {
+            assert!((p - q).abs() < 1e-6);
+        }
+        assert!((distribution.iter().sum::<f32>() - 1.0).abs() < 1e-6);
+
+        assert!(copy_distribution(1.5, &vocabulary_distribution, &attention, &source_extended_ids, 6).is_err());
+        assert!(copy_distribution(0.5, &vocabulary_distribution, &attention[..2], &source_extended_ids, 6).is_err());
+        assert!(copy_distribution(0.5, &vocabulary_distribution, &attention, &[4, 5, 6], 6).is_err());
+    }
+}
@@ -0,0 +1,294 @@
+use serde::{Serialize, Deserialize};
+use rand::Rng;
+use rand::seq::SliceRandom;
+use crate::result::Result;
+use crate::data_loader::{batch_order, LastBatch};
+
+/// `CurriculumUnit` is the unit the progress of a `Curriculum` is measured in.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CurriculumUnit {
+    Step,
+    Epoch,
+}
+
+impl Default for CurriculumUnit {
+    fn default() -> CurriculumUnit {
+        CurriculumUnit::Step
+    }
+}
+
+/// `CurriculumStage` admits the sources up to `max_len` tokens from the progress `from` on.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct CurriculumStage {
+    pub from: u64,
+    pub max_len: usize,
+}
+
+/// `CurriculumSchedule` is how the maximum admitted source length grows with the progress.
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum CurriculumSchedule {
+    /// `Linear` grows the maximum length linearly from `start_len` to `end_len` over `duration`.
+    Linear {
+        start_len: usize,
+        end_len: usize,
+        duration: u64,
+    },
+    /// `Stages` uses the maximum length of the last stage started.
+    Stages {
+        stages: Vec<CurriculumStage>,
+    },
+}
+
+/// `Curriculum` starts the training on the shorter sources and gradually admits the longer ones.
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct Curriculum {
+    pub unit: CurriculumUnit,
+    pub schedule: CurriculumSchedule,
+}
+
+impl Curriculum {
+    /// `validate` returns an error if the `Curriculum` is invalid.
+    pub fn validate(&self) -> Result<()> {
+        match self.schedule {
+            CurriculumSchedule::Linear { start_len, end_len, duration } => {
+                if start_len == 0 || start_len > end_len || duration == 0 {
+                    return Err("invalid linear curriculum".to_string());
+                }
+            },
+            CurriculumSchedule::Stages { ref stages } => {
+                if stages.first().map(|s| s.from) != Some(0) {
+                    return Err("invalid curriculum stages: the first stage must start from 0".to_string());
+                }
+
+                for pair in stages.windows(2) {
+                    if pair[1].from <= pair[0].from || pair[1].max_len < pair[0].max_len {
+                        return Err("invalid curriculum stages: must be increasing".to_string());
+                    }
+                }
+
+                if stages.iter().any(|s| s.max_len == 0) {
+                    return Err("invalid curriculum stages: max_len must be positive".to_string());
+                }
+            },
+        }
+
+        Ok(())
+    }
+
+    /// `max_len` returns the maximum admitted source length at `step` of `epoch`.
+    pub fn max_len(&self, step: u64, epoch: u64) -> usize {
+        let progress = match self.unit {
+            CurriculumUnit::Step => step,
+            CurriculumUnit::Epoch => epoch,
+        };
+
+        match self.schedule {
+            CurriculumSchedule::Linear { start_len, end_len, duration } => {
+                let t = progress.min(duration) as f64 / duration.max(1) as f64;
+                start_len + ((end_len - start_len) as f64 * t).round() as usize
+            },
+            CurriculumSchedule::Stages { ref stages } => {
+                stages
+                    .iter()
+                    .take_while(|s| s.from <= progress)
+                    .last()
+                    .or_else(|| stages.first())
+                    .map(|s| s.max_len)
+                    .unwrap_or(usize::MAX)
+            },
+        }
+    }
+}
+
+/// `LengthBuckets` groups the entries by length between increasing `boundaries`: the
+/// bucket `i` holds the lengths less than `boundaries[i]`, the last one the longer ones.
+#[derive(Clone, Default, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct LengthBuckets {
+    pub boundaries: Vec<usize>,
+}
+
+impl LengthBuckets {
+    /// `new` creates a new `LengthBuckets` with `boundaries`.
+    pub fn new(boundaries: Vec<usize>) -> Result<LengthBuckets> {
+        if boundaries.windows(2).any(|pair| pair[0] >= pair[1]) {
+            return Err("invalid length buckets: boundaries must be increasing".to_string());
+        }
+
+        Ok(LengthBuckets { boundaries })
+    }
+
+    /// `len` returns the number of buckets.
+    pub fn len(&self) -> usize {
+        self.boundaries.len() + 1
+    }
+
+    /// `is_empty` returns false, as there is always at least one bucket.
+    pub fn is_empty(&self) -> bool {
+        false
+    }
+
+    /// `bucket` returns the bucket of `length`.
+    pub fn bucket(&self, length: usize) -> usize {
+        self.boundaries.iter().take_while(|b| length >= **b).count()
+    }
+}
+
+/// `bucketed_batches` splits the entries of `order` with `lengths` up to `max_len` in batches
+/// of `batch_size` entries of the same length bucket, applying `last_batch` to every bucket.
+/// The batches are then shuffled with `rng`, so that the lengths still vary across the epoch.
+pub fn bucketed_batches<R: Rng>(order: &[usize],
+                                lengths: &[usize],
+                                max_len: usize,
+                                buckets: &LengthBuckets,
+                                batch_size: usize,
+                                last_batch: LastBatch,
+                                rng: &mut R) -> Result<Vec<Vec<usize>>>
+{
+    let mut grouped = vec![Vec::new(); buckets.len()];
+
+    for idx in order.iter() {
+        let length = *lengths.get(*idx).ok_or_else(|| format!("missing length of entry: {}", idx))?;
+        if length <= max_len {
+            grouped[buckets.bucket(length)].push(*idx);
+        }
+    }
+
+    let mut batches: Vec<Vec<usize>> = grouped
+        .iter()
+        .flat_map(|bucket| batch_order(bucket, batch_size, last_batch))
+        .collect();
+    batches.shuffle(rng);
+
+    Ok(batches)
+}
+
+/// `curriculum_batches` drops from every batch of `batches` the entries with `lengths` above
+/// the maximum length admitted by `curriculum` at the step of the batch, the first batch being
+/// at `step` of `epoch` and every batch left non-empty taking a step. It returns the non-empty
+/// batches with the number of entries of `batches` each consumes, the ones of the emptied
+/// batches before it included.
+pub fn curriculum_batches(batches: Vec<Vec<usize>>,
+                          lengths: &[usize],
+                          curriculum: Option<&Curriculum>,
+                          mut step: u64,
+                          epoch: u64) -> Result<Vec<(usize, Vec<usize>)>>
+{
+    let mut filtered = Vec::with_capacity(batches.len());
+    let mut consumed = 0;
+
+    for batch in batches {
+        consumed += batch.len();
+        let max_len = curriculum.map(|curriculum| curriculum.max_len(step, epoch)).unwrap_or(usize::MAX);
+
+        let mut admitted = Vec::with_capacity(batch.len());
+        for idx in batch {
+            let length = *lengths.get(idx).ok_or_else(|| format!("missing length of entry: {}", idx))?;
+            if length <= max_len {
+                admitted.push(idx);
+            }
+        }
+
+        if !admitted.is_empty() {
+            filtered.push((consumed, admitted));
+            consumed = 0;
+            step += 1;
+        }
+    }
+
+    Ok(filtered)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{bucketed_batches, curriculum_batches, Curriculum, CurriculumSchedule, CurriculumStage, CurriculumUnit, LengthBuckets};
+    use crate::data_loader::LastBatch;
+    use crate::seed::Seeds;
+
+    #[test]
+    fn test_curriculum_max_len() {
+        let linear = Curriculum {
+            unit: CurriculumUnit::Step,
+            schedule: CurriculumSchedule::Linear { start_len: 100, end_len: 1100, duration: 1000 },
+        };
+        assert!(linear.validate().is_ok());
+        assert_eq!(linear.max_len(0, 5), 100);
+        assert_eq!(linear.max_len(500, 5), 600);
+        assert_eq!(linear.max_len(5000, 5), 1100);
+
+        let stages = Curriculum {
+            unit: CurriculumUnit::Epoch,
+            schedule: CurriculumSchedule::Stages {
+                stages: vec![
+                    CurriculumStage { from: 0, max_len: 200 },
+                    CurriculumStage { from: 2, max_len: 800 },
+                ],
+            },
+        };
+        assert!(stages.validate().is_ok());
+        assert_eq!(stages.max_len(10_000, 1), 200);
+        assert_eq!(stages.max_len(0, 2), 800);
+
+        let invalid = Curriculum {
+            unit: CurriculumUnit::Epoch,
+            schedule: CurriculumSchedule::Stages {
+                stages: vec![CurriculumStage { from: 1, max_len: 200 }],
+            },
+        };
+        assert!(invalid.validate().is_err());
+    }
+
+    #[test]
+    fn test_curriculum_batches() {
+        let curriculum = Curriculum {
+            unit: CurriculumUnit::Step,
+            schedule: CurriculumSchedule::Stages {
+                stages: vec![
+                    CurriculumStage { from: 0, max_len: 10 },
+                    CurriculumStage { from: 12, max_len: 100 },
+                ],
+            },
+        };
+        let lengths = [5, 50, 50, 50, 6, 60, 7, 8];
+        let batches = vec![vec![0, 1], vec![2, 3], vec![4, 5], vec![6, 7]];
+
+        let filtered = curriculum_batches(batches.clone(), &lengths, Some(&curriculum), 10, 0).unwrap();
+        assert_eq!(filtered, vec![(2, vec![0]), (4, vec![4]), (2, vec![6, 7])]);
+
+        let filtered = curriculum_batches(batches.clone(), &lengths, Some(&curriculum), 11, 0).unwrap();
+        assert_eq!(filtered, vec![(2, vec![0]), (2, vec![2, 3]), (2, vec![4, 5]), (2, vec![6, 7])]);
+
+        let filtered = curriculum_batches(batches.clone(), &lengths, None, 0, 0).unwrap();
+        assert_eq!(filtered, batches.into_iter().map(|batch| (2, batch)).collect::<Vec<(usize, Vec<usize>)>>());
+        assert!(curriculum_batches(vec![vec![8]], &lengths, None, 0, 0).is_err());
+    }
+
+    #[test]
+    fn test_curriculum_bucketed_batches() {
+        let buckets = LengthBuckets::new(vec![10, 100]).unwrap();
+        assert_eq!(buckets.len(), 3);
+        assert_eq!(buckets.bucket(9), 0);
+        assert_eq!(buckets.bucket(10), 1);
+        assert_eq!(buckets.bucket(5000), 2);
+        assert!(LengthBuckets::new(vec![100, 10]).is_err());
+
+        let lengths = [5, 50, 500, 6, 60, 600, 7, 70, 700, 8];
+        let order: Vec<usize> = (0..lengths.len()).collect();
+        let mut rng = Seeds::rng(1);
+
+        let batches = bucketed_batches(&order, &lengths, 100, &buckets, 2, LastBatch::Keep, &mut rng).unwrap();
+        assert_eq!(batches.iter().map(Vec::len).sum::<usize>(), 7);
+
+        for batch in batches.iter() {
+            let bucket = buckets.bucket(lengths[batch[0]]);
+            assert!(batch.iter().all(|idx| buckets.bucket(lengths[*idx]) == bucket));
+            assert!(batch.iter().all(|idx| lengths[*idx] <= 100));
+        }
+
+        let batches = bucketed_batches(&order, &lengths, 1000, &buckets, 2, LastBatch::Drop, &mut rng).unwrap();
+        assert_eq!(batches.len(), 4);
+
+        assert!(bucketed_batches(&[10], &lengths, 100, &buckets, 2, LastBatch::Keep, &mut rng).is_err());
+    }
+}
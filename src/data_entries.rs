@@ -0,0 +1,334 @@
+use serde::{Serialize, Deserialize};
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rayon::prelude::*;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::ops::{Index, IndexMut};
+use std::iter::{Iterator, FromIterator};
+use std::slice::{self, SliceIndex};
+use std::thread;
+use std::vec;
+use crate::result::Result;
+use crate::path::tifu_training_data_path;
+use crate::raw_data_entry::RawDataEntry;
+use crate::data_entry::DataEntry;
+use crate::dataset_reader::{read_dataset_file, ReadOptions, ReadProgress};
+
+/// `DataEntries` represent multiple data entries of a `DataEntry` type.
+#[derive(Clone, Default, PartialEq, PartialOrd, Debug, Serialize, Deserialize)]
+pub struct DataEntries<T> {
+    data: Vec<T>,
+}
+
+impl<T> DataEntries<T> {
+    /// `new` creates a new `DataEntries`.
+    pub fn new() -> DataEntries<T> {
+        DataEntries { data: Vec::new() }
+    }
+
+    /// `with_capacity` creates a new `DataEntries` with a given capacity.
+    pub fn with_capacity(capacity: usize) -> DataEntries<T> {
+        DataEntries { data: Vec::with_capacity(capacity) }
+    }
+
+    /// `len` returns the `DataEntries` number of entries.
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    /// `is_empty` returns if the `DataEntries` is empty.
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// `push` pushes an entry in the `DataEntries`.
+    pub fn push(&mut self, entry: T) {
+        self.data.push(entry);
+    }
+
+    /// `pop` pops an entry from the `DataEntries`.
+    pub fn pop(&mut self) -> Option<T> {
+        self.data.pop()
+    }
+
+    /// `as_slice` returns the entries of the `DataEntries` as a slice.
+    pub fn as_slice(&self) -> &[T] {
+        &self.data
+    }
+
+    /// `into_vec` converts the `DataEntries` into a `Vec` of entries.
+    pub fn into_vec(self) -> Vec<T> {
+        self.data
+    }
+
+    /// `iter` returns an iterator over the references of the entries.
+    pub fn iter(&self) -> slice::Iter<'_, T> {
+        self.data.iter()
+    }
+
+    /// `iter_mut` returns an iterator over the mutable references of the entries.
+    pub fn iter_mut(&mut self) -> slice::IterMut<'_, T> {
+        self.data.iter_mut()
+    }
+
+    /// `chunks` returns an iterator over slices of `size` entries. The last slice
+    /// can be shorter.
+    pub fn chunks(&self, size: usize) -> slice::Chunks<'_, T> {
+        self.data.chunks(size)
+    }
+
+    /// `shuffle` shuffles the entries in place with a rng seeded by `seed`.
+    pub fn shuffle(&mut self, seed: u64) {
+        let mut rng = StdRng::seed_from_u64(seed);
+        self.data.shuffle(&mut rng);
+    }
+
+    /// `sort_by_key` sorts the entries in place by the key returned by `f`.
+    pub fn sort_by_key<K, F>(&mut self, f: F)
+        where K: Ord,
+              F: FnMut(&T) -> K
+    {
+        self.data.sort_by_key(f)
+    }
+}
+
+impl<T: Clone> DataEntries<T> {
+    /// `extend_from_slice` extends the `DataEntries` with a slice of entries.
+    pub fn extend_from_slice(&mut self, entries: &[T]) {
+        self.data.extend_from_slice(entries)
+    }
+}
+
+impl<T: DataEntry + Send + 'static> DataEntries<T> {
+    /// `from_tifu_dataset_file` creates a `DataEntries` from the first `count` entries in `TIFU_TRAINING_DATA_PATH`.
+    /// A negative `count` reads all the entries.
+    pub fn from_tifu_dataset_file(count: i32) -> Result<DataEntries<T>> {
+        DataEntries::from_tifu_dataset_file_with_options(count, &ReadOptions::default())
+    }
+
+    /// `from_tifu_dataset_file_with_options` creates a `DataEntries` from the first `count` entries in
+    /// `TIFU_TRAINING_DATA_PATH` parsed with `options`. The entries skipped by `options` are not counted.
+    /// A negative `count` reads all the entries.
+    pub fn from_tifu_dataset_file_with_options(count: i32, options: &ReadOptions) -> Result<DataEntries<T>> {
+        let options = *options;
+
+        thread::spawn(move || {
+            let path = tifu_training_data_path();
+            let file = File::open(&path).map_err(|e| format!("{}", e))?;
+            let reader = BufReader::new(file);
+            let mut data_entries = DataEntries::new();
+
+            for (i, line) in reader.lines().enumerate() {
+                if data_entries.len() as i32 == count {
+                    break;
+                }
+
+                let json_raw_data_entry = line.map_err(|e| format!("{} at line: {}", e, i + 1))?;
+                let (raw_data_entry, _) = RawDataEntry::from_json_string_with_options(&json_raw_data_entry, &options.parse)?;
+                let mut data_entry = T::from_raw(&raw_data_entry);
+
+                if options.skip_without_summary && !data_entry.has_summary() {
+                    continue;
+                }
+
+                if options.split_sentences {
+                    data_entry.split_source_sentences();
+                }
+
+                data_entries.push(data_entry);
+            }
+
+            Ok(data_entries)
+        })
+        .join()
+        .unwrap()
+    }
+
+    /// `from_tifu_dataset_file_all` creates a `DataEntries` from all the entries in `TIFU_TRAINING_DATA_PATH`,
+    /// parsing the file in parallel.
+    pub fn from_tifu_dataset_file_all() -> Result<DataEntries<T>> {
+        DataEntries::from_tifu_dataset_file_par(&ReadOptions::default(), |_| {})
+    }
+
+    /// `from_tifu_dataset_file_par` creates a `DataEntries` from all the entries in `TIFU_TRAINING_DATA_PATH`,
+    /// parsing the file in parallel with `options` and reporting the progress to `progress`.
+    pub fn from_tifu_dataset_file_par<F>(options: &ReadOptions, progress: F) -> Result<DataEntries<T>>
+        where F: Fn(ReadProgress) + Sync
+    {
+        read_dataset_file(tifu_training_data_path(), options, progress)
+    }
+}
+
+impl<T> From<Vec<T>> for DataEntries<T> {
+    fn from(data: Vec<T>) -> DataEntries<T> {
+        DataEntries { data }
+    }
+}
+
+impl<T, I: SliceIndex<[T]>> Index<I> for DataEntries<T> {
+    type Output = I::Output;
+
+    fn index(&self, idx: I) -> &I::Output {
+        &self.data[idx]
+    }
+}
+
+impl<T, I: SliceIndex<[T]>> IndexMut<I> for DataEntries<T> {
+    fn index_mut(&mut self, idx: I) -> &mut I::Output {
+        &mut self.data[idx]
+    }
+}
+
+impl<T> IntoIterator for DataEntries<T> {
+    type Item = T;
+    type IntoIter = vec::IntoIter<T>;
+
+    fn into_iter(self) -> vec::IntoIter<T> {
+        self.data.into_iter()
+    }
+}
+
+impl<'a, T> IntoIterator for &'a DataEntries<T> {
+    type Item = &'a T;
+    type IntoIter = slice::Iter<'a, T>;
+
+    fn into_iter(self) -> slice::Iter<'a, T> {
+        self.data.iter()
+    }
+}
+
+impl<'a, T> IntoIterator for &'a mut DataEntries<T> {
+    type Item = &'a mut T;
+    type IntoIter = slice::IterMut<'a, T>;
+
+    fn into_iter(self) -> slice::IterMut<'a, T> {
+        self.data.iter_mut()
+    }
+}
+
+impl<T> FromIterator<T> for DataEntries<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> DataEntries<T> {
+        DataEntries { data: Vec::from_iter(iter) }
+    }
+}
+
+impl<T> Extend<T> for DataEntries<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        self.data.extend(iter)
+    }
+}
+
+impl<T: Send> IntoParallelIterator for DataEntries<T> {
+    type Item = T;
+    type Iter = rayon::vec::IntoIter<T>;
+
+    fn into_par_iter(self) -> rayon::vec::IntoIter<T> {
+        self.data.into_par_iter()
+    }
+}
+
+impl<'a, T: Sync> IntoParallelIterator for &'a DataEntries<T> {
+    type Item = &'a T;
+    type Iter = rayon::slice::Iter<'a, T>;
+
+    fn into_par_iter(self) -> rayon::slice::Iter<'a, T> {
+        self.data.par_iter()
+    }
+}
+
+impl<'a, T: Send> IntoParallelIterator for &'a mut DataEntries<T> {
+    type Item = &'a mut T;
+    type Iter = rayon::slice::IterMut<'a, T>;
+
+    fn into_par_iter(self) -> rayon::slice::IterMut<'a, T> {
+        self.data.par_iter_mut()
+    }
+}
+
+impl<T: Send> FromParallelIterator<T> for DataEntries<T> {
+    fn from_par_iter<I: IntoParallelIterator<Item = T>>(iter: I) -> DataEntries<T> {
+        DataEntries { data: Vec::from_par_iter(iter) }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::DataEntries;
+    use rayon::prelude::*;
+
+    #[test]
+    fn test_data_entries_iterators() {
+        let ds: DataEntries<u32> = (0..10).collect();
+        assert_eq!(ds.len(), 10);
+
+        for _ in 0..2 {
+            for (i, v) in ds.iter().enumerate() {
+                assert_eq!(*v, i as u32);
+            }
+        }
+
+        let mut count = 0;
+        for v in &ds {
+            assert_eq!(*v, count);
+            count += 1;
+        }
+        assert_eq!(count, 10);
+
+        let mut ds_mut = ds.clone();
+        for v in &mut ds_mut {
+            *v *= 2;
+        }
+        assert_eq!(ds_mut[3], 6);
+
+        let mut ds_ext = DataEntries::new();
+        ds_ext.extend(ds.clone());
+        ds_ext.extend(vec![10, 11]);
+        assert_eq!(ds_ext.len(), 12);
+        assert_eq!(ds_ext.into_iter().last(), Some(11));
+
+        let sum: u32 = ds.par_iter().sum();
+        assert_eq!(sum, 45);
+
+        let ds_par: DataEntries<u32> = ds.clone().into_par_iter().map(|v| v + 1).collect();
+        assert_eq!(ds_par[0], 1);
+        assert_eq!(ds_par[9], 10);
+    }
+
+    #[test]
+    fn test_data_entries_modifiers() {
+        let mut ds: DataEntries<u32> = DataEntries::new();
+        assert!(ds.pop().is_none());
+        assert_eq!(ds.len(), 0);
+        assert!(ds.is_empty());
+
+        ds.extend_from_slice(&[3, 1, 2]);
+        assert_eq!(&ds[1..], &[1, 2]);
+
+        ds.sort_by_key(|v| *v);
+        assert_eq!(ds.as_slice(), &[1, 2, 3]);
+
+        let chunks: Vec<&[u32]> = ds.chunks(2).collect();
+        assert_eq!(chunks, vec![&[1, 2][..], &[3][..]]);
+    }
+
+    #[test]
+    fn test_data_entries_shuffle() {
+        let ds: DataEntries<u32> = (0..100).collect();
+
+        let mut ds_1 = ds.clone();
+        ds_1.shuffle(42);
+        let mut ds_2 = ds.clone();
+        ds_2.shuffle(42);
+        let mut ds_3 = ds.clone();
+        ds_3.shuffle(43);
+
+        assert_eq!(ds_1, ds_2);
+        assert_ne!(ds_1, ds);
+        assert_ne!(ds_1, ds_3);
+
+        ds_1.sort_by_key(|v| *v);
+        assert_eq!(ds_1, ds);
+    }
+}
@@ -0,0 +1,91 @@
+use serde::{Serialize, Deserialize};
+use crate::raw_data_entry::RawDataEntry;
+use crate::augmentation::Augmenter;
+use crate::summarizer::SummaryMode;
+
+/// `Provenance` records the post and the `RawDataEntry` fields an entry was derived from.
+#[derive(Clone, Default, PartialEq, PartialOrd, Debug, Serialize, Deserialize)]
+pub struct Provenance {
+    pub permalink: String,
+    pub url: String,
+    /// `source_field` is the name of the `RawDataEntry` field of the source.
+    pub source_field: String,
+    /// `summary_field` is the name of the `RawDataEntry` field of the summary.
+    pub summary_field: String,
+}
+
+impl Provenance {
+    /// `new` creates a new `Provenance`.
+    pub fn new() -> Provenance {
+        Provenance::default()
+    }
+
+    /// `from_raw` creates the `Provenance` of an entry with the `source_field` source and the
+    /// `summary_field` summary of `rde`.
+    pub fn from_raw(rde: &RawDataEntry, source_field: &str, summary_field: &str) -> Provenance {
+        Provenance {
+            permalink: rde.permalink.to_owned(),
+            url: rde.url.to_owned(),
+            source_field: source_field.to_owned(),
+            summary_field: summary_field.to_owned(),
+        }
+    }
+}
+
+/// `DataEntry` is the trait implemented by the entry types that can be collected in a `DataEntries`.
+pub trait DataEntry: Clone + Default {
+    /// `from_raw` creates the entry from a `RawDataEntry`.
+    fn from_raw(rde: &RawDataEntry) -> Self;
+
+    /// `id` returns the id of the entry.
+    fn id(&self) -> &str;
+
+    /// `source` returns the source text of the entry.
+    fn source(&self) -> &str;
+
+    /// `source_mut` returns a mutable reference to the source text of the entry.
+    fn source_mut(&mut self) -> &mut String;
+
+    /// `source_tokenized` returns the source tokens of the entry.
+    fn source_tokenized(&self) -> &[String];
+
+    /// `source_tokenized_mut` returns a mutable reference to the source tokens of the entry.
+    fn source_tokenized_mut(&mut self) -> &mut Vec<String>;
+
+    /// `summary_tokenized` returns the reference summary tokens of the entry, empty if it has none.
+    fn summary_tokenized(&self) -> &[String] {
+        &[]
+    }
+
+    /// `source_sentences_mut` returns a mutable reference to the source sentences of the entry,
+    /// if the entry has them.
+    fn source_sentences_mut(&mut self) -> Option<&mut Vec<Vec<String>>> {
+        None
+    }
+
+    /// `split_source_sentences` splits the source tokens of the entry in sentences, if the
+    /// entry has them. The sentences are not split by `from_raw`.
+    fn split_source_sentences(&mut self) {}
+
+    /// `augmented_from` returns the id of the original entry if the entry was generated by
+    /// data augmentation.
+    fn augmented_from(&self) -> Option<&str> {
+        None
+    }
+
+    /// `has_summary` returns if the entry has a reference summary.
+    fn has_summary(&self) -> bool {
+        true
+    }
+
+    /// `mode` returns the kind of the reference summary of the entry.
+    fn mode(&self) -> SummaryMode {
+        SummaryMode::Short
+    }
+
+    /// `augment` returns the entries generated from the entry by `augmenter`, none if the
+    /// entry type does not support data augmentation.
+    fn augment(&self, _augmenter: &Augmenter) -> Vec<Self> {
+        Vec::new()
+    }
+}
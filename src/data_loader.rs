@@ -0,0 +1,446 @@
+use serde::{Serialize, Deserialize};
+use rand::distributions::{Distribution, WeightedIndex};
+use rand::seq::SliceRandom;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Arc;
+use std::sync::mpsc::{self, Receiver};
+use std::thread::{self, JoinHandle};
+use crate::result::{panic_message, Result};
+use crate::data_entries::DataEntries;
+use crate::seed::{derive_seed, Seeds};
+use crate::curriculum::{bucketed_batches, LengthBuckets};
+
+/// `BUCKETS_STREAM` is the stream of the seed shuffling the bucketed batches.
+const BUCKETS_STREAM: u64 = 1;
+
+/// `Dataset` is a collection of entries a `DataLoader` reads by index, in memory or on disk.
+pub trait Dataset: Send + Sync {
+    type Item;
+
+    /// `len` returns the number of entries of the `Dataset`.
+    fn len(&self) -> usize;
+
+    /// `is_empty` returns if the `Dataset` has no entries.
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// `get` returns the entry at `idx`.
+    fn get(&self, idx: usize) -> Result<Self::Item>;
+}
+
+impl<T: Clone + Send + Sync> Dataset for DataEntries<T> {
+    type Item = T;
+
+    fn len(&self) -> usize {
+        DataEntries::len(self)
+    }
+
+    fn get(&self, idx: usize) -> Result<T> {
+        self.as_slice()
+            .get(idx)
+            .cloned()
+            .ok_or_else(|| format!("invalid index: {}", idx))
+    }
+}
+
+/// `LastBatch` is what a `DataLoader` does with the last batch of an epoch when it is smaller
+/// than the batch size.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LastBatch {
+    /// `Keep` yields the smaller batch.
+    Keep,
+    /// `Drop` skips the smaller batch.
+    Drop,
+    /// `Pad` fills the batch with the first entries of the epoch.
+    Pad,
+}
+
+impl Default for LastBatch {
+    fn default() -> LastBatch {
+        LastBatch::Keep
+    }
+}
+
+/// `DataLoaderOptions` are the options of a `DataLoader`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct DataLoaderOptions {
+    pub batch_size: usize,
+    /// `seed` is the shuffle seed, reshuffling the entries every epoch.
+    pub seed: u64,
+    pub shuffle: bool,
+    pub last_batch: LastBatch,
+    /// `prefetch` is the number of batches prepared ahead by the background thread.
+    pub prefetch: usize,
+}
+
+impl DataLoaderOptions {
+    /// `new` creates a new `DataLoaderOptions`.
+    pub fn new() -> DataLoaderOptions {
+        DataLoaderOptions::default()
+    }
+}
+
+impl Default for DataLoaderOptions {
+    fn default() -> DataLoaderOptions {
+        DataLoaderOptions {
+            batch_size: 32,
+            seed: 0,
+            shuffle: true,
+            last_batch: LastBatch::Keep,
+            prefetch: 2,
+        }
+    }
+}
+
+/// `DataLoader` splits a `Dataset` in batches, reshuffled every epoch, and converts them
+/// with a collate function in a background thread while the previous batches are used.
+pub struct DataLoader<D, F> {
+    dataset: Arc<D>,
+    options: DataLoaderOptions,
+    weights: Option<Vec<f64>>,
+    collate: Arc<F>,
+}
+
+impl<D, F, B> DataLoader<D, F>
+    where D: Dataset + 'static,
+          F: Fn(Vec<D::Item>) -> Result<B> + Send + Sync + 'static,
+          B: Send + 'static
+{
+    /// `new` creates a new `DataLoader` over `dataset`, converting the batches with `collate`.
+    pub fn new(dataset: D, options: DataLoaderOptions, collate: F) -> Result<DataLoader<D, F>> {
+        if options.batch_size == 0 {
+            return Err("invalid batch_size: 0".to_string());
+        }
+
+        Ok(DataLoader {
+            dataset: Arc::new(dataset),
+            options,
+            weights: None,
+            collate: Arc::new(collate),
+        })
+    }
+
+    /// `with_weights` makes the `DataLoader` sample the entries with replacement with
+    /// probabilities proportional to `weights`, instead of shuffling them. The weights must be
+    /// finite and non-negative, and not all zero.
+    pub fn with_weights(mut self, weights: Vec<f64>) -> Result<DataLoader<D, F>> {
+        if weights.len() != self.dataset.len() {
+            return Err(format!("invalid weights: expected {}, found {}", self.dataset.len(), weights.len()));
+        }
+
+        if let Some(idx) = weights.iter().position(|w| !w.is_finite() || *w < 0.0) {
+            return Err(format!("invalid weights: weight {} of entry {}", weights[idx], idx));
+        }
+
+        if weights.iter().all(|w| *w == 0.0) {
+            return Err("invalid weights: all the weights are zero".to_string());
+        }
+
+        WeightedIndex::new(&weights).map_err(|e| format!("invalid weights: {}", e))?;
+
+        self.weights = Some(weights);
+        Ok(self)
+    }
+
+    /// `with_score_weights` makes the `DataLoader` sample the entries with probabilities
+    /// proportional to their `scores`, e.g. the post upvotes, clamped to zero and increased by
+    /// `smoothing` so that the entries without score are still sampled.
+    pub fn with_score_weights(self, scores: &[f64], smoothing: f64) -> Result<DataLoader<D, F>> {
+        if !smoothing.is_finite() || smoothing < 0.0 {
+            return Err(format!("invalid smoothing: {}", smoothing));
+        }
+
+        if let Some(idx) = scores.iter().position(|score| !score.is_finite()) {
+            return Err(format!("invalid scores: score {} of entry {}", scores[idx], idx));
+        }
+
+        let weights = scores.iter().map(|score| score.max(0.0) + smoothing).collect();
+        self.with_weights(weights)
+    }
+
+    /// `with_length_bucket_weights` makes the `DataLoader` sample the entries with
+    /// probabilities inversely proportional to the size of the length bucket of their
+    /// `lengths`, so that every bucket is sampled as often.
+    pub fn with_length_bucket_weights(self, lengths: &[usize], buckets: &LengthBuckets) -> Result<DataLoader<D, F>> {
+        let mut sizes = vec![0usize; buckets.len()];
+        for length in lengths.iter() {
+            sizes[buckets.bucket(*length)] += 1;
+        }
+
+        let weights = lengths.iter().map(|length| 1.0 / sizes[buckets.bucket(*length)] as f64).collect();
+        self.with_weights(weights)
+    }
+
+    /// `dataset` returns the `Dataset` of the `DataLoader`.
+    pub fn dataset(&self) -> &D {
+        &self.dataset
+    }
+
+    /// `order` returns the order of the entries in `epoch`.
+    pub fn order(&self, epoch: u64) -> Vec<usize> {
+        let len = self.dataset.len();
+        let mut rng = Seeds::rng(derive_seed(self.options.seed, epoch));
+
+        match self.weights {
+            Some(ref weights) => {
+                let distribution = WeightedIndex::new(weights).expect("validated weights");
+                (0..len).map(|_| distribution.sample(&mut rng)).collect()
+            },
+            None => {
+                let mut order: Vec<usize> = (0..len).collect();
+                if self.options.shuffle {
+                    order.shuffle(&mut rng);
+                }
+                order
+            },
+        }
+    }
+
+    /// `batch_indices` returns the entry indices of every batch of `epoch`.
+    pub fn batch_indices(&self, epoch: u64) -> Vec<Vec<usize>> {
+        batch_order(&self.order(epoch), self.options.batch_size, self.options.last_batch)
+    }
+
+    /// `bucketed_batch_indices` returns the entry indices of every batch of `epoch`, keeping
+    /// only the entries with `lengths` up to `max_len` and batching them by length bucket.
+    pub fn bucketed_batch_indices(&self, epoch: u64, lengths: &[usize], max_len: usize, buckets: &LengthBuckets) -> Result<Vec<Vec<usize>>> {
+        let mut rng = Seeds::rng(derive_seed(derive_seed(self.options.seed, epoch), BUCKETS_STREAM));
+        bucketed_batches(&self.order(epoch), lengths, max_len, buckets, self.options.batch_size, self.options.last_batch, &mut rng)
+    }
+
+    /// `epoch` returns the iterator over the collated batches of `epoch`.
+    pub fn epoch(&self, epoch: u64) -> Batches<B> {
+        self.batches(self.batch_indices(epoch))
+    }
+
+    /// `batches` returns the iterator over the collated batches of `indices`, prepared by a
+    /// background thread up to `prefetch` batches ahead. A panic while preparing a batch is
+    /// returned as the error of that batch and ends the iteration.
+    pub fn batches(&self, indices: Vec<Vec<usize>>) -> Batches<B> {
+        let (sender, receiver) = mpsc::sync_channel(self.options.prefetch);
+        let dataset = self.dataset.clone();
+        let collate = self.collate.clone();
+
+        let worker = thread::spawn(move || {
+            for batch in indices {
+                let res = panic::catch_unwind(AssertUnwindSafe(|| {
+                    batch
+                        .into_iter()
+                        .map(|idx| dataset.get(idx))
+                        .collect::<Result<Vec<D::Item>>>()
+                        .and_then(|entries| collate(entries))
+                }));
+
+                let (batch, panicked) = match res {
+                    Ok(batch) => (batch, false),
+                    Err(payload) => (Err(format!("data loader worker panicked: {}", panic_message(&*payload))), true),
+                };
+
+                if sender.send(batch).is_err() || panicked {
+                    return;
+                }
+            }
+        });
+
+        Batches {
+            receiver,
+            worker: Some(worker),
+        }
+    }
+}
+
+/// `batch_order` splits `order` in batches of `batch_size` with the `last_batch` policy.
+pub fn batch_order(order: &[usize], batch_size: usize, last_batch: LastBatch) -> Vec<Vec<usize>> {
+    let mut batches: Vec<Vec<usize>> = order.chunks(batch_size).map(|batch| batch.to_vec()).collect();
+
+    let partial = batches.last().map(|batch| batch.len() < batch_size).unwrap_or(false);
+    if partial {
+        match last_batch {
+            LastBatch::Keep => {},
+            LastBatch::Drop => {
+                batches.pop();
+            },
+            LastBatch::Pad => {
+                let last = batches.last_mut().expect("partial batch");
+                let missing = batch_size - last.len();
+                last.extend(order.iter().cycle().take(missing));
+            },
+        }
+    }
+
+    batches
+}
+
+/// `Batches` is the iterator over the prefetched batches of a `DataLoader`.
+pub struct Batches<B> {
+    receiver: Receiver<Result<B>>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl<B> Iterator for Batches<B> {
+    type Item = Result<B>;
+
+    /// `next` returns the next batch. The iteration ends when the worker is done, or with an
+    /// error if the worker died without reporting it.
+    fn next(&mut self) -> Option<Result<B>> {
+        match self.receiver.recv() {
+            Ok(batch) => Some(batch),
+            Err(_) => match self.worker.take().map(JoinHandle::join) {
+                Some(Err(payload)) => Some(Err(format!("data loader worker panicked: {}", panic_message(&*payload)))),
+                _ => None,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{batch_order, DataLoader, DataLoaderOptions, LastBatch};
+    use crate::curriculum::LengthBuckets;
+    use crate::data_entries::DataEntries;
+
+    fn loader(len: usize, options: DataLoaderOptions) -> DataLoader<DataEntries<usize>, impl Fn(Vec<usize>) -> crate::result::Result<Vec<usize>>> {
+        let dataset: DataEntries<usize> = (0..len).collect();
+        DataLoader::new(dataset, options, |batch: Vec<usize>| Ok(batch)).unwrap()
+    }
+
+    #[test]
+    fn test_data_loader_batch_order() {
+        let order: Vec<usize> = (0..7).collect();
+
+        assert_eq!(batch_order(&order, 3, LastBatch::Keep), vec![vec![0, 1, 2], vec![3, 4, 5], vec![6]]);
+        assert_eq!(batch_order(&order, 3, LastBatch::Drop), vec![vec![0, 1, 2], vec![3, 4, 5]]);
+        assert_eq!(batch_order(&order, 3, LastBatch::Pad), vec![vec![0, 1, 2], vec![3, 4, 5], vec![6, 0, 1]]);
+        assert_eq!(batch_order(&order[..1], 3, LastBatch::Pad), vec![vec![0, 0, 0]]);
+        assert!(batch_order(&[], 3, LastBatch::Pad).is_empty());
+    }
+
+    #[test]
+    fn test_data_loader_epoch() {
+        let options = DataLoaderOptions {
+            batch_size: 4,
+            seed: 7,
+            prefetch: 1,
+            ..DataLoaderOptions::default()
+        };
+        let loader = loader(10, options);
+
+        let batches: Vec<Vec<usize>> = loader.epoch(0).map(|b| b.unwrap()).collect();
+        assert_eq!(batches.len(), 3);
+        assert_eq!(batches, loader.batch_indices(0));
+
+        let mut entries: Vec<usize> = batches.into_iter().flatten().collect();
+        assert_ne!(entries, (0..10).collect::<Vec<usize>>());
+        entries.sort();
+        assert_eq!(entries, (0..10).collect::<Vec<usize>>());
+
+        assert_eq!(loader.order(1), loader.order(1));
+        assert_ne!(loader.order(1), loader.order(2));
+
+        let unshuffled = self::loader(10, DataLoaderOptions { shuffle: false, ..options });
+        assert_eq!(unshuffled.order(3), (0..10).collect::<Vec<usize>>());
+    }
+
+    #[test]
+    fn test_data_loader_weights() {
+        let mut weights = vec![0.0; 10];
+        weights[3] = 1.0;
+        weights[8] = 3.0;
+
+        let loader = loader(10, DataLoaderOptions::default()).with_weights(weights).unwrap();
+        let order = loader.order(0);
+        assert_eq!(order.len(), 10);
+        assert!(order.iter().all(|idx| *idx == 3 || *idx == 8));
+
+        assert!(self::loader(10, DataLoaderOptions::default()).with_weights(vec![1.0; 3]).is_err());
+        assert!(self::loader(10, DataLoaderOptions::default()).with_weights(vec![0.0; 10]).is_err());
+
+        for invalid in [f64::NAN, f64::INFINITY, -1.0].iter() {
+            let mut weights = vec![1.0; 10];
+            weights[5] = *invalid;
+            assert!(self::loader(10, DataLoaderOptions::default()).with_weights(weights).is_err());
+        }
+    }
+
+    #[test]
+    fn test_data_loader_score_weights() {
+        let mut scores = vec![-5.0; 10];
+        scores[2] = 100.0;
+
+        let loader = loader(10, DataLoaderOptions::default()).with_score_weights(&scores, 0.0).unwrap();
+        assert!(loader.order(0).iter().all(|idx| *idx == 2));
+
+        let options = DataLoaderOptions { batch_size: 1, ..DataLoaderOptions::default() };
+        let loader = self::loader(1_000, options).with_score_weights(&[0.0; 1_000], 1.0).unwrap();
+        let order = loader.order(0);
+        assert!(order.iter().any(|idx| *idx != order[0]));
+
+        assert!(self::loader(10, DataLoaderOptions::default()).with_score_weights(&[0.0; 10], 0.0).is_err());
+        assert!(self::loader(10, DataLoaderOptions::default()).with_score_weights(&[f64::NAN; 10], 1.0).is_err());
+        assert!(self::loader(10, DataLoaderOptions::default()).with_score_weights(&scores, -1.0).is_err());
+    }
+
+    #[test]
+    fn test_data_loader_length_bucket_weights() {
+        let lengths: Vec<usize> = (0..1_000).map(|idx| if idx < 10 { 5 } else { 50 }).collect();
+        let buckets = LengthBuckets::new(vec![20]).unwrap();
+
+        let loader = loader(1_000, DataLoaderOptions::default()).with_length_bucket_weights(&lengths, &buckets).unwrap();
+        let short = loader.order(0).iter().filter(|idx| **idx < 10).count();
+        assert!(short > 400 && short < 600, "{} short entries", short);
+
+        assert!(self::loader(10, DataLoaderOptions::default()).with_length_bucket_weights(&lengths, &buckets).is_err());
+    }
+
+    #[test]
+    fn test_data_loader_bucketed_batch_indices() {
+        let loader = loader(100, DataLoaderOptions { batch_size: 5, ..DataLoaderOptions::default() });
+        let lengths: Vec<usize> = (0..100).collect();
+        let buckets = LengthBuckets::new(vec![20, 40]).unwrap();
+
+        let indices = loader.bucketed_batch_indices(0, &lengths, 49, &buckets).unwrap();
+        assert_eq!(indices, loader.bucketed_batch_indices(0, &lengths, 49, &buckets).unwrap());
+        assert_eq!(indices.len(), 10);
+
+        let batches: Vec<Vec<usize>> = loader.batches(indices).map(|b| b.unwrap()).collect();
+        for batch in batches {
+            assert!(batch.iter().all(|idx| *idx < 50 && buckets.bucket(*idx) == buckets.bucket(batch[0])));
+        }
+    }
+
+    #[test]
+    fn test_data_loader_collate_error() {
+        let dataset: DataEntries<usize> = (0..4).collect();
+        let options = DataLoaderOptions { batch_size: 2, ..DataLoaderOptions::default() };
+        let loader = DataLoader::new(dataset, options, |batch: Vec<usize>| {
+            if batch.contains(&0) {
+                Err("bad entry".to_string())
+            } else {
+                Ok(batch.len())
+            }
+        }).unwrap();
+
+        let results: Vec<_> = loader.epoch(0).collect();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results.iter().filter(|r| r.is_err()).count(), 1);
+    }
+
+    #[test]
+    fn test_data_loader_worker_panic() {
+        let dataset: DataEntries<usize> = (0..6).collect();
+        let options = DataLoaderOptions { batch_size: 2, shuffle: false, ..DataLoaderOptions::default() };
+        let loader = DataLoader::new(dataset, options, |batch: Vec<usize>| {
+            if batch.contains(&2) {
+                panic!("bad entry");
+            }
+            Ok(batch.len())
+        }).unwrap();
+
+        let results: Vec<_> = loader.epoch(0).collect();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0], Ok(2));
+        assert!(results[1].as_ref().unwrap_err().contains("bad entry"));
+    }
+}
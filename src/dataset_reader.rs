@@ -0,0 +1,329 @@
+use rayon::prelude::*;
+use rayon::ThreadPoolBuilder;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, AtomicU64, Ordering};
+use crate::result::Result;
+use crate::raw_data_entry::RawDataEntry;
+use crate::parse_options::ParseOptions;
+use crate::data_entry::DataEntry;
+use crate::data_entries::DataEntries;
+
+/// `DEFAULT_CHUNK_SIZE` is the default size in bytes of the chunks read by a single worker.
+pub const DEFAULT_CHUNK_SIZE: u64 = 4 * 1024 * 1024;
+
+/// `ReadOptions` are the options used to read a dataset file in parallel.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ReadOptions {
+    /// `workers` is the number of worker threads. Zero uses one worker per CPU core.
+    pub workers: usize,
+    /// `chunk_size` is the approximate size in bytes of a chunk. Chunks always end on a line boundary.
+    pub chunk_size: u64,
+    /// `ordered` keeps the entries in the order of the file. If false, the entries
+    /// are collected in the order in which their chunks are parsed.
+    pub ordered: bool,
+    /// `parse` are the options used to parse every entry.
+    pub parse: ParseOptions,
+    /// `skip_without_summary` skips the entries without a reference summary.
+    pub skip_without_summary: bool,
+    /// `split_sentences` splits the sources of the entries in sentences.
+    pub split_sentences: bool,
+}
+
+impl ReadOptions {
+    /// `new` creates a new `ReadOptions`.
+    pub fn new() -> ReadOptions {
+        ReadOptions::default()
+    }
+}
+
+impl Default for ReadOptions {
+    fn default() -> ReadOptions {
+        ReadOptions {
+            workers: 0,
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            ordered: true,
+            parse: ParseOptions::default(),
+            skip_without_summary: false,
+            split_sentences: false,
+        }
+    }
+}
+
+/// `ReadProgress` is the progress of a parallel read, reported every time a chunk is parsed.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct ReadProgress {
+    pub chunks_done: usize,
+    pub chunks_total: usize,
+    pub bytes_done: u64,
+    pub bytes_total: u64,
+    pub entries_done: usize,
+}
+
+/// `line_chunks` splits the file at `path` in byte ranges of about `chunk_size` bytes
+/// ending on line boundaries.
+pub fn line_chunks<P: AsRef<Path>>(path: P, chunk_size: u64) -> Result<Vec<(u64, u64)>> {
+    if chunk_size == 0 {
+        return Err("invalid chunk size".to_string());
+    }
+
+    let len = fs::metadata(&path).map_err(|e| format!("{}", e))?.len();
+    let file = File::open(&path).map_err(|e| format!("{}", e))?;
+    let mut reader = BufReader::new(file);
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    let mut buf = Vec::new();
+
+    while start < len {
+        let mut end = start + chunk_size;
+
+        if end >= len {
+            end = len;
+        } else {
+            reader.seek(SeekFrom::Start(end)).map_err(|e| format!("{}", e))?;
+            buf.clear();
+            end += reader.read_until(b'\n', &mut buf).map_err(|e| format!("{}", e))? as u64;
+        }
+
+        chunks.push((start, end));
+        start = end;
+    }
+
+    Ok(chunks)
+}
+
+/// `line_number` returns the 1-based number of the line starting at byte `offset` of the
+/// file at `path`.
+fn line_number<P: AsRef<Path>>(path: P, offset: u64) -> Result<usize> {
+    let file = File::open(&path).map_err(|e| format!("{}", e))?;
+    let mut reader = BufReader::new(file.take(offset));
+    let mut newlines = 0;
+
+    loop {
+        let buf = reader.fill_buf().map_err(|e| format!("{}", e))?;
+        if buf.is_empty() {
+            break;
+        }
+
+        let len = buf.len();
+        newlines += buf.iter().filter(|b| **b == b'\n').count();
+        reader.consume(len);
+    }
+
+    Ok(newlines + 1)
+}
+
+/// `read_chunk` parses the lines in the byte range `[start, end)` of the file at `path`.
+fn read_chunk<T, P>(path: P, start: u64, end: u64, options: &ReadOptions) -> Result<Vec<T>>
+    where T: DataEntry,
+          P: AsRef<Path>
+{
+    let mut file = File::open(&path).map_err(|e| format!("{}", e))?;
+    file.seek(SeekFrom::Start(start)).map_err(|e| format!("{}", e))?;
+    let mut reader = BufReader::new(file.take(end - start));
+    let mut entries = Vec::new();
+    let mut offset = start;
+    let mut buf = Vec::new();
+
+    loop {
+        buf.clear();
+        let size = reader.read_until(b'\n', &mut buf).map_err(|e| format!("{}", e))?;
+        if size == 0 {
+            break;
+        }
+
+        while buf.last() == Some(&b'\n') || buf.last() == Some(&b'\r') {
+            buf.pop();
+        }
+
+        let (raw_data_entry, _) = match RawDataEntry::from_json_bytes_with_options(&buf, &options.parse) {
+            Ok(parsed) => parsed,
+            Err(e) => return Err(format!("{} at line: {}", e, line_number(&path, offset)?)),
+        };
+        offset += size as u64;
+
+        let mut entry = T::from_raw(&raw_data_entry);
+        if options.skip_without_summary && !entry.has_summary() {
+            continue;
+        }
+
+        if options.split_sentences {
+            entry.split_source_sentences();
+        }
+
+        entries.push(entry);
+    }
+
+    Ok(entries)
+}
+
+/// `read_dataset_file` reads the entries of the dataset file at `path` in parallel,
+/// calling `progress` every time a chunk is parsed.
+pub fn read_dataset_file<T, P, F>(path: P, options: &ReadOptions, progress: F) -> Result<DataEntries<T>>
+    where T: DataEntry + Send,
+          P: AsRef<Path> + Sync,
+          F: Fn(ReadProgress) + Sync
+{
+    let chunks = line_chunks(&path, options.chunk_size)?;
+    let chunks_total = chunks.len();
+    let bytes_total = chunks.last().map(|c| c.1).unwrap_or(0);
+
+    let chunks_done = AtomicUsize::new(0);
+    let bytes_done = AtomicU64::new(0);
+    let entries_done = AtomicUsize::new(0);
+
+    let read = |&(start, end): &(u64, u64)| -> Result<Vec<T>> {
+        let entries = read_chunk(&path, start, end, options)?;
+
+        progress(ReadProgress {
+            chunks_done: chunks_done.fetch_add(1, Ordering::SeqCst) + 1,
+            chunks_total,
+            bytes_done: bytes_done.fetch_add(end - start, Ordering::SeqCst) + end - start,
+            bytes_total,
+            entries_done: entries_done.fetch_add(entries.len(), Ordering::SeqCst) + entries.len(),
+        });
+
+        Ok(entries)
+    };
+
+    let pool = ThreadPoolBuilder::new()
+        .num_threads(options.workers)
+        .build()
+        .map_err(|e| format!("{}", e))?;
+
+    pool.install(|| {
+        if options.ordered {
+            let parsed = chunks.par_iter()
+                .map(read)
+                .collect::<Result<Vec<Vec<T>>>>()?;
+
+            Ok(parsed.into_iter().flatten().collect())
+        } else {
+            let parsed = Mutex::new(DataEntries::new());
+
+            chunks.par_iter()
+                .try_for_each(|chunk| -> Result<()> {
+                    let entries = read(chunk)?;
+                    parsed.lock().unwrap().extend(entries);
+                    Ok(())
+                })?;
+
+            Ok(parsed.into_inner().unwrap())
+        }
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::{line_chunks, read_dataset_file, ReadOptions};
+    use crate::raw_data_entry::RawDataEntry;
+    use crate::long_data_entry::LongDataEntry;
+    use crate::data_entries::DataEntries;
+    use std::env;
+    use std::fs::{self, File};
+    use std::io::Write;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn write_dataset_file(name: &str, count: usize) -> std::path::PathBuf {
+        let mut path = env::temp_dir();
+        path.push(name);
+
+        let mut file = File::create(&path).unwrap();
+        for i in 0..count {
+            let mut entry = RawDataEntry::new();
+            entry.id = format!("{}", i);
+            entry.title = format!("title {}", i);
+            writeln!(file, "{}", entry.to_json_string().unwrap()).unwrap();
+        }
+
+        path
+    }
+
+    #[test]
+    fn test_dataset_reader_line_chunks() {
+        let path = write_dataset_file("mmn_test_dataset_reader_line_chunks.json", 50);
+        let len = fs::metadata(&path).unwrap().len();
+        let contents = fs::read(&path).unwrap();
+
+        let chunks = line_chunks(&path, 100).unwrap();
+        assert!(chunks.len() > 1);
+        assert_eq!(chunks[0].0, 0);
+        assert_eq!(chunks.last().unwrap().1, len);
+
+        for w in chunks.windows(2) {
+            assert_eq!(w[0].1, w[1].0);
+            assert_eq!(contents[w[0].1 as usize - 1], b'\n');
+        }
+
+        assert!(line_chunks(&path, 0).is_err());
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_dataset_reader_read_dataset_file() {
+        let count = 200;
+        let path = write_dataset_file("mmn_test_dataset_reader_read_dataset_file.json", count);
+
+        let mut options = ReadOptions::new();
+        options.chunk_size = 256;
+        options.workers = 4;
+
+        let calls = AtomicUsize::new(0);
+        let ds: DataEntries<RawDataEntry> = read_dataset_file(&path, &options, |p| {
+            calls.fetch_add(1, Ordering::SeqCst);
+            assert!(p.chunks_done <= p.chunks_total);
+            assert!(p.bytes_done <= p.bytes_total);
+        }).unwrap();
+
+        assert_eq!(ds.len(), count);
+        assert_eq!(calls.load(Ordering::SeqCst), line_chunks(&path, 256).unwrap().len());
+        for (i, entry) in ds.iter().enumerate() {
+            assert_eq!(entry.id, format!("{}", i));
+        }
+
+        options.ordered = false;
+        let mut ds: DataEntries<RawDataEntry> = read_dataset_file(&path, &options, |_| {}).unwrap();
+        assert_eq!(ds.len(), count);
+        ds.sort_by_key(|entry| entry.id.parse::<usize>().unwrap());
+        for (i, entry) in ds.iter().enumerate() {
+            assert_eq!(entry.id, format!("{}", i));
+        }
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_dataset_reader_skip_without_summary() {
+        let count = 20;
+        let path = write_dataset_file("mmn_test_dataset_reader_skip_without_summary.json", count);
+
+        let mut options = ReadOptions::new();
+        let ds: DataEntries<LongDataEntry> = read_dataset_file(&path, &options, |_| {}).unwrap();
+        assert_eq!(ds.len(), count);
+        assert!(ds.iter().all(|entry| entry.summary.is_none()));
+
+        options.skip_without_summary = true;
+        let ds: DataEntries<LongDataEntry> = read_dataset_file(&path, &options, |_| {}).unwrap();
+        assert!(ds.is_empty());
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_dataset_reader_invalid_line() {
+        let path = write_dataset_file("mmn_test_dataset_reader_invalid_line.json", 10);
+        let mut file = fs::OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(b"{\"id\": \0xff}\n").unwrap();
+
+        let mut options = ReadOptions::new();
+        options.chunk_size = 64;
+
+        let res: Result<DataEntries<RawDataEntry>, String> = read_dataset_file(&path, &options, |_| {});
+        assert!(res.unwrap_err().ends_with("at line: 11"));
+
+        fs::remove_file(&path).unwrap();
+    }
+}
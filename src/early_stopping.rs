@@ -0,0 +1,240 @@
+use serde::{Serialize, Deserialize};
+use std::path::PathBuf;
+use crate::result::Result;
+use crate::multi_task::MultiTaskScores;
+
+/// `Metric` is the validation metric used to rank checkpoints.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Metric {
+    /// `Loss` is the validation loss, lower is better.
+    Loss,
+    /// `RougeL` is the validation ROUGE-L F1, higher is better.
+    RougeL,
+}
+
+impl Metric {
+    /// `is_better` returns if the score `a` is better than the score `b`.
+    pub fn is_better(self, a: f64, b: f64) -> bool {
+        match self {
+            Metric::Loss => a < b,
+            Metric::RougeL => a > b,
+        }
+    }
+}
+
+/// `EarlyStoppingOptions` are the options of an `EarlyStopping`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct EarlyStoppingOptions {
+    /// `validate_every` is the number of steps between two validations.
+    pub validate_every: u64,
+    /// `metric` is the metric used to rank the checkpoints.
+    pub metric: Metric,
+    /// `keep_best` is the number of best checkpoints kept.
+    pub keep_best: usize,
+    /// `patience` is the number of validations without improvement before stopping.
+    pub patience: usize,
+}
+
+impl EarlyStoppingOptions {
+    /// `new` creates a new `EarlyStoppingOptions`.
+    pub fn new() -> EarlyStoppingOptions {
+        EarlyStoppingOptions::default()
+    }
+
+    /// `validate` returns an error if the `EarlyStoppingOptions` are invalid.
+    pub fn validate(&self) -> Result<()> {
+        let positive = [
+            ("validate_every", self.validate_every as usize),
+            ("keep_best", self.keep_best),
+            ("patience", self.patience),
+        ];
+
+        for (name, value) in positive.iter() {
+            if *value == 0 {
+                return Err(format!("invalid {}: must be positive", name));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for EarlyStoppingOptions {
+    fn default() -> EarlyStoppingOptions {
+        EarlyStoppingOptions {
+            validate_every: 1000,
+            metric: Metric::RougeL,
+            keep_best: 3,
+            patience: 5,
+        }
+    }
+}
+
+/// `Validation` is the result of a validation run.
+#[derive(Clone, Copy, Default, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Validation {
+    pub step: u64,
+    pub loss: f64,
+    pub rouge_l: f64,
+    /// `multi_task` are the ROUGE-L scores of every summary kind, with the multi-task training.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub multi_task: Option<MultiTaskScores>,
+}
+
+impl Validation {
+    /// `score` returns the score of the `Validation` for `metric`.
+    pub fn score(&self, metric: Metric) -> f64 {
+        match metric {
+            Metric::Loss => self.loss,
+            Metric::RougeL => self.rouge_l,
+        }
+    }
+}
+
+/// `EarlyStoppingUpdate` is the outcome of `EarlyStopping::update`.
+#[derive(Clone, Default, PartialEq, Eq, Debug)]
+pub struct EarlyStoppingUpdate {
+    /// `improved` is true if the validation is the best so far.
+    pub improved: bool,
+    /// `keep` is true if the checkpoint of the validation is among the best ones and must be kept.
+    pub keep: bool,
+    /// `evicted` are the checkpoints no longer among the best ones, that can be removed.
+    pub evicted: Vec<PathBuf>,
+    /// `stop` is true if the training should stop.
+    pub stop: bool,
+}
+
+/// `EarlyStopping` tracks the validations of a training run, keeping the best checkpoints
+/// and stopping the run when the metric stops improving.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct EarlyStopping {
+    options: EarlyStoppingOptions,
+    best: Vec<(Validation, PathBuf)>,
+    stale_validations: usize,
+}
+
+impl EarlyStopping {
+    /// `new` creates a new `EarlyStopping`.
+    pub fn new(options: EarlyStoppingOptions) -> EarlyStopping {
+        EarlyStopping {
+            options,
+            best: Vec::new(),
+            stale_validations: 0,
+        }
+    }
+
+    /// `should_validate` returns if a validation should be run at `step`.
+    pub fn should_validate(&self, step: u64) -> bool {
+        self.options.validate_every != 0 && step != 0 && step % self.options.validate_every == 0
+    }
+
+    /// `best` returns the best checkpoints, best first.
+    pub fn best(&self) -> &[(Validation, PathBuf)] {
+        &self.best
+    }
+
+    /// `update` records the `validation` of the checkpoint at `checkpoint`.
+    pub fn update(&mut self, validation: Validation, checkpoint: PathBuf) -> EarlyStoppingUpdate {
+        let metric = self.options.metric;
+        let score = validation.score(metric);
+        let improved = self.best.first()
+            .map(|(best, _)| metric.is_better(score, best.score(metric)))
+            .unwrap_or(true);
+
+        let mut update = EarlyStoppingUpdate {
+            improved,
+            ..EarlyStoppingUpdate::default()
+        };
+
+        if improved {
+            self.stale_validations = 0;
+        } else {
+            self.stale_validations += 1;
+        }
+
+        let idx = self.best.iter()
+            .position(|(v, _)| metric.is_better(score, v.score(metric)))
+            .unwrap_or(self.best.len());
+
+        if idx < self.options.keep_best {
+            self.best.insert(idx, (validation, checkpoint));
+            update.keep = true;
+        } else {
+            update.evicted.push(checkpoint);
+        }
+
+        while self.best.len() > self.options.keep_best {
+            if let Some((_, path)) = self.best.pop() {
+                update.evicted.push(path);
+            }
+        }
+
+        update.stop = self.stale_validations >= self.options.patience;
+        update
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{EarlyStopping, EarlyStoppingOptions, Metric, Validation};
+    use std::path::PathBuf;
+
+    fn validation(step: u64, loss: f64, rouge_l: f64) -> Validation {
+        Validation { step, loss, rouge_l, multi_task: None }
+    }
+
+    #[test]
+    fn test_early_stopping_should_validate() {
+        let mut options = EarlyStoppingOptions::new();
+        options.validate_every = 100;
+        let es = EarlyStopping::new(options);
+
+        assert!(!es.should_validate(0));
+        assert!(!es.should_validate(50));
+        assert!(es.should_validate(100));
+        assert!(es.should_validate(300));
+    }
+
+    #[test]
+    fn test_early_stopping_update() {
+        let mut options = EarlyStoppingOptions::new();
+        options.metric = Metric::RougeL;
+        options.keep_best = 2;
+        options.patience = 2;
+        let mut es = EarlyStopping::new(options);
+
+        let update = es.update(validation(1, 3.0, 0.10), PathBuf::from("c1"));
+        assert!(update.improved && update.keep && !update.stop);
+        assert!(update.evicted.is_empty());
+
+        let update = es.update(validation(2, 2.0, 0.20), PathBuf::from("c2"));
+        assert!(update.improved && update.keep);
+        assert!(update.evicted.is_empty());
+
+        let update = es.update(validation(3, 1.5, 0.15), PathBuf::from("c3"));
+        assert!(!update.improved && update.keep && !update.stop);
+        assert_eq!(update.evicted, vec![PathBuf::from("c1")]);
+
+        let update = es.update(validation(4, 1.4, 0.05), PathBuf::from("c4"));
+        assert!(!update.improved && !update.keep && update.stop);
+        assert_eq!(update.evicted, vec![PathBuf::from("c4")]);
+
+        let best: Vec<&PathBuf> = es.best().iter().map(|(_, p)| p).collect();
+        assert_eq!(best, vec![&PathBuf::from("c2"), &PathBuf::from("c3")]);
+    }
+
+    #[test]
+    fn test_early_stopping_loss() {
+        let mut options = EarlyStoppingOptions::new();
+        options.metric = Metric::Loss;
+        options.keep_best = 1;
+        let mut es = EarlyStopping::new(options);
+
+        es.update(validation(1, 3.0, 0.3), PathBuf::from("c1"));
+        let update = es.update(validation(2, 2.0, 0.1), PathBuf::from("c2"));
+        assert!(update.improved);
+        assert_eq!(update.evicted, vec![PathBuf::from("c1")]);
+        assert_eq!(es.best()[0].0.step, 2);
+    }
+}
@@ -0,0 +1,230 @@
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::{SystemTime, UNIX_EPOCH};
+use crate::result::Result;
+
+/// `CRC32C_POLY` is the reversed Castagnoli polynomial.
+const CRC32C_POLY: u32 = 0x82f6_3b78;
+/// `CRC_MASK_DELTA` is the delta used to mask the TFRecord checksums.
+const CRC_MASK_DELTA: u32 = 0xa282_ead8;
+/// `FILE_VERSION` is the version of the event files.
+const FILE_VERSION: &str = "brain.Event:2";
+/// `MAX_FILE_ATTEMPTS` is the number of file names tried before failing to create an event file.
+const MAX_FILE_ATTEMPTS: usize = 1000;
+
+/// `crc32c` returns the CRC-32C checksum of `bytes`.
+pub fn crc32c(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+
+    for b in bytes {
+        crc ^= u32::from(*b);
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ CRC32C_POLY } else { crc >> 1 };
+        }
+    }
+
+    !crc
+}
+
+/// `masked_crc32c` returns the masked CRC-32C checksum of `bytes` used by the TFRecord format.
+fn masked_crc32c(bytes: &[u8]) -> u32 {
+    let crc = crc32c(bytes);
+    crc.rotate_right(15).wrapping_add(CRC_MASK_DELTA)
+}
+
+/// `put_varint` appends the protobuf varint encoding of `v` to `buf`.
+fn put_varint(buf: &mut Vec<u8>, mut v: u64) {
+    while v >= 0x80 {
+        buf.push((v as u8) | 0x80);
+        v >>= 7;
+    }
+    buf.push(v as u8);
+}
+
+/// `put_key` appends the protobuf key of the field `field` with wire type `wire_type` to `buf`.
+fn put_key(buf: &mut Vec<u8>, field: u64, wire_type: u64) {
+    put_varint(buf, (field << 3) | wire_type);
+}
+
+/// `put_bytes` appends the protobuf length-delimited field `field` to `buf`.
+fn put_bytes(buf: &mut Vec<u8>, field: u64, bytes: &[u8]) {
+    put_key(buf, field, 2);
+    put_varint(buf, bytes.len() as u64);
+    buf.extend_from_slice(bytes);
+}
+
+/// `encode_event` encodes a `tensorflow.Event` protobuf message with the scalar `values`
+/// or, if `file_version` is set, the file version header.
+fn encode_event(wall_time: f64, step: i64, file_version: Option<&str>, values: &[(&str, f32)]) -> Vec<u8> {
+    let mut event = Vec::new();
+
+    put_key(&mut event, 1, 1);
+    event.extend_from_slice(&wall_time.to_le_bytes());
+
+    put_key(&mut event, 2, 0);
+    put_varint(&mut event, step as u64);
+
+    if let Some(file_version) = file_version {
+        put_bytes(&mut event, 3, file_version.as_bytes());
+    }
+
+    if !values.is_empty() {
+        let mut summary = Vec::new();
+
+        for (tag, value) in values.iter() {
+            let mut v = Vec::new();
+            put_bytes(&mut v, 1, tag.as_bytes());
+            put_key(&mut v, 2, 5);
+            v.extend_from_slice(&value.to_le_bytes());
+            put_bytes(&mut summary, 1, &v);
+        }
+
+        put_bytes(&mut event, 5, &summary);
+    }
+
+    event
+}
+
+/// `wall_time` returns the seconds since the unix epoch.
+fn wall_time() -> f64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs_f64())
+        .unwrap_or(0.0)
+}
+
+/// `EventWriter` writes scalar summaries to a TensorBoard event file.
+pub struct EventWriter {
+    path: PathBuf,
+    writer: BufWriter<File>,
+}
+
+impl EventWriter {
+    /// `new` creates a new event file in the directory `dir`. An existing event file is never
+    /// overwritten: if the file name is taken, a counter suffix is appended to it.
+    pub fn new<P: AsRef<Path>>(dir: P) -> Result<EventWriter> {
+        let wall_time = wall_time();
+        let name = format!("events.out.tfevents.{}.mmn.{}", wall_time as u64, process::id());
+
+        let mut attempt = 0;
+        let (path, file) = loop {
+            let mut path = PathBuf::new();
+            path.push(&dir);
+            if attempt == 0 {
+                path.push(&name);
+            } else {
+                path.push(format!("{}.{}", name, attempt));
+            }
+
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(file) => break (path, file),
+                Err(ref e) if e.kind() == ErrorKind::AlreadyExists && attempt + 1 < MAX_FILE_ATTEMPTS => attempt += 1,
+                Err(e) => return Err(format!("{}: {}", path.display(), e)),
+            }
+        };
+
+        let mut event_writer = EventWriter {
+            path,
+            writer: BufWriter::new(file),
+        };
+
+        event_writer.write_record(&encode_event(wall_time, 0, Some(FILE_VERSION), &[]))?;
+        Ok(event_writer)
+    }
+
+    /// `path` returns the path of the event file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// `write_record` writes `data` as a TFRecord.
+    fn write_record(&mut self, data: &[u8]) -> Result<()> {
+        let len = (data.len() as u64).to_le_bytes();
+
+        self.writer.write_all(&len).map_err(|e| format!("{}", e))?;
+        self.writer.write_all(&masked_crc32c(&len).to_le_bytes()).map_err(|e| format!("{}", e))?;
+        self.writer.write_all(data).map_err(|e| format!("{}", e))?;
+        self.writer.write_all(&masked_crc32c(data).to_le_bytes()).map_err(|e| format!("{}", e))
+    }
+
+    /// `write_scalars` writes the scalar summaries `values` at `step`.
+    pub fn write_scalars(&mut self, step: u64, values: &[(&str, f32)]) -> Result<()> {
+        self.write_record(&encode_event(wall_time(), step as i64, None, values))
+    }
+
+    /// `flush` flushes the event file.
+    pub fn flush(&mut self) -> Result<()> {
+        self.writer.flush().map_err(|e| format!("{}", e))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{crc32c, masked_crc32c, encode_event, EventWriter};
+    use std::env;
+    use std::fs;
+
+    #[test]
+    fn test_event_writer_crc32c() {
+        assert_eq!(crc32c(b""), 0);
+        assert_eq!(crc32c(b"123456789"), 0xe306_9283);
+        assert_ne!(masked_crc32c(b"123456789"), crc32c(b"123456789"));
+    }
+
+    #[test]
+    fn test_event_writer_encode_event() {
+        let event = encode_event(1.0, 300, None, &[("loss", 0.5)]);
+
+        let mut expected = vec![0x09];
+        expected.extend_from_slice(&1.0f64.to_le_bytes());
+        expected.extend_from_slice(&[0x10, 0xac, 0x02]);
+        expected.extend_from_slice(&[0x2a, 0x0d, 0x0a, 0x0b, 0x0a, 0x04]);
+        expected.extend_from_slice(b"loss");
+        expected.push(0x15);
+        expected.extend_from_slice(&0.5f32.to_le_bytes());
+
+        assert_eq!(event, expected);
+    }
+
+    #[test]
+    fn test_event_writer_write_scalars() {
+        let mut dir = env::temp_dir();
+        dir.push("mmn_test_event_writer_write_scalars");
+        fs::create_dir_all(&dir).unwrap();
+
+        let mut writer = EventWriter::new(&dir).unwrap();
+        writer.write_scalars(1, &[("train/loss", 2.5)]).unwrap();
+        writer.flush().unwrap();
+
+        let contents = fs::read(writer.path()).unwrap();
+        let mut offset = 0;
+        let mut records = 0;
+
+        while offset < contents.len() {
+            let mut len = [0u8; 8];
+            len.copy_from_slice(&contents[offset..offset + 8]);
+            let len = u64::from_le_bytes(len) as usize;
+            let data = &contents[offset + 12..offset + 12 + len];
+
+            let mut crc = [0u8; 4];
+            crc.copy_from_slice(&contents[offset + 12 + len..offset + 16 + len]);
+            assert_eq!(u32::from_le_bytes(crc), masked_crc32c(data));
+
+            offset += 16 + len;
+            records += 1;
+        }
+
+        assert_eq!(records, 2);
+
+        let writers: Vec<EventWriter> = (0..3).map(|_| EventWriter::new(&dir).unwrap()).collect();
+        for (idx, w) in writers.iter().enumerate() {
+            assert!(writers[..idx].iter().all(|other| other.path() != w.path()));
+            assert_ne!(w.path(), writer.path());
+        }
+        assert_eq!(fs::read(writer.path()).unwrap(), contents);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
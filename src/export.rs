@@ -0,0 +1,307 @@
+use serde::{Serialize, Deserialize};
+use std::fs;
+use std::path::Path;
+use crate::result::Result;
+use crate::model::Model;
+use crate::copy::CopyExample;
+use crate::predict::{input_batch, PredictInput};
+#[cfg(feature = "tensorflow")]
+use crate::saved_model::{self, SAVED_MODEL_DIR};
+use crate::summarizer::{Batch, DecodeOptions, Hypothesis, Summarizer, SummaryMode};
+
+/// `SIGNATURES_FILE` is the name of the serving signatures file in an export directory.
+pub const SIGNATURES_FILE: &str = "signatures.json";
+
+/// `TEXT_SIGNATURE` is the name of the signature summarizing raw texts.
+pub const TEXT_SIGNATURE: &str = "summarize_text";
+
+/// `IDS_SIGNATURE` is the name of the signature summarizing token ids.
+pub const IDS_SIGNATURE: &str = "summarize_ids";
+
+/// `DType` is the type of the elements of a `TensorSpec`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DType {
+    Int64,
+    Float32,
+    String,
+}
+
+/// `TensorSpec` describes an input or an output of a `Signature`.
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct TensorSpec {
+    pub name: String,
+    pub dtype: DType,
+    /// `shape` is the shape of the tensor, -1 for the dimensions of any size.
+    pub shape: Vec<i64>,
+}
+
+impl TensorSpec {
+    /// `new` creates a new `TensorSpec`.
+    pub fn new(name: &str, dtype: DType, shape: &[i64]) -> TensorSpec {
+        TensorSpec {
+            name: name.to_owned(),
+            dtype,
+            shape: shape.to_vec(),
+        }
+    }
+}
+
+/// `Signature` is a serving entry point of an exported model.
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct Signature {
+    pub name: String,
+    pub inputs: Vec<TensorSpec>,
+    pub outputs: Vec<TensorSpec>,
+}
+
+/// `ServingSignatures` are the signatures of an exported model.
+#[derive(Clone, Default, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct ServingSignatures {
+    /// `model` is the name of the exported summarizer.
+    pub model: String,
+    pub signatures: Vec<Signature>,
+}
+
+impl ServingSignatures {
+    /// `new` creates the `ServingSignatures` of `model`: every model summarizes raw texts, and
+    /// the models with a vocabulary also summarize token ids.
+    pub fn new(model: &Model) -> ServingSignatures {
+        let mut signatures = vec![Signature {
+            name: TEXT_SIGNATURE.to_owned(),
+            inputs: vec![TensorSpec::new("text", DType::String, &[-1])],
+            outputs: vec![
+                TensorSpec::new("summary", DType::String, &[-1]),
+                TensorSpec::new("score", DType::Float32, &[-1]),
+            ],
+        }];
+
+        if model.seq2seq().is_some() {
+            signatures.push(Signature {
+                name: IDS_SIGNATURE.to_owned(),
+                inputs: vec![
+                    TensorSpec::new("source_ids", DType::Int64, &[-1, -1]),
+                    TensorSpec::new("source_oovs", DType::String, &[-1, -1]),
+                ],
+                outputs: vec![
+                    TensorSpec::new("summary_ids", DType::Int64, &[-1, -1]),
+                    TensorSpec::new("summary_oovs", DType::String, &[-1, -1]),
+                    TensorSpec::new("score", DType::Float32, &[-1]),
+                ],
+            });
+        }
+
+        ServingSignatures {
+            model: model.name().to_owned(),
+            signatures,
+        }
+    }
+
+    /// `get` returns the signature `name`.
+    pub fn get(&self, name: &str) -> Result<&Signature> {
+        self.signatures
+            .iter()
+            .find(|signature| signature.name == name)
+            .ok_or_else(|| format!("missing signature {} in the {} export", name, self.model))
+    }
+}
+
+/// `SummaryIds` is an output of the ids signature: the summary ids in the extended vocabulary
+/// of its source, the ids from the vocabulary length on being the out-of-vocabulary source
+/// tokens `oovs`, and the score of the summary.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct SummaryIds {
+    pub ids: Vec<usize>,
+    pub oovs: Vec<String>,
+    pub score: f32,
+}
+
+/// `export` writes the files of `model` needed to decode, without the optimizer state and the
+/// dropout rng, and its `ServingSignatures` in the new directory `dir`. With the `tensorflow`
+/// feature, the MMN models are also written as a TensorFlow SavedModel in its `saved_model`
+/// directory, with a `serving_default` signature decoding token ids greedily.
+pub fn export<P: AsRef<Path>>(model: &Model, dir: P) -> Result<()> {
+    let dir = dir.as_ref();
+
+    if dir.exists() && dir.read_dir().map_err(|e| format!("{}", e))?.next().is_some() {
+        return Err(format!("export directory not empty: {}", dir.display()));
+    }
+
+    fs::create_dir_all(dir).map_err(|e| format!("{}", e))?;
+
+    match model {
+        Model::Lead(model) => model.save(dir)?,
+        Model::Seq2Seq(model) | Model::Mmn(model) => model.save_inference(dir)?,
+    }
+
+    #[cfg(feature = "tensorflow")]
+    {
+        if let Model::Mmn(model) = model {
+            saved_model::save(model, dir.join(SAVED_MODEL_DIR), model.config.model.max_summary_len)?;
+        }
+    }
+
+    let contents = serde_json::to_vec_pretty(&ServingSignatures::new(model)).map_err(|e| format!("{}", e))?;
+    fs::write(dir.join(SIGNATURES_FILE), contents).map_err(|e| format!("{}", e))
+}
+
+/// `ExportedModel` is a model loaded from an export directory, run through its signatures.
+#[derive(Clone, PartialEq, Debug)]
+pub struct ExportedModel {
+    pub model: Model,
+    pub signatures: ServingSignatures,
+}
+
+impl ExportedModel {
+    /// `load` loads the `ExportedModel` of the export directory `dir`.
+    pub fn load<P: AsRef<Path>>(dir: P) -> Result<ExportedModel> {
+        let dir = dir.as_ref();
+        let contents = fs::read(dir.join(SIGNATURES_FILE)).map_err(|e| format!("{}", e))?;
+        let signatures: ServingSignatures = serde_json::from_slice(&contents).map_err(|e| format!("{}", e))?;
+        let model = Model::load(dir)?;
+
+        if model.name() != signatures.model {
+            return Err(format!("invalid export: signatures of {}, model {}", signatures.model, model.name()));
+        }
+
+        Ok(ExportedModel { model, signatures })
+    }
+
+    /// `summarize_text` runs the text signature: it tokenizes and summarizes `texts`.
+    pub fn summarize_text(&self, texts: &[String], options: &DecodeOptions) -> Result<Vec<Hypothesis>> {
+        self.signatures.get(TEXT_SIGNATURE)?;
+
+        let inputs: Vec<PredictInput> = texts
+            .iter()
+            .enumerate()
+            .map(|(idx, text)| PredictInput { id: idx.to_string(), text: text.to_owned() })
+            .collect();
+
+        self.model.decode(&input_batch(&inputs), options)
+    }
+
+    /// `summarize_ids` runs the ids signature: it summarizes the `sources` given as their ids
+    /// in their extended vocabulary, with the table of their out-of-vocabulary tokens, and
+    /// returns the summary ids in the same extended vocabulary, so that the copied tokens can
+    /// be read back from the table. The ids are the ones of the model vocabulary, of the
+    /// subwords with the `Bpe` tokenization.
+    pub fn summarize_ids(&self, sources: &[CopyExample], options: &DecodeOptions) -> Result<Vec<SummaryIds>> {
+        self.signatures.get(IDS_SIGNATURE)?;
+
+        let model = self
+            .model
+            .seq2seq()
+            .ok_or_else(|| format!("{} has no vocabulary", self.model.name()))?;
+
+        let tokens: Vec<Vec<String>> = sources
+            .iter()
+            .map(|source| source.decode(&model.vocabulary, &source.source_extended_ids))
+            .collect();
+
+        let batch = Batch {
+            ids: (0..sources.len()).map(|idx| idx.to_string()).collect(),
+            modes: vec![SummaryMode::default(); sources.len()],
+            summaries: vec![Vec::new(); sources.len()],
+            sources: tokens
+                .iter()
+                .map(|tokens| match model.bpe {
+                    Some(ref bpe) => bpe.decode(tokens),
+                    None => tokens.to_owned(),
+                })
+                .collect(),
+            metadata: Vec::new(),
+        };
+
+        let hypotheses = self.model.decode(&batch, options)?;
+
+        Ok(hypotheses
+            .into_iter()
+            .zip(sources.iter())
+            .map(|(hypothesis, source)| {
+                let tokens = match model.bpe {
+                    Some(ref bpe) => bpe.encode(&hypothesis.tokens),
+                    None => hypothesis.tokens,
+                };
+
+                SummaryIds {
+                    ids: source.encode_summary(&model.vocabulary, &tokens),
+                    oovs: source.oovs.to_owned(),
+                    score: hypothesis.score,
+                }
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{export, ExportedModel, ServingSignatures, IDS_SIGNATURE, SIGNATURES_FILE, TEXT_SIGNATURE};
+    use crate::config::{Architecture, Config};
+    use crate::copy::CopyExample;
+    use crate::model::Model;
+    use crate::optimizer::OPTIMIZER_FILE;
+    use crate::predict::{input_batch, PredictInput};
+    use crate::seed::Seeds;
+    use crate::summarizer::{DecodeOptions, Summarizer, TrainStepOptions};
+    use crate::vocabulary::Vocabulary;
+    use std::env;
+    use std::fs;
+
+    #[test]
+    fn test_export_round_trip() {
+        let texts = vec!["My cat ate my homework.".to_string(), "I broke the build, today!".to_string()];
+        let inputs: Vec<PredictInput> = texts
+            .iter()
+            .map(|text| PredictInput { id: "x".to_string(), text: text.to_owned() })
+            .collect();
+        let mut batch = input_batch(&inputs);
+        batch.summaries = vec![batch.sources[0][1..3].to_vec(), batch.sources[1][1..3].to_vec()];
+
+        let mut config = Config::tifu_short();
+        config.model.embedding_size = 8;
+        config.model.copy = true;
+        let vocabulary = Vocabulary::build(batch.sources[..1].iter().map(Vec::as_slice), 100, 1);
+        let mut model = Model::new(&config, vocabulary, None, &Seeds::new(0)).unwrap();
+        for _ in 0..20 {
+            model.train_step(&batch, &TrainStepOptions::default()).unwrap();
+        }
+
+        let mut dir = env::temp_dir();
+        dir.push("mmn_test_export_round_trip");
+        export(&model, &dir).unwrap();
+        assert!(dir.join(SIGNATURES_FILE).exists());
+        assert!(!dir.join(OPTIMIZER_FILE).exists());
+        assert!(export(&model, &dir).is_err());
+
+        let exported = ExportedModel::load(&dir).unwrap();
+        assert_eq!(exported.signatures, ServingSignatures::new(&model));
+        assert!(exported.signatures.get(IDS_SIGNATURE).is_ok());
+
+        let options = DecodeOptions::default();
+        let expected = model.decode(&batch, &options).unwrap();
+        assert_eq!(exported.summarize_text(&texts, &options).unwrap(), expected);
+
+        let vocabulary = model.seq2seq().unwrap().vocabulary.clone();
+        let sources: Vec<CopyExample> = batch.sources.iter().map(|source| CopyExample::new(&vocabulary, source)).collect();
+        assert!(!sources[1].oovs.is_empty());
+
+        let outputs = exported.summarize_ids(&sources, &options).unwrap();
+        for ((output, source), hypothesis) in outputs.iter().zip(sources.iter()).zip(expected.iter()) {
+            assert_eq!(output.oovs, source.oovs);
+            assert_eq!(CopyExample { oovs: output.oovs.clone(), ..CopyExample::default() }.decode(&vocabulary, &output.ids), hypothesis.tokens);
+            assert_eq!(output.score, hypothesis.score);
+        }
+        fs::remove_dir_all(&dir).unwrap();
+
+        config.model.architecture = Architecture::Lead;
+        let mut lead = Model::new(&config, Vocabulary::new(), None, &Seeds::new(0)).unwrap();
+        lead.train_step(&batch, &TrainStepOptions::default()).unwrap();
+        export(&lead, &dir).unwrap();
+
+        let exported = ExportedModel::load(&dir).unwrap();
+        assert!(exported.signatures.get(TEXT_SIGNATURE).is_ok());
+        assert_eq!(exported.summarize_text(&texts, &options).unwrap(), lead.decode(&batch, &options).unwrap());
+        assert!(exported.summarize_ids(&sources, &options).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,102 @@
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use crate::result::Result;
+
+/// `FNV_OFFSET_BASIS` is the 64 bits FNV-1a offset basis.
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+/// `FNV_PRIME` is the 64 bits FNV-1a prime.
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// `Hasher` is a streaming 64 bits FNV-1a hasher. Unlike `std::collections::hash_map::DefaultHasher`
+/// its output is stable across builds, so it can be stored in checkpoints and manifests.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Hasher {
+    state: u64,
+}
+
+impl Hasher {
+    /// `new` creates a new `Hasher`.
+    pub fn new() -> Hasher {
+        Hasher::default()
+    }
+
+    /// `update` feeds `bytes` to the `Hasher`.
+    pub fn update(&mut self, bytes: &[u8]) {
+        for b in bytes {
+            self.state ^= u64::from(*b);
+            self.state = self.state.wrapping_mul(FNV_PRIME);
+        }
+    }
+
+    /// `finish` returns the hash as an hex string.
+    pub fn finish(&self) -> String {
+        format!("{:016x}", self.state)
+    }
+
+    /// `finish_u64` returns the hash as an `u64`.
+    pub fn finish_u64(&self) -> u64 {
+        self.state
+    }
+}
+
+impl Default for Hasher {
+    fn default() -> Hasher {
+        Hasher { state: FNV_OFFSET_BASIS }
+    }
+}
+
+/// `hash_bytes` returns the hex hash of `bytes`.
+pub fn hash_bytes(bytes: &[u8]) -> String {
+    let mut hasher = Hasher::new();
+    hasher.update(bytes);
+    hasher.finish()
+}
+
+/// `hash_file` returns the hex hash of the contents of the file at `path`.
+pub fn hash_file<P: AsRef<Path>>(path: P) -> Result<String> {
+    let mut file = File::open(path).map_err(|e| format!("{}", e))?;
+    let mut hasher = Hasher::new();
+    let mut buf = vec![0; 1 << 16];
+
+    loop {
+        let size = file.read(&mut buf).map_err(|e| format!("{}", e))?;
+        if size == 0 {
+            break;
+        }
+        hasher.update(&buf[..size]);
+    }
+
+    Ok(hasher.finish())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{hash_bytes, hash_file, Hasher};
+    use std::env;
+    use std::fs;
+
+    #[test]
+    fn test_hash_bytes() {
+        assert_eq!(hash_bytes(b""), "cbf29ce484222325");
+        assert_eq!(hash_bytes(b"a"), "af63dc4c8601ec8c");
+        assert_ne!(hash_bytes(b"ab"), hash_bytes(b"ba"));
+
+        let mut hasher = Hasher::new();
+        hasher.update(b"a");
+        hasher.update(b"b");
+        assert_eq!(hasher.finish(), hash_bytes(b"ab"));
+    }
+
+    #[test]
+    fn test_hash_file() {
+        let mut path = env::temp_dir();
+        path.push("mmn_test_hash_file.txt");
+
+        let contents = vec![7u8; 200_000];
+        fs::write(&path, &contents).unwrap();
+        assert_eq!(hash_file(&path).unwrap(), hash_bytes(&contents));
+
+        fs::remove_file(&path).unwrap();
+    }
+}
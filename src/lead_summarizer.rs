@@ -0,0 +1,144 @@
+use serde::{Serialize, Deserialize};
+use std::fs;
+use std::path::Path;
+use crate::result::Result;
+use crate::attention::AttentionLevel;
+use crate::summarizer::{Batch, DecodeOptions, Hypothesis, Summarizer, TrainStepOptions, TrainStepStats};
+
+/// `LEAD_FILE` is the name of the `LeadSummarizer` file in a model directory.
+pub const LEAD_FILE: &str = "lead.json";
+
+/// `LeadSummarizer` is the lead-N baseline: it summarizes a source with its first tokens,
+/// N being the mean length of the reference summaries seen in training.
+#[derive(Clone, Default, PartialEq, Debug, Serialize, Deserialize)]
+pub struct LeadSummarizer {
+    pub summaries: u64,
+    pub summary_tokens: u64,
+}
+
+impl LeadSummarizer {
+    /// `new` creates a new `LeadSummarizer`.
+    pub fn new() -> LeadSummarizer {
+        LeadSummarizer::default()
+    }
+
+    /// `lead_len` returns the number of leading tokens of the summaries.
+    pub fn lead_len(&self) -> usize {
+        if self.summaries == 0 {
+            return 0;
+        }
+
+        (self.summary_tokens as f64 / self.summaries as f64).round() as usize
+    }
+}
+
+impl Summarizer for LeadSummarizer {
+    fn name(&self) -> &str {
+        "lead"
+    }
+
+    /// `train_step` updates the mean summary length and returns the mean absolute
+    /// difference between the lead length and the lengths of the `batch` summaries.
+    fn train_step(&mut self, batch: &Batch, _options: &TrainStepOptions) -> Result<TrainStepStats> {
+        let stats = self.loss(batch)?;
+
+        self.summaries += batch.len() as u64;
+        self.summary_tokens += batch.summaries.iter().map(|s| s.len() as u64).sum::<u64>();
+
+        Ok(stats)
+    }
+
+    /// `loss` returns the mean absolute difference between the lead length and the lengths
+    /// of the `batch` summaries.
+    fn loss(&self, batch: &Batch) -> Result<TrainStepStats> {
+        if batch.is_empty() {
+            return Ok(TrainStepStats::default());
+        }
+
+        let lead_len = self.lead_len() as f64;
+        let loss = batch.summaries.iter().map(|s| (s.len() as f64 - lead_len).abs()).sum::<f64>();
+
+        Ok(TrainStepStats {
+            loss: loss / batch.len() as f64,
+            grad_norm: 0.0,
+            tokens: batch.summaries.iter().map(Vec::len).sum(),
+        })
+    }
+
+    /// `decode` returns the leading tokens of every source. The attention of a summary token
+    /// is all on the source token it is copied from.
+    fn decode(&self, batch: &Batch, options: &DecodeOptions) -> Result<Vec<Hypothesis>> {
+        let len = self.lead_len().min(options.max_len);
+
+        Ok(batch
+            .sources
+            .iter()
+            .map(|source| {
+                let tokens: Vec<String> = source.iter().take(len).cloned().collect();
+                let attention = if options.attention {
+                    let weights = (0..tokens.len())
+                        .map(|i| (0..tokens.len()).map(|j| if i == j { 1.0 } else { 0.0 }).collect())
+                        .collect();
+                    vec![AttentionLevel { dilation: 1, weights }]
+                } else {
+                    Vec::new()
+                };
+
+                Hypothesis { tokens, score: 0.0, attention }
+            })
+            .collect())
+    }
+
+    fn save(&self, dir: &Path) -> Result<()> {
+        fs::create_dir_all(dir).map_err(|e| format!("{}", e))?;
+        let contents = serde_json::to_vec_pretty(self).map_err(|e| format!("{}", e))?;
+        fs::write(dir.join(LEAD_FILE), contents).map_err(|e| format!("{}", e))
+    }
+
+    fn load(dir: &Path) -> Result<LeadSummarizer> {
+        let contents = fs::read(dir.join(LEAD_FILE)).map_err(|e| format!("{}", e))?;
+        serde_json::from_slice(&contents).map_err(|e| format!("{}", e))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::LeadSummarizer;
+    use crate::summarizer::{evaluate, Batch, DecodeOptions, Summarizer, SummaryMode, TrainStepOptions};
+    use std::env;
+    use std::fs;
+
+    fn tokens(s: &str) -> Vec<String> {
+        s.split_whitespace().map(ToOwned::to_owned).collect()
+    }
+
+    #[test]
+    fn test_lead_summarizer() {
+        let batch = Batch {
+            ids: vec!["a".to_string(), "b".to_string()],
+            modes: vec![SummaryMode::Short; 2],
+            sources: vec![tokens("i broke the build today"), tokens("my cat ate my homework")],
+            summaries: vec![tokens("i broke"), tokens("my cat ate")],
+            metadata: Vec::new(),
+        };
+
+        let options = TrainStepOptions::default();
+        let mut lead = LeadSummarizer::new();
+        assert_eq!(lead.train_step(&batch, &options).unwrap().loss, 2.5);
+        assert_eq!(lead.lead_len(), 3);
+        assert_eq!(lead.train_step(&batch, &options).unwrap().loss, 0.5);
+
+        let hypotheses = lead.decode(&batch, &DecodeOptions::default()).unwrap();
+        assert_eq!(hypotheses[0].tokens, tokens("i broke the"));
+
+        let score = evaluate(&lead, &[batch], &DecodeOptions::default()).unwrap();
+        assert!(score.f1 > 0.8);
+
+        let mut dir = env::temp_dir();
+        dir.push("mmn_test_lead_summarizer");
+        lead.save(&dir).unwrap();
+        assert_eq!(LeadSummarizer::load(&dir).unwrap(), lead);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -4,12 +4,30 @@ pub mod result;
 /// `path` is the module containing the raw_data paths.
 pub mod path;
 
+/// `data_entry` is the module containing the `DataEntry` trait.
+pub mod data_entry;
+
+/// `data_entries` is the module containing the generic `DataEntries` type.
+pub mod data_entries;
+
+/// `dataset_reader` is the module containing the parallel dataset file reader.
+pub mod dataset_reader;
+
+/// `parse_options` is the module containing the `ParseOptions` type.
+pub mod parse_options;
+
 /// `raw_data_entry` is the module containing the `RawDataEntry` type.
 pub mod raw_data_entry;
 
 /// `raw_data_entries` is the module containing the `RawDataEntries` type.
 pub mod raw_data_entries;
 
+/// `normalization` is the module containing the text `Normalizer`.
+pub mod normalization;
+
+/// `validation` is the module containing the dataset file validation.
+pub mod validation;
+
 /// `short_data_entry` is the module containing the `ShortDataEntry` type.
 pub mod short_data_entry;
 
@@ -22,3 +40,105 @@ pub mod long_data_entry;
 /// `long_data_entries` is the module containing the `LongDataEntries` type.
 pub mod long_data_entries;
 
+/// `hash` is the module containing the stable content hashing functions.
+pub mod hash;
+
+/// `checkpoint` is the module containing the checkpoint `TrainState` type.
+pub mod checkpoint;
+
+/// `rouge` is the module containing the ROUGE metrics.
+pub mod rouge;
+
+/// `early_stopping` is the module containing the `EarlyStopping` type.
+pub mod early_stopping;
+
+/// `lr_schedule` is the module containing the learning rate schedules and the gradient clipping.
+pub mod lr_schedule;
+
+/// `event_writer` is the module containing the TensorBoard `EventWriter` type.
+pub mod event_writer;
+
+/// `metrics` is the module containing the training `MetricsWriter` type.
+pub mod metrics;
+
+/// `seed` is the module containing the `Seeds` type deriving all the seeds of a run.
+pub mod seed;
+
+/// `split` is the module containing the deterministic train, validation and test `Splits`.
+pub mod split;
+
+/// `manifest` is the module containing the `RunManifest` type.
+pub mod manifest;
+
+/// `server` is the module containing the local HTTP inference `Server` type.
+pub mod server;
+
+/// `predict` is the module containing the batch inference over json lines files.
+pub mod predict;
+
+/// `attention` is the module containing the `AttentionMap` type and its renderers.
+pub mod attention;
+
+/// `config` is the module containing the model and training `Config` type.
+pub mod config;
+
+/// `summarizer` is the module containing the `Summarizer` trait shared by the models.
+pub mod summarizer;
+
+/// `lead_summarizer` is the module containing the `LeadSummarizer` baseline.
+pub mod lead_summarizer;
+
+/// `variables` is the module containing the `Variables` tensors of the models.
+pub mod variables;
+
+/// `optimizer` is the module containing the `OptimizerState` type.
+pub mod optimizer;
+
+/// `seq2seq` is the module containing the `Seq2Seq` attentional RNN summarizer.
+pub mod seq2seq;
+
+/// `mmn` is the module containing the dilated convolution layers of the multi-level memory encoder.
+pub mod mmn;
+
+/// `model` is the module containing the `Model` type, any of the summarizers.
+pub mod model;
+
+/// `export` is the module containing the export of the models for serving.
+pub mod export;
+
+/// `saved_model` is the module containing the TensorFlow SavedModel export of the MMN models.
+#[cfg(feature = "tensorflow")]
+pub mod saved_model;
+
+/// `train` is the module containing the `Trainer` type running the training loop.
+pub mod train;
+
+/// `vocabulary` is the module containing the `Vocabulary` type.
+pub mod vocabulary;
+
+/// `copy` is the module containing the copy mechanism of the out-of-vocabulary tokens.
+pub mod copy;
+
+/// `bpe` is the module containing the `Bpe` subword tokenizer.
+pub mod bpe;
+
+/// `data_loader` is the module containing the prefetching `DataLoader` type.
+pub mod data_loader;
+
+/// `curriculum` is the module containing the curriculum and the length bucketed sampling.
+pub mod curriculum;
+
+/// `augmentation` is the module containing the data augmentation `Augmenter` type.
+pub mod augmentation;
+
+/// `multi_task` is the module containing the multi-task batches and per-target evaluation.
+pub mod multi_task;
+
+/// `metadata` is the module containing the post metadata features of the entries.
+pub mod metadata;
+
+/// `sentence` is the module containing the sentence splitter of the informal sources.
+pub mod sentence;
+
+/// `tokenizer` is the module containing the tokenizer of the raw texts.
+pub mod tokenizer;
@@ -1,5 +1,11 @@
 use serde::{Serialize, Deserialize};
+use std::convert::TryFrom;
 use crate::raw_data_entry::RawDataEntry;
+use crate::data_entry::{DataEntry, Provenance};
+use crate::metadata::Metadata;
+use crate::sentence::split_sentences;
+use crate::augmentation::Augmenter;
+use crate::summarizer::SummaryMode;
 
 /// LongDataEntry is a struct representing an entry in the Long TIFU dataset.
 #[derive(Clone, Default, PartialEq, PartialOrd, Debug, Serialize, Deserialize)]
@@ -9,6 +15,18 @@ pub struct LongDataEntry {
     pub summary_tokenized: Option<Vec<String>>,
     pub source: String,
     pub source_tokenized: Vec<String>,
+    /// `source_sentences` are the source tokens split in sentences.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub source_sentences: Vec<Vec<String>>,
+    /// `augmented_from` is the id of the entry an augmented entry was generated from.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub augmented_from: Option<String>,
+    /// `metadata` are the post metadata of the entry, if known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<Metadata>,
+    /// `provenance` records the post and the fields the entry was derived from, if known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provenance: Option<Provenance>,
 }
 
 impl LongDataEntry {
@@ -16,35 +34,147 @@ impl LongDataEntry {
     pub fn new() -> LongDataEntry {
         LongDataEntry::default()
     }
+}
+
+impl TryFrom<&RawDataEntry> for LongDataEntry {
+    type Error = String;
+
+    /// `try_from` creates a `LongDataEntry` from a `RawDataEntry`, returning an error if the
+    /// `RawDataEntry` has no tl;dr.
+    fn try_from(rde: &RawDataEntry) -> Result<LongDataEntry, String> {
+        if rde.tldr.is_none() {
+            return Err(format!("entry {} has no tldr", rde.id));
+        }
+
+        Ok(LongDataEntry::from_raw(rde))
+    }
+}
 
-    /// `from_raw` creates a `LongDataEntry` from a `RawDataEntry`.
-    pub fn from_raw(rde: &RawDataEntry) -> LongDataEntry {
+impl DataEntry for LongDataEntry {
+    /// `from_raw` creates a `LongDataEntry` from a `RawDataEntry`. The summary is `None` if the
+    /// `RawDataEntry` has no tl;dr.
+    fn from_raw(rde: &RawDataEntry) -> LongDataEntry {
         LongDataEntry {
             id: rde.id.to_owned(),
             summary: rde.tldr.to_owned(),
             summary_tokenized: rde.tldr_tokenized.to_owned(),
             source: rde.selftext_without_tldr.to_owned(),
             source_tokenized: rde.selftext_without_tldr_tokenized.to_owned(),
+            source_sentences: Vec::new(),
+            augmented_from: None,
+            metadata: Some(Metadata::from_raw(rde)),
+            provenance: Some(Provenance::from_raw(rde, "selftext_without_tldr", "tldr")),
         }
     }
+
+    /// `id` returns the id of the `LongDataEntry`.
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// `source` returns the source text of the `LongDataEntry`.
+    fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// `source_mut` returns a mutable reference to the source text of the `LongDataEntry`.
+    fn source_mut(&mut self) -> &mut String {
+        &mut self.source
+    }
+
+    /// `source_tokenized` returns the source tokens of the `LongDataEntry`.
+    fn source_tokenized(&self) -> &[String] {
+        &self.source_tokenized
+    }
+
+    /// `source_tokenized_mut` returns a mutable reference to the source tokens of the `LongDataEntry`.
+    fn source_tokenized_mut(&mut self) -> &mut Vec<String> {
+        &mut self.source_tokenized
+    }
+
+    /// `summary_tokenized` returns the summary tokens of the `LongDataEntry`.
+    fn summary_tokenized(&self) -> &[String] {
+        self.summary_tokenized.as_deref().unwrap_or(&[])
+    }
+
+    /// `source_sentences_mut` returns a mutable reference to the source sentences of the `LongDataEntry`.
+    fn source_sentences_mut(&mut self) -> Option<&mut Vec<Vec<String>>> {
+        Some(&mut self.source_sentences)
+    }
+
+    /// `split_source_sentences` splits the source tokens of the `LongDataEntry` in sentences.
+    fn split_source_sentences(&mut self) {
+        self.source_sentences = split_sentences(&self.source, &self.source_tokenized);
+    }
+
+    /// `augmented_from` returns the id of the entry the `LongDataEntry` was augmented from.
+    fn augmented_from(&self) -> Option<&str> {
+        self.augmented_from.as_deref()
+    }
+
+    /// `has_summary` returns if the `LongDataEntry` has a tl;dr.
+    fn has_summary(&self) -> bool {
+        self.summary.is_some()
+    }
+
+    /// `mode` returns the kind of the summary of the `LongDataEntry`, a tl;dr.
+    fn mode(&self) -> SummaryMode {
+        SummaryMode::Long
+    }
+
+    /// `augment` returns the entries generated from the `LongDataEntry` by `augmenter`.
+    fn augment(&self, augmenter: &Augmenter) -> Vec<LongDataEntry> {
+        augmenter.augment_entry(self)
+    }
 }
 
 #[cfg(test)]
 mod test {
     use super::LongDataEntry;
+    use crate::data_entry::DataEntry;
+    use crate::raw_data_entry::RawDataEntry;
     use crate::raw_data_entries::RawDataEntries;
+    use crate::short_data_entry::ShortDataEntry;
+    use std::convert::TryFrom;
 
     #[test]
     fn test_long_data_entry_from_raw() {
         let count = 10;
         let rds = RawDataEntries::from_tifu_dataset_file(count).unwrap();
         for rd in rds {
-            let ld = LongDataEntry::from_raw(&rd);
+            let mut ld = LongDataEntry::from_raw(&rd);
             assert_eq!(&ld.id, &rd.id);
             assert_eq!(&ld.summary, &rd.tldr);
             assert_eq!(&ld.summary_tokenized, &rd.tldr_tokenized);
             assert_eq!(&ld.source, &rd.selftext_without_tldr);
             assert_eq!(&ld.source_tokenized, &rd.selftext_without_tldr_tokenized);
+            assert!(ld.source_sentences.is_empty());
+            ld.split_source_sentences();
+            assert_eq!(ld.source_sentences.concat(), rd.selftext_without_tldr_tokenized);
+            assert_eq!(ld.metadata.map(|m| m.score), Some(rd.score));
         }
     }
+
+    #[test]
+    fn test_long_data_entry_try_from() {
+        let mut rd = RawDataEntry::new();
+        rd.id = "a".to_string();
+        rd.permalink = "/r/tifu/comments/a/".to_string();
+        rd.url = "https://www.reddit.com/r/tifu/comments/a/".to_string();
+        assert!(LongDataEntry::try_from(&rd).is_err());
+        assert!(!LongDataEntry::from_raw(&rd).has_summary());
+
+        rd.tldr = Some("i broke the build".to_string());
+        let ld = LongDataEntry::try_from(&rd).unwrap();
+        assert!(ld.has_summary());
+
+        let provenance = ld.provenance.unwrap();
+        assert_eq!(provenance.permalink, rd.permalink);
+        assert_eq!(provenance.url, rd.url);
+        assert_eq!(provenance.source_field, "selftext_without_tldr");
+        assert_eq!(provenance.summary_field, "tldr");
+
+        let sd = ShortDataEntry::from(&rd);
+        assert_eq!(sd.provenance.map(|p| p.summary_field), Some("trimmed_title".to_string()));
+    }
 }
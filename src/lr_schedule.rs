@@ -0,0 +1,194 @@
+use serde::{Serialize, Deserialize};
+use std::f64::consts::PI;
+use crate::result::Result;
+
+/// `LearningRateSchedule` is the learning rate schedule of a training run.
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum LearningRateSchedule {
+    /// `Constant` keeps the learning rate at `learning_rate`.
+    Constant {
+        learning_rate: f64,
+    },
+    /// `WarmupInverseSqrt` increases the learning rate linearly up to `learning_rate` for
+    /// `warmup_steps` steps, then decays it with the inverse square root of the step.
+    WarmupInverseSqrt {
+        learning_rate: f64,
+        warmup_steps: u64,
+    },
+    /// `Cosine` increases the learning rate linearly up to `learning_rate` for `warmup_steps`
+    /// steps, then decays it to `min_learning_rate` along a cosine curve ending at `total_steps`.
+    Cosine {
+        learning_rate: f64,
+        min_learning_rate: f64,
+        warmup_steps: u64,
+        total_steps: u64,
+    },
+    /// `StepDecay` multiplies the learning rate by `decay_rate` every `decay_steps` steps.
+    StepDecay {
+        learning_rate: f64,
+        decay_rate: f64,
+        decay_steps: u64,
+    },
+}
+
+impl Default for LearningRateSchedule {
+    fn default() -> LearningRateSchedule {
+        LearningRateSchedule::Constant { learning_rate: 1e-3 }
+    }
+}
+
+impl LearningRateSchedule {
+    /// `learning_rate` returns the learning rate at `step`, starting from zero.
+    pub fn learning_rate(&self, step: u64) -> f64 {
+        match *self {
+            LearningRateSchedule::Constant { learning_rate } => learning_rate,
+            LearningRateSchedule::WarmupInverseSqrt { learning_rate, warmup_steps } => {
+                let step = (step + 1) as f64;
+                let warmup_steps = warmup_steps.max(1) as f64;
+
+                if step < warmup_steps {
+                    learning_rate * step / warmup_steps
+                } else {
+                    learning_rate * (warmup_steps / step).sqrt()
+                }
+            },
+            LearningRateSchedule::Cosine { learning_rate, min_learning_rate, warmup_steps, total_steps } => {
+                if step < warmup_steps {
+                    return learning_rate * (step + 1) as f64 / warmup_steps as f64;
+                }
+
+                let decay_steps = total_steps.saturating_sub(warmup_steps).max(1) as f64;
+                let progress = ((step - warmup_steps) as f64 / decay_steps).min(1.0);
+                min_learning_rate + 0.5 * (learning_rate - min_learning_rate) * (1.0 + (PI * progress).cos())
+            },
+            LearningRateSchedule::StepDecay { learning_rate, decay_rate, decay_steps } => {
+                learning_rate * decay_rate.powi((step / decay_steps.max(1)) as i32)
+            },
+        }
+    }
+
+    /// `validate` returns an error if the parameters of the schedule are invalid.
+    pub fn validate(&self) -> Result<()> {
+        let learning_rate = match *self {
+            LearningRateSchedule::Constant { learning_rate } => learning_rate,
+            LearningRateSchedule::WarmupInverseSqrt { learning_rate, .. } => learning_rate,
+            LearningRateSchedule::Cosine { learning_rate, min_learning_rate, warmup_steps, total_steps } => {
+                if min_learning_rate < 0.0 || min_learning_rate > learning_rate {
+                    return Err("invalid cosine schedule min_learning_rate".to_string());
+                }
+
+                if total_steps <= warmup_steps {
+                    return Err("invalid cosine schedule total_steps: must be greater than warmup_steps".to_string());
+                }
+
+                learning_rate
+            },
+            LearningRateSchedule::StepDecay { learning_rate, decay_rate, decay_steps } => {
+                if decay_rate <= 0.0 || decay_rate > 1.0 {
+                    return Err("invalid step decay schedule decay_rate".to_string());
+                }
+
+                if decay_steps == 0 {
+                    return Err("invalid step decay schedule decay_steps".to_string());
+                }
+
+                learning_rate
+            },
+        };
+
+        if !(learning_rate > 0.0 && learning_rate.is_finite()) {
+            return Err("invalid schedule learning_rate".to_string());
+        }
+
+        Ok(())
+    }
+}
+
+/// `global_norm` returns the global L2 norm of the gradients `grads`.
+pub fn global_norm(grads: &[Vec<f32>]) -> f32 {
+    grads.iter()
+        .flat_map(|g| g.iter())
+        .map(|x| f64::from(*x) * f64::from(*x))
+        .sum::<f64>()
+        .sqrt() as f32
+}
+
+/// `clip_by_global_norm` scales the gradients `grads` in place so that their global norm is at most
+/// `max_norm`, returning the global norm before clipping.
+pub fn clip_by_global_norm(grads: &mut [Vec<f32>], max_norm: f32) -> f32 {
+    let norm = global_norm(grads);
+
+    if norm > max_norm && norm > 0.0 {
+        let scale = max_norm / norm;
+        for x in grads.iter_mut().flat_map(|g| g.iter_mut()) {
+            *x *= scale;
+        }
+    }
+
+    norm
+}
+
+#[cfg(test)]
+mod test {
+    use super::{LearningRateSchedule, global_norm, clip_by_global_norm};
+
+    fn assert_close(a: f64, b: f64) {
+        assert!((a - b).abs() < 1e-9, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn test_lr_schedule_learning_rate() {
+        let constant = LearningRateSchedule::Constant { learning_rate: 0.1 };
+        assert_close(constant.learning_rate(0), 0.1);
+        assert_close(constant.learning_rate(10_000), 0.1);
+
+        let inv_sqrt = LearningRateSchedule::WarmupInverseSqrt { learning_rate: 1.0, warmup_steps: 100 };
+        assert_close(inv_sqrt.learning_rate(0), 0.01);
+        assert_close(inv_sqrt.learning_rate(49), 0.5);
+        assert_close(inv_sqrt.learning_rate(99), 1.0);
+        assert_close(inv_sqrt.learning_rate(399), 0.5);
+
+        let cosine = LearningRateSchedule::Cosine { learning_rate: 1.0, min_learning_rate: 0.0, warmup_steps: 10, total_steps: 110 };
+        assert_close(cosine.learning_rate(4), 0.5);
+        assert_close(cosine.learning_rate(10), 1.0);
+        assert_close(cosine.learning_rate(60), 0.5);
+        assert_close(cosine.learning_rate(110), 0.0);
+        assert_close(cosine.learning_rate(1000), 0.0);
+
+        let step_decay = LearningRateSchedule::StepDecay { learning_rate: 1.0, decay_rate: 0.5, decay_steps: 10 };
+        assert_close(step_decay.learning_rate(9), 1.0);
+        assert_close(step_decay.learning_rate(10), 0.5);
+        assert_close(step_decay.learning_rate(25), 0.25);
+    }
+
+    #[test]
+    fn test_lr_schedule_validate() {
+        assert!(LearningRateSchedule::default().validate().is_ok());
+        assert!(LearningRateSchedule::Constant { learning_rate: 0.0 }.validate().is_err());
+        assert!(LearningRateSchedule::Cosine { learning_rate: 1.0, min_learning_rate: 0.0, warmup_steps: 10, total_steps: 10 }.validate().is_err());
+        assert!(LearningRateSchedule::StepDecay { learning_rate: 1.0, decay_rate: 1.5, decay_steps: 10 }.validate().is_err());
+    }
+
+    #[test]
+    fn test_lr_schedule_serialize() {
+        let json = r#"{"type": "warmup_inverse_sqrt", "learning_rate": 0.001, "warmup_steps": 4000}"#;
+        let schedule: LearningRateSchedule = serde_json::from_str(json).unwrap();
+        assert_eq!(schedule, LearningRateSchedule::WarmupInverseSqrt { learning_rate: 0.001, warmup_steps: 4000 });
+    }
+
+    #[test]
+    fn test_lr_schedule_clip_by_global_norm() {
+        let mut grads = vec![vec![3.0, 0.0], vec![4.0]];
+        assert_eq!(global_norm(&grads), 5.0);
+
+        let norm = clip_by_global_norm(&mut grads, 10.0);
+        assert_eq!(norm, 5.0);
+        assert_eq!(grads, vec![vec![3.0, 0.0], vec![4.0]]);
+
+        let norm = clip_by_global_norm(&mut grads, 1.0);
+        assert_eq!(norm, 5.0);
+        assert!((global_norm(&grads) - 1.0).abs() < 1e-6);
+        assert!((grads[0][0] - 0.6).abs() < 1e-6);
+    }
+}
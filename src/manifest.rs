@@ -0,0 +1,165 @@
+use serde::{Serialize, Deserialize};
+use serde_json::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use crate::result::Result;
+use crate::hash::hash_file;
+use crate::seed::Seeds;
+use crate::split::SplitSizes;
+
+/// `MANIFEST_FILE` is the name of the manifest file in a run directory.
+pub const MANIFEST_FILE: &str = "run.json";
+
+/// `RunManifest` records everything needed to reproduce a training or evaluation run.
+#[derive(Clone, Default, PartialEq, Debug, Serialize, Deserialize)]
+pub struct RunManifest {
+    /// `command` is the command of the run, e.g. "train" or "evaluate".
+    pub command: String,
+    pub crate_version: String,
+    /// `git_commit` is the commit the binary was built from, if known.
+    pub git_commit: Option<String>,
+    /// `created_utc` is the creation time in seconds since the unix epoch.
+    pub created_utc: u64,
+    pub seeds: Seeds,
+    pub config: Value,
+    pub dataset_hash: Option<String>,
+    pub vocabulary_hash: Option<String>,
+    pub split_sizes: SplitSizes,
+    /// `resumes` are the resumptions of a training run, oldest first.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub resumes: Vec<ResumeRecord>,
+}
+
+/// `ResumeRecord` records a resumption of a training run from a checkpoint.
+#[derive(Clone, Default, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct ResumeRecord {
+    /// `checkpoint` is the checkpoint directory the run was resumed from.
+    pub checkpoint: String,
+    /// `global_step` is the step the run was resumed at.
+    pub global_step: u64,
+    pub git_commit: Option<String>,
+    /// `created_utc` is the resumption time in seconds since the unix epoch.
+    pub created_utc: u64,
+    pub dataset_hash: Option<String>,
+}
+
+impl RunManifest {
+    /// `new` creates a new `RunManifest` of the run `command` with the seeds derived from
+    /// `seed`, filling in the crate version, the git commit and the creation time.
+    pub fn new(command: &str, seed: u64) -> RunManifest {
+        RunManifest {
+            command: command.to_owned(),
+            crate_version: env!("CARGO_PKG_VERSION").to_owned(),
+            git_commit: option_env!("MMN_GIT_COMMIT").map(ToOwned::to_owned),
+            created_utc: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+            seeds: Seeds::new(seed),
+            ..RunManifest::default()
+        }
+    }
+
+    /// `set_config` sets the serialized `config` of the run.
+    pub fn set_config<C: Serialize>(&mut self, config: &C) -> Result<()> {
+        self.config = serde_json::to_value(config).map_err(|e| format!("{}", e))?;
+        Ok(())
+    }
+
+    /// `set_dataset_file` sets the hash of the dataset file at `path`.
+    pub fn set_dataset_file<P: AsRef<Path>>(&mut self, path: P) -> Result<()> {
+        self.dataset_hash = Some(hash_file(path)?);
+        Ok(())
+    }
+
+    /// `manifest_path` returns the path of the manifest file in the run directory `dir`.
+    pub fn manifest_path<P: AsRef<Path>>(dir: P) -> PathBuf {
+        let mut path = PathBuf::new();
+        path.push(dir);
+        path.push(MANIFEST_FILE);
+        path
+    }
+
+    /// `write` writes the `RunManifest` in the run directory `dir`, creating it if missing.
+    pub fn write<P: AsRef<Path>>(&self, dir: P) -> Result<()> {
+        fs::create_dir_all(&dir).map_err(|e| format!("{}", e))?;
+        let contents = serde_json::to_vec_pretty(self).map_err(|e| format!("{}", e))?;
+        fs::write(RunManifest::manifest_path(dir), contents).map_err(|e| format!("{}", e))
+    }
+
+    /// `write_resume` records the resumption of the run from the directory `checkpoint` at
+    /// `global_step` in the manifest of the run directory `dir`, keeping the original manifest.
+    /// The `RunManifest` is the one of the resumed run: it is written if the directory has no
+    /// manifest yet, and its commit, time and dataset hash are recorded with the resumption.
+    pub fn write_resume<P: AsRef<Path>, Q: AsRef<Path>>(&self, dir: P, checkpoint: Q, global_step: u64) -> Result<()> {
+        let mut manifest = if RunManifest::manifest_path(&dir).exists() {
+            RunManifest::read(&dir)?
+        } else {
+            self.clone()
+        };
+
+        manifest.resumes.push(ResumeRecord {
+            checkpoint: checkpoint.as_ref().display().to_string(),
+            global_step,
+            git_commit: self.git_commit.clone(),
+            created_utc: self.created_utc,
+            dataset_hash: self.dataset_hash.clone(),
+        });
+
+        manifest.write(dir)
+    }
+
+    /// `read` reads the `RunManifest` from the run directory `dir`.
+    pub fn read<P: AsRef<Path>>(dir: P) -> Result<RunManifest> {
+        let contents = fs::read(RunManifest::manifest_path(dir)).map_err(|e| format!("{}", e))?;
+        serde_json::from_slice(&contents).map_err(|e| format!("{}", e))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::RunManifest;
+    use crate::hash::hash_bytes;
+    use crate::seed::Seeds;
+    use crate::split::SplitSizes;
+    use std::env;
+    use std::fs;
+
+    #[test]
+    fn test_run_manifest_write_read() {
+        let mut dir = env::temp_dir();
+        dir.push("mmn_test_run_manifest_write_read");
+        fs::create_dir_all(&dir).unwrap();
+
+        let dataset_path = dir.join("dataset.json");
+        fs::write(&dataset_path, b"{}\n").unwrap();
+
+        let mut manifest = RunManifest::new("train", 42);
+        assert_eq!(manifest.crate_version, env!("CARGO_PKG_VERSION"));
+        assert_eq!(manifest.seeds, Seeds::new(42));
+        manifest.set_config(&serde_json::json!({"batch_size": 32})).unwrap();
+        manifest.set_dataset_file(&dataset_path).unwrap();
+        manifest.split_sizes = SplitSizes { train: 90, validation: 5, test: 5 };
+
+        manifest.write(&dir).unwrap();
+        let read = RunManifest::read(&dir).unwrap();
+        assert_eq!(read, manifest);
+        assert_eq!(read.dataset_hash, Some(hash_bytes(b"{}\n")));
+        assert_eq!(read.config["batch_size"], 32);
+
+        let mut resumed = RunManifest::new("train", 42);
+        resumed.set_config(&serde_json::json!({"batch_size": 32})).unwrap();
+        resumed.dataset_hash = Some(hash_bytes(b"[]\n"));
+        resumed.write_resume(&dir, "checkpoints/last", 100).unwrap();
+        resumed.write_resume(&dir, "checkpoints/last", 200).unwrap();
+
+        let read = RunManifest::read(&dir).unwrap();
+        assert_eq!(RunManifest { resumes: Vec::new(), ..read.clone() }, manifest);
+        assert_eq!(read.resumes.iter().map(|r| r.global_step).collect::<Vec<u64>>(), vec![100, 200]);
+        assert_eq!(read.resumes[0].checkpoint, "checkpoints/last");
+        assert_eq!(read.resumes[0].dataset_hash, resumed.dataset_hash);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,251 @@
+use serde::{Serialize, Deserialize};
+use crate::result::Result;
+use crate::raw_data_entry::RawDataEntry;
+
+/// `COUNT_BUCKETS` is the number of buckets of the score and of the number of comments.
+pub const COUNT_BUCKETS: usize = 16;
+/// `RATIO_BUCKETS` is the number of buckets of the upvote ratio.
+pub const RATIO_BUCKETS: usize = 10;
+
+/// `SECONDS_PER_HOUR` is the number of seconds in an hour.
+const SECONDS_PER_HOUR: u64 = 3_600;
+/// `SECONDS_PER_DAY` is the number of seconds in a day.
+const SECONDS_PER_DAY: u64 = 86_400;
+
+/// `Metadata` are the post metadata of an entry.
+#[derive(Clone, Default, PartialEq, PartialOrd, Debug, Serialize, Deserialize)]
+pub struct Metadata {
+    pub score: u64,
+    pub num_comments: u64,
+    pub upvote_ratio: f64,
+    pub created_utc: f64,
+}
+
+/// `MetadataFeature` is a bucketed feature of the `Metadata`.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MetadataFeature {
+    /// `Score` is the log2 bucket of the score.
+    Score,
+    /// `NumComments` is the log2 bucket of the number of comments.
+    NumComments,
+    /// `UpvoteRatio` is the decile of the upvote ratio.
+    UpvoteRatio,
+    /// `Hour` is the UTC hour of the posting time.
+    Hour,
+    /// `Weekday` is the UTC weekday of the posting time, Sunday being 0.
+    Weekday,
+}
+
+impl MetadataFeature {
+    /// `name` returns the name of the `MetadataFeature`.
+    pub fn name(self) -> &'static str {
+        match self {
+            MetadataFeature::Score => "score",
+            MetadataFeature::NumComments => "num_comments",
+            MetadataFeature::UpvoteRatio => "upvote_ratio",
+            MetadataFeature::Hour => "hour",
+            MetadataFeature::Weekday => "weekday",
+        }
+    }
+
+    /// `buckets` returns the number of buckets of the `MetadataFeature`.
+    pub fn buckets(self) -> usize {
+        match self {
+            MetadataFeature::Score | MetadataFeature::NumComments => COUNT_BUCKETS,
+            MetadataFeature::UpvoteRatio => RATIO_BUCKETS,
+            MetadataFeature::Hour => 24,
+            MetadataFeature::Weekday => 7,
+        }
+    }
+}
+
+/// `count_bucket` returns the log2 bucket of `count`, 0 being the bucket of 0.
+fn count_bucket(count: u64) -> usize {
+    let bucket = (64 - count.leading_zeros()) as usize;
+    bucket.min(COUNT_BUCKETS - 1)
+}
+
+/// `bucket_token` returns the token of the `bucket` of `feature`.
+fn bucket_token(feature: MetadataFeature, bucket: usize) -> String {
+    format!("<{}:{}>", feature.name(), bucket)
+}
+
+impl Metadata {
+    /// `new` creates a new `Metadata`.
+    pub fn new() -> Metadata {
+        Metadata::default()
+    }
+
+    /// `from_raw` creates the `Metadata` of a `RawDataEntry`.
+    pub fn from_raw(rde: &RawDataEntry) -> Metadata {
+        Metadata {
+            score: rde.score,
+            num_comments: rde.num_comments,
+            upvote_ratio: rde.upvote_ratio,
+            created_utc: rde.created_utc,
+        }
+    }
+
+    /// `bucket` returns the bucket of `feature`, lower than `feature.buckets()`.
+    pub fn bucket(&self, feature: MetadataFeature) -> usize {
+        let created = self.created_utc.max(0.0) as u64;
+
+        match feature {
+            MetadataFeature::Score => count_bucket(self.score),
+            MetadataFeature::NumComments => count_bucket(self.num_comments),
+            MetadataFeature::UpvoteRatio => {
+                let bucket = (self.upvote_ratio.max(0.0) * RATIO_BUCKETS as f64) as usize;
+                bucket.min(RATIO_BUCKETS - 1)
+            },
+            MetadataFeature::Hour => ((created % SECONDS_PER_DAY) / SECONDS_PER_HOUR) as usize,
+            // 1970-01-01 was a Thursday.
+            MetadataFeature::Weekday => ((created / SECONDS_PER_DAY + 4) % 7) as usize,
+        }
+    }
+}
+
+/// `MetadataEncoding` is how the `Metadata` are fed to the model.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MetadataEncoding {
+    /// `Tokens` prefixes the sources with a token per feature bucket.
+    Tokens,
+    /// `Embeddings` feeds the feature buckets to learned embeddings of the model.
+    Embeddings,
+}
+
+impl Default for MetadataEncoding {
+    fn default() -> MetadataEncoding {
+        MetadataEncoding::Tokens
+    }
+}
+
+/// `MetadataOptions` are the metadata features the summaries are conditioned on.
+#[derive(Clone, Default, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MetadataOptions {
+    pub features: Vec<MetadataFeature>,
+    #[serde(default)]
+    pub encoding: MetadataEncoding,
+}
+
+impl MetadataOptions {
+    /// `new` creates a new `MetadataOptions`.
+    pub fn new() -> MetadataOptions {
+        MetadataOptions::default()
+    }
+
+    /// `validate` returns an error if the `MetadataOptions` are invalid.
+    pub fn validate(&self) -> Result<()> {
+        for (idx, feature) in self.features.iter().enumerate() {
+            if self.features[..idx].contains(feature) {
+                return Err(format!("duplicate feature: {}", feature.name()));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// `tokens` returns the feature bucket tokens of `metadata`, as `<name:bucket>`.
+    pub fn tokens(&self, metadata: &Metadata) -> Vec<String> {
+        self.features
+            .iter()
+            .map(|f| bucket_token(*f, metadata.bucket(*f)))
+            .collect()
+    }
+
+    /// `vocabulary_tokens` returns the tokens of every bucket of every feature.
+    pub fn vocabulary_tokens(&self) -> Vec<String> {
+        self.features
+            .iter()
+            .flat_map(|f| (0..f.buckets()).map(move |bucket| bucket_token(*f, bucket)))
+            .collect()
+    }
+
+    /// `embedding_ids` returns the feature bucket ids of `metadata` in a single embedding
+    /// table, the buckets of every feature following the ones of the previous feature.
+    pub fn embedding_ids(&self, metadata: &Metadata) -> Vec<usize> {
+        let mut offset = 0;
+
+        self.features
+            .iter()
+            .map(|f| {
+                let id = offset + metadata.bucket(*f);
+                offset += f.buckets();
+                id
+            })
+            .collect()
+    }
+
+    /// `embedding_size` returns the number of rows of the embedding table of the features.
+    pub fn embedding_size(&self) -> usize {
+        self.features.iter().map(|f| f.buckets()).sum()
+    }
+
+    /// `condition` returns `source` prefixed with the feature tokens of `metadata` with the
+    /// `Tokens` encoding, and `source` itself otherwise.
+    pub fn condition(&self, source: &[String], metadata: Option<&Metadata>) -> Vec<String> {
+        match (self.encoding, metadata) {
+            (MetadataEncoding::Tokens, Some(metadata)) => {
+                let mut tokens = self.tokens(metadata);
+                tokens.extend_from_slice(source);
+                tokens
+            },
+            _ => source.to_vec(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Metadata, MetadataEncoding, MetadataFeature, MetadataOptions, COUNT_BUCKETS};
+
+    #[test]
+    fn test_metadata_buckets() {
+        let metadata = Metadata {
+            score: 5,
+            num_comments: 0,
+            upvote_ratio: 0.97,
+            // Saturday, 2018-06-02 13:20:00 UTC.
+            created_utc: 1_527_945_600.0,
+        };
+
+        assert_eq!(metadata.bucket(MetadataFeature::Score), 3);
+        assert_eq!(metadata.bucket(MetadataFeature::NumComments), 0);
+        assert_eq!(metadata.bucket(MetadataFeature::UpvoteRatio), 9);
+        assert_eq!(metadata.bucket(MetadataFeature::Hour), 13);
+        assert_eq!(metadata.bucket(MetadataFeature::Weekday), 6);
+
+        let popular = Metadata { score: u64::MAX, ..Metadata::new() };
+        assert_eq!(popular.bucket(MetadataFeature::Score), COUNT_BUCKETS - 1);
+    }
+
+    #[test]
+    fn test_metadata_options() {
+        let metadata = Metadata { score: 5, upvote_ratio: 0.5, ..Metadata::new() };
+        let mut options = MetadataOptions {
+            features: vec![MetadataFeature::Score, MetadataFeature::UpvoteRatio],
+            encoding: MetadataEncoding::Tokens,
+        };
+        assert!(options.validate().is_ok());
+
+        let source = vec!["hello".to_string()];
+        let conditioned = options.condition(&source, Some(&metadata));
+        assert_eq!(conditioned, vec!["<score:3>", "<upvote_ratio:5>", "hello"]);
+        assert_eq!(options.condition(&source, None), source);
+
+        assert_eq!(options.embedding_ids(&metadata), vec![3, COUNT_BUCKETS + 5]);
+        assert_eq!(options.embedding_size(), COUNT_BUCKETS + 10);
+
+        let vocabulary = options.vocabulary_tokens();
+        assert_eq!(vocabulary.len(), options.embedding_size());
+        assert!(conditioned[..2].iter().all(|token| vocabulary.contains(token)));
+
+        options.encoding = MetadataEncoding::Embeddings;
+        assert_eq!(options.condition(&source, Some(&metadata)), source);
+
+        options.features.push(MetadataFeature::Score);
+        assert!(options.validate().is_err());
+    }
+}
@@ -0,0 +1,184 @@
+use serde::{Serialize, Deserialize};
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use crate::result::Result;
+use crate::event_writer::EventWriter;
+
+/// `CSV_FILE` is the name of the csv metrics log.
+pub const CSV_FILE: &str = "metrics.csv";
+/// `JSONL_FILE` is the name of the json lines metrics log.
+pub const JSONL_FILE: &str = "metrics.jsonl";
+
+/// `CSV_HEADER` is the header of the csv metrics log.
+const CSV_HEADER: &str = "step,loss,learning_rate,grad_norm,tokens_per_sec,validation_loss,validation_rouge_l,validation_rouge_l_short,validation_rouge_l_long";
+
+/// `StepMetrics` are the metrics of a training step.
+#[derive(Clone, Copy, Default, PartialEq, Debug, Serialize, Deserialize)]
+pub struct StepMetrics {
+    pub step: u64,
+    pub loss: f64,
+    pub learning_rate: f64,
+    pub grad_norm: f64,
+    pub tokens_per_sec: f64,
+    /// `validation_loss` is set on the steps where a validation was run.
+    pub validation_loss: Option<f64>,
+    /// `validation_rouge_l` is set on the steps where a validation was run.
+    pub validation_rouge_l: Option<f64>,
+    /// `validation_rouge_l_short` is the title ROUGE-L of a multi-task validation.
+    #[serde(default)]
+    pub validation_rouge_l_short: Option<f64>,
+    /// `validation_rouge_l_long` is the tl;dr ROUGE-L of a multi-task validation.
+    #[serde(default)]
+    pub validation_rouge_l_long: Option<f64>,
+}
+
+impl StepMetrics {
+    /// `new` creates a new `StepMetrics`.
+    pub fn new() -> StepMetrics {
+        StepMetrics::default()
+    }
+
+    /// `to_csv_record` returns the `StepMetrics` as a csv record.
+    pub fn to_csv_record(&self) -> String {
+        let optional = |v: Option<f64>| v.map(|v| v.to_string()).unwrap_or_default();
+
+        format!("{},{},{},{},{},{},{},{},{}",
+            self.step,
+            self.loss,
+            self.learning_rate,
+            self.grad_norm,
+            self.tokens_per_sec,
+            optional(self.validation_loss),
+            optional(self.validation_rouge_l),
+            optional(self.validation_rouge_l_short),
+            optional(self.validation_rouge_l_long))
+    }
+
+    /// `scalars` returns the `StepMetrics` as TensorBoard scalar summaries.
+    pub fn scalars(&self) -> Vec<(&'static str, f32)> {
+        let mut scalars = vec![
+            ("train/loss", self.loss as f32),
+            ("train/learning_rate", self.learning_rate as f32),
+            ("train/grad_norm", self.grad_norm as f32),
+            ("train/tokens_per_sec", self.tokens_per_sec as f32),
+        ];
+
+        if let Some(loss) = self.validation_loss {
+            scalars.push(("validation/loss", loss as f32));
+        }
+
+        if let Some(rouge_l) = self.validation_rouge_l {
+            scalars.push(("validation/rouge_l", rouge_l as f32));
+        }
+
+        if let Some(rouge_l) = self.validation_rouge_l_short {
+            scalars.push(("validation/rouge_l_short", rouge_l as f32));
+        }
+
+        if let Some(rouge_l) = self.validation_rouge_l_long {
+            scalars.push(("validation/rouge_l_long", rouge_l as f32));
+        }
+
+        scalars
+    }
+}
+
+/// `MetricsWriter` writes the `StepMetrics` of a run to a csv log, a json lines log and a
+/// TensorBoard event file in the same directory.
+pub struct MetricsWriter {
+    csv: BufWriter<File>,
+    jsonl: BufWriter<File>,
+    events: EventWriter,
+}
+
+impl MetricsWriter {
+    /// `new` creates a new `MetricsWriter` in the directory `dir`, creating it if missing.
+    /// The csv and json lines logs are appended to, so a resumed run continues the same logs.
+    pub fn new<P: AsRef<Path>>(dir: P) -> Result<MetricsWriter> {
+        fs::create_dir_all(&dir).map_err(|e| format!("{}", e))?;
+
+        let csv_path = dir.as_ref().join(CSV_FILE);
+        let new_csv = !csv_path.exists();
+        let mut csv = BufWriter::new(MetricsWriter::open_append(&csv_path)?);
+        if new_csv {
+            writeln!(csv, "{}", CSV_HEADER).map_err(|e| format!("{}", e))?;
+        }
+
+        let jsonl = BufWriter::new(MetricsWriter::open_append(&dir.as_ref().join(JSONL_FILE))?);
+        let events = EventWriter::new(&dir)?;
+
+        Ok(MetricsWriter { csv, jsonl, events })
+    }
+
+    /// `open_append` opens the file at `path` in append mode, creating it if missing.
+    fn open_append(path: &Path) -> Result<File> {
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| format!("{}", e))
+    }
+
+    /// `write` writes `metrics` to all the logs.
+    pub fn write(&mut self, metrics: &StepMetrics) -> Result<()> {
+        writeln!(self.csv, "{}", metrics.to_csv_record()).map_err(|e| format!("{}", e))?;
+
+        let json = serde_json::to_string(metrics).map_err(|e| format!("{}", e))?;
+        writeln!(self.jsonl, "{}", json).map_err(|e| format!("{}", e))?;
+
+        self.events.write_scalars(metrics.step, &metrics.scalars())
+    }
+
+    /// `flush` flushes all the logs.
+    pub fn flush(&mut self) -> Result<()> {
+        self.csv.flush().map_err(|e| format!("{}", e))?;
+        self.jsonl.flush().map_err(|e| format!("{}", e))?;
+        self.events.flush()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{MetricsWriter, StepMetrics, CSV_FILE, JSONL_FILE};
+    use std::env;
+    use std::fs;
+
+    #[test]
+    fn test_metrics_writer_write() {
+        let mut dir = env::temp_dir();
+        dir.push("mmn_test_metrics_writer_write");
+        let _ = fs::remove_dir_all(&dir);
+
+        let mut metrics = StepMetrics::new();
+        metrics.step = 10;
+        metrics.loss = 2.5;
+        metrics.learning_rate = 0.001;
+
+        let mut writer = MetricsWriter::new(&dir).unwrap();
+        writer.write(&metrics).unwrap();
+        writer.flush().unwrap();
+
+        metrics.step = 20;
+        metrics.validation_rouge_l = Some(0.25);
+
+        let mut writer = MetricsWriter::new(&dir).unwrap();
+        writer.write(&metrics).unwrap();
+        writer.flush().unwrap();
+
+        let csv = fs::read_to_string(dir.join(CSV_FILE)).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[1], "10,2.5,0.001,0,0,,,,");
+        assert_eq!(lines[2], "20,2.5,0.001,0,0,,0.25,,");
+
+        let jsonl = fs::read_to_string(dir.join(JSONL_FILE)).unwrap();
+        let entries: Vec<StepMetrics> = jsonl.lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1], metrics);
+
+        assert_eq!(metrics.scalars().len(), 5);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,145 @@
+use crate::variables::{add_outer, add_scaled, Variables};
+
+/// `ConvLayer` is a residual dilated convolution layer of the multi-level memory encoder: the
+/// output at a position is its input plus the tanh of the kernel applied to the inputs around
+/// it, `rate` positions apart, the positions out of the sequence reading zeros.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ConvLayer {
+    /// `weights` is the index of the `[size, kernel_size * size]` kernel variable.
+    pub weights: usize,
+    /// `bias` is the index of the `[size]` bias variable.
+    pub bias: usize,
+    /// `rate` is the dilation rate of the layer.
+    pub rate: usize,
+}
+
+impl ConvLayer {
+    /// `kernel` returns the kernel offsets of the layer, centered on zero.
+    fn kernel(&self, variables: &Variables) -> Vec<isize> {
+        let size = variables.get(self.bias).len();
+        let kernel_size = variables.get(self.weights).cols() / size;
+
+        (0..kernel_size)
+            .map(|j| (j as isize - (kernel_size / 2) as isize) * self.rate as isize)
+            .collect()
+    }
+
+    /// `window` returns the concatenated inputs of the kernel `offsets` around the position `t`
+    /// of `x`.
+    fn window(offsets: &[isize], x: &[Vec<f32>], t: usize, size: usize) -> Vec<f32> {
+        let mut window = vec![0.0; offsets.len() * size];
+
+        for (j, offset) in offsets.iter().enumerate() {
+            let pos = t as isize + offset;
+            if pos >= 0 && (pos as usize) < x.len() {
+                window[j * size..(j + 1) * size].copy_from_slice(&x[pos as usize]);
+            }
+        }
+
+        window
+    }
+
+    /// `forward` returns the outputs of the layer reading `x`.
+    pub fn forward(&self, variables: &Variables, x: &[Vec<f32>]) -> Vec<Vec<f32>> {
+        let offsets = self.kernel(variables);
+        let size = variables.get(self.bias).len();
+
+        (0..x.len())
+            .map(|t| {
+                let mut y = variables.get(self.weights).matvec(&ConvLayer::window(&offsets, x, t, size));
+                add_scaled(&mut y, &variables.get(self.bias).data, 1.0);
+
+                for (y, x) in y.iter_mut().zip(x[t].iter()) {
+                    *y = y.tanh() + x;
+                }
+
+                y
+            })
+            .collect()
+    }
+
+    /// `backward` adds to `grads` the gradients of the layer reading `x` with the outputs `y`,
+    /// `d_y` being the gradients of the outputs. It returns the gradients of `x`.
+    pub fn backward(&self, variables: &Variables, x: &[Vec<f32>], y: &[Vec<f32>], d_y: &[Vec<f32>], grads: &mut [Vec<f32>]) -> Vec<Vec<f32>> {
+        let offsets = self.kernel(variables);
+        let size = variables.get(self.bias).len();
+        let mut d_x = d_y.to_vec();
+
+        for t in 0..x.len() {
+            let d_pre: Vec<f32> = d_y[t]
+                .iter()
+                .zip(y[t].iter().zip(x[t].iter()))
+                .map(|(d, (y, x))| d * (1.0 - (y - x) * (y - x)))
+                .collect();
+
+            add_outer(&mut grads[self.weights], &d_pre, &ConvLayer::window(&offsets, x, t, size));
+            add_scaled(&mut grads[self.bias], &d_pre, 1.0);
+
+            let d_window = variables.get(self.weights).matvec_transposed(&d_pre);
+            for (j, offset) in offsets.iter().enumerate() {
+                let pos = t as isize + offset;
+                if pos >= 0 && (pos as usize) < x.len() {
+                    add_scaled(&mut d_x[pos as usize], &d_window[j * size..(j + 1) * size], 1.0);
+                }
+            }
+        }
+
+        d_x
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ConvLayer;
+    use crate::seed::StreamRng;
+    use crate::variables::{dot, Tensor, Variables};
+
+    #[test]
+    fn test_mmn_conv_layer() {
+        let mut rng = StreamRng::new(0);
+        let mut variables = Variables::new();
+        let layer = ConvLayer {
+            weights: variables.insert("weights", Tensor::uniform(&[3, 9], 0.5, &mut rng)).unwrap(),
+            bias: variables.insert("bias", Tensor::uniform(&[3], 0.5, &mut rng)).unwrap(),
+            rate: 2,
+        };
+        let x: Vec<Vec<f32>> = (0..5).map(|t| vec![0.1 * t as f32, -0.2, 0.3]).collect();
+        let d_y: Vec<Vec<f32>> = (0..5).map(|t| vec![1.0, 0.5 * t as f32, -1.0]).collect();
+
+        let y = layer.forward(&variables, &x);
+        assert_eq!(y.len(), x.len());
+        assert!(y.iter().zip(x.iter()).all(|(y, x)| y.iter().zip(x.iter()).all(|(y, x)| (y - x).abs() < 1.0)));
+
+        let loss = |variables: &Variables, x: &[Vec<f32>]| -> f32 {
+            layer.forward(variables, x).iter().zip(d_y.iter()).map(|(y, d)| dot(y, d)).sum()
+        };
+
+        let mut grads = variables.zero_gradients();
+        let d_x = layer.backward(&variables, &x, &y, &d_y, &mut grads);
+
+        let eps = 1e-2;
+        for (idx, grad) in grads.iter().enumerate() {
+            for (i, analytic) in grad.iter().enumerate() {
+                let mut plus = variables.clone();
+                plus.get_mut(idx).data[i] += eps;
+                let mut minus = variables.clone();
+                minus.get_mut(idx).data[i] -= eps;
+
+                let numeric = (loss(&plus, &x) - loss(&minus, &x)) / (2.0 * eps);
+                assert!((numeric - analytic).abs() < 1e-2, "{}[{}]: {} != {}", idx, i, numeric, analytic);
+            }
+        }
+
+        for t in 0..x.len() {
+            for i in 0..3 {
+                let mut plus = x.clone();
+                plus[t][i] += eps;
+                let mut minus = x.clone();
+                minus[t][i] -= eps;
+
+                let numeric = (loss(&variables, &plus) - loss(&variables, &minus)) / (2.0 * eps);
+                assert!((numeric - d_x[t][i]).abs() < 1e-2, "x[{}][{}]: {} != {}", t, i, numeric, d_x[t][i]);
+            }
+        }
+    }
+}
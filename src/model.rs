@@ -0,0 +1,120 @@
+use std::path::Path;
+use crate::result::Result;
+use crate::config::{Architecture, Config};
+use crate::vocabulary::Vocabulary;
+use crate::bpe::Bpe;
+use crate::seed::Seeds;
+use crate::lead_summarizer::{LeadSummarizer, LEAD_FILE};
+use crate::seq2seq::Seq2Seq;
+use crate::summarizer::{Batch, DecodeOptions, Hypothesis, Summarizer, TrainStepOptions, TrainStepStats};
+
+/// `Model` is any of the `Summarizer`s of the crate, built from the architecture of a config
+/// or loaded from a model directory.
+#[derive(Clone, PartialEq, Debug)]
+pub enum Model {
+    Lead(LeadSummarizer),
+    Seq2Seq(Box<Seq2Seq>),
+    /// `Mmn` is the multi-level memory network, a `Seq2Seq` with the dilated convolution
+    /// encoder of the `Mmn` architecture.
+    Mmn(Box<Seq2Seq>),
+}
+
+impl Model {
+    /// `new` creates a new `Model` of the `config` architecture over `vocabulary`, with the
+    /// `bpe` merges of the `Bpe` tokenization. The lead baseline ignores both.
+    pub fn new(config: &Config, vocabulary: Vocabulary, bpe: Option<Bpe>, seeds: &Seeds) -> Result<Model> {
+        match config.model.architecture {
+            Architecture::Lead => Ok(Model::Lead(LeadSummarizer::new())),
+            Architecture::Rnn => Ok(Model::Seq2Seq(Box::new(Seq2Seq::new(config, vocabulary, bpe, seeds)?))),
+            Architecture::Mmn => Ok(Model::Mmn(Box::new(Seq2Seq::new(config, vocabulary, bpe, seeds)?))),
+        }
+    }
+
+    /// `seq2seq` returns the `Seq2Seq` of the neural models, with their vocabulary.
+    pub fn seq2seq(&self) -> Option<&Seq2Seq> {
+        match self {
+            Model::Lead(_) => None,
+            Model::Seq2Seq(model) | Model::Mmn(model) => Some(model),
+        }
+    }
+}
+
+impl Summarizer for Model {
+    fn name(&self) -> &str {
+        match self {
+            Model::Lead(model) => model.name(),
+            Model::Seq2Seq(model) | Model::Mmn(model) => model.name(),
+        }
+    }
+
+    fn train_step(&mut self, batch: &Batch, options: &TrainStepOptions) -> Result<TrainStepStats> {
+        match self {
+            Model::Lead(model) => model.train_step(batch, options),
+            Model::Seq2Seq(model) | Model::Mmn(model) => model.train_step(batch, options),
+        }
+    }
+
+    fn loss(&self, batch: &Batch) -> Result<TrainStepStats> {
+        match self {
+            Model::Lead(model) => model.loss(batch),
+            Model::Seq2Seq(model) | Model::Mmn(model) => model.loss(batch),
+        }
+    }
+
+    fn decode(&self, batch: &Batch, options: &DecodeOptions) -> Result<Vec<Hypothesis>> {
+        match self {
+            Model::Lead(model) => model.decode(batch, options),
+            Model::Seq2Seq(model) | Model::Mmn(model) => model.decode(batch, options),
+        }
+    }
+
+    fn save(&self, dir: &Path) -> Result<()> {
+        match self {
+            Model::Lead(model) => model.save(dir),
+            Model::Seq2Seq(model) | Model::Mmn(model) => model.save(dir),
+        }
+    }
+
+    /// `load` loads the `Model` saved in the directory `dir`, whatever its architecture.
+    fn load(dir: &Path) -> Result<Model> {
+        if dir.join(LEAD_FILE).exists() {
+            Ok(Model::Lead(LeadSummarizer::load(dir)?))
+        } else {
+            let model = Box::new(Seq2Seq::load(dir)?);
+            match model.config.model.architecture {
+                Architecture::Mmn => Ok(Model::Mmn(model)),
+                _ => Ok(Model::Seq2Seq(model)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Model;
+    use crate::config::{Architecture, Config};
+    use crate::seed::Seeds;
+    use crate::summarizer::Summarizer;
+    use crate::vocabulary::Vocabulary;
+    use std::env;
+    use std::fs;
+
+    #[test]
+    fn test_model_architectures() {
+        let mut config = Config::tifu_short();
+        config.model.embedding_size = 4;
+
+        let mut dir = env::temp_dir();
+        dir.push("mmn_test_model_architectures");
+
+        for (architecture, name) in [(Architecture::Rnn, "seq2seq"), (Architecture::Mmn, "mmn"), (Architecture::Lead, "lead")].iter() {
+            config.model.architecture = *architecture;
+            let model = Model::new(&config, Vocabulary::new(), None, &Seeds::new(0)).unwrap();
+            assert_eq!(model.name(), *name);
+
+            model.save(&dir).unwrap();
+            assert_eq!(Model::load(&dir).unwrap(), model);
+            fs::remove_dir_all(&dir).unwrap();
+        }
+    }
+}
@@ -0,0 +1,438 @@
+use serde::{Serialize, Deserialize};
+use rand::Rng;
+use rand::seq::SliceRandom;
+use crate::result::Result;
+use crate::augmentation::Augmenter;
+use crate::data_entry::DataEntry;
+use crate::data_entries::DataEntries;
+use crate::raw_data_entry::RawDataEntry;
+use crate::raw_data_entries::RawDataEntries;
+use crate::short_data_entry::ShortDataEntry;
+use crate::short_data_entries::ShortDataEntries;
+use crate::long_data_entry::LongDataEntry;
+use crate::long_data_entries::LongDataEntries;
+use crate::rouge::{mean_rouge_l, RougeScore};
+use crate::summarizer::{Batch, DecodeOptions, Summarizer, SummaryMode};
+
+/// `SHORT_TARGET_TOKEN` is the encoder input asking for a title-like summary.
+pub const SHORT_TARGET_TOKEN: &str = "<short>";
+/// `LONG_TARGET_TOKEN` is the encoder input asking for a tl;dr-like summary.
+pub const LONG_TARGET_TOKEN: &str = "<long>";
+/// `TARGET_TOKENS` are the target-type tokens of every summary kind.
+pub const TARGET_TOKENS: [&str; 2] = [SHORT_TARGET_TOKEN, LONG_TARGET_TOKEN];
+
+/// `target_token` returns the target-type token of `mode`.
+pub fn target_token(mode: SummaryMode) -> &'static str {
+    match mode {
+        SummaryMode::Short => SHORT_TARGET_TOKEN,
+        SummaryMode::Long => LONG_TARGET_TOKEN,
+    }
+}
+
+/// `TargetConditioning` is how a multi-task model knows the summary kind to generate, given
+/// by the batch modes.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TargetConditioning {
+    /// `Token` feeds the target-type token to the encoder before the source.
+    Token,
+    /// `Heads` uses a decoder output layer per summary kind.
+    Heads,
+}
+
+impl Default for TargetConditioning {
+    fn default() -> TargetConditioning {
+        TargetConditioning::Token
+    }
+}
+
+/// `MultiTaskOptions` are the options of the joint training on the title and tl;dr targets.
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub struct MultiTaskOptions {
+    /// `short_ratio` is the fraction of the entries of a batch with a title target.
+    pub short_ratio: f64,
+}
+
+impl MultiTaskOptions {
+    /// `new` creates a new `MultiTaskOptions`.
+    pub fn new() -> MultiTaskOptions {
+        MultiTaskOptions::default()
+    }
+
+    /// `validate` returns an error if the `MultiTaskOptions` are invalid.
+    pub fn validate(&self) -> Result<()> {
+        if !(0.0..=1.0).contains(&self.short_ratio) {
+            return Err(format!("invalid short_ratio: {}", self.short_ratio));
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for MultiTaskOptions {
+    fn default() -> MultiTaskOptions {
+        MultiTaskOptions {
+            short_ratio: 0.5,
+        }
+    }
+}
+
+/// `MultiTaskEntries` are the title and tl;dr entries of the same raw entries.
+#[derive(Clone, Default, PartialEq, Debug)]
+pub struct MultiTaskEntries {
+    pub short: ShortDataEntries,
+    pub long: LongDataEntries,
+}
+
+impl MultiTaskEntries {
+    /// `from_raw` creates the `MultiTaskEntries` of `raw`. The tl;dr entries are only
+    /// created for the raw entries with a tl;dr.
+    pub fn from_raw(raw: &RawDataEntries) -> MultiTaskEntries {
+        MultiTaskEntries {
+            short: raw.iter().map(ShortDataEntry::from_raw).collect(),
+            long: raw
+                .iter()
+                .filter(|rde| rde.tldr_tokenized.is_some())
+                .map(LongDataEntry::from_raw)
+                .collect(),
+        }
+    }
+
+    /// `into_tasks` returns the title and tl;dr entries as `TaskEntry`s. The entries of the
+    /// same raw entry share its id, so they are assigned to the same split.
+    pub fn into_tasks(self) -> DataEntries<TaskEntry> {
+        self.short
+            .into_iter()
+            .map(TaskEntry::Short)
+            .chain(self.long.into_iter().map(TaskEntry::Long))
+            .collect()
+    }
+}
+
+/// `TaskEntry` is an entry of the multi-task training, with a title or a tl;dr target.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(tag = "mode", content = "entry", rename_all = "snake_case")]
+pub enum TaskEntry {
+    Short(ShortDataEntry),
+    Long(LongDataEntry),
+}
+
+impl Default for TaskEntry {
+    fn default() -> TaskEntry {
+        TaskEntry::Short(ShortDataEntry::default())
+    }
+}
+
+impl DataEntry for TaskEntry {
+    /// `from_raw` creates the title `TaskEntry` of a `RawDataEntry`. Both entries of a raw
+    /// entry are created by `MultiTaskEntries::from_raw`.
+    fn from_raw(rde: &RawDataEntry) -> TaskEntry {
+        TaskEntry::Short(ShortDataEntry::from_raw(rde))
+    }
+
+    /// `id` returns the id of the `TaskEntry`.
+    fn id(&self) -> &str {
+        match self {
+            TaskEntry::Short(entry) => entry.id(),
+            TaskEntry::Long(entry) => entry.id(),
+        }
+    }
+
+    /// `source` returns the source text of the `TaskEntry`.
+    fn source(&self) -> &str {
+        match self {
+            TaskEntry::Short(entry) => entry.source(),
+            TaskEntry::Long(entry) => entry.source(),
+        }
+    }
+
+    /// `source_mut` returns a mutable reference to the source text of the `TaskEntry`.
+    fn source_mut(&mut self) -> &mut String {
+        match self {
+            TaskEntry::Short(entry) => entry.source_mut(),
+            TaskEntry::Long(entry) => entry.source_mut(),
+        }
+    }
+
+    /// `source_tokenized` returns the source tokens of the `TaskEntry`.
+    fn source_tokenized(&self) -> &[String] {
+        match self {
+            TaskEntry::Short(entry) => entry.source_tokenized(),
+            TaskEntry::Long(entry) => entry.source_tokenized(),
+        }
+    }
+
+    /// `source_tokenized_mut` returns a mutable reference to the source tokens of the `TaskEntry`.
+    fn source_tokenized_mut(&mut self) -> &mut Vec<String> {
+        match self {
+            TaskEntry::Short(entry) => entry.source_tokenized_mut(),
+            TaskEntry::Long(entry) => entry.source_tokenized_mut(),
+        }
+    }
+
+    /// `summary_tokenized` returns the summary tokens of the `TaskEntry`.
+    fn summary_tokenized(&self) -> &[String] {
+        match self {
+            TaskEntry::Short(entry) => entry.summary_tokenized(),
+            TaskEntry::Long(entry) => entry.summary_tokenized(),
+        }
+    }
+
+    /// `source_sentences_mut` returns a mutable reference to the source sentences of the `TaskEntry`.
+    fn source_sentences_mut(&mut self) -> Option<&mut Vec<Vec<String>>> {
+        match self {
+            TaskEntry::Short(entry) => entry.source_sentences_mut(),
+            TaskEntry::Long(entry) => entry.source_sentences_mut(),
+        }
+    }
+
+    /// `split_source_sentences` splits the source tokens of the `TaskEntry` in sentences.
+    fn split_source_sentences(&mut self) {
+        match self {
+            TaskEntry::Short(entry) => entry.split_source_sentences(),
+            TaskEntry::Long(entry) => entry.split_source_sentences(),
+        }
+    }
+
+    /// `augmented_from` returns the id of the entry the `TaskEntry` was augmented from.
+    fn augmented_from(&self) -> Option<&str> {
+        match self {
+            TaskEntry::Short(entry) => entry.augmented_from(),
+            TaskEntry::Long(entry) => entry.augmented_from(),
+        }
+    }
+
+    /// `has_summary` returns if the `TaskEntry` has a reference summary.
+    fn has_summary(&self) -> bool {
+        match self {
+            TaskEntry::Short(entry) => entry.has_summary(),
+            TaskEntry::Long(entry) => entry.has_summary(),
+        }
+    }
+
+    /// `mode` returns the kind of the summary of the `TaskEntry`.
+    fn mode(&self) -> SummaryMode {
+        match self {
+            TaskEntry::Short(_) => SummaryMode::Short,
+            TaskEntry::Long(_) => SummaryMode::Long,
+        }
+    }
+
+    /// `augment` returns the entries generated from the `TaskEntry` by `augmenter`, only the
+    /// tl;dr entries being augmented.
+    fn augment(&self, augmenter: &Augmenter) -> Vec<TaskEntry> {
+        match self {
+            TaskEntry::Short(_) => Vec::new(),
+            TaskEntry::Long(entry) => augmenter.augment_entry(entry).into_iter().map(TaskEntry::Long).collect(),
+        }
+    }
+}
+
+impl From<&[TaskEntry]> for Batch {
+    fn from(entries: &[TaskEntry]) -> Batch {
+        let mut batch = Batch::new();
+
+        for entry in entries.iter() {
+            let (summary, metadata) = match entry {
+                TaskEntry::Short(entry) => (entry.summary_tokenized.to_owned(), &entry.metadata),
+                TaskEntry::Long(entry) => (entry.summary_tokenized.to_owned().unwrap_or_default(), &entry.metadata),
+            };
+
+            batch.ids.push(entry.id().to_owned());
+            batch.modes.push(entry.mode());
+            batch.sources.push(entry.source_tokenized().to_owned());
+            batch.summaries.push(summary);
+            batch.metadata.push(metadata.to_owned());
+        }
+
+        batch
+    }
+}
+
+/// `Task` is an entry of a mixed batch: the index of an entry of the `mode` entries.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct Task {
+    pub mode: SummaryMode,
+    pub idx: usize,
+}
+
+/// `mix_batches` shuffles `short_len` title entries and `long_len` tl;dr entries with `rng`
+/// in batches of `batch_size` with `short_ratio` title entries. When an entry kind runs out,
+/// the batches are filled with the other kind.
+pub fn mix_batches<R: Rng>(short_len: usize,
+                           long_len: usize,
+                           options: &MultiTaskOptions,
+                           batch_size: usize,
+                           rng: &mut R) -> Result<Vec<Vec<Task>>>
+{
+    options.validate()?;
+    if batch_size == 0 {
+        return Err("invalid batch_size: 0".to_string());
+    }
+
+    let mut short: Vec<usize> = (0..short_len).collect();
+    let mut long: Vec<usize> = (0..long_len).collect();
+    short.shuffle(rng);
+    long.shuffle(rng);
+
+    let mut short = short.into_iter().map(|idx| Task { mode: SummaryMode::Short, idx });
+    let mut long = long.into_iter().map(|idx| Task { mode: SummaryMode::Long, idx });
+
+    let short_size = (batch_size as f64 * options.short_ratio).round() as usize;
+    let mut batches = Vec::new();
+
+    loop {
+        let mut batch: Vec<Task> = short.by_ref().take(short_size).collect();
+        batch.extend(long.by_ref().take(batch_size - batch.len()));
+        batch.extend(short.by_ref().take(batch_size - batch.len()));
+
+        if batch.is_empty() {
+            break;
+        }
+
+        batch.shuffle(rng);
+        batches.push(batch);
+    }
+
+    Ok(batches)
+}
+
+/// `multi_task_batch` returns the `Batch` of the `tasks` of `entries`. The summary kind of
+/// every entry is in the batch modes, which the model is conditioned on with the
+/// `target_conditioning` of its config.
+pub fn multi_task_batch(entries: &MultiTaskEntries, tasks: &[Task]) -> Result<Batch> {
+    let mut batch = Batch::new();
+
+    for task in tasks.iter() {
+        let (id, source, summary, metadata) = match task.mode {
+            SummaryMode::Short => {
+                let entry = entries.short.as_slice().get(task.idx).ok_or_else(|| format!("invalid short index: {}", task.idx))?;
+                (&entry.id, &entry.source_tokenized, entry.summary_tokenized.to_owned(), &entry.metadata)
+            },
+            SummaryMode::Long => {
+                let entry = entries.long.as_slice().get(task.idx).ok_or_else(|| format!("invalid long index: {}", task.idx))?;
+                (&entry.id, &entry.source_tokenized, entry.summary_tokenized.to_owned().unwrap_or_default(), &entry.metadata)
+            },
+        };
+
+        batch.ids.push(id.to_owned());
+        batch.modes.push(task.mode);
+        batch.sources.push(source.to_owned());
+        batch.summaries.push(summary);
+        batch.metadata.push(metadata.to_owned());
+    }
+
+    Ok(batch)
+}
+
+/// `MultiTaskScores` are the ROUGE-L scores of every summary kind.
+#[derive(Clone, Copy, Default, PartialEq, Debug, Serialize, Deserialize)]
+pub struct MultiTaskScores {
+    pub short: RougeScore,
+    pub long: RougeScore,
+}
+
+impl MultiTaskScores {
+    /// `f1` returns the mean of the ROUGE-L F1 of both summary kinds, so that both count the
+    /// same whatever their number of entries.
+    pub fn f1(&self) -> f64 {
+        (self.short.f1 + self.long.f1) / 2.0
+    }
+}
+
+/// `evaluate_multi_task` returns the mean ROUGE-L of the summaries decoded by `summarizer`
+/// on `batches`, separately for the title and the tl;dr targets.
+pub fn evaluate_multi_task<'a, S, I>(summarizer: &S, batches: I, options: &DecodeOptions) -> Result<MultiTaskScores>
+    where S: Summarizer,
+          I: IntoIterator<Item = &'a Batch>
+{
+    let mut short = (Vec::new(), Vec::new());
+    let mut long = (Vec::new(), Vec::new());
+
+    for batch in batches {
+        let hypotheses = summarizer.decode(batch, options)?;
+        if hypotheses.len() != batch.len() || batch.modes.len() != batch.len() {
+            return Err(format!("{}: expected {} hypotheses, found {}", summarizer.name(), batch.len(), hypotheses.len()));
+        }
+
+        for ((hypothesis, reference), mode) in hypotheses.into_iter().zip(batch.summaries.iter()).zip(batch.modes.iter()) {
+            let (candidates, references) = match mode {
+                SummaryMode::Short => &mut short,
+                SummaryMode::Long => &mut long,
+            };
+
+            candidates.push(hypothesis.tokens);
+            references.push(reference.to_owned());
+        }
+    }
+
+    Ok(MultiTaskScores {
+        short: mean_rouge_l(&short.0, &short.1),
+        long: mean_rouge_l(&long.0, &long.1),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::{evaluate_multi_task, mix_batches, multi_task_batch, MultiTaskEntries, MultiTaskOptions};
+    use crate::lead_summarizer::LeadSummarizer;
+    use crate::long_data_entry::LongDataEntry;
+    use crate::seed::Seeds;
+    use crate::short_data_entry::ShortDataEntry;
+    use crate::summarizer::{DecodeOptions, SummaryMode};
+
+    fn tokens(s: &str) -> Vec<String> {
+        s.split_whitespace().map(ToOwned::to_owned).collect()
+    }
+
+    #[test]
+    fn test_multi_task_mix_batches() {
+        let options = MultiTaskOptions { short_ratio: 0.75 };
+        let batches = mix_batches(10, 6, &options, 4, &mut Seeds::rng(0)).unwrap();
+
+        assert_eq!(batches.len(), 4);
+        assert!(batches.iter().all(|b| b.len() == 4));
+
+        let short = |b: &Vec<super::Task>| b.iter().filter(|t| t.mode == SummaryMode::Short).count();
+        assert_eq!(short(&batches[0]), 3);
+        assert_eq!(batches.iter().map(short).sum::<usize>(), 10);
+
+        let mut long: Vec<usize> = batches.iter().flatten().filter(|t| t.mode == SummaryMode::Long).map(|t| t.idx).collect();
+        long.sort();
+        assert_eq!(long, (0..6).collect::<Vec<usize>>());
+
+        assert!(mix_batches(1, 1, &MultiTaskOptions { short_ratio: 2.0 }, 4, &mut Seeds::rng(0)).is_err());
+    }
+
+    #[test]
+    fn test_multi_task_evaluate() {
+        let mut short = ShortDataEntry::new();
+        short.id = "a".to_string();
+        short.source_tokenized = tokens("my cat ate my homework");
+        short.summary_tokenized = tokens("my cat");
+
+        let mut long = LongDataEntry::new();
+        long.id = "a".to_string();
+        long.source_tokenized = short.source_tokenized.clone();
+        long.summary_tokenized = Some(tokens("cat ate homework"));
+
+        let entries = MultiTaskEntries {
+            short: vec![short].into(),
+            long: vec![long].into(),
+        };
+
+        let tasks = mix_batches(1, 1, &MultiTaskOptions::default(), 2, &mut Seeds::rng(0)).unwrap();
+        let batch = multi_task_batch(&entries, &tasks[0]).unwrap();
+        assert_eq!(batch.len(), 2);
+        assert!(batch.modes.contains(&SummaryMode::Long));
+        assert!(batch.sources.iter().all(|source| *source == entries.short[0].source_tokenized));
+
+        let mut lead = LeadSummarizer::new();
+        lead.summaries = 1;
+        lead.summary_tokens = 2;
+
+        let scores = evaluate_multi_task(&lead, &[batch], &DecodeOptions::default()).unwrap();
+        assert_eq!(scores.short.f1, 1.0);
+        assert!(scores.long.f1 > 0.0 && scores.long.f1 < 1.0);
+    }
+}
@@ -0,0 +1,311 @@
+use serde::{Serialize, Deserialize};
+use regex::{Captures, Regex};
+use rayon::prelude::*;
+use unicode_normalization::UnicodeNormalization;
+use std::char;
+use crate::data_entry::DataEntry;
+use crate::data_entries::DataEntries;
+
+/// `URL_TOKEN` is the placeholder token replacing urls.
+pub const URL_TOKEN: &str = "<url>";
+/// `USER_TOKEN` is the placeholder token replacing Reddit usernames.
+pub const USER_TOKEN: &str = "<user>";
+/// `SUBREDDIT_TOKEN` is the placeholder token replacing subreddit mentions.
+pub const SUBREDDIT_TOKEN: &str = "<subreddit>";
+
+/// `NormalizeOptions` are the steps run by a `Normalizer`, in the order in which they are run.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NormalizeOptions {
+    pub strip_html_tags: bool,
+    pub decode_html_entities: bool,
+    pub strip_markdown: bool,
+    pub replace_urls: bool,
+    pub replace_usernames: bool,
+    pub replace_subreddits: bool,
+    pub normalize_unicode: bool,
+    pub lowercase: bool,
+    pub collapse_whitespace: bool,
+}
+
+impl NormalizeOptions {
+    /// `new` creates a new `NormalizeOptions`.
+    pub fn new() -> NormalizeOptions {
+        NormalizeOptions::default()
+    }
+}
+
+impl Default for NormalizeOptions {
+    fn default() -> NormalizeOptions {
+        NormalizeOptions {
+            strip_html_tags: true,
+            decode_html_entities: true,
+            strip_markdown: true,
+            replace_urls: true,
+            replace_usernames: true,
+            replace_subreddits: true,
+            normalize_unicode: true,
+            lowercase: false,
+            collapse_whitespace: true,
+        }
+    }
+}
+
+/// `named_entity` returns the character of a named html entity.
+fn named_entity(name: &str) -> Option<&'static str> {
+    let c = match name {
+        "amp" => "&",
+        "lt" => "<",
+        "gt" => ">",
+        "quot" => "\"",
+        "apos" => "'",
+        "nbsp" => " ",
+        "hellip" => "...",
+        "ndash" => "-",
+        "mdash" => "-",
+        "lsquo" | "rsquo" => "'",
+        "ldquo" | "rdquo" => "\"",
+        "copy" => "©",
+        "reg" => "®",
+        "trade" => "™",
+        _ => return None,
+    };
+
+    Some(c)
+}
+
+/// `decode_entity` decodes the html entity captured by `Normalizer::entity`,
+/// keeping it unchanged if unknown.
+fn decode_entity(caps: &Captures) -> String {
+    let name = &caps[1];
+
+    let decoded = if let Some(hex) = name.strip_prefix("#x").or_else(|| name.strip_prefix("#X")) {
+        u32::from_str_radix(hex, 16).ok().and_then(char::from_u32).map(|c| c.to_string())
+    } else if let Some(dec) = name.strip_prefix('#') {
+        dec.parse::<u32>().ok().and_then(char::from_u32).map(|c| c.to_string())
+    } else {
+        named_entity(name).map(ToOwned::to_owned)
+    };
+
+    decoded.unwrap_or_else(|| caps[0].to_owned())
+}
+
+/// `normalize_char` maps typographic characters to their ascii counterparts,
+/// removing the zero-width ones.
+fn normalize_char(c: char, s: &mut String) {
+    match c {
+        '\u{2018}' | '\u{2019}' | '\u{201a}' | '\u{201b}' | '\u{2032}' => s.push('\''),
+        '\u{201c}' | '\u{201d}' | '\u{201e}' | '\u{201f}' | '\u{2033}' => s.push('"'),
+        '\u{2010}'..='\u{2015}' | '\u{2212}' => s.push('-'),
+        '\u{200b}' | '\u{200c}' | '\u{200d}' | '\u{2060}' | '\u{feff}' => {},
+        c => s.push(c),
+    }
+}
+
+/// `Normalizer` normalizes Reddit texts before tokenization.
+#[derive(Clone, Debug)]
+pub struct Normalizer {
+    options: NormalizeOptions,
+    html_tag: Regex,
+    entity: Regex,
+    markdown: Vec<(Regex, &'static str)>,
+    url: Regex,
+    username: Regex,
+    subreddit: Regex,
+}
+
+impl Normalizer {
+    /// `new` creates a new `Normalizer` running the steps enabled in `options`.
+    pub fn new(options: NormalizeOptions) -> Normalizer {
+        let markdown = vec![
+            (r"(?m)^[ \t]*(?:[-*_][ \t]*){3,}$", ""),
+            (r"(?m)^[ \t]{0,3}#{1,6}[ \t]+", ""),
+            (r"(?m)^[ \t]*(?:>[ \t]?)+", ""),
+            (r"(?m)^[ \t]*(?:[-*+]|\d+\.)[ \t]+", ""),
+            (r"!?\[([^\]]*)\]\([^)]*\)", "$1"),
+            (r"\*\*([^*]+)\*\*", "$1"),
+            (r"__([^_]+)__", "$1"),
+            (r"\*([^*\n]+)\*", "$1"),
+            (r"\b_([^_\n]+)_\b", "$1"),
+            (r"~~([^~]+)~~", "$1"),
+            (r"`([^`]*)`", "$1"),
+            (r"\^\(([^)]*)\)", "$1"),
+            (r"\\([\\`*_{}\[\]()#+\-.!>~^])", "$1"),
+        ];
+
+        Normalizer {
+            options,
+            html_tag: Regex::new(r"(?s)<!--.*?-->|</?[a-zA-Z][^<>]*>").unwrap(),
+            entity: Regex::new(r"&(#[0-9]{1,7}|#[xX][0-9a-fA-F]{1,6}|[a-zA-Z]{2,8});").unwrap(),
+            markdown: markdown.into_iter()
+                .map(|(re, rep)| (Regex::new(re).unwrap(), rep))
+                .collect(),
+            url: Regex::new(r"(?i)\b(?:https?://|www\.)[^\s<>()\[\]]+").unwrap(),
+            username: Regex::new(r"(?i)/?\bu/[a-z0-9_-]+").unwrap(),
+            subreddit: Regex::new(r"(?i)/?\br/[a-z0-9_]+").unwrap(),
+        }
+    }
+
+    /// `options` returns the `NormalizeOptions` of the `Normalizer`.
+    pub fn options(&self) -> &NormalizeOptions {
+        &self.options
+    }
+
+    /// `normalize` returns the normalized `text`.
+    pub fn normalize(&self, text: &str) -> String {
+        let mut s = text.to_owned();
+
+        if self.options.strip_html_tags {
+            s = self.html_tag.replace_all(&s, " ").into_owned();
+        }
+
+        if self.options.decode_html_entities {
+            s = self.entity.replace_all(&s, decode_entity).into_owned();
+        }
+
+        if self.options.strip_markdown {
+            for (re, rep) in self.markdown.iter() {
+                s = re.replace_all(&s, *rep).into_owned();
+            }
+        }
+
+        if self.options.replace_urls {
+            s = self.url.replace_all(&s, URL_TOKEN).into_owned();
+        }
+
+        if self.options.replace_usernames {
+            s = self.username.replace_all(&s, USER_TOKEN).into_owned();
+        }
+
+        if self.options.replace_subreddits {
+            s = self.subreddit.replace_all(&s, SUBREDDIT_TOKEN).into_owned();
+        }
+
+        if self.options.normalize_unicode {
+            let mut normalized = String::with_capacity(s.len());
+            for c in s.nfkc() {
+                normalize_char(c, &mut normalized);
+            }
+            s = normalized;
+        }
+
+        if self.options.lowercase {
+            s = s.to_lowercase();
+        }
+
+        if self.options.collapse_whitespace {
+            s = s.split_whitespace().collect::<Vec<&str>>().join(" ");
+        }
+
+        s
+    }
+
+    /// `normalize_tokens` normalizes every token of `tokens`, splitting the tokens containing
+    /// whitespace once normalized and dropping the empty ones.
+    pub fn normalize_tokens(&self, tokens: &[String]) -> Vec<String> {
+        tokens
+            .iter()
+            .flat_map(|token| {
+                self.normalize(token)
+                    .split_whitespace()
+                    .map(ToOwned::to_owned)
+                    .collect::<Vec<String>>()
+            })
+            .collect()
+    }
+
+    /// `normalize_entry` normalizes the source text and the source tokens of `entry`, splitting
+    /// again the source sentences of the entries that have them.
+    pub fn normalize_entry<T: DataEntry>(&self, entry: &mut T) {
+        let source = self.normalize(entry.source());
+        *entry.source_mut() = source;
+
+        let tokens = self.normalize_tokens(entry.source_tokenized_mut());
+        *entry.source_tokenized_mut() = tokens;
+
+        if entry.source_sentences_mut().map(|sentences| !sentences.is_empty()).unwrap_or(false) {
+            entry.split_source_sentences();
+        }
+    }
+
+    /// `normalize_entries` normalizes in parallel the sources of `entries`.
+    pub fn normalize_entries<T: DataEntry + Send>(&self, entries: &mut DataEntries<T>) {
+        entries.par_iter_mut()
+            .for_each(|entry| self.normalize_entry(entry));
+    }
+}
+
+impl Default for Normalizer {
+    fn default() -> Normalizer {
+        Normalizer::new(NormalizeOptions::default())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Normalizer, NormalizeOptions};
+    use crate::data_entry::DataEntry;
+    use crate::data_entries::DataEntries;
+    use crate::short_data_entry::ShortDataEntry;
+
+    #[test]
+    fn test_normalizer_html() {
+        let normalizer = Normalizer::default();
+
+        let text = "<!-- SC_OFF --><div class=\"md\"><p>I don&#39;t &amp; won&#x27;t &lt;3 &bogus;</p>\n</div>";
+        assert_eq!(normalizer.normalize(text), "I don't & won't <3 &bogus;");
+    }
+
+    #[test]
+    fn test_normalizer_markdown() {
+        let normalizer = Normalizer::default();
+
+        let text = "# Title\n&gt; quoted *text*\n\n* item **one**\n1. item ~~two~~\n\n***\n\nsee [this post](https://reddit.com/x) and `code`";
+        assert_eq!(normalizer.normalize(text), "Title quoted text item one item two see this post and code");
+
+        assert_eq!(normalizer.normalize("snake_case_name stays"), "snake_case_name stays");
+    }
+
+    #[test]
+    fn test_normalizer_placeholders() {
+        let normalizer = Normalizer::default();
+
+        let text = "thanks /u/some_user and u/other-user, see r/tifu at https://www.reddit.com/r/tifu or www.example.com";
+        assert_eq!(normalizer.normalize(text), "thanks <user> and <user>, see <subreddit> at <url> or <url>");
+
+        let mut options = NormalizeOptions::new();
+        options.replace_urls = false;
+        options.replace_usernames = false;
+        let normalizer = Normalizer::new(options);
+        assert_eq!(normalizer.normalize("u/name at http://x.y"), "u/name at http://x.y");
+    }
+
+    #[test]
+    fn test_normalizer_unicode_whitespace() {
+        let mut options = NormalizeOptions::new();
+        options.lowercase = true;
+        let normalizer = Normalizer::new(options);
+
+        let text = "  \u{201c}Caf\u{0065}\u{0301}\u{201d}\u{2026}\u{200b}\tit\u{2019}s   \n\n over \u{2014} done ";
+        assert_eq!(normalizer.normalize(text), "\"caf\u{e9}\"... it's over - done");
+    }
+
+    #[test]
+    fn test_normalizer_entries() {
+        let normalizer = Normalizer::default();
+
+        let mut entry = ShortDataEntry::new();
+        entry.source = "i don&#39;t   know. see r/tifu".to_string();
+        entry.source_tokenized = ["i", "don&#39;t", "know", ".", "\u{200b}", "see", "r/tifu"].iter().map(|t| t.to_string()).collect();
+        entry.source_sentences = vec![entry.source_tokenized.clone()];
+
+        let mut entries: DataEntries<ShortDataEntry> = vec![entry.clone(), entry].into();
+        normalizer.normalize_entries(&mut entries);
+
+        for entry in entries.iter() {
+            assert_eq!(entry.source(), "i don't know. see <subreddit>");
+            assert_eq!(entry.source_tokenized, vec!["i", "don't", "know", ".", "see", "<subreddit>"]);
+            assert_eq!(entry.source_sentences, vec![vec!["i", "don't", "know", "."], vec!["see", "<subreddit>"]]);
+        }
+    }
+}
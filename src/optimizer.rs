@@ -0,0 +1,178 @@
+use serde::{Serialize, Deserialize};
+use std::fs;
+use std::path::Path;
+use crate::result::Result;
+use crate::config::Optimizer;
+use crate::variables::{Tensor, Variables};
+
+/// `OPTIMIZER_FILE` is the name of the optimizer step file in a model directory.
+pub const OPTIMIZER_FILE: &str = "optimizer.json";
+/// `OPTIMIZER_SLOTS_FILE` is the name of the optimizer slots file in a model directory.
+pub const OPTIMIZER_SLOTS_FILE: &str = "optimizer.bin";
+
+/// `OptimizerStep` is the serialized step counter of an `OptimizerState`.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug, Serialize, Deserialize)]
+struct OptimizerStep {
+    step: u64,
+}
+
+/// `OptimizerState` is the state of an `Optimizer`: the number of updates applied and the
+/// slots of every variable, the first and second moments with Adam and the velocity with
+/// momentum SGD.
+#[derive(Clone, Default, PartialEq, Debug)]
+pub struct OptimizerState {
+    pub step: u64,
+    pub slots: Variables,
+}
+
+impl OptimizerState {
+    /// `new` creates the initial `OptimizerState` of `optimizer` for `variables`.
+    pub fn new(optimizer: &Optimizer, variables: &Variables) -> Result<OptimizerState> {
+        let mut slots = Variables::new();
+
+        for (idx, name) in variables.names().iter().enumerate() {
+            for slot in slot_names(optimizer) {
+                slots.insert(&format!("{}/{}", name, slot), Tensor::zeros(&variables.get(idx).shape))?;
+            }
+        }
+
+        Ok(OptimizerState { step: 0, slots })
+    }
+
+    /// `apply` updates `variables` with the gradients `grads` at `learning_rate`.
+    pub fn apply(&mut self, optimizer: &Optimizer, variables: &mut Variables, grads: &[Vec<f32>], learning_rate: f64) -> Result<()> {
+        let names = slot_names(optimizer);
+        if grads.len() != variables.len() || self.slots.len() != names.len() * variables.len() {
+            return Err(format!("invalid optimizer state: {} variables, {} gradients, {} slots",
+                variables.len(), grads.len(), self.slots.len()));
+        }
+
+        self.step += 1;
+        let lr = learning_rate as f32;
+
+        for (idx, grad) in grads.iter().enumerate() {
+            match *optimizer {
+                Optimizer::Adam { beta1, beta2, epsilon } => {
+                    let (beta1, beta2, epsilon) = (beta1 as f32, beta2 as f32, epsilon as f32);
+                    let m_correction = 1.0 - beta1.powi(self.step as i32);
+                    let v_correction = 1.0 - beta2.powi(self.step as i32);
+
+                    for (i, g) in grad.iter().enumerate() {
+                        let m = &mut self.slots.get_mut(2 * idx).data[i];
+                        *m = beta1 * *m + (1.0 - beta1) * g;
+                        let m_hat = *m / m_correction;
+
+                        let v = &mut self.slots.get_mut(2 * idx + 1).data[i];
+                        *v = beta2 * *v + (1.0 - beta2) * g * g;
+                        let v_hat = *v / v_correction;
+
+                        variables.get_mut(idx).data[i] -= lr * m_hat / (v_hat.sqrt() + epsilon);
+                    }
+                },
+                Optimizer::Sgd { momentum } if momentum > 0.0 => {
+                    let velocity = &mut self.slots.get_mut(idx).data;
+
+                    for (i, g) in grad.iter().enumerate() {
+                        velocity[i] = momentum as f32 * velocity[i] + g;
+                        variables.get_mut(idx).data[i] -= lr * velocity[i];
+                    }
+                },
+                Optimizer::Sgd { .. } => {
+                    for (w, g) in variables.get_mut(idx).data.iter_mut().zip(grad.iter()) {
+                        *w -= lr * g;
+                    }
+                },
+            }
+        }
+
+        Ok(())
+    }
+
+    /// `save` saves the `OptimizerState` in the directory `dir`.
+    pub fn save<P: AsRef<Path>>(&self, dir: P) -> Result<()> {
+        fs::create_dir_all(&dir).map_err(|e| format!("{}", e))?;
+        let contents = serde_json::to_vec(&OptimizerStep { step: self.step }).map_err(|e| format!("{}", e))?;
+        fs::write(dir.as_ref().join(OPTIMIZER_FILE), contents).map_err(|e| format!("{}", e))?;
+        self.slots.save_file(dir.as_ref().join(OPTIMIZER_SLOTS_FILE))
+    }
+
+    /// `load` loads an `OptimizerState` from the directory `dir`.
+    pub fn load<P: AsRef<Path>>(dir: P) -> Result<OptimizerState> {
+        let contents = fs::read(dir.as_ref().join(OPTIMIZER_FILE)).map_err(|e| format!("{}", e))?;
+        let step: OptimizerStep = serde_json::from_slice(&contents).map_err(|e| format!("{}", e))?;
+
+        Ok(OptimizerState {
+            step: step.step,
+            slots: Variables::load_file(dir.as_ref().join(OPTIMIZER_SLOTS_FILE))?,
+        })
+    }
+}
+
+/// `slot_names` returns the names of the slots of every variable with `optimizer`.
+fn slot_names(optimizer: &Optimizer) -> &'static [&'static str] {
+    match *optimizer {
+        Optimizer::Adam { .. } => &["m", "v"],
+        Optimizer::Sgd { momentum } if momentum > 0.0 => &["velocity"],
+        Optimizer::Sgd { .. } => &[],
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::OptimizerState;
+    use crate::config::Optimizer;
+    use crate::variables::{Tensor, Variables};
+    use std::env;
+    use std::fs;
+
+    fn variables() -> Variables {
+        let mut variables = Variables::new();
+        variables.insert("w", Tensor { shape: vec![2], data: vec![1.0, -1.0] }).unwrap();
+        variables
+    }
+
+    #[test]
+    fn test_optimizer_apply() {
+        let grads = vec![vec![0.5, -2.0]];
+
+        let sgd = Optimizer::Sgd { momentum: 0.0 };
+        let mut w = variables();
+        let mut state = OptimizerState::new(&sgd, &w).unwrap();
+        state.apply(&sgd, &mut w, &grads, 0.1).unwrap();
+        assert_eq!(w.get(0).data, vec![0.95, -0.8]);
+
+        let momentum = Optimizer::Sgd { momentum: 0.5 };
+        let mut w = variables();
+        let mut state = OptimizerState::new(&momentum, &w).unwrap();
+        state.apply(&momentum, &mut w, &grads, 1.0).unwrap();
+        state.apply(&momentum, &mut w, &grads, 1.0).unwrap();
+        assert_eq!(state.slots.get(0).data, vec![0.75, -3.0]);
+        assert_eq!(w.get(0).data, vec![-0.25, 4.0]);
+
+        let adam = Optimizer::default();
+        let mut w = variables();
+        let mut state = OptimizerState::new(&adam, &w).unwrap();
+        state.apply(&adam, &mut w, &grads, 0.01).unwrap();
+        assert_eq!(state.step, 1);
+        assert!((w.get(0).data[0] - 0.99).abs() < 1e-5);
+        assert!((w.get(0).data[1] + 0.99).abs() < 1e-5);
+
+        assert!(state.apply(&adam, &mut w, &[], 0.01).is_err());
+    }
+
+    #[test]
+    fn test_optimizer_save_load() {
+        let mut dir = env::temp_dir();
+        dir.push("mmn_test_optimizer_save_load");
+
+        let adam = Optimizer::default();
+        let mut w = variables();
+        let mut state = OptimizerState::new(&adam, &w).unwrap();
+        state.apply(&adam, &mut w, &[vec![1.0, 2.0]], 0.01).unwrap();
+
+        state.save(&dir).unwrap();
+        assert_eq!(OptimizerState::load(&dir).unwrap(), state);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,69 @@
+use serde::{Serialize, Deserialize};
+use std::fmt;
+
+/// `ParseMode` is the mode used to parse the json dataset entries.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum ParseMode {
+    /// `Strict` rejects every entry that does not follow the TIFU dataset schema.
+    Strict,
+    /// `Lenient` coerces numeric types, defaults missing metadata and keeps unknown fields,
+    /// collecting a warning for every fix.
+    Lenient,
+}
+
+impl Default for ParseMode {
+    fn default() -> ParseMode {
+        ParseMode::Strict
+    }
+}
+
+/// `ParseOptions` are the options used to parse the json dataset entries.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct ParseOptions {
+    pub mode: ParseMode,
+}
+
+impl ParseOptions {
+    /// `new` creates a new `ParseOptions`.
+    pub fn new() -> ParseOptions {
+        ParseOptions::default()
+    }
+
+    /// `strict` creates a new `ParseOptions` in `ParseMode::Strict`.
+    pub fn strict() -> ParseOptions {
+        ParseOptions { mode: ParseMode::Strict }
+    }
+
+    /// `lenient` creates a new `ParseOptions` in `ParseMode::Lenient`.
+    pub fn lenient() -> ParseOptions {
+        ParseOptions { mode: ParseMode::Lenient }
+    }
+
+    /// `is_lenient` returns if the `ParseOptions` are in `ParseMode::Lenient`.
+    pub fn is_lenient(&self) -> bool {
+        self.mode == ParseMode::Lenient
+    }
+}
+
+/// `ParseWarning` is a fix applied to a field while parsing an entry in `ParseMode::Lenient`.
+#[derive(Clone, Default, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct ParseWarning {
+    pub field: String,
+    pub reason: String,
+}
+
+impl ParseWarning {
+    /// `new` creates a new `ParseWarning`.
+    pub fn new(field: &str, reason: &str) -> ParseWarning {
+        ParseWarning {
+            field: field.to_owned(),
+            reason: reason.to_owned(),
+        }
+    }
+}
+
+impl fmt::Display for ParseWarning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} field: {}", self.field, self.reason)
+    }
+}
@@ -0,0 +1,359 @@
+use serde::{Serialize, Deserialize};
+use serde_json::Value;
+use rayon::prelude::*;
+use rayon::ThreadPoolBuilder;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Lines, Write};
+use std::path::Path;
+use crate::result::Result;
+use crate::data_entry::DataEntry;
+use crate::raw_data_entry::RawDataEntry;
+use crate::summarizer::{Batch, SummarizeResponse};
+use crate::tokenizer::tokenize;
+
+/// `PredictInput` is an entry to summarize.
+#[derive(Clone, Default, PartialEq, Debug, Serialize, Deserialize)]
+pub struct PredictInput {
+    pub id: String,
+    pub text: String,
+}
+
+impl PredictInput {
+    /// `from_json_value` creates a new `PredictInput` from either a `{id, text}` object or
+    /// a `RawDataEntry`, whose text is the selftext without the tl;dr.
+    pub fn from_json_value(v: &Value) -> Result<PredictInput> {
+        if v.get("text").is_some() {
+            return serde_json::from_value(v.clone()).map_err(|e| format!("{}", e));
+        }
+
+        let entry = RawDataEntry::from_json_value(v)?;
+
+        Ok(PredictInput {
+            id: entry.id().to_owned(),
+            text: entry.source().to_owned(),
+        })
+    }
+}
+
+/// `input_batch` returns the `Batch` of the tokenized `inputs`, without reference summaries.
+pub fn input_batch(inputs: &[PredictInput]) -> Batch {
+    Batch {
+        ids: inputs.iter().map(|input| input.id.to_owned()).collect(),
+        modes: vec![Default::default(); inputs.len()],
+        sources: inputs.iter().map(|input| tokenize(&input.text)).collect(),
+        summaries: vec![Vec::new(); inputs.len()],
+        metadata: Vec::new(),
+    }
+}
+
+/// `Prediction` is the summary of a `PredictInput`.
+#[derive(Clone, Default, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Prediction {
+    pub id: String,
+    pub summary: String,
+    pub score: f32,
+}
+
+/// `PredictOptions` are the options of `predict_file`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct PredictOptions {
+    /// `batch_size` is the number of inputs decoded together.
+    pub batch_size: usize,
+    /// `workers` is the number of worker threads. Zero uses one worker per CPU core.
+    pub workers: usize,
+    /// `resume` skips the inputs already predicted in the output file instead of overwriting it.
+    pub resume: bool,
+}
+
+impl PredictOptions {
+    /// `new` creates a new `PredictOptions`.
+    pub fn new() -> PredictOptions {
+        PredictOptions::default()
+    }
+}
+
+impl Default for PredictOptions {
+    fn default() -> PredictOptions {
+        PredictOptions {
+            batch_size: 32,
+            workers: 0,
+            resume: true,
+        }
+    }
+}
+
+/// `PredictSummary` is the outcome of `predict_file`.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct PredictSummary {
+    /// `inputs` is the number of inputs in the input file.
+    pub inputs: usize,
+    /// `skipped` is the number of inputs already predicted by a previous run.
+    pub skipped: usize,
+    /// `predicted` is the number of inputs predicted by this run.
+    pub predicted: usize,
+}
+
+/// `Inputs` is the iterator over the `PredictInput`s of a json lines file, read one line at
+/// a time.
+pub struct Inputs {
+    lines: Lines<BufReader<File>>,
+    line: usize,
+}
+
+impl Iterator for Inputs {
+    type Item = Result<PredictInput>;
+
+    fn next(&mut self) -> Option<Result<PredictInput>> {
+        for line in self.lines.by_ref() {
+            self.line += 1;
+            let idx = self.line;
+
+            let line = match line {
+                Ok(line) => line,
+                Err(e) => return Some(Err(format!("{} at line: {}", e, idx))),
+            };
+
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let input = serde_json::from_str(&line)
+                .map_err(|e| format!("{}", e))
+                .and_then(|v: Value| PredictInput::from_json_value(&v))
+                .map_err(|e| format!("{} at line: {}", e, idx));
+
+            return Some(input);
+        }
+
+        None
+    }
+}
+
+/// `read_inputs` returns the iterator over the `PredictInput`s of the json lines file at `path`.
+pub fn read_inputs<P: AsRef<Path>>(path: P) -> Result<Inputs> {
+    let file = File::open(path).map_err(|e| format!("{}", e))?;
+
+    Ok(Inputs {
+        lines: BufReader::new(file).lines(),
+        line: 0,
+    })
+}
+
+/// `resume_output` returns the number of inputs already predicted in the output file at
+/// `path`, consuming them from `inputs`, and truncates a partially written last line.
+fn resume_output(path: &Path, inputs: &mut Inputs) -> Result<usize> {
+    if !path.exists() {
+        return Ok(0);
+    }
+
+    let file = File::open(path).map_err(|e| format!("{}", e))?;
+    let len = file.metadata().map_err(|e| format!("{}", e))?.len();
+    let mut reader = BufReader::new(file);
+    let mut line = Vec::new();
+    let (mut done, mut complete) = (0, 0);
+
+    loop {
+        line.clear();
+        let read = reader.read_until(b'\n', &mut line).map_err(|e| format!("{}", e))?;
+        if read == 0 || line.last() != Some(&b'\n') {
+            break;
+        }
+
+        complete += read as u64;
+        if line.len() == 1 {
+            continue;
+        }
+
+        let prediction: Prediction = serde_json::from_slice(&line)
+            .map_err(|e| format!("{} at output line: {}", e, done + 1))?;
+
+        match inputs.next().transpose()? {
+            Some(ref input) if input.id == prediction.id => done += 1,
+            _ => return Err(format!("output does not match input at line: {}", done + 1)),
+        }
+    }
+
+    if complete < len {
+        OpenOptions::new()
+            .write(true)
+            .open(path)
+            .and_then(|file| file.set_len(complete))
+            .map_err(|e| format!("{}", e))?;
+    }
+
+    Ok(done)
+}
+
+/// `predict_file` summarizes the json lines file at `input` with `decode` and writes a
+/// `Prediction` per line, in input order, to `output`. The inputs are read a round at a time,
+/// the batches of `batch_size` inputs of a round are decoded in parallel, and the output is
+/// flushed after every round so that an interrupted run can be resumed.
+pub fn predict_file<P, Q, F>(input: P, output: Q, options: &PredictOptions, decode: F) -> Result<PredictSummary>
+    where P: AsRef<Path>,
+          Q: AsRef<Path>,
+          F: Fn(&[PredictInput]) -> Result<Vec<SummarizeResponse>> + Sync
+{
+    if options.batch_size == 0 {
+        return Err("invalid batch_size: 0".to_string());
+    }
+
+    let mut inputs = read_inputs(input)?;
+    let output = output.as_ref();
+
+    let skipped = if options.resume {
+        resume_output(output, &mut inputs)?
+    } else {
+        0
+    };
+
+    let file = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(options.resume)
+        .truncate(!options.resume)
+        .open(output)
+        .map_err(|e| format!("{}", e))?;
+    let mut writer = BufWriter::new(file);
+
+    let pool = ThreadPoolBuilder::new()
+        .num_threads(options.workers)
+        .build()
+        .map_err(|e| format!("{}", e))?;
+    let round_size = options.batch_size * pool.current_num_threads();
+    let mut predicted = 0;
+
+    loop {
+        let round = inputs.by_ref().take(round_size).collect::<Result<Vec<PredictInput>>>()?;
+        if round.is_empty() {
+            break;
+        }
+        let batches: Vec<Vec<Prediction>> = pool.install(|| {
+            round
+                .par_chunks(options.batch_size)
+                .map(|batch| {
+                    let responses = decode(batch)?;
+                    if responses.len() != batch.len() {
+                        return Err(format!("expected {} summaries, found {}", batch.len(), responses.len()));
+                    }
+
+                    Ok(batch
+                        .iter()
+                        .zip(responses)
+                        .map(|(input, response)| Prediction {
+                            id: input.id.to_owned(),
+                            summary: response.summary,
+                            score: response.score,
+                        })
+                        .collect())
+                })
+                .collect::<Result<Vec<Vec<Prediction>>>>()
+        })?;
+
+        for prediction in batches.iter().flatten() {
+            serde_json::to_writer(&mut writer, prediction).map_err(|e| format!("{}", e))?;
+            writer.write_all(b"\n").map_err(|e| format!("{}", e))?;
+        }
+
+        writer.flush().map_err(|e| format!("{}", e))?;
+        predicted += round.len();
+    }
+
+    Ok(PredictSummary {
+        inputs: skipped + predicted,
+        skipped,
+        predicted,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::{input_batch, predict_file, read_inputs, PredictInput, PredictOptions, Prediction};
+    use crate::summarizer::SummarizeResponse;
+    use std::env;
+    use std::fs;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn decode(inputs: &[PredictInput]) -> crate::result::Result<Vec<SummarizeResponse>> {
+        Ok(inputs
+            .iter()
+            .map(|input| SummarizeResponse {
+                summary: input.text.to_uppercase(),
+                score: input.text.len() as f32,
+            })
+            .collect())
+    }
+
+    #[test]
+    fn test_predict_read_inputs() {
+        let mut dir = env::temp_dir();
+        dir.push("mmn_test_predict_read_inputs");
+        fs::create_dir_all(&dir).unwrap();
+
+        let raw = r#"{"id": "raw", "url": "u", "permalink": "p", "created_utc": 0.0, "title": "t", "title_tokenized": ["t"], "trimmed_title": "t", "trimmed_title_tokenized": ["t"], "tldr": null, "tldr_tokenized": null, "selftext_html": null, "selftext": "s", "selftext_without_tldr": "the post", "selftext_without_tldr_tokenized": ["the", "post"], "score": 1, "num_comments": 0, "ups": 1, "upvote_ratio": 1.0}"#;
+        let path = dir.join("inputs.jsonl");
+        fs::write(&path, format!("{{\"id\": \"a\", \"text\": \"some text\"}}\n\n{}\n", raw)).unwrap();
+
+        let inputs: Vec<PredictInput> = read_inputs(&path).unwrap().map(Result::unwrap).collect();
+        assert_eq!(inputs.len(), 2);
+        assert_eq!(inputs[0].text, "some text");
+        assert_eq!(inputs[1].id, "raw");
+        assert_eq!(inputs[1].text, "the post");
+
+        let batch = input_batch(&inputs);
+        assert_eq!(batch.ids, vec!["a".to_string(), "raw".to_string()]);
+        assert_eq!(batch.sources[0], vec!["some".to_string(), "text".to_string()]);
+        assert!(batch.summaries[1].is_empty());
+
+        fs::write(&path, "{\"id\": \"a\"}\n").unwrap();
+        assert!(read_inputs(&path).unwrap().next().unwrap().unwrap_err().ends_with("at line: 1"));
+        assert!(read_inputs(dir.join("missing.jsonl")).is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_predict_file() {
+        let mut dir = env::temp_dir();
+        dir.push("mmn_test_predict_file");
+        fs::create_dir_all(&dir).unwrap();
+
+        let input = dir.join("inputs.jsonl");
+        let output = dir.join("predictions.jsonl");
+        let lines: Vec<String> = (0..50)
+            .map(|i| format!("{{\"id\": \"id{}\", \"text\": \"text {}\"}}", i, i))
+            .collect();
+        fs::write(&input, lines.join("\n")).unwrap();
+
+        let options = PredictOptions { batch_size: 4, workers: 3, resume: true };
+        let summary = predict_file(&input, &output, &options, decode).unwrap();
+        assert_eq!(summary.predicted, 50);
+
+        let expected = fs::read_to_string(&output).unwrap();
+        let predictions: Vec<Prediction> = expected
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        for (i, prediction) in predictions.iter().enumerate() {
+            assert_eq!(prediction.id, format!("id{}", i));
+            assert_eq!(prediction.summary, format!("TEXT {}", i));
+        }
+
+        let cut = expected.match_indices('\n').nth(19).unwrap().0 + 10;
+        fs::write(&output, &expected[..cut]).unwrap();
+
+        let decoded = AtomicUsize::new(0);
+        let summary = predict_file(&input, &output, &options, |inputs: &[PredictInput]| {
+            decoded.fetch_add(inputs.len(), Ordering::SeqCst);
+            decode(inputs)
+        }).unwrap();
+        assert_eq!(summary.skipped, 20);
+        assert_eq!(summary.predicted, 30);
+        assert_eq!(decoded.load(Ordering::SeqCst), 30);
+        assert_eq!(fs::read_to_string(&output).unwrap(), expected);
+
+        fs::write(&output, "{\"id\": \"other\", \"summary\": \"\", \"score\": 0.0}\n").unwrap();
+        assert!(predict_file(&input, &output, &options, decode).is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
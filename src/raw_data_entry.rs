@@ -1,6 +1,45 @@
 use serde::{Serialize, Deserialize};
-use serde_json::{self, Value};
+use serde_json::{self, Map, Value};
+use std::cmp::Ordering;
+use std::ops::{Deref, DerefMut};
 use crate::result::Result;
+use crate::data_entry::DataEntry;
+use crate::parse_options::{ParseOptions, ParseWarning};
+
+/// `FIELDS` are the fields of the TIFU dataset json entries.
+const FIELDS: [&str; 18] = [
+    "id", "url", "permalink", "created_utc", "title", "title_tokenized",
+    "trimmed_title", "trimmed_title_tokenized", "tldr", "tldr_tokenized",
+    "selftext_html", "selftext", "selftext_without_tldr", "selftext_without_tldr_tokenized",
+    "score", "num_comments", "ups", "upvote_ratio",
+];
+
+/// `ExtraFields` are the json fields of an entry unknown to the TIFU dataset schema.
+#[derive(Clone, Default, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct ExtraFields(pub Map<String, Value>);
+
+impl PartialOrd for ExtraFields {
+    /// `partial_cmp` compares the `ExtraFields` field by field, by name and json value.
+    fn partial_cmp(&self, other: &ExtraFields) -> Option<Ordering> {
+        let fields = |extra: &ExtraFields| extra.0.iter().map(|(k, v)| (k.to_owned(), v.to_string())).collect::<Vec<_>>();
+        fields(self).partial_cmp(&fields(other))
+    }
+}
+
+impl Deref for ExtraFields {
+    type Target = Map<String, Value>;
+
+    fn deref(&self) -> &Map<String, Value> {
+        &self.0
+    }
+}
+
+impl DerefMut for ExtraFields {
+    fn deref_mut(&mut self) -> &mut Map<String, Value> {
+        &mut self.0
+    }
+}
 
 /// RawDataEntry is a struct representing an entry in the json training data entry.
 #[derive(Clone, Default, PartialEq, PartialOrd, Debug, Serialize, Deserialize)]
@@ -23,167 +62,174 @@ pub struct RawDataEntry {
     pub num_comments: u64,
     pub ups: u64,
     pub upvote_ratio: f64,
+    /// `extra` contains the fields unknown to the TIFU dataset schema, kept when parsing in `ParseMode::Lenient`.
+    #[serde(flatten)]
+    pub extra: ExtraFields,
 }
 
-impl RawDataEntry {
-    /// `new` creates a new `RawDataEntry`.
-    pub fn new() -> RawDataEntry {
-        RawDataEntry::default()
-    }
-
-    /// `from_json_value` converts a `serde_json::Value` into a `RawDataEntry`.
-    pub fn from_json_value(v: &Value) -> Result<RawDataEntry> {
-        let mut entry = RawDataEntry::new();
-
-        if let Some(tt) = v["title_tokenized"].as_array() {
-            if !tt.is_empty() {
-                let mut res = Vec::new();
-
-                for (i, v) in tt.iter().enumerate() {
-                    if let Some(x) = v.as_str().map(ToOwned::to_owned) {
-                        res.push(x);
-                    } else {
-                        return Err(format!("invalid title_tokenized field element at index: {}", i));
-                    }
-                }
-
-                entry.title_tokenized = res;
+/// `tokens_field` parses the tokenized field `field` of `v`. It returns `None` if the field is not an array.
+fn tokens_field(v: &Value, field: &str, options: &ParseOptions, warnings: &mut Vec<ParseWarning>) -> Result<Option<Vec<String>>> {
+    if let Some(tokens) = v[field].as_array() {
+        let mut res = Vec::new();
+
+        for (i, v) in tokens.iter().enumerate() {
+            if let Some(x) = v.as_str().map(ToOwned::to_owned) {
+                res.push(x);
+            } else if options.is_lenient() && v.is_number() {
+                res.push(v.to_string());
+                warnings.push(ParseWarning::new(field, &format!("coerced number to string at index: {}", i)));
+            } else {
+                return Err(format!("invalid {} field element at index: {}", field, i));
             }
-        } else if !v["title_tokenized"].is_array() && !v["selftext_without_tldr_tokenized"].is_null() {
-            return Err("invalid title_tokenized field".to_string());
         }
 
-        if let Some(ttt) = v["trimmed_title_tokenized"].as_array() {
-            if !ttt.is_empty() {
-                let mut res = Vec::new();
-
-                for (i, v) in ttt.iter().enumerate() {
-                    if let Some(x) = v.as_str().map(ToOwned::to_owned) {
-                        res.push(x);
-                    } else {
-                        return Err(format!("invalid trimmed_title_tokenized field element at index: {}", i));
-                    }
-                }
-
-                entry.trimmed_title_tokenized = res;
-            }
-        } else if !v["trimmed_title_tokenized"].is_array() && !v["selftext_without_tldr_tokenized"].is_null() {
-            return Err("invalid trimmed_title_tokenized field".to_string());
-        }
+        Ok(Some(res))
+    } else {
+        Ok(None)
+    }
+}
 
-        if let Some(swtt) = v["selftext_without_tldr_tokenized"].as_array() {
-            if !swtt.is_empty() {
-                let mut res = Vec::new();
+/// `string_field` parses the string field `field` of `v`. In `ParseMode::Lenient` a missing
+/// field defaults to an empty string if `optional`.
+fn string_field(v: &Value, field: &str, optional: bool, options: &ParseOptions, warnings: &mut Vec<ParseWarning>) -> Result<String> {
+    if let Some(s) = v[field].as_str().map(ToOwned::to_owned) {
+        Ok(s)
+    } else if options.is_lenient() && optional && v[field].is_null() {
+        warnings.push(ParseWarning::new(field, "missing field, defaulted to empty string"));
+        Ok(String::new())
+    } else {
+        Err(format!("invalid {} field", field))
+    }
+}
 
-                for (i, v) in swtt.iter().enumerate() {
-                    if let Some(x) = v.as_str().map(ToOwned::to_owned) {
-                        res.push(x);
-                    } else {
-                        return Err(format!("invalid selftext_without_tldr_tokenized field element at index: {}", i));
-                    }
-                }
+/// `u64_field` parses the unsigned integer field `field` of `v`. In `ParseMode::Lenient` floats,
+/// negative integers and numeric strings are coerced and a missing field defaults to zero.
+fn u64_field(v: &Value, field: &str, options: &ParseOptions, warnings: &mut Vec<ParseWarning>) -> Result<u64> {
+    if let Some(n) = v[field].as_u64() {
+        return Ok(n);
+    }
 
-                entry.selftext_without_tldr_tokenized = res;
-            }
-        } else if !v["selftext_without_tldr_tokenized"].is_array() && !v["selftext_without_tldr_tokenized"].is_null() {
-            return Err("invalid selftext_without_tldr_tokenized field".to_string());
-        }
+    if !options.is_lenient() {
+        return Err(format!("invalid {} field", field));
+    }
 
-        if let Some(tt) = v["tldr_tokenized"].as_array() {
-            if !tt.is_empty() {
-                let mut res = Vec::new();
+    if v[field].is_null() {
+        warnings.push(ParseWarning::new(field, "missing field, defaulted to 0"));
+        return Ok(0);
+    }
 
-                for (i, v) in tt.iter().enumerate() {
-                    if let Some(x) = v.as_str().map(ToOwned::to_owned) {
-                        res.push(x);
-                    } else {
-                        return Err(format!("invalid tldr_tokenized field element at index: {}", i));
-                    }
-                }
+    let n = if let Some(s) = v[field].as_str() {
+        s.trim().parse::<f64>().ok()
+    } else {
+        v[field].as_f64()
+    };
+
+    match n {
+        Some(n) if n.is_finite() => {
+            let coerced = if n < 0.0 { 0 } else { n.round() as u64 };
+            warnings.push(ParseWarning::new(field, &format!("coerced {} to {}", v[field], coerced)));
+            Ok(coerced)
+        },
+        _ => Err(format!("invalid {} field", field)),
+    }
+}
 
-                entry.tldr_tokenized = Some(res);
-            }
-        } else if !v["tldr_tokenized"].is_array() && !v["tldr_tokenized"].is_null() {
-            return Err("invalid tldr_tokenized field".to_string());
-        }
+/// `f64_field` parses the float field `field` of `v`. In `ParseMode::Lenient` numeric strings
+/// are coerced and a missing field defaults to zero.
+fn f64_field(v: &Value, field: &str, options: &ParseOptions, warnings: &mut Vec<ParseWarning>) -> Result<f64> {
+    if let Some(n) = v[field].as_f64() {
+        return Ok(n);
+    }
 
-        if let Some(p) = v["permalink"].as_str().map(ToOwned::to_owned) {
-            entry.permalink = p;
-        } else {
-            return Err("invalid permalink field".to_string());
-        }
+    if !options.is_lenient() {
+        return Err(format!("invalid {} field", field));
+    }
 
-        if let Some(t) = v["title"].as_str().map(ToOwned::to_owned) {
-            entry.title = t;
-        } else {
-            return Err("invalid title field".to_string());
-        }
+    if v[field].is_null() {
+        warnings.push(ParseWarning::new(field, "missing field, defaulted to 0"));
+        return Ok(0.0);
+    }
 
-        if let Some(u) = v["url"].as_str().map(ToOwned::to_owned) {
-            entry.url = u;
-        } else {
-            return Err("invalid url field".to_string());
-        }
+    match v[field].as_str().and_then(|s| s.trim().parse::<f64>().ok()) {
+        Some(n) if n.is_finite() => {
+            warnings.push(ParseWarning::new(field, &format!("coerced {} to {}", v[field], n)));
+            Ok(n)
+        },
+        _ => Err(format!("invalid {} field", field)),
+    }
+}
 
-        if let Some(st) = v["selftext"].as_str().map(ToOwned::to_owned) {
-            entry.selftext = st;
-        } else {
-            return Err("invalid selftext field".to_string());
-        }
+impl RawDataEntry {
+    /// `new` creates a new `RawDataEntry`.
+    pub fn new() -> RawDataEntry {
+        RawDataEntry::default()
+    }
 
-        if let Some(tt) = v["trimmed_title"].as_str().map(ToOwned::to_owned) {
-            entry.trimmed_title = tt;
-        } else {
-            return Err("invalid trimmed_title field".to_string());
-        }
+    /// `from_json_value` converts a `serde_json::Value` into a `RawDataEntry` in `ParseMode::Strict`.
+    pub fn from_json_value(v: &Value) -> Result<RawDataEntry> {
+        RawDataEntry::from_json_value_with_options(v, &ParseOptions::strict())
+            .map(|(entry, _)| entry)
+    }
 
-        if let Some(swt) = v["selftext_without_tldr"].as_str().map(ToOwned::to_owned) {
-            entry.selftext_without_tldr = swt;
-        } else {
-            return Err("invalid selftext_without_tldr field".to_string());
-        }
+    /// `from_json_value_with_options` converts a `serde_json::Value` into a `RawDataEntry` using `options`,
+    /// returning the warnings collected in `ParseMode::Lenient`.
+    pub fn from_json_value_with_options(v: &Value, options: &ParseOptions) -> Result<(RawDataEntry, Vec<ParseWarning>)> {
+        let mut entry = RawDataEntry::new();
+        let mut warnings = Vec::new();
+        let has_source = !v["selftext_without_tldr_tokenized"].is_null();
 
-        if let Some(i) = v["id"].as_str().map(ToOwned::to_owned) {
-            entry.id = i;
-        } else {
-            return Err("invalid id field".to_string());
+        match tokens_field(v, "title_tokenized", options, &mut warnings)? {
+            Some(tt) => entry.title_tokenized = tt,
+            None if has_source => return Err("invalid title_tokenized field".to_string()),
+            None => {},
         }
 
-        if let Some(nc) = v["num_comments"].as_u64() {
-            entry.num_comments = nc;
-        } else {
-            return Err("invalid num_comments field".to_string());
+        match tokens_field(v, "trimmed_title_tokenized", options, &mut warnings)? {
+            Some(ttt) => entry.trimmed_title_tokenized = ttt,
+            None if has_source => return Err("invalid trimmed_title_tokenized field".to_string()),
+            None => {},
         }
 
-        if let Some(u) = v["ups"].as_u64() {
-            entry.ups = u;
-        } else {
-            return Err("invalid ups field".to_string());
+        match tokens_field(v, "selftext_without_tldr_tokenized", options, &mut warnings)? {
+            Some(swtt) => entry.selftext_without_tldr_tokenized = swtt,
+            None if has_source => return Err("invalid selftext_without_tldr_tokenized field".to_string()),
+            None => {},
         }
 
-        if let Some(s) = v["score"].as_u64() {
-            entry.score = s;
-        } else {
-            return Err("invalid score field".to_string());
+        match tokens_field(v, "tldr_tokenized", options, &mut warnings)? {
+            Some(tt) if !tt.is_empty() => entry.tldr_tokenized = Some(tt),
+            Some(_) => {},
+            None if !v["tldr_tokenized"].is_null() => return Err("invalid tldr_tokenized field".to_string()),
+            None => {},
         }
 
-        if let Some(cu) = v["created_utc"].as_f64() {
-            entry.created_utc = cu;
-        } else {
-            return Err("invalid created_utc field".to_string());
-        }
+        entry.permalink = string_field(v, "permalink", true, options, &mut warnings)?;
+        entry.title = string_field(v, "title", false, options, &mut warnings)?;
+        entry.url = string_field(v, "url", true, options, &mut warnings)?;
+        entry.selftext = string_field(v, "selftext", false, options, &mut warnings)?;
+        entry.trimmed_title = string_field(v, "trimmed_title", false, options, &mut warnings)?;
+        entry.selftext_without_tldr = string_field(v, "selftext_without_tldr", false, options, &mut warnings)?;
+        entry.id = string_field(v, "id", false, options, &mut warnings)?;
 
-        if let Some(ur) = v["upvote_ratio"].as_f64() {
-            entry.upvote_ratio = ur;
-        } else {
-            return Err("invalid upvote_ratio field".to_string());
-        }
+        entry.num_comments = u64_field(v, "num_comments", options, &mut warnings)?;
+        entry.ups = u64_field(v, "ups", options, &mut warnings)?;
+        entry.score = u64_field(v, "score", options, &mut warnings)?;
+        entry.created_utc = f64_field(v, "created_utc", options, &mut warnings)?;
+        entry.upvote_ratio = f64_field(v, "upvote_ratio", options, &mut warnings)?;
 
         entry.tldr = v["tldr"].as_str().map(ToOwned::to_owned);
 
         entry.selftext_html = v["selftext_html"].as_str().map(ToOwned::to_owned);
 
-        Ok(entry)
+        if options.is_lenient() {
+            if let Some(obj) = v.as_object() {
+                for (k, v) in obj.iter().filter(|(k, _)| !FIELDS.contains(&k.as_str())) {
+                    entry.extra.insert(k.to_owned(), v.to_owned());
+                }
+            }
+        }
+
+        Ok((entry, warnings))
     }
 
     /// `from_json_string` converts a json `str` to a `RawDataEntry`.
@@ -193,6 +239,14 @@ impl RawDataEntry {
         RawDataEntry::from_json_value(&value)
     }
 
+    /// `from_json_string_with_options` converts a json `str` to a `RawDataEntry` using `options`,
+    /// returning the warnings collected in `ParseMode::Lenient`.
+    pub fn from_json_string_with_options(s: &str, options: &ParseOptions) -> Result<(RawDataEntry, Vec<ParseWarning>)> {
+        let value: Value = serde_json::from_str(s)
+            .map_err(|e| format!("{}", e))?;
+        RawDataEntry::from_json_value_with_options(&value, options)
+    }
+
     /// `to_json_string` converts the `RawDataEntry` to a `String`.
     pub fn to_json_string(&self) -> Result<String> {
         serde_json::to_string(self)
@@ -206,6 +260,14 @@ impl RawDataEntry {
         RawDataEntry::from_json_value(&value)
     }
 
+    /// `from_json_bytes_with_options` converts a `&[u8]` to `RawDataEntry` using `options`,
+    /// returning the warnings collected in `ParseMode::Lenient`.
+    pub fn from_json_bytes_with_options(b: &[u8], options: &ParseOptions) -> Result<(RawDataEntry, Vec<ParseWarning>)> {
+        let value: Value = serde_json::from_slice(b)
+            .map_err(|e| format!("{}", e))?;
+        RawDataEntry::from_json_value_with_options(&value, options)
+    }
+
     /// `to_json_bytes` converts the `RawDataEntry` to a `Vec<u8>`.
     pub fn to_json_bytes(&self) -> Result<Vec<u8>> {
         serde_json::to_vec(self)
@@ -213,9 +275,42 @@ impl RawDataEntry {
     }
 }
 
+impl DataEntry for RawDataEntry {
+    /// `from_raw` creates a `RawDataEntry` from a `RawDataEntry`.
+    fn from_raw(rde: &RawDataEntry) -> RawDataEntry {
+        rde.to_owned()
+    }
+
+    /// `id` returns the id of the `RawDataEntry`.
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// `source` returns the source text of the `RawDataEntry`.
+    fn source(&self) -> &str {
+        &self.selftext_without_tldr
+    }
+
+    /// `source_mut` returns a mutable reference to the source text of the `RawDataEntry`.
+    fn source_mut(&mut self) -> &mut String {
+        &mut self.selftext_without_tldr
+    }
+
+    /// `source_tokenized` returns the source tokens of the `RawDataEntry`.
+    fn source_tokenized(&self) -> &[String] {
+        &self.selftext_without_tldr_tokenized
+    }
+
+    /// `source_tokenized_mut` returns a mutable reference to the source tokens of the `RawDataEntry`.
+    fn source_tokenized_mut(&mut self) -> &mut Vec<String> {
+        &mut self.selftext_without_tldr_tokenized
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::RawDataEntry;
+    use crate::parse_options::ParseOptions;
 
     const VALID_ENTRY: &str = r#"{"title_tokenized": ["tifu", "by", "forgetting", "to", "pull", "my", "underwear", "down", "before", "i", "pooped"], "permalink": "/r/tifu/comments/1ghd5r/tifu_by_forgetting_to_pull_my_underwear_down/", "title": "TIFU by forgetting to pull my underwear down before I pooped.", "url": "https://www.reddit.com/r/tifu/comments/1ghd5r/tifu_by_forgetting_to_pull_my_underwear_down/", "num_comments": 13, "tldr": null, "created_utc": 1371426179.0, "trimmed_title_tokenized": ["forgetting", "to", "pull", "my", "underwear", "down", "before", "i", "pooped"], "id": "1ghd5r", "selftext_html": "<!-- SC_OFF --><div class=\"md\"><p>I was on Skype on my tablet as I went to the toilet IMing a friend. I don&#39;t multitask very well, so I forgot one of the most important things to do before pooping. I think the best part was when I realised and told my mate who just freaked out because I was talking to him on the John!</p>\n</div><!-- SC_ON -->", "score": 50, "upvote_ratio": 0.77, "tldr_tokenized": null, "selftext": "I was on Skype on my tablet as I went to the toilet IMing a friend. I don't multitask very well, so I forgot one of the most important things to do before pooping. I think the best part was when I realised and told my mate who just freaked out because I was talking to him on the John!", "trimmed_title": "forgetting to pull my underwear down before i pooped.", "selftext_without_tldr_tokenized": ["i", "was", "on", "skype", "on", "my", "tablet", "as", "i", "went", "to", "the", "toilet", "iming", "a", "friend", "i", "do", "n't", "multitask", "very", "well", "so", "i", "forgot", "one", "of", "the", "most", "important", "things", "to", "do", "before", "pooping", "i", "think", "the", "best", "part", "was", "when", "i", "realised", "and", "told", "my", "mate", "who", "just", "freaked", "out", "because", "i", "was", "talking", "to", "him", "on", "the", "john"], "ups": 50, "selftext_without_tldr": "i was on skype on my tablet as i went to the toilet iming a friend. i don't multitask very well, so i forgot one of the most important things to do before pooping. i think the best part was when i realised and told my mate who just freaked out because i was talking to him on the john!"}"#;
 
@@ -348,4 +443,96 @@ mod test {
         let res = RawDataEntry::from_json_value(&u64_created_utc_value);
         assert!(res.is_ok());
     }
+
+    #[test]
+    fn test_raw_data_entry_lenient_num_fields() {
+        let res = serde_json::from_str(VALID_ENTRY);
+        assert!(res.is_ok());
+        let json_value: serde_json::Value = res.unwrap();
+
+        let mut f64_score_obj = json_value.clone().as_object().unwrap().to_owned();
+        f64_score_obj["score"] = serde_json::json!(49.6);
+        f64_score_obj["ups"] = serde_json::json!("50");
+        f64_score_obj["upvote_ratio"] = serde_json::json!("0.77");
+
+        let f64_score_value: serde_json::Value = f64_score_obj.into();
+        let res = RawDataEntry::from_json_value_with_options(&f64_score_value, &ParseOptions::strict());
+        assert!(res.is_err());
+
+        let res = RawDataEntry::from_json_value_with_options(&f64_score_value, &ParseOptions::lenient());
+        assert!(res.is_ok());
+        let (entry, warnings) = res.unwrap();
+        assert_eq!(entry.score, 50);
+        assert_eq!(entry.ups, 50);
+        assert_eq!(entry.upvote_ratio, 0.77);
+        assert_eq!(warnings.len(), 3);
+
+        let mut invalid_score_obj = json_value.clone().as_object().unwrap().to_owned();
+        invalid_score_obj["score"] = serde_json::json!("many");
+
+        let invalid_score_value: serde_json::Value = invalid_score_obj.into();
+        let res = RawDataEntry::from_json_value_with_options(&invalid_score_value, &ParseOptions::lenient());
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn test_raw_data_entry_lenient_missing_fields() {
+        let res = serde_json::from_str(VALID_ENTRY);
+        assert!(res.is_ok());
+        let json_value: serde_json::Value = res.unwrap();
+
+        let mut missing_upvote_ratio_obj = json_value.clone().as_object().unwrap().to_owned();
+        missing_upvote_ratio_obj.remove("upvote_ratio");
+        missing_upvote_ratio_obj.remove("url");
+
+        let missing_upvote_ratio_value: serde_json::Value = missing_upvote_ratio_obj.into();
+        let res = RawDataEntry::from_json_value(&missing_upvote_ratio_value);
+        assert!(res.is_err());
+
+        let res = RawDataEntry::from_json_value_with_options(&missing_upvote_ratio_value, &ParseOptions::lenient());
+        assert!(res.is_ok());
+        let (entry, warnings) = res.unwrap();
+        assert_eq!(entry.upvote_ratio, 0.0);
+        assert_eq!(entry.url, "");
+        assert_eq!(warnings.len(), 2);
+        assert!(warnings.iter().any(|w| w.field == "upvote_ratio"));
+
+        let mut missing_title_obj = json_value.clone().as_object().unwrap().to_owned();
+        missing_title_obj.remove("title");
+
+        let missing_title_value: serde_json::Value = missing_title_obj.into();
+        let res = RawDataEntry::from_json_value_with_options(&missing_title_value, &ParseOptions::lenient());
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn test_raw_data_entry_lenient_extra_fields() {
+        let res = serde_json::from_str(VALID_ENTRY);
+        assert!(res.is_ok());
+        let json_value: serde_json::Value = res.unwrap();
+
+        let mut extra_obj = json_value.clone().as_object().unwrap().to_owned();
+        extra_obj.insert("gilded".to_string(), serde_json::json!(2));
+
+        let extra_value: serde_json::Value = extra_obj.into();
+        let res = RawDataEntry::from_json_value(&extra_value);
+        assert!(res.is_ok());
+        assert!(res.unwrap().extra.is_empty());
+
+        let res = RawDataEntry::from_json_value_with_options(&extra_value, &ParseOptions::lenient());
+        assert!(res.is_ok());
+        let (entry, warnings) = res.unwrap();
+        assert!(warnings.is_empty());
+        assert_eq!(entry.extra.len(), 1);
+        assert_eq!(entry.extra["gilded"], serde_json::json!(2));
+
+        let json_string = entry.to_json_string().unwrap();
+        let res = RawDataEntry::from_json_string_with_options(&json_string, &ParseOptions::lenient());
+        assert_eq!(res.unwrap().0, entry);
+
+        let mut plain = entry.clone();
+        plain.extra.clear();
+        assert!(plain < entry);
+        assert_eq!(entry.partial_cmp(&entry.clone()), Some(std::cmp::Ordering::Equal));
+    }
 }
@@ -1,2 +1,13 @@
 /// `Result` is an alias of the std library `std::result::Result` with `String` as `Error` type.
 pub type Result<T> = std::result::Result<T, String>;
+
+/// `panic_message` returns the message of the `payload` of a caught panic.
+pub fn panic_message(payload: &(dyn std::any::Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.to_owned()
+    } else {
+        "unknown panic".to_string()
+    }
+}
@@ -0,0 +1,112 @@
+use serde::{Serialize, Deserialize};
+use std::cmp;
+
+/// `RougeScore` is a ROUGE score between a candidate and a reference summary.
+#[derive(Clone, Copy, Default, PartialEq, Debug, Serialize, Deserialize)]
+pub struct RougeScore {
+    pub precision: f64,
+    pub recall: f64,
+    pub f1: f64,
+}
+
+impl RougeScore {
+    /// `new` creates a new `RougeScore` from the number of matching tokens and
+    /// the candidate and reference lengths.
+    pub fn new(matches: usize, candidate_len: usize, reference_len: usize) -> RougeScore {
+        if matches == 0 {
+            return RougeScore::default();
+        }
+
+        let precision = matches as f64 / candidate_len as f64;
+        let recall = matches as f64 / reference_len as f64;
+        let f1 = 2.0 * precision * recall / (precision + recall);
+
+        RougeScore { precision, recall, f1 }
+    }
+}
+
+/// `lcs_len` returns the length of the longest common subsequence of `a` and `b`,
+/// using two rows of the dynamic programming table.
+pub fn lcs_len<T: PartialEq>(a: &[T], b: &[T]) -> usize {
+    let (a, b) = if a.len() < b.len() { (b, a) } else { (a, b) };
+    let mut prev = vec![0; b.len() + 1];
+    let mut curr = vec![0; b.len() + 1];
+
+    for x in a.iter() {
+        for (j, y) in b.iter().enumerate() {
+            curr[j + 1] = if x == y {
+                prev[j] + 1
+            } else {
+                cmp::max(prev[j + 1], curr[j])
+            };
+        }
+        std::mem::swap(&mut prev, &mut curr);
+    }
+
+    prev[b.len()]
+}
+
+/// `rouge_l` returns the ROUGE-L score of the `candidate` tokens against the `reference` tokens.
+pub fn rouge_l<T: PartialEq>(candidate: &[T], reference: &[T]) -> RougeScore {
+    RougeScore::new(lcs_len(candidate, reference), candidate.len(), reference.len())
+}
+
+/// `mean_rouge_l` returns the mean ROUGE-L score of the `candidates` against the `references`.
+pub fn mean_rouge_l<T: PartialEq>(candidates: &[Vec<T>], references: &[Vec<T>]) -> RougeScore {
+    let len = cmp::min(candidates.len(), references.len());
+    if len == 0 {
+        return RougeScore::default();
+    }
+
+    let mut mean = RougeScore::default();
+
+    for (candidate, reference) in candidates.iter().zip(references.iter()) {
+        let score = rouge_l(candidate, reference);
+        mean.precision += score.precision;
+        mean.recall += score.recall;
+        mean.f1 += score.f1;
+    }
+
+    mean.precision /= len as f64;
+    mean.recall /= len as f64;
+    mean.f1 /= len as f64;
+    mean
+}
+
+#[cfg(test)]
+mod test {
+    use super::{lcs_len, rouge_l, mean_rouge_l};
+
+    fn tokens(s: &str) -> Vec<String> {
+        s.split_whitespace().map(ToOwned::to_owned).collect()
+    }
+
+    #[test]
+    fn test_rouge_lcs_len() {
+        assert_eq!(lcs_len::<u8>(&[], &[]), 0);
+        assert_eq!(lcs_len(b"abcbdab", b"bdcaba"), 4);
+        assert_eq!(lcs_len(b"bdcaba", b"abcbdab"), 4);
+        assert_eq!(lcs_len(b"abc", b"abc"), 3);
+        assert_eq!(lcs_len(b"abc", b"def"), 0);
+    }
+
+    #[test]
+    fn test_rouge_l() {
+        let reference = tokens("police killed the gunman");
+        let candidate = tokens("police kill the gunman");
+
+        let score = rouge_l(&candidate, &reference);
+        assert_eq!(score.precision, 0.75);
+        assert_eq!(score.recall, 0.75);
+        assert_eq!(score.f1, 0.75);
+
+        let score = rouge_l(&reference, &reference);
+        assert_eq!(score.f1, 1.0);
+
+        let score = rouge_l(&tokens(""), &reference);
+        assert_eq!(score.f1, 0.0);
+
+        let mean = mean_rouge_l(&[candidate, reference.clone()], &[reference.clone(), reference]);
+        assert_eq!(mean.f1, 0.875);
+    }
+}
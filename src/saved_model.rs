@@ -0,0 +1,495 @@
+use std::fs;
+use std::path::Path;
+use tensorflow::{ops, DataType, Graph, Operation, Output, OutputName, SavedModelBuilder, SavedModelBundle, Scope, Session};
+use tensorflow::{SessionOptions, SessionRunArgs, Shape, SignatureDef, Status, Tensor, TensorInfo, Variable};
+use tensorflow::{DEFAULT_SERVING_SIGNATURE_DEF_KEY, PREDICT_METHOD_NAME};
+use crate::result::Result;
+use crate::config::Architecture;
+use crate::multi_task::{target_token, TargetConditioning};
+use crate::seq2seq::Seq2Seq;
+use crate::summarizer::SummaryMode;
+use crate::vocabulary::{BOS_ID, EOS_ID, UNK_ID};
+
+/// `SAVED_MODEL_DIR` is the name of the SavedModel directory in an export directory.
+pub const SAVED_MODEL_DIR: &str = "saved_model";
+
+/// `SERVE_TAG` is the tag of the SavedModel meta graph.
+pub const SERVE_TAG: &str = "serve";
+
+/// `INPUT_IDS` is the input of the `serving_default` signature with the encoder input ids:
+/// the target-type token id, if any, then the source ids in the extended vocabulary of the
+/// source, ended by the end of sequence.
+pub const INPUT_IDS: &str = "input_ids";
+
+/// `PREFIX_LEN` is the input of the `serving_default` signature with the number of encoder
+/// input ids read before the source.
+pub const PREFIX_LEN: &str = "prefix_len";
+
+/// `SUMMARY_IDS` is the output of the `serving_default` signature with the greedily decoded
+/// summary ids, in the extended vocabulary of the source with the copy mechanism.
+pub const SUMMARY_IDS: &str = "summary_ids";
+
+/// `LOG_PROBS` is the output of the `serving_default` signature with the log probability of
+/// every summary id.
+pub const LOG_PROBS: &str = "log_probs";
+
+/// `tf` converts the TensorFlow `Status` of `res` to an error.
+fn tf<T>(res: std::result::Result<T, Status>) -> Result<T> {
+    res.map_err(|e| format!("{}", e))
+}
+
+/// `prefix_ids` returns the encoder input ids read before a source of a summary of kind `mode`
+/// by `model`: the target-type token id with the `Token` target conditioning.
+pub fn prefix_ids(model: &Seq2Seq, mode: SummaryMode) -> Vec<usize> {
+    match model.config.model.target_conditioning {
+        Some(TargetConditioning::Token) => vec![model.vocabulary.id(target_token(mode))],
+        _ => Vec::new(),
+    }
+}
+
+/// `DecoderGraph` builds the graph of the greedy decoding of a `Seq2Seq` of the MMN
+/// architecture, whose variables are initialized with the ones of the model.
+struct DecoderGraph<'a> {
+    model: &'a Seq2Seq,
+    scope: Scope,
+    variables: Vec<Variable>,
+}
+
+impl<'a> DecoderGraph<'a> {
+    /// `new` creates a new empty `DecoderGraph` of `model`.
+    fn new(model: &'a Seq2Seq) -> Result<DecoderGraph<'a>> {
+        let config = &model.config.model;
+
+        if config.architecture != Architecture::Mmn {
+            return Err(format!("invalid SavedModel export: {:?} architecture, expected Mmn", config.architecture));
+        }
+
+        if config.target_conditioning == Some(TargetConditioning::Heads) || config.metadata.is_some() {
+            return Err("invalid SavedModel export: only the Token target conditioning is supported".to_string());
+        }
+
+        Ok(DecoderGraph {
+            model,
+            scope: Scope::new_root_scope(),
+            variables: Vec::new(),
+        })
+    }
+
+    /// `variable` returns a new variable `name` of `shape` initialized with `data`.
+    fn variable(&mut self, name: &str, shape: &[usize], data: &[f32]) -> Result<Output> {
+        let shape: Vec<u64> = shape.iter().map(|dim| *dim as u64).collect();
+        let tensor = tf(Tensor::<f32>::new(&shape).with_values(data))?;
+        let variable = tf(Variable::builder().const_initial_value(tensor).build(&mut self.scope.with_op_name(name)))?;
+
+        let output = variable.output().clone();
+        self.variables.push(variable);
+        Ok(output)
+    }
+
+    /// `model_variable` returns the variable initialized with the model variable `name`.
+    fn model_variable(&mut self, name: &str) -> Result<Output> {
+        let tensor = self.model.variables.get(self.model.variables.index(name)?).clone();
+        self.variable(name, &tensor.shape, &tensor.data)
+    }
+
+    /// `model_columns` returns the variable initialized with the columns `from..to` of the
+    /// model matrix variable `name`.
+    fn model_columns(&mut self, name: &str, from: usize, to: usize) -> Result<Output> {
+        let tensor = self.model.variables.get(self.model.variables.index(name)?);
+        let rows = tensor.len() / tensor.cols();
+        let data: Vec<f32> = (0..rows).flat_map(|row| tensor.row(row)[from..to].to_vec()).collect();
+
+        self.variable(&format!("{}/{}_{}", name, from, to), &[rows, to - from], &data)
+    }
+
+    /// `constant_i32` returns the int32 constant of `values` with `shape`.
+    fn constant_i32(&mut self, values: &[i32], shape: &[u64]) -> Result<Output> {
+        let tensor = tf(Tensor::<i32>::new(shape).with_values(values))?;
+        Ok(tf(ops::constant(tensor, &mut self.scope))?.into())
+    }
+
+    /// `constant_i64` returns the int64 constant of `values` with `shape`.
+    fn constant_i64(&mut self, values: &[i64], shape: &[u64]) -> Result<Output> {
+        let tensor = tf(Tensor::<i64>::new(shape).with_values(values))?;
+        Ok(tf(ops::constant(tensor, &mut self.scope))?.into())
+    }
+
+    /// `constant_f32` returns the float scalar constant `value`.
+    fn constant_f32(&mut self, value: f32) -> Result<Output> {
+        let tensor = tf(Tensor::<f32>::new(&[]).with_values(&[value]))?;
+        Ok(tf(ops::constant(tensor, &mut self.scope))?.into())
+    }
+
+    /// `op` returns the first output of the operation built by `build` in the scope.
+    fn op<F>(&mut self, build: F) -> Result<Output>
+        where F: FnOnce(&mut Scope) -> std::result::Result<Operation, Status>
+    {
+        Ok(tf(build(&mut self.scope))?.into())
+    }
+
+    /// `linear` returns `x · wᵀ`, the rows of `x` multiplied by the matrix `w`.
+    fn linear(&mut self, x: Output, w: Output) -> Result<Output> {
+        self.op(|scope| ops::MatMul::new().transpose_b(true).build(x, w, scope))
+    }
+
+    /// `rows` returns the rows of `x` from `start` to `len` rows later.
+    fn rows(&mut self, x: Output, start: Output, len: Output) -> Result<Output> {
+        let one = self.constant_i32(&[1], &[])?;
+        let limit = self.op(|scope| ops::add(start.clone(), len, scope))?;
+        let indices = self.op(|scope| ops::range(start, limit, one, scope))?;
+        let axis = self.constant_i32(&[0], &[])?;
+        self.op(|scope| ops::gather_v2(x, indices, axis, scope))
+    }
+
+    /// `embedding` returns the embeddings of the extended `ids`, the ids out of the vocabulary
+    /// reading the `UNK_ID` row.
+    fn embedding(&mut self, embedding: Output, ids: Output) -> Result<Output> {
+        let len = self.constant_i64(&[self.model.vocabulary.len() as i64], &[])?;
+        let unk = self.constant_i64(&[UNK_ID as i64], &[])?;
+        let known = self.op(|scope| ops::less(ids.clone(), len, scope))?;
+        let ids = self.op(|scope| ops::select_v2(known, ids, unk, scope))?;
+        let axis = self.constant_i32(&[0], &[])?;
+        self.op(|scope| ops::gather_v2(embedding, ids, axis, scope))
+    }
+
+    /// `conv_layer` returns the outputs of the residual dilated convolution layer `layer` of
+    /// `rate` reading the `len` positions of `x`, the positions out of the sequence reading zeros.
+    fn conv_layer(&mut self, layer: usize, rate: usize, x: Output, len: Output) -> Result<Output> {
+        let size = self.model.config.model.embedding_size;
+        let name = format!("encoder/conv{}/weights", layer);
+        let kernel_size = self.model.variables.get(self.model.variables.index(&name)?).cols() / size;
+
+        let offsets: Vec<i32> = (0..kernel_size)
+            .map(|j| (j as i32 - (kernel_size / 2) as i32) * rate as i32)
+            .collect();
+        let padding = offsets.iter().map(|offset| offset.abs()).max().unwrap_or(0);
+
+        let paddings = self.constant_i32(&[padding, padding, 0, 0], &[2, 2])?;
+        let padded = self.op(|scope| ops::pad(x.clone(), paddings, scope))?;
+
+        let mut pre = self.model_variable(&format!("encoder/conv{}/bias", layer))?;
+        for (j, offset) in offsets.iter().enumerate() {
+            let start = self.constant_i32(&[padding + offset], &[])?;
+            let window = self.rows(padded.clone(), start, len.clone())?;
+            let weights = self.model_columns(&name, j * size, (j + 1) * size)?;
+            let y = self.linear(window, weights)?;
+            pre = self.op(|scope| ops::add(pre, y, scope))?;
+        }
+
+        let y = self.op(|scope| ops::tanh(pre, scope))?;
+        self.op(|scope| ops::add(x, y, scope))
+    }
+
+    /// `build` builds the graph decoding `max_len` ids, and returns the operations of the
+    /// inputs and of the outputs of the `serving_default` signature.
+    fn build(&mut self, max_len: usize) -> Result<(Operation, Operation, Operation, Operation)> {
+        let config = self.model.config.model.clone();
+        let size = config.embedding_size;
+        let vocabulary_len = self.model.vocabulary.len();
+
+        let input_ids = tf(ops::Placeholder::new()
+            .dtype(DataType::Int64)
+            .shape(Shape::from(Some(vec![None])))
+            .build(&mut self.scope.with_op_name(INPUT_IDS)))?;
+        let prefix_len = tf(ops::Placeholder::new()
+            .dtype(DataType::Int32)
+            .shape(Shape::from(Some(vec![])))
+            .build(&mut self.scope.with_op_name(PREFIX_LEN)))?;
+
+        let len = self.op(|scope| ops::size(input_ids.clone(), scope))?;
+        let source_len = self.op(|scope| ops::sub(len.clone(), prefix_len.clone(), scope))?;
+
+        let embedding = self.model_variable("embedding")?;
+        let mut x = self.embedding(embedding.clone(), input_ids.clone().into())?;
+        let mut layers = Vec::with_capacity(config.dilation_rates.len());
+        for (layer, rate) in config.dilation_rates.iter().enumerate() {
+            x = self.conv_layer(layer, *rate, x, len.clone())?;
+            layers.push(x.clone());
+        }
+
+        let top = layers[layers.len() - 1].clone();
+        let axis = self.constant_i32(&[0], &[1])?;
+        let mut state = self.op(|scope| ops::Mean::new().keep_dims(true).build(top, axis, scope))?;
+
+        let mut memories = Vec::with_capacity(config.memory_levels);
+        for (level, layer) in layers[layers.len() - config.memory_levels..].to_vec().into_iter().enumerate() {
+            let memory = self.rows(layer, prefix_len.clone().into(), source_len.clone())?;
+            memories.push((self.model_variable(&format!("attention/{}", level))?, memory));
+        }
+        let source_ids = self.rows(input_ids.clone().into(), prefix_len.clone().into(), source_len.clone())?;
+
+        let decoder_input = self.model_variable("decoder/input")?;
+        let decoder_recurrent = self.model_variable("decoder/recurrent")?;
+        let decoder_bias = self.model_variable("decoder/bias")?;
+        let output_context = self.model_columns("output/weights", 0, size)?;
+        let output_state = self.model_columns("output/weights", size, 2 * size)?;
+        let output_bias = self.model_variable("output/bias")?;
+        let copy = if config.copy {
+            Some((
+                self.model_columns("copy/weights", 0, size)?,
+                self.model_columns("copy/weights", size, 2 * size)?,
+                self.model_variable("copy/bias")?,
+            ))
+        } else {
+            None
+        };
+
+        let scale = self.constant_f32(1.0 / config.memory_levels as f32)?;
+        let last_axis = self.constant_i32(&[1], &[])?;
+        let mut prev = self.constant_i64(&[BOS_ID as i64], &[1])?;
+        let mut outputs: Option<(Output, Output)> = None;
+
+        for step in 0..max_len {
+            let x = self.embedding(embedding.clone(), prev)?;
+            let input = self.linear(x, decoder_input.clone())?;
+            let recurrent = self.linear(state, decoder_recurrent.clone())?;
+            let pre = self.op(|scope| ops::add(input, recurrent, scope))?;
+            let pre = self.op(|scope| ops::add(pre, decoder_bias.clone(), scope))?;
+            state = self.op(|scope| ops::tanh(pre, scope))?;
+
+            let (mut context, mut attention): (Option<Output>, Option<Output>) = (None, None);
+            for (weights, memory) in memories.clone() {
+                let query = self.linear(state.clone(), weights)?;
+                let scores = self.linear(query, memory.clone())?;
+                let level = self.op(|scope| ops::softmax(scores, scope))?;
+                let level_context = self.op(|scope| ops::mat_mul(level.clone(), memory, scope))?;
+
+                context = Some(match context {
+                    Some(context) => self.op(|scope| ops::add(context, level_context, scope))?,
+                    None => level_context,
+                });
+                attention = Some(match attention {
+                    Some(attention) => self.op(|scope| ops::add(attention, level, scope))?,
+                    None => level,
+                });
+            }
+            let context = self.op(|scope| ops::mul(context.expect("memory levels"), scale.clone(), scope))?;
+            let attention = self.op(|scope| ops::mul(attention.expect("memory levels"), scale.clone(), scope))?;
+
+            let logits_context = self.linear(context.clone(), output_context.clone())?;
+            let logits_state = self.linear(state.clone(), output_state.clone())?;
+            let logits = self.op(|scope| ops::add(logits_context, logits_state, scope))?;
+            let logits = self.op(|scope| ops::add(logits, output_bias.clone(), scope))?;
+
+            let log_probs = match copy {
+                Some((ref copy_context, ref copy_state, ref copy_bias)) => {
+                    let gate_context = self.linear(context, copy_context.clone())?;
+                    let gate_state = self.linear(state.clone(), copy_state.clone())?;
+                    let gate = self.op(|scope| ops::add(gate_context, gate_state, scope))?;
+                    let gate = self.op(|scope| ops::add(gate, copy_bias.clone(), scope))?;
+                    let p_gen = self.op(|scope| ops::sigmoid(gate, scope))?;
+                    let one = self.constant_f32(1.0)?;
+                    let p_copy = self.op(|scope| ops::sub(one, p_gen.clone(), scope))?;
+
+                    let flat = self.constant_i32(&[-1], &[1])?;
+                    let probs = self.op(|scope| ops::softmax(logits, scope))?;
+                    let generated = self.op(|scope| ops::mul(probs, p_gen, scope))?;
+                    let generated = self.op(|scope| ops::reshape(generated, flat.clone(), scope))?;
+                    let copied = self.op(|scope| ops::mul(attention, p_copy, scope))?;
+                    let copied = self.op(|scope| ops::reshape(copied, flat, scope))?;
+
+                    let vocabulary_len_i32 = self.constant_i32(&[vocabulary_len as i32], &[])?;
+                    let extended_len = self.op(|scope| ops::add(vocabulary_len_i32, source_len.clone(), scope))?;
+                    let vocabulary_ids: Vec<i64> = (0..vocabulary_len as i64).collect();
+                    let vocabulary_ids = self.constant_i64(&vocabulary_ids, &[vocabulary_len as u64])?;
+
+                    let generated = self.op(|scope| ops::unsorted_segment_sum(generated, vocabulary_ids, extended_len.clone(), scope))?;
+                    let copied = self.op(|scope| ops::unsorted_segment_sum(copied, source_ids.clone(), extended_len, scope))?;
+                    let distribution = self.op(|scope| ops::add(generated, copied, scope))?;
+
+                    let row = self.constant_i32(&[1, -1], &[2])?;
+                    let distribution = self.op(|scope| ops::reshape(distribution, row, scope))?;
+                    let min = self.constant_f32(f32::MIN_POSITIVE)?;
+                    let distribution = self.op(|scope| ops::maximum(distribution, min, scope))?;
+                    self.op(|scope| ops::log(distribution, scope))?
+                },
+                None => self.op(|scope| ops::log_softmax(logits, scope))?,
+            };
+
+            let id = self.op(|scope| ops::arg_max(log_probs.clone(), last_axis.clone(), scope))?;
+            let log_prob = self.op(|scope| ops::max(log_probs, last_axis.clone(), scope))?;
+
+            let paddings = self.constant_i32(&[step as i32, (max_len - step - 1) as i32], &[1, 2])?;
+            let ids = self.op(|scope| ops::pad(id.clone(), paddings.clone(), scope))?;
+            let log_probs = self.op(|scope| ops::pad(log_prob, paddings, scope))?;
+
+            outputs = Some(match outputs {
+                Some((all_ids, all_log_probs)) => (
+                    self.op(|scope| ops::add(all_ids, ids, scope))?,
+                    self.op(|scope| ops::add(all_log_probs, log_probs, scope))?,
+                ),
+                None => (ids, log_probs),
+            });
+            prev = id;
+        }
+
+        let (ids, log_probs) = outputs.ok_or("invalid SavedModel export: max_len must be positive")?;
+        let summary_ids = tf(ops::identity(ids, &mut self.scope.with_op_name(SUMMARY_IDS)))?;
+        let log_probs = tf(ops::identity(log_probs, &mut self.scope.with_op_name(LOG_PROBS)))?;
+
+        Ok((input_ids, prefix_len, summary_ids, log_probs))
+    }
+}
+
+/// `tensor_info` returns the `TensorInfo` of the first output of `operation`.
+fn tensor_info(operation: &Operation, dtype: DataType, shape: Shape) -> Result<TensorInfo> {
+    Ok(TensorInfo::new(dtype, shape, OutputName { name: tf(operation.name())?, index: 0 }))
+}
+
+/// `save` writes `model` as a TensorFlow SavedModel in the new directory `dir`, with the
+/// `serving_default` signature decoding up to `max_len` ids greedily. Only the MMN
+/// architecture without metadata conditioning can be exported.
+pub fn save<P: AsRef<Path>>(model: &Seq2Seq, dir: P, max_len: usize) -> Result<()> {
+    let mut graph = DecoderGraph::new(model)?;
+    let (input_ids, prefix_len, summary_ids, log_probs) = graph.build(max_len)?;
+
+    let mut signature = SignatureDef::new(PREDICT_METHOD_NAME.to_string());
+    signature.add_input_info(INPUT_IDS.to_string(), tensor_info(&input_ids, DataType::Int64, Shape::from(Some(vec![None])))?);
+    signature.add_input_info(PREFIX_LEN.to_string(), tensor_info(&prefix_len, DataType::Int32, Shape::from(Some(vec![])))?);
+    signature.add_output_info(SUMMARY_IDS.to_string(), tensor_info(&summary_ids, DataType::Int64, Shape::from(Some(vec![Some(max_len as i64)])))?);
+    signature.add_output_info(LOG_PROBS.to_string(), tensor_info(&log_probs, DataType::Float, Shape::from(Some(vec![Some(max_len as i64)])))?);
+
+    let mut builder = SavedModelBuilder::new();
+    builder
+        .add_collection("variables", &graph.variables)
+        .add_tag(SERVE_TAG)
+        .add_signature(DEFAULT_SERVING_SIGNATURE_DEF_KEY, signature);
+    let saver = tf(builder.inject(&mut graph.scope))?;
+
+    let session = tf(Session::new(&SessionOptions::new(), &graph.scope.graph()))?;
+    let mut args = SessionRunArgs::new();
+    for variable in graph.variables.iter() {
+        args.add_target(variable.initializer());
+    }
+    tf(session.run(&mut args))?;
+
+    fs::create_dir_all(dir.as_ref()).map_err(|e| format!("{}", e))?;
+    tf(saver.save(&session, &graph.scope.graph(), dir.as_ref()))
+}
+
+/// `SavedModel` is a TensorFlow SavedModel written by `save`, run through its
+/// `serving_default` signature.
+pub struct SavedModel {
+    graph: Graph,
+    bundle: SavedModelBundle,
+}
+
+impl SavedModel {
+    /// `load` loads the `SavedModel` of the directory `dir`.
+    pub fn load<P: AsRef<Path>>(dir: P) -> Result<SavedModel> {
+        let mut graph = Graph::new();
+        let bundle = tf(SavedModelBundle::load(&SessionOptions::new(), &[SERVE_TAG], &mut graph, dir.as_ref()))?;
+
+        Ok(SavedModel { graph, bundle })
+    }
+
+    /// `operation` returns the operation and the output index of the input or output `name`
+    /// of the `serving_default` signature.
+    fn operation(&self, name: &str, input: bool) -> Result<(Operation, i32)> {
+        let signature = tf(self.bundle.meta_graph_def().get_signature(DEFAULT_SERVING_SIGNATURE_DEF_KEY))?;
+        let info = if input { tf(signature.get_input(name))? } else { tf(signature.get_output(name))? };
+        let operation = tf(self.graph.operation_by_name_required(&info.name().name))?;
+
+        Ok((operation, info.name().index))
+    }
+
+    /// `decode` returns the summary ids decoded from the encoder `input_ids`, the first
+    /// `prefix_len` ones read before the source, up to the end of sequence included, and
+    /// their length normalized log probability.
+    pub fn decode(&self, input_ids: &[usize], prefix_len: usize) -> Result<(Vec<usize>, f32)> {
+        let ids: Vec<i64> = input_ids.iter().map(|id| *id as i64).collect();
+        let ids = tf(Tensor::<i64>::new(&[ids.len() as u64]).with_values(&ids))?;
+        let prefix = tf(Tensor::<i32>::new(&[]).with_values(&[prefix_len as i32]))?;
+
+        let (input_op, input_idx) = self.operation(INPUT_IDS, true)?;
+        let (prefix_op, prefix_idx) = self.operation(PREFIX_LEN, true)?;
+        let (ids_op, ids_idx) = self.operation(SUMMARY_IDS, false)?;
+        let (log_probs_op, log_probs_idx) = self.operation(LOG_PROBS, false)?;
+
+        let mut args = SessionRunArgs::new();
+        args.add_feed(&input_op, input_idx, &ids);
+        args.add_feed(&prefix_op, prefix_idx, &prefix);
+        let ids_token = args.request_fetch(&ids_op, ids_idx);
+        let log_probs_token = args.request_fetch(&log_probs_op, log_probs_idx);
+        tf(self.bundle.session.run(&mut args))?;
+
+        let ids: Tensor<i64> = tf(args.fetch(ids_token))?;
+        let log_probs: Tensor<f32> = tf(args.fetch(log_probs_token))?;
+
+        let len = ids.iter().position(|id| *id as usize == EOS_ID).map(|idx| idx + 1).unwrap_or(ids.len());
+        let log_prob: f64 = log_probs[..len].iter().map(|p| f64::from(*p)).sum();
+
+        Ok((ids[..len].iter().map(|id| *id as usize).collect(), (log_prob / len.max(1) as f64) as f32))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{prefix_ids, save, SavedModel};
+    use crate::config::Config;
+    use crate::model::Model;
+    use crate::multi_task::TargetConditioning;
+    use crate::seed::Seeds;
+    use crate::summarizer::{Batch, DecodeOptions, Summarizer, SummaryMode, TrainStepOptions};
+    use crate::vocabulary::Vocabulary;
+    use std::env;
+    use std::fs;
+
+    fn tokens(s: &str) -> Vec<String> {
+        s.split_whitespace().map(ToOwned::to_owned).collect()
+    }
+
+    #[test]
+    fn test_saved_model_round_trip() {
+        let mut batch = Batch::new();
+        batch.ids = vec!["a".to_string(), "b".to_string()];
+        batch.modes = vec![SummaryMode::Short, SummaryMode::Long];
+        batch.sources = vec![tokens("my cat ate my homework today"), tokens("i broke the zorglub build again")];
+        batch.summaries = vec![tokens("cat ate homework"), tokens("broke the zorglub")];
+
+        for (copy, conditioning) in [(false, None), (true, Some(TargetConditioning::Token))].iter() {
+            let mut config = Config::tifu_short();
+            config.model.embedding_size = 8;
+            config.model.max_summary_len = 6;
+            config.model.copy = *copy;
+            config.model.target_conditioning = *conditioning;
+
+            let vocabulary = Vocabulary::build(batch.summaries.iter().map(Vec::as_slice), 100, 1);
+            let mut model = Model::new(&config, vocabulary, None, &Seeds::new(0)).unwrap();
+            for _ in 0..20 {
+                model.train_step(&batch, &TrainStepOptions::default()).unwrap();
+            }
+            let model = model.seq2seq().unwrap().clone();
+
+            let mut dir = env::temp_dir();
+            dir.push("mmn_test_saved_model_round_trip");
+            let _ = fs::remove_dir_all(&dir);
+            save(&model, &dir, config.model.max_summary_len).unwrap();
+
+            let saved_model = SavedModel::load(&dir).unwrap();
+            let options = DecodeOptions { beam_size: 1, max_len: config.model.max_summary_len, attention: false };
+            let expected = model.decode(&batch, &options).unwrap();
+
+            for ((source, mode), hypothesis) in batch.sources.iter().zip(batch.modes.iter()).zip(expected.iter()) {
+                let mut input_ids = prefix_ids(&model, *mode);
+                let prefix_len = input_ids.len();
+                let copy_example = model.copy_example(source);
+                match copy_example {
+                    Some(ref copy) => input_ids.extend(copy.source_extended_ids.iter().cloned()),
+                    None => input_ids.extend(model.source_ids(source)),
+                }
+
+                let (ids, score) = saved_model.decode(&input_ids, prefix_len).unwrap();
+                let tokens = match copy_example {
+                    Some(ref copy) => copy.decode(&model.vocabulary, &ids),
+                    None => model.vocabulary.decode(&ids),
+                };
+
+                assert_eq!(tokens, hypothesis.tokens);
+                assert!((score - hypothesis.score).abs() < 1e-4, "{} != {}", score, hypothesis.score);
+            }
+
+            fs::remove_dir_all(&dir).unwrap();
+        }
+    }
+}
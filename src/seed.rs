@@ -0,0 +1,143 @@
+use serde::{Serialize, Deserialize};
+use rand::{Error, RngCore, SeedableRng};
+use rand::rngs::StdRng;
+
+/// `SHUFFLE_STREAM` is the stream of the data shuffling seed.
+const SHUFFLE_STREAM: u64 = 1;
+/// `SPLIT_STREAM` is the stream of the split assignment seed.
+const SPLIT_STREAM: u64 = 2;
+/// `DROPOUT_STREAM` is the stream of the dropout seed.
+const DROPOUT_STREAM: u64 = 3;
+/// `INIT_STREAM` is the stream of the parameter initialization seed.
+const INIT_STREAM: u64 = 4;
+
+/// `splitmix64` returns the SplitMix64 mix of `z`.
+pub fn splitmix64(z: u64) -> u64 {
+    let mut z = z.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// `derive_seed` derives the seed of the independent stream `stream` from `seed`.
+pub fn derive_seed(seed: u64, stream: u64) -> u64 {
+    splitmix64(seed ^ splitmix64(stream))
+}
+
+/// `Seeds` are the seeds of a run, all derived from a single seed so that a run is
+/// reproduced by its seed alone.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct Seeds {
+    /// `seed` is the seed all the other seeds are derived from.
+    pub seed: u64,
+    /// `shuffle` is the seed of the data shuffling.
+    pub shuffle: u64,
+    /// `split` is the seed of the split assignment.
+    pub split: u64,
+    /// `dropout` is the seed of the dropout masks.
+    pub dropout: u64,
+    /// `init` is the seed of the parameter initialization.
+    pub init: u64,
+}
+
+impl Seeds {
+    /// `new` creates the `Seeds` derived from `seed`.
+    pub fn new(seed: u64) -> Seeds {
+        Seeds {
+            seed,
+            shuffle: derive_seed(seed, SHUFFLE_STREAM),
+            split: derive_seed(seed, SPLIT_STREAM),
+            dropout: derive_seed(seed, DROPOUT_STREAM),
+            init: derive_seed(seed, INIT_STREAM),
+        }
+    }
+
+    /// `epoch_shuffle` returns the seed of the data shuffling of `epoch`.
+    pub fn epoch_shuffle(&self, epoch: u64) -> u64 {
+        derive_seed(self.shuffle, epoch)
+    }
+
+    /// `rng` returns a rng seeded by `seed`.
+    pub fn rng(seed: u64) -> StdRng {
+        StdRng::seed_from_u64(seed)
+    }
+}
+
+/// `StreamRng` is a SplitMix64 rng whose whole state is a serializable counter, so that the
+/// live rng of a training run can be saved in a checkpoint and restored exactly.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct StreamRng {
+    pub state: u64,
+}
+
+impl StreamRng {
+    /// `new` creates a new `StreamRng` seeded by `seed`.
+    pub fn new(seed: u64) -> StreamRng {
+        StreamRng { state: seed }
+    }
+}
+
+impl RngCore for StreamRng {
+    fn next_u32(&mut self) -> u32 {
+        (self.next_u64() >> 32) as u32
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let z = splitmix64(self.state);
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        z
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for chunk in dest.chunks_mut(8) {
+            let bytes = self.next_u64().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Seeds, StreamRng};
+    use rand::Rng;
+
+    #[test]
+    fn test_seeds_new() {
+        let seeds = Seeds::new(42);
+        assert_eq!(seeds, Seeds::new(42));
+        assert_ne!(seeds, Seeds::new(43));
+
+        let derived = [seeds.shuffle, seeds.split, seeds.dropout, seeds.init];
+        for (i, a) in derived.iter().enumerate() {
+            for b in derived[i + 1..].iter() {
+                assert_ne!(a, b);
+            }
+        }
+
+        assert_eq!(seeds.epoch_shuffle(1), Seeds::new(42).epoch_shuffle(1));
+        assert_ne!(seeds.epoch_shuffle(1), seeds.epoch_shuffle(2));
+
+        let a: u64 = Seeds::rng(seeds.init).gen();
+        let b: u64 = Seeds::rng(seeds.init).gen();
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn test_seeds_stream_rng() {
+        let mut rng = StreamRng::new(7);
+        let _: u64 = rng.gen();
+
+        let json = serde_json::to_string(&rng).unwrap();
+        let mut restored: StreamRng = serde_json::from_str(&json).unwrap();
+
+        let a: Vec<f32> = (0..10).map(|_| rng.gen()).collect();
+        let b: Vec<f32> = (0..10).map(|_| restored.gen()).collect();
+        assert_eq!(a, b);
+        assert_ne!(a[0], a[1]);
+    }
+}
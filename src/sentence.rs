@@ -0,0 +1,205 @@
+use std::ops::Range;
+
+/// `TERMINAL_CHARS` are the characters of the tokens ending a sentence.
+const TERMINAL_CHARS: [char; 4] = ['.', '!', '?', '…'];
+
+/// `CLOSING_TOKENS` are the tokens kept with the sentence they follow.
+const CLOSING_TOKENS: [&str; 8] = ["\"", "'", "''", ")", "]", "”", "’", "*"];
+
+/// `ABBREVIATIONS` are the tokens that are not ended by a following period.
+const ABBREVIATIONS: [&str; 13] = [
+    "mr", "mrs", "ms", "dr", "st", "jr", "sr", "vs", "etc", "e.g", "i.e", "approx", "lbs",
+];
+
+/// `EMOTICONS` are the emoticons ending a sentence, lowercase.
+const EMOTICONS: [&str; 18] = [
+    ":)", ":(", ":-)", ":-(", ":d", ":-d", ":p", ":-p", ";)", ";-)", ":/", ":'(", ":o", ":|",
+    "xd", "<3", "^^", "-_-",
+];
+
+/// `MAX_EMOTICON_TOKENS` is the maximum number of tokens an emoticon can be split in.
+const MAX_EMOTICON_TOKENS: usize = 3;
+
+/// `is_terminal` returns if `token` ends a sentence, as in ".", "?!" or "...".
+fn is_terminal(token: &str) -> bool {
+    !token.is_empty() && token.chars().all(|c| TERMINAL_CHARS.contains(&c))
+}
+
+/// `is_ellipsis` returns if `token` is an ellipsis, which only ends a sentence before a line
+/// break or a capitalized token.
+fn is_ellipsis(token: &str) -> bool {
+    token == "…" || (token.len() > 1 && token.chars().all(|c| c == '.'))
+}
+
+/// `is_capitalized` returns if `token` starts with an uppercase letter.
+fn is_capitalized(token: &str) -> bool {
+    token.chars().next().map(char::is_uppercase).unwrap_or(false)
+}
+
+/// `emoticon_len` returns the number of tokens of the emoticon starting at `tokens[idx]`, or
+/// zero if no emoticon starts there. Emoticons split by the tokenizer are joined back.
+fn emoticon_len(tokens: &[String], idx: usize) -> usize {
+    let mut joined = String::new();
+
+    for (len, token) in tokens[idx..].iter().take(MAX_EMOTICON_TOKENS).enumerate() {
+        joined.push_str(&token.to_lowercase());
+        if EMOTICONS.contains(&joined.as_str()) {
+            return len + 1;
+        }
+    }
+
+    0
+}
+
+/// `line_breaks` returns the indices of the `tokens` of `text` preceded by a line break. The
+/// tokens are matched in `text` case-insensitively; a token must start before the next
+/// alphanumeric character of `text`, otherwise it is considered missing from `text` and
+/// skipped. The search never goes past that character, so a missing token costs at most
+/// the length of the gap before it.
+pub fn line_breaks(text: &str, tokens: &[String]) -> Vec<usize> {
+    let text = text.to_lowercase();
+    let gap_len = |pos: usize| text[pos..].find(char::is_alphanumeric).unwrap_or(text.len() - pos);
+
+    let mut pos = 0;
+    let mut gap = gap_len(pos);
+    let mut breaks = Vec::new();
+
+    for (idx, token) in tokens.iter().enumerate() {
+        let token = token.to_lowercase();
+        if token.trim().is_empty() {
+            continue;
+        }
+
+        let found = (0..=gap)
+            .filter(|offset| text.is_char_boundary(pos + offset))
+            .find(|offset| text[pos + offset..].starts_with(&token));
+
+        let found = match found {
+            Some(found) => found,
+            None => continue,
+        };
+
+        if idx > 0 && text[pos..pos + found].contains('\n') {
+            breaks.push(idx);
+        }
+
+        pos += found + token.len();
+        gap = gap_len(pos);
+    }
+
+    breaks
+}
+
+/// `sentence_spans` returns the token ranges of the sentences of `tokens`, `breaks` being the
+/// indices of the tokens preceded by a line break. Sentences end at terminal punctuation, at
+/// emoticons and at line breaks, and keep the closing quotes, brackets and emoticons that
+/// follow them. Abbreviations and ellipses in the middle of a sentence do not end it.
+pub fn sentence_spans(tokens: &[String], breaks: &[usize]) -> Vec<Range<usize>> {
+    let len = tokens.len();
+    let mut spans = Vec::new();
+    let mut start = 0;
+    let mut idx = 0;
+
+    while idx < len {
+        if idx > start && breaks.contains(&idx) {
+            spans.push(start..idx);
+            start = idx;
+        }
+
+        let token = tokens[idx].as_str();
+        let emoticon = emoticon_len(tokens, idx);
+
+        if emoticon == 0 && !is_terminal(token) {
+            idx += 1;
+            continue;
+        }
+
+        if token == "." && idx > start && ABBREVIATIONS.contains(&tokens[idx - 1].to_lowercase().as_str()) {
+            idx += 1;
+            continue;
+        }
+
+        idx += emoticon.max(1);
+
+        loop {
+            if idx >= len || breaks.contains(&idx) {
+                break;
+            }
+
+            let next = tokens[idx].as_str();
+            if is_terminal(next) || CLOSING_TOKENS.contains(&next) {
+                idx += 1;
+            } else {
+                match emoticon_len(tokens, idx) {
+                    0 => break,
+                    emoticon => idx += emoticon,
+                }
+            }
+        }
+
+        if emoticon == 0 && is_ellipsis(token) && idx < len && !breaks.contains(&idx) && !is_capitalized(&tokens[idx]) {
+            continue;
+        }
+
+        spans.push(start..idx);
+        start = idx;
+    }
+
+    if start < len {
+        spans.push(start..len);
+    }
+
+    spans
+}
+
+/// `split_sentences` splits the `tokens` of `text` in sentences.
+pub fn split_sentences(text: &str, tokens: &[String]) -> Vec<Vec<String>> {
+    sentence_spans(tokens, &line_breaks(text, tokens))
+        .into_iter()
+        .map(|span| tokens[span].to_vec())
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{line_breaks, sentence_spans, split_sentences};
+
+    fn tokens(s: &str) -> Vec<String> {
+        s.split_whitespace().map(ToOwned::to_owned).collect()
+    }
+
+    fn sentences(s: &str) -> Vec<Vec<String>> {
+        s.split('|').map(tokens).collect()
+    }
+
+    #[test]
+    fn test_sentence_line_breaks() {
+        let text = "So I went home\n\nTurns out it was *Sunday*.";
+        let source = tokens("so i went home turns out it was sunday .");
+        assert_eq!(line_breaks(text, &source), vec![4]);
+
+        let source = tokens("so i went xyz home");
+        assert!(line_breaks(text, &source).is_empty());
+
+        let source = tokens("so i went home home turns");
+        assert_eq!(line_breaks(text, &source), vec![5]);
+
+        let text = format!("{}\nend", "word ".repeat(20_000));
+        let mut source = vec!["missing".to_string(); 20_000];
+        source.push("end".to_string());
+        assert_eq!(line_breaks(&text, &source), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn test_sentence_split_sentences() {
+        let text = "i went to the store... it was closed :( so i went home\nturns out it was sunday lol. what an idiot!!";
+        let source = tokens("i went to the store ... it was closed :( so i went home turns out it was sunday lol . what an idiot !!");
+        assert_eq!(split_sentences(text, &source), sentences(
+            "i went to the store ... it was closed :( | so i went home | turns out it was sunday lol . | what an idiot !!"));
+
+        let source = tokens("mr . smith said \" no . \" : ) i waited ... Then i left ? ! :p");
+        assert_eq!(sentence_spans(&source, &[]), vec![0..10, 10..13, 13..source.len()]);
+
+        assert!(split_sentences("", &[]).is_empty());
+    }
+}
@@ -0,0 +1,1566 @@
+use rand::Rng;
+use rayon::prelude::*;
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::fs;
+use std::path::Path;
+use crate::result::Result;
+use crate::config::{Architecture, Config, ModelConfig, Tokenization};
+use crate::bpe::{word_lengths, Bpe, BPE_FILE};
+use crate::copy::{copy_distribution, CopyExample};
+use crate::vocabulary::{Vocabulary, BOS_ID, EOS_ID, PAD_ID, UNK_ID};
+use crate::variables::{add_outer, add_scaled, dot, Tensor, Variables};
+use crate::optimizer::{OptimizerState, OPTIMIZER_FILE};
+use crate::seed::{Seeds, StreamRng};
+use crate::lr_schedule::{clip_by_global_norm, global_norm};
+use crate::summarizer::{Batch, DecodeOptions, Hypothesis, Summarizer, SummaryMode, TrainStepOptions, TrainStepStats};
+use crate::multi_task::{target_token, TargetConditioning, TARGET_TOKENS};
+use crate::metadata::{Metadata, MetadataEncoding};
+use crate::mmn::ConvLayer;
+use crate::attention::AttentionLevel;
+
+/// `RNG_FILE` is the name of the dropout rng state file in a model directory.
+pub const RNG_FILE: &str = "rng.json";
+
+/// `Encoder` are the indices of the encoder variables of a `Seq2Seq`.
+#[derive(Clone, PartialEq, Eq, Debug)]
+enum Encoder {
+    /// `Rnn` are the input, recurrent and bias variables of the RNN encoder.
+    Rnn(usize, usize, usize),
+    /// `Conv` are the dilated convolution layers of the MMN encoder, bottom first.
+    Conv(Vec<ConvLayer>),
+}
+
+/// `Params` are the indices of the variables of a `Seq2Seq`.
+#[derive(Clone, PartialEq, Eq, Debug)]
+struct Params {
+    embedding: usize,
+    encoder: Encoder,
+    decoder_input: usize,
+    decoder_recurrent: usize,
+    decoder_bias: usize,
+    /// `attention` are the attention variables of the memory levels, bottom first. The RNN
+    /// encoder has a single level.
+    attention: Vec<usize>,
+    output_weights: usize,
+    output_bias: usize,
+    /// `copy` are the weights and the bias of the generation probability, with the copy
+    /// mechanism.
+    copy: Option<(usize, usize)>,
+    /// `long_output` are the output weights and bias of the tl;dr summaries, with the `Heads`
+    /// target conditioning.
+    long_output: Option<(usize, usize)>,
+    /// `metadata` are the embeddings of the metadata feature buckets, with the `Embeddings`
+    /// metadata encoding.
+    metadata: Option<usize>,
+}
+
+impl Params {
+    /// `new` returns the `Params` of `variables` for the architecture of `config`.
+    fn new(config: &ModelConfig, variables: &Variables) -> Result<Params> {
+        let (encoder, attention) = match config.architecture {
+            Architecture::Rnn => {
+                let encoder = Encoder::Rnn(
+                    variables.index("encoder/input")?,
+                    variables.index("encoder/recurrent")?,
+                    variables.index("encoder/bias")?,
+                );
+                (encoder, vec![variables.index("attention")?])
+            },
+            Architecture::Mmn => {
+                let layers = config
+                    .dilation_rates
+                    .iter()
+                    .enumerate()
+                    .map(|(idx, rate)| Ok(ConvLayer {
+                        weights: variables.index(&format!("encoder/conv{}/weights", idx))?,
+                        bias: variables.index(&format!("encoder/conv{}/bias", idx))?,
+                        rate: *rate,
+                    }))
+                    .collect::<Result<Vec<ConvLayer>>>()?;
+                let attention = (0..config.memory_levels)
+                    .map(|level| variables.index(&format!("attention/{}", level)))
+                    .collect::<Result<Vec<usize>>>()?;
+                (Encoder::Conv(layers), attention)
+            },
+            Architecture::Lead => return Err("invalid model.architecture: lead has no variables".to_string()),
+        };
+
+        Ok(Params {
+            embedding: variables.index("embedding")?,
+            encoder,
+            decoder_input: variables.index("decoder/input")?,
+            decoder_recurrent: variables.index("decoder/recurrent")?,
+            decoder_bias: variables.index("decoder/bias")?,
+            attention,
+            output_weights: variables.index("output/weights")?,
+            output_bias: variables.index("output/bias")?,
+            copy: match variables.index("copy/weights") {
+                Ok(weights) => Some((weights, variables.index("copy/bias")?)),
+                Err(_) => None,
+            },
+            long_output: match variables.index("output_long/weights") {
+                Ok(weights) => Some((weights, variables.index("output_long/bias")?)),
+                Err(_) => None,
+            },
+            metadata: variables.index("metadata/embedding").ok(),
+        })
+    }
+}
+
+/// `Input` is an embedding read by a RNN, as the index of its variable and its row.
+type Input = (usize, usize);
+
+/// `Layers` are the encoder states of a sequence, one sequence of states per layer, bottom
+/// first. The RNN encoder has a single layer.
+type Layers = Vec<Vec<Vec<f32>>>;
+
+/// `Condition` is what a summary is conditioned on besides its source.
+struct Condition {
+    /// `prefix` are the encoder inputs read before the source.
+    prefix: Vec<Input>,
+    /// `head` are the output weights and bias of the decoder.
+    head: (usize, usize),
+}
+
+/// `DecoderStep` is the forward pass of a decoder step, kept for the backward pass.
+struct DecoderStep {
+    input: usize,
+    /// `head` are the output weights and bias of the step.
+    head: (usize, usize),
+    prev: Vec<f32>,
+    state: Vec<f32>,
+    /// `queries` are the attention queries of `state`, one per memory level.
+    queries: Vec<Vec<f32>>,
+    /// `levels` are the attention weights of every memory level.
+    levels: Vec<Vec<f32>>,
+    /// `attention` is the mean of the attention weights of the levels.
+    attention: Vec<f32>,
+    /// `output` is the context vector followed by `state`, after dropout.
+    output: Vec<f32>,
+    /// `mask` is the dropout mask of `output`, empty without dropout.
+    mask: Vec<f32>,
+    logits: Vec<f32>,
+    /// `p_gen` is the probability of generating rather than copying, one without the copy
+    /// mechanism.
+    p_gen: f32,
+}
+
+/// `StepGradients` are the gradients of the loss of a decoder step with respect to its
+/// outputs.
+struct StepGradients {
+    logits: Vec<f32>,
+    /// `gate` is the gradient of the pre-activation of `p_gen`.
+    gate: f32,
+    /// `attention` is the gradient of the attention weights through the copy distribution.
+    attention: Vec<f32>,
+}
+
+/// `Example` are the condition, the source ids, the copy example and the target ids of a batch
+/// entry.
+type Example = (Condition, Vec<usize>, Option<CopyExample>, Vec<usize>);
+
+/// `sigmoid` returns the logistic sigmoid of `x`.
+fn sigmoid(x: f32) -> f32 {
+    1.0 / (1.0 + (-x).exp())
+}
+
+/// `Beam` is a partial hypothesis of the beam search.
+#[derive(Clone)]
+struct Beam {
+    ids: Vec<usize>,
+    log_prob: f64,
+    state: Vec<f32>,
+    done: bool,
+    /// `attention` are the attention weights of every memory level for every id, recorded
+    /// with the `attention` decode option.
+    attention: Vec<Vec<Vec<f32>>>,
+}
+
+impl Beam {
+    /// `score` returns the length normalized log probability of the `Beam`.
+    fn score(&self) -> f64 {
+        self.log_prob / self.ids.len().max(1) as f64
+    }
+}
+
+/// `softmax` returns the softmax of `x`.
+fn softmax(x: &[f32]) -> Vec<f32> {
+    let max = x.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+    let exp: Vec<f32> = x.iter().map(|v| (v - max).exp()).collect();
+    let sum: f32 = exp.iter().sum();
+    exp.into_iter().map(|v| v / sum).collect()
+}
+
+/// `log_softmax` returns the log of the softmax of `x`.
+fn log_softmax(x: &[f32]) -> Vec<f32> {
+    let max = x.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+    let log_sum = x.iter().map(|v| (v - max).exp()).sum::<f32>().ln() + max;
+    x.iter().map(|v| v - log_sum).collect()
+}
+
+/// `top_k` returns the indices of the `k` largest `values`, largest first.
+fn top_k(values: &[f32], k: usize) -> Vec<usize> {
+    let desc = |a: &usize, b: &usize| values[*b].partial_cmp(&values[*a]).unwrap_or(Ordering::Equal);
+    let mut indices: Vec<usize> = (0..values.len()).collect();
+
+    if k < indices.len() {
+        indices.select_nth_unstable_by(k, desc);
+        indices.truncate(k);
+    }
+
+    indices.sort_by(desc);
+    indices
+}
+
+/// `word_hypothesis` returns the `hypothesis` decoded from the subwords `source` with its
+/// subwords merged back in words by `bpe`. The attention rows of the subwords of a summary
+/// word are averaged, and the attention columns of the subwords of a source word summed.
+fn word_hypothesis(bpe: &Bpe, source: &[String], hypothesis: Hypothesis) -> Hypothesis {
+    let columns = word_lengths(source);
+    let rows = word_lengths(&hypothesis.tokens);
+
+    let attention = hypothesis
+        .attention
+        .into_iter()
+        .map(|level| AttentionLevel {
+            dilation: level.dilation,
+            weights: word_weights(&columns, &rows, &level.weights),
+        })
+        .collect();
+
+    Hypothesis {
+        tokens: bpe.decode(&hypothesis.tokens),
+        score: hypothesis.score,
+        attention,
+    }
+}
+
+/// `word_weights` returns the subword attention `weights` merged in words, given the subword
+/// lengths of the source words `columns` and of the summary words `rows`.
+fn word_weights(columns: &[usize], rows: &[usize], weights: &[Vec<f32>]) -> Vec<Vec<f32>> {
+    let mut merged = Vec::new();
+    let mut start = 0;
+
+    for len in rows.iter().cloned() {
+        let rows = match weights.get(start..start + len) {
+            Some(rows) => rows,
+            None => break,
+        };
+        start += len;
+
+        let width = rows.first().map(Vec::len).unwrap_or(0);
+        let mut row = Vec::new();
+        let mut column = 0;
+
+        for column_len in columns.iter() {
+            if column >= width {
+                break;
+            }
+
+            let end = (column + column_len).min(width);
+            let weight: f32 = rows.iter().map(|r| r[column..end].iter().sum::<f32>()).sum();
+            row.push(weight / len as f32);
+            column = end;
+        }
+
+        merged.push(row);
+    }
+
+    merged
+}
+
+/// `Seq2Seq` is an attentional RNN encoder-decoder: a tanh RNN encodes the source, a tanh RNN
+/// decoder attends over the encoder states with bilinear attention and predicts every token
+/// from the context vector and its state. With the `copy` option of the config it is a
+/// pointer-generator, mixing the vocabulary distribution with the attention over the source
+/// so that it can copy the out-of-vocabulary source tokens. It is trained with teacher
+/// forcing and backpropagation through time, and decodes with beam search. With the `Bpe`
+/// tokenization, it reads and writes subwords and decodes back to words. With the `Mmn`
+/// architecture, the encoder is a stack of residual dilated convolutions and the decoder
+/// attends over the outputs of its top layers, the memory levels, averaging their contexts.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Seq2Seq {
+    pub config: Config,
+    pub vocabulary: Vocabulary,
+    pub variables: Variables,
+    pub optimizer: OptimizerState,
+    /// `rng` is the rng of the dropout masks.
+    pub rng: StreamRng,
+    /// `bpe` splits the tokens in subwords, with the `Bpe` tokenization.
+    pub bpe: Option<Bpe>,
+    params: Params,
+}
+
+impl Seq2Seq {
+    /// `new` creates a new `Seq2Seq` of `config` over `vocabulary`, initialized with `seeds`.
+    /// The `bpe` merges are required by the `Bpe` tokenization, and the vocabulary must then
+    /// be built over their subwords. The target-type tokens of the `Token` target conditioning
+    /// and the metadata tokens of the `Tokens` metadata encoding are added to the vocabulary.
+    pub fn new(config: &Config, mut vocabulary: Vocabulary, bpe: Option<Bpe>, seeds: &Seeds) -> Result<Seq2Seq> {
+        config.validate()?;
+
+        if config.model.target_conditioning == Some(TargetConditioning::Token) {
+            vocabulary.extend(TARGET_TOKENS.iter().cloned());
+        }
+
+        if let Some(ref metadata) = config.model.metadata {
+            if metadata.encoding == MetadataEncoding::Tokens {
+                vocabulary.extend(metadata.vocabulary_tokens().iter().map(String::as_str));
+            }
+        }
+
+        let size = config.model.embedding_size;
+        let len = vocabulary.len();
+        let scale = 1.0 / (size as f32).sqrt();
+        let mut rng = StreamRng::new(seeds.init);
+        let mut variables = Variables::new();
+
+        variables.insert("embedding", Tensor::uniform(&[len, size], 0.1, &mut rng))?;
+        let mmn = config.model.architecture == Architecture::Mmn;
+        let prefixes: &[&str] = if mmn { &["decoder"] } else { &["encoder", "decoder"] };
+
+        if mmn {
+            let kernel_size = config.model.kernel_size;
+            let conv_scale = 1.0 / ((kernel_size * size) as f32).sqrt();
+            for idx in 0..config.model.layers {
+                variables.insert(&format!("encoder/conv{}/weights", idx), Tensor::uniform(&[size, kernel_size * size], conv_scale, &mut rng))?;
+                variables.insert(&format!("encoder/conv{}/bias", idx), Tensor::zeros(&[size]))?;
+            }
+        }
+
+        for prefix in prefixes.iter() {
+            variables.insert(&format!("{}/input", prefix), Tensor::uniform(&[size, size], scale, &mut rng))?;
+            variables.insert(&format!("{}/recurrent", prefix), Tensor::uniform(&[size, size], scale, &mut rng))?;
+            variables.insert(&format!("{}/bias", prefix), Tensor::zeros(&[size]))?;
+        }
+
+        if mmn {
+            for level in 0..config.model.memory_levels {
+                variables.insert(&format!("attention/{}", level), Tensor::uniform(&[size, size], scale, &mut rng))?;
+            }
+        } else {
+            variables.insert("attention", Tensor::uniform(&[size, size], scale, &mut rng))?;
+        }
+        variables.insert("output/weights", Tensor::uniform(&[len, 2 * size], scale, &mut rng))?;
+        variables.insert("output/bias", Tensor::zeros(&[len]))?;
+
+        if config.model.copy {
+            variables.insert("copy/weights", Tensor::uniform(&[1, 2 * size], scale, &mut rng))?;
+            variables.insert("copy/bias", Tensor::zeros(&[1]))?;
+        }
+
+        if config.model.target_conditioning == Some(TargetConditioning::Heads) {
+            variables.insert("output_long/weights", Tensor::uniform(&[len, 2 * size], scale, &mut rng))?;
+            variables.insert("output_long/bias", Tensor::zeros(&[len]))?;
+        }
+
+        if let Some(ref metadata) = config.model.metadata {
+            if metadata.encoding == MetadataEncoding::Embeddings {
+                variables.insert("metadata/embedding", Tensor::uniform(&[metadata.embedding_size(), size], 0.1, &mut rng))?;
+            }
+        }
+
+        Seq2Seq::from_variables(config.to_owned(), vocabulary, variables, None, StreamRng::new(seeds.dropout), bpe)
+    }
+
+    /// `from_variables` creates a `Seq2Seq` from its parts, checking the variable shapes. A
+    /// missing `optimizer` state is initialized.
+    fn from_variables(config: Config,
+                      vocabulary: Vocabulary,
+                      variables: Variables,
+                      optimizer: Option<OptimizerState>,
+                      rng: StreamRng,
+                      bpe: Option<Bpe>) -> Result<Seq2Seq>
+    {
+        let params = Params::new(&config.model, &variables)?;
+        let size = config.model.embedding_size;
+        let len = vocabulary.len();
+
+        let tokenization = config.model.tokenization;
+        if bpe.is_some() != (tokenization != Tokenization::Word) {
+            return Err(format!("invalid bpe: the merges do not match model.tokenization: {:?}", tokenization));
+        }
+
+        if params.copy.is_some() != config.model.copy {
+            return Err(format!("invalid variables: the copy variables do not match model.copy: {}", config.model.copy));
+        }
+
+        let conditioning = config.model.target_conditioning;
+        if params.long_output.is_some() != (conditioning == Some(TargetConditioning::Heads)) {
+            return Err(format!("invalid variables: the output heads do not match model.target_conditioning: {:?}", conditioning));
+        }
+
+        if conditioning == Some(TargetConditioning::Token) && !TARGET_TOKENS.iter().all(|t| vocabulary.contains(t)) {
+            return Err("invalid vocabulary: missing target-type tokens".to_string());
+        }
+
+        let encoding = config.model.metadata.as_ref().map(|m| m.encoding);
+        if params.metadata.is_some() != (encoding == Some(MetadataEncoding::Embeddings)) {
+            return Err(format!("invalid variables: the metadata embeddings do not match model.metadata: {:?}", encoding));
+        }
+
+        if let (Some(metadata), Some(MetadataEncoding::Tokens)) = (config.model.metadata.as_ref(), encoding) {
+            if !metadata.vocabulary_tokens().iter().all(|t| vocabulary.contains(t)) {
+                return Err("invalid vocabulary: missing metadata tokens".to_string());
+            }
+        }
+
+        let mut shapes = vec![
+            (params.embedding, vec![len, size]),
+            (params.decoder_input, vec![size, size]),
+            (params.decoder_recurrent, vec![size, size]),
+            (params.decoder_bias, vec![size]),
+            (params.output_weights, vec![len, 2 * size]),
+            (params.output_bias, vec![len]),
+        ];
+
+        match params.encoder {
+            Encoder::Rnn(input, recurrent, bias) => {
+                shapes.push((input, vec![size, size]));
+                shapes.push((recurrent, vec![size, size]));
+                shapes.push((bias, vec![size]));
+            },
+            Encoder::Conv(ref layers) => {
+                for layer in layers.iter() {
+                    shapes.push((layer.weights, vec![size, config.model.kernel_size * size]));
+                    shapes.push((layer.bias, vec![size]));
+                }
+            },
+        }
+
+        for attention in params.attention.iter() {
+            shapes.push((*attention, vec![size, size]));
+        }
+
+        if let Some((weights, bias)) = params.copy {
+            shapes.push((weights, vec![1, 2 * size]));
+            shapes.push((bias, vec![1]));
+        }
+
+        if let Some((weights, bias)) = params.long_output {
+            shapes.push((weights, vec![len, 2 * size]));
+            shapes.push((bias, vec![len]));
+        }
+
+        if let (Some(embedding), Some(metadata)) = (params.metadata, config.model.metadata.as_ref()) {
+            shapes.push((embedding, vec![metadata.embedding_size(), size]));
+        }
+
+        for (idx, shape) in shapes.iter() {
+            if &variables.get(*idx).shape != shape {
+                return Err(format!("invalid shape of variable {}: expected {:?}, found {:?}",
+                    variables.names()[*idx], shape, variables.get(*idx).shape));
+            }
+        }
+
+        let optimizer = match optimizer {
+            Some(optimizer) => optimizer,
+            None => OptimizerState::new(&config.train.optimizer, &variables)?,
+        };
+
+        Ok(Seq2Seq {
+            config,
+            vocabulary,
+            variables,
+            optimizer,
+            rng,
+            bpe,
+            params,
+        })
+    }
+
+    /// `save_inference` saves the config, the vocabulary, the bpe merges and the variables of
+    /// the `Seq2Seq`, the files needed to decode, in the directory `dir`.
+    pub fn save_inference(&self, dir: &Path) -> Result<()> {
+        self.config.save(dir)?;
+        self.vocabulary.save(dir)?;
+        if let Some(ref bpe) = self.bpe {
+            bpe.save(dir)?;
+        }
+
+        self.variables.save(dir)
+    }
+
+    /// `subwords` returns `batch` with its tokens split in subwords, with the `Bpe` tokenization.
+    fn subwords<'a>(&self, batch: &'a Batch) -> Cow<'a, Batch> {
+        match self.bpe {
+            Some(ref bpe) => Cow::Owned(batch.encode_bpe(bpe)),
+            None => Cow::Borrowed(batch),
+        }
+    }
+
+    /// `size` returns the size of the embeddings and of the hidden states.
+    fn size(&self) -> usize {
+        self.config.model.embedding_size
+    }
+
+    /// `source_ids` returns the encoder input ids of `source`, truncated to `max_source_len`
+    /// and ended by `EOS_ID`.
+    pub fn source_ids(&self, source: &[String]) -> Vec<usize> {
+        let len = source.len().min(self.config.model.max_source_len);
+        let mut ids = self.vocabulary.encode(&source[..len]);
+        ids.push(EOS_ID);
+        ids
+    }
+
+    /// `target_ids` returns the decoder target ids of `summary`, truncated to
+    /// `max_summary_len` and ended by `EOS_ID`. With the copy mechanism, the out-of-vocabulary
+    /// tokens of `copy` have their extended ids.
+    pub fn target_ids(&self, summary: &[String], copy: Option<&CopyExample>) -> Vec<usize> {
+        let len = summary.len().min(self.config.model.max_summary_len);
+        let mut ids = match copy {
+            Some(copy) => copy.encode_summary(&self.vocabulary, &summary[..len]),
+            None => self.vocabulary.encode(&summary[..len]),
+        };
+        ids.push(EOS_ID);
+        ids
+    }
+
+    /// `copy_example` returns the `CopyExample` of `source` with the copy mechanism, whose
+    /// extended ids end with `EOS_ID` as the source ids do.
+    pub fn copy_example(&self, source: &[String]) -> Option<CopyExample> {
+        if !self.config.model.copy {
+            return None;
+        }
+
+        let len = source.len().min(self.config.model.max_source_len);
+        let mut copy = CopyExample::new(&self.vocabulary, &source[..len]);
+        copy.source_ids.push(EOS_ID);
+        copy.source_extended_ids.push(EOS_ID);
+        Some(copy)
+    }
+
+    /// `condition` returns the `Condition` of the summaries of kind `mode` of a post with
+    /// `metadata`: with the `Token` target conditioning the encoder reads the target-type token
+    /// before the source, and with the `Heads` one the tl;dr summaries have their own output
+    /// layer. The encoder then reads the metadata feature buckets, as tokens or embeddings.
+    fn condition(&self, mode: SummaryMode, metadata: Option<&Metadata>) -> Condition {
+        let p = &self.params;
+        let mut condition = Condition {
+            prefix: Vec::new(),
+            head: (p.output_weights, p.output_bias),
+        };
+
+        match (self.config.model.target_conditioning, mode, p.long_output) {
+            (Some(TargetConditioning::Token), _, _) => condition.prefix.push(self.input(self.vocabulary.id(target_token(mode)))),
+            (Some(TargetConditioning::Heads), SummaryMode::Long, Some(head)) => condition.head = head,
+            _ => {},
+        }
+
+        if let (Some(options), Some(metadata)) = (self.config.model.metadata.as_ref(), metadata) {
+            match p.metadata {
+                Some(embedding) => condition.prefix.extend(options.embedding_ids(metadata).into_iter().map(|row| (embedding, row))),
+                None => condition.prefix.extend(options.tokens(metadata).iter().map(|token| self.input(self.vocabulary.id(token)))),
+            }
+        }
+
+        condition
+    }
+
+    /// `conditions` returns the `Condition` of every entry of `batch`.
+    fn conditions(&self, batch: &Batch) -> Result<Vec<Condition>> {
+        if batch.modes.len() != batch.sources.len() {
+            return Err(format!("invalid batch: {} sources, {} modes", batch.sources.len(), batch.modes.len()));
+        }
+
+        if !batch.metadata.is_empty() && batch.metadata.len() != batch.sources.len() {
+            return Err(format!("invalid batch: {} sources, {} metadata", batch.sources.len(), batch.metadata.len()));
+        }
+
+        Ok(batch
+            .modes
+            .iter()
+            .enumerate()
+            .map(|(idx, mode)| self.condition(*mode, batch.metadata.get(idx).and_then(Option::as_ref)))
+            .collect())
+    }
+
+    /// `input` returns the encoder or decoder `Input` of `id`, the ids out of the vocabulary
+    /// reading the `UNK_ID` row.
+    fn input(&self, id: usize) -> Input {
+        (self.params.embedding, if id < self.vocabulary.len() { id } else { UNK_ID })
+    }
+
+    /// `embedding` returns the embedding of `input`.
+    fn embedding(&self, (variable, row): Input) -> &[f32] {
+        self.variables.get(variable).row(row)
+    }
+
+    /// `rnn` returns the next state of the rnn with the `input`, `recurrent` and `bias`
+    /// variables reading `x` from the state `h`.
+    fn rnn(&self, input: usize, recurrent: usize, bias: usize, x: &[f32], h: &[f32]) -> Vec<f32> {
+        let mut state = self.variables.get(input).matvec(x);
+        add_scaled(&mut state, &self.variables.get(recurrent).matvec(h), 1.0);
+        add_scaled(&mut state, &self.variables.get(bias).data, 1.0);
+
+        for v in state.iter_mut() {
+            *v = v.tanh();
+        }
+
+        state
+    }
+
+    /// `encoder_inputs` returns the encoder inputs of `source_ids`, after the `prefix` ones.
+    fn encoder_inputs(&self, prefix: &[Input], source_ids: &[usize]) -> Vec<Input> {
+        let mut inputs = prefix.to_vec();
+        inputs.extend(source_ids.iter().map(|id| self.input(*id)));
+        inputs
+    }
+
+    /// `encode` returns the encoder `Layers` of `inputs`.
+    fn encode(&self, inputs: &[Input]) -> Layers {
+        match self.params.encoder {
+            Encoder::Rnn(input, recurrent, bias) => {
+                let mut h = vec![0.0; self.size()];
+                let mut states = Vec::with_capacity(inputs.len());
+
+                for x in inputs.iter() {
+                    h = self.rnn(input, recurrent, bias, self.embedding(*x), &h);
+                    states.push(h.clone());
+                }
+
+                vec![states]
+            },
+            Encoder::Conv(ref layers) => {
+                let mut x: Vec<Vec<f32>> = inputs.iter().map(|input| self.embedding(*input).to_vec()).collect();
+                let mut states = Vec::with_capacity(layers.len());
+
+                for layer in layers.iter() {
+                    x = layer.forward(&self.variables, &x);
+                    states.push(x.clone());
+                }
+
+                states
+            },
+        }
+    }
+
+    /// `dilations` returns the dilation rates of the memory levels, bottom first, one for the
+    /// RNN encoder.
+    fn dilations(&self) -> Vec<usize> {
+        match self.params.encoder {
+            Encoder::Rnn(..) => vec![1],
+            Encoder::Conv(ref layers) => layers[layers.len() - self.params.attention.len()..]
+                .iter()
+                .map(|layer| layer.rate)
+                .collect(),
+        }
+    }
+
+    /// `memories` returns the memory levels of the encoder `layers`, the states of the top
+    /// layers following the `prefix` ones, bottom first.
+    fn memories<'a>(&self, layers: &'a [Vec<Vec<f32>>], prefix: usize) -> Vec<&'a [Vec<f32>]> {
+        layers[layers.len() - self.params.attention.len()..]
+            .iter()
+            .map(|states| &states[prefix..])
+            .collect()
+    }
+
+    /// `initial_state` returns the initial decoder state of the encoder `layers`: the last
+    /// state of the RNN encoder, or the mean of the top layer of the convolutional one.
+    fn initial_state(&self, layers: &[Vec<Vec<f32>>]) -> Vec<f32> {
+        let mut state = vec![0.0; self.size()];
+        let top = match layers.last() {
+            Some(top) if !top.is_empty() => top,
+            _ => return state,
+        };
+
+        match self.params.encoder {
+            Encoder::Rnn(..) => state.copy_from_slice(&top[top.len() - 1]),
+            Encoder::Conv(_) => {
+                for h in top.iter() {
+                    add_scaled(&mut state, h, 1.0 / top.len() as f32);
+                }
+            },
+        }
+
+        state
+    }
+
+    /// `decoder_step` runs a decoder step reading `input` from the state `prev`, attending
+    /// over the encoder `memories` and predicting with the output `head`. The output is
+    /// dropped out with `rng`, if given.
+    fn decoder_step(&self, input: usize, prev: &[f32], memories: &[&[Vec<f32>]], head: (usize, usize), rng: Option<&mut StreamRng>) -> DecoderStep {
+        let p = &self.params;
+        let size = self.size();
+
+        let state = self.rnn(p.decoder_input, p.decoder_recurrent, p.decoder_bias, self.embedding(self.input(input)), prev);
+        let queries: Vec<Vec<f32>> = p.attention.iter().map(|a| self.variables.get(*a).matvec(&state)).collect();
+        let levels: Vec<Vec<f32>> = memories
+            .iter()
+            .zip(queries.iter())
+            .map(|(states, query)| softmax(&states.iter().map(|h| dot(h, query)).collect::<Vec<f32>>()))
+            .collect();
+
+        let scale = 1.0 / levels.len() as f32;
+        let mut output = vec![0.0; 2 * size];
+        let mut attention = vec![0.0; memories[0].len()];
+        for (states, weights) in memories.iter().zip(levels.iter()) {
+            for (a, h) in weights.iter().zip(states.iter()) {
+                add_scaled(&mut output[..size], h, a * scale);
+            }
+            add_scaled(&mut attention, weights, scale);
+        }
+        output[size..].copy_from_slice(&state);
+
+        let mut mask = Vec::new();
+        let dropout = self.config.model.dropout as f32;
+        if let (Some(rng), true) = (rng, dropout > 0.0) {
+            mask = (0..output.len())
+                .map(|_| if rng.gen::<f32>() < dropout { 0.0 } else { 1.0 / (1.0 - dropout) })
+                .collect();
+
+            for (o, m) in output.iter_mut().zip(mask.iter()) {
+                *o *= m;
+            }
+        }
+
+        let mut logits = self.variables.get(head.0).matvec(&output);
+        add_scaled(&mut logits, &self.variables.get(head.1).data, 1.0);
+
+        let p_gen = match p.copy {
+            Some((weights, bias)) => sigmoid(dot(&self.variables.get(weights).data, &output) + self.variables.get(bias).data[0]),
+            None => 1.0,
+        };
+
+        DecoderStep {
+            input,
+            head,
+            prev: prev.to_vec(),
+            state,
+            queries,
+            levels,
+            attention,
+            output,
+            mask,
+            logits,
+            p_gen,
+        }
+    }
+
+    /// `distribution` returns the output distribution of `step`, over the extended vocabulary
+    /// of `copy` with the copy mechanism.
+    fn distribution(&self, step: &DecoderStep, copy: Option<&CopyExample>) -> Result<Vec<f32>> {
+        let probs = softmax(&step.logits);
+
+        match copy {
+            Some(copy) => copy_distribution(step.p_gen, &probs, &step.attention, &copy.source_extended_ids, copy.extended_len(&self.vocabulary)),
+            None => Ok(probs),
+        }
+    }
+
+    /// `step_gradients` returns the gradients of the loss of `step`, scaled by `scale`, when
+    /// the target is `target` and the probability of the target is `prob`.
+    fn step_gradients(&self, step: &DecoderStep, copy: Option<&CopyExample>, target: usize, prob: f32, scale: f32) -> StepGradients {
+        let probs = softmax(&step.logits);
+        let copy = match copy {
+            Some(copy) => copy,
+            None => {
+                let mut logits: Vec<f32> = probs.iter().map(|p| p * scale).collect();
+                logits[target] -= scale;
+                return StepGradients { logits, gate: 0.0, attention: Vec::new() };
+            },
+        };
+
+        // p(y) = p_gen * q(y) + (1 - p_gen) * sum of the attention on the positions of y.
+        let d_prob = -scale / prob;
+        let generated = probs.get(target).cloned().unwrap_or(0.0);
+        let copied: f32 = copy
+            .source_extended_ids
+            .iter()
+            .zip(step.attention.iter())
+            .filter(|(id, _)| **id == target)
+            .map(|(_, a)| a)
+            .sum();
+
+        let mut logits = vec![0.0; probs.len()];
+        if target < probs.len() {
+            let d_generated = d_prob * step.p_gen * generated;
+            for (d, q) in logits.iter_mut().zip(probs.iter()) {
+                *d = -d_generated * q;
+            }
+            logits[target] += d_generated;
+        }
+
+        let attention = copy
+            .source_extended_ids
+            .iter()
+            .map(|id| if *id == target { d_prob * (1.0 - step.p_gen) } else { 0.0 })
+            .collect();
+
+        StepGradients {
+            logits,
+            gate: d_prob * (generated - copied) * step.p_gen * (1.0 - step.p_gen),
+            attention,
+        }
+    }
+
+    /// `example_loss` returns the summed negative log-likelihood of `target_ids` given
+    /// `condition`, `source_ids` and `copy` with teacher forcing, adding its gradients scaled
+    /// by the second element of `grads` to the first one, if given.
+    fn example_loss(&self,
+                    condition: &Condition,
+                    source_ids: &[usize],
+                    copy: Option<&CopyExample>,
+                    target_ids: &[usize],
+                    mut rng: Option<&mut StreamRng>,
+                    mut grads: Option<(&mut [Vec<f32>], f32)>) -> Result<f64>
+    {
+        let inputs = self.encoder_inputs(&condition.prefix, source_ids);
+        let layers = self.encode(&inputs);
+        let memories = self.memories(&layers, condition.prefix.len());
+        let mut prev = self.initial_state(&layers);
+        let mut input = BOS_ID;
+        let mut steps = Vec::with_capacity(target_ids.len());
+        let mut loss = 0.0;
+
+        for target in target_ids.iter() {
+            let step = self.decoder_step(input, &prev, &memories, condition.head, rng.as_deref_mut());
+            let prob = self
+                .distribution(&step, copy)?
+                .get(*target)
+                .cloned()
+                .ok_or_else(|| format!("invalid target id: {}", target))?
+                .max(f32::MIN_POSITIVE);
+            loss -= f64::from(prob).ln();
+
+            if let Some((_, scale)) = grads {
+                let gradients = self.step_gradients(&step, copy, *target, prob, scale);
+                steps.push((step, gradients));
+            } else {
+                steps.push((step, StepGradients { logits: Vec::new(), gate: 0.0, attention: Vec::new() }));
+            }
+
+            prev = steps[steps.len() - 1].0.state.clone();
+            input = *target;
+        }
+
+        if let Some((ref mut grads, _)) = grads {
+            self.backward(&inputs, &layers, condition.prefix.len(), &steps, grads);
+        }
+
+        Ok(loss)
+    }
+
+    /// `backward` adds to `grads` the gradients of the loss of the decoder `steps`,
+    /// backpropagating through the decoder, the attention over the memory levels of the
+    /// encoder `layers` following the `prefix` states, and the encoder reading `inputs`.
+    fn backward(&self,
+                inputs: &[Input],
+                layers: &[Vec<Vec<f32>>],
+                prefix: usize,
+                steps: &[(DecoderStep, StepGradients)],
+                grads: &mut [Vec<f32>])
+    {
+        let p = &self.params;
+        let size = self.size();
+        let memories = self.memories(layers, prefix);
+        let first_level = layers.len() - memories.len();
+        let mut layer_grads: Layers = layers.iter().map(|states| vec![vec![0.0; size]; states.len()]).collect();
+        let mut next = vec![0.0; size];
+
+        for (step, gradients) in steps.iter().rev() {
+            let (weights, bias) = step.head;
+            add_outer(&mut grads[weights], &gradients.logits, &step.output);
+            add_scaled(&mut grads[bias], &gradients.logits, 1.0);
+
+            let mut d_output = self.variables.get(weights).matvec_transposed(&gradients.logits);
+
+            if let Some((weights, bias)) = p.copy {
+                add_scaled(&mut grads[weights], &step.output, gradients.gate);
+                grads[bias][0] += gradients.gate;
+                add_scaled(&mut d_output, &self.variables.get(weights).data, gradients.gate);
+            }
+
+            for (d, m) in d_output.iter_mut().zip(step.mask.iter()) {
+                *d *= m;
+            }
+
+            let (d_context, d_state) = d_output.split_at(size);
+            let mut d_state: Vec<f32> = d_state.iter().zip(next.iter()).map(|(a, b)| a + b).collect();
+            let scale = 1.0 / memories.len() as f32;
+
+            for (level, states) in memories.iter().enumerate() {
+                let weights = &step.levels[level];
+                let state_grads = &mut layer_grads[first_level + level];
+                let mut d_attention: Vec<f32> = states.iter().map(|h| scale * dot(h, d_context)).collect();
+                add_scaled(&mut d_attention, &gradients.attention, scale);
+                let mean = dot(weights, &d_attention);
+                let mut d_query = vec![0.0; size];
+
+                for (i, h) in states.iter().enumerate() {
+                    let a = weights[i];
+                    add_scaled(&mut state_grads[prefix + i], d_context, a * scale);
+
+                    let d_score = a * (d_attention[i] - mean);
+                    add_scaled(&mut state_grads[prefix + i], &step.queries[level], d_score);
+                    add_scaled(&mut d_query, h, d_score);
+                }
+
+                add_outer(&mut grads[p.attention[level]], &d_query, &step.state);
+                add_scaled(&mut d_state, &self.variables.get(p.attention[level]).matvec_transposed(&d_query), 1.0);
+            }
+
+            let d_pre: Vec<f32> = d_state.iter().zip(step.state.iter()).map(|(d, s)| d * (1.0 - s * s)).collect();
+            next = self.rnn_backward((p.decoder_input, p.decoder_recurrent, p.decoder_bias), self.input(step.input), &step.prev, &d_pre, grads);
+        }
+
+        match p.encoder {
+            Encoder::Rnn(input, recurrent, bias) => {
+                let states = &layers[0];
+                let state_grads = &mut layer_grads[0];
+                if let Some(last) = state_grads.last_mut() {
+                    add_scaled(last, &next, 1.0);
+                }
+
+                let zeros = vec![0.0; size];
+                let mut next = vec![0.0; size];
+
+                for (i, x) in inputs.iter().enumerate().rev() {
+                    let prev = if i > 0 { &states[i - 1] } else { &zeros };
+                    let d_pre: Vec<f32> = state_grads[i]
+                        .iter()
+                        .zip(next.iter())
+                        .zip(states[i].iter())
+                        .map(|((d, n), h)| (d + n) * (1.0 - h * h))
+                        .collect();
+
+                    next = self.rnn_backward((input, recurrent, bias), *x, prev, &d_pre, grads);
+                }
+            },
+            Encoder::Conv(ref conv) => {
+                let top = layer_grads.len() - 1;
+                let len = layer_grads[top].len();
+                for d in layer_grads[top].iter_mut() {
+                    add_scaled(d, &next, 1.0 / len as f32);
+                }
+
+                let embeddings: Vec<Vec<f32>> = inputs.iter().map(|input| self.embedding(*input).to_vec()).collect();
+                let mut d_x = layer_grads.pop().unwrap_or_default();
+
+                for (idx, layer) in conv.iter().enumerate().rev() {
+                    let x = if idx > 0 { &layers[idx - 1] } else { &embeddings };
+                    d_x = layer.backward(&self.variables, x, &layers[idx], &d_x, grads);
+
+                    if let Some(d_below) = layer_grads.pop() {
+                        for (d, d_below) in d_x.iter_mut().zip(d_below.iter()) {
+                            add_scaled(d, d_below, 1.0);
+                        }
+                    }
+                }
+
+                for ((embedding, row), d) in inputs.iter().zip(d_x.iter()) {
+                    add_scaled(&mut grads[*embedding][row * size..(row + 1) * size], d, 1.0);
+                }
+            },
+        }
+    }
+
+    /// `rnn_backward` adds to `grads` the gradients of the rnn step with the `input`,
+    /// `recurrent` and `bias` variables reading `x` from the state `prev`, `d_pre` being the
+    /// gradient of its pre-activation. It returns the gradient of `prev`.
+    fn rnn_backward(&self, (input, recurrent, bias): (usize, usize, usize), x: Input, prev: &[f32], d_pre: &[f32], grads: &mut [Vec<f32>]) -> Vec<f32> {
+        let size = self.size();
+        let (embedding, row) = x;
+
+        add_outer(&mut grads[input], d_pre, self.embedding(x));
+        let d_embedding = self.variables.get(input).matvec_transposed(d_pre);
+        add_scaled(&mut grads[embedding][row * size..(row + 1) * size], &d_embedding, 1.0);
+
+        add_outer(&mut grads[recurrent], d_pre, prev);
+        add_scaled(&mut grads[bias], d_pre, 1.0);
+
+        self.variables.get(recurrent).matvec_transposed(d_pre)
+    }
+
+    /// `examples` returns the condition, the source ids, the copy example and the target ids
+    /// of every entry of `batch`.
+    fn examples(&self, batch: &Batch) -> Result<Vec<Example>> {
+        if batch.sources.len() != batch.summaries.len() {
+            return Err(format!("invalid batch: {} sources, {} summaries", batch.sources.len(), batch.summaries.len()));
+        }
+
+        Ok(self
+            .conditions(batch)?
+            .into_iter()
+            .zip(batch.sources.iter())
+            .zip(batch.summaries.iter())
+            .map(|((condition, source), summary)| {
+                let copy = self.copy_example(source);
+                let target = self.target_ids(summary, copy.as_ref());
+                (condition, self.source_ids(source), copy, target)
+            })
+            .collect())
+    }
+
+    /// `beam_search` returns the best `Hypothesis` of `source` given `condition` found by a
+    /// beam search.
+    fn beam_search(&self, condition: &Condition, source: &[String], options: &DecodeOptions) -> Result<Hypothesis> {
+        let source_ids = self.source_ids(source);
+        let copy = self.copy_example(source);
+        let layers = self.encode(&self.encoder_inputs(&condition.prefix, &source_ids));
+        let memories = self.memories(&layers, condition.prefix.len());
+        let mut beams = vec![Beam {
+            ids: Vec::new(),
+            log_prob: 0.0,
+            state: self.initial_state(&layers),
+            done: false,
+            attention: Vec::new(),
+        }];
+
+        for _ in 0..options.max_len {
+            if beams.iter().all(|beam| beam.done) {
+                break;
+            }
+
+            let mut candidates = Vec::new();
+
+            for beam in beams.iter() {
+                if beam.done {
+                    candidates.push(beam.clone());
+                    continue;
+                }
+
+                let input = beam.ids.last().cloned().unwrap_or(BOS_ID);
+                let step = self.decoder_step(input, &beam.state, &memories, condition.head, None);
+                let log_probs = match copy {
+                    Some(ref copy) => self.distribution(&step, Some(copy))?.iter().map(|p| p.max(f32::MIN_POSITIVE).ln()).collect(),
+                    None => log_softmax(&step.logits),
+                };
+
+                for id in top_k(&log_probs, options.beam_size) {
+                    let mut ids = beam.ids.clone();
+                    ids.push(id);
+
+                    let mut attention = Vec::new();
+                    if options.attention {
+                        attention.reserve(beam.attention.len() + 1);
+                        attention.extend(beam.attention.iter().cloned());
+                        attention.push(step.levels.clone());
+                    }
+
+                    candidates.push(Beam {
+                        ids,
+                        log_prob: beam.log_prob + f64::from(log_probs[id]),
+                        state: step.state.clone(),
+                        done: id == EOS_ID,
+                        attention,
+                    });
+                }
+            }
+
+            candidates.sort_by(|a, b| b.score().partial_cmp(&a.score()).unwrap_or(Ordering::Equal));
+            candidates.truncate(options.beam_size);
+            beams = candidates;
+        }
+
+        let best = &beams[0];
+        let tokens = match copy {
+            Some(copy) => copy.decode(&self.vocabulary, &best.ids),
+            None => self.vocabulary.decode(&best.ids),
+        };
+
+        // The last source position is the end of sequence, which is not a source token.
+        let columns = source_ids.len() - 1;
+        let rows: Vec<&Vec<Vec<f32>>> = best
+            .ids
+            .iter()
+            .zip(best.attention.iter())
+            .take_while(|(id, _)| **id != EOS_ID)
+            .filter(|(id, _)| **id != PAD_ID && **id != BOS_ID)
+            .map(|(_, levels)| levels)
+            .collect();
+
+        let attention = if options.attention {
+            self.dilations()
+                .into_iter()
+                .enumerate()
+                .map(|(level, dilation)| AttentionLevel {
+                    dilation,
+                    weights: rows.iter().map(|levels| levels[level][..columns].to_vec()).collect(),
+                })
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+        Ok(Hypothesis {
+            tokens,
+            score: best.score() as f32,
+            attention,
+        })
+    }
+}
+
+impl Summarizer for Seq2Seq {
+    fn name(&self) -> &str {
+        if self.config.model.architecture == Architecture::Mmn {
+            "mmn"
+        } else if self.config.model.copy {
+            "pointer_generator"
+        } else {
+            "seq2seq"
+        }
+    }
+
+    /// `train_step` runs a step of the optimizer of the config on the mean negative
+    /// log-likelihood of the `batch` summary tokens.
+    fn train_step(&mut self, batch: &Batch, options: &TrainStepOptions) -> Result<TrainStepStats> {
+        let batch = self.subwords(batch);
+        let examples = self.examples(&batch)?;
+        let tokens: usize = examples.iter().map(|(_, _, _, target)| target.len()).sum();
+        if tokens == 0 {
+            return Ok(TrainStepStats::default());
+        }
+
+        let scale = 1.0 / tokens as f32;
+        let mut grads = self.variables.zero_gradients();
+        let mut rng = self.rng;
+        let mut loss = 0.0;
+
+        for (condition, source, copy, target) in examples.iter() {
+            loss += self.example_loss(condition, source, copy.as_ref(), target, Some(&mut rng), Some((&mut grads, scale)))?;
+        }
+
+        let grad_norm = if options.max_grad_norm > 0.0 {
+            clip_by_global_norm(&mut grads, options.max_grad_norm as f32)
+        } else {
+            global_norm(&grads)
+        };
+
+        if !grad_norm.is_finite() {
+            return Err(format!("{}: non-finite gradient norm: {}", self.name(), grad_norm));
+        }
+
+        self.optimizer.apply(&self.config.train.optimizer, &mut self.variables, &grads, options.learning_rate)?;
+        self.rng = rng;
+
+        Ok(TrainStepStats {
+            loss: loss / tokens as f64,
+            grad_norm: f64::from(grad_norm),
+            tokens,
+        })
+    }
+
+    /// `loss` returns the mean negative log-likelihood of the `batch` summary tokens, without
+    /// dropout.
+    fn loss(&self, batch: &Batch) -> Result<TrainStepStats> {
+        let examples = self.examples(&self.subwords(batch))?;
+        let tokens: usize = examples.iter().map(|(_, _, _, target)| target.len()).sum();
+        if tokens == 0 {
+            return Ok(TrainStepStats::default());
+        }
+
+        let loss = examples
+            .par_iter()
+            .map(|(condition, source, copy, target)| self.example_loss(condition, source, copy.as_ref(), target, None, None))
+            .collect::<Result<Vec<f64>>>()?
+            .iter()
+            .sum::<f64>();
+
+        Ok(TrainStepStats {
+            loss: loss / tokens as f64,
+            grad_norm: 0.0,
+            tokens,
+        })
+    }
+
+    fn decode(&self, batch: &Batch, options: &DecodeOptions) -> Result<Vec<Hypothesis>> {
+        if options.beam_size == 0 {
+            return Err("invalid beam_size: 0".to_string());
+        }
+
+        self.conditions(batch)?
+            .par_iter()
+            .zip(batch.sources.par_iter())
+            .map(|(condition, source)| match self.bpe {
+                Some(ref bpe) => {
+                    let subwords = bpe.encode(source);
+                    let hypothesis = self.beam_search(condition, &subwords, options)?;
+                    Ok(word_hypothesis(bpe, &subwords, hypothesis))
+                },
+                None => self.beam_search(condition, source, options),
+            })
+            .collect()
+    }
+
+    /// `save` saves the config, the vocabulary, the bpe merges, the variables, the optimizer
+    /// state and the dropout rng of the `Seq2Seq` in the directory `dir`.
+    fn save(&self, dir: &Path) -> Result<()> {
+        self.save_inference(dir)?;
+        self.optimizer.save(dir)?;
+
+        let rng = serde_json::to_vec(&self.rng).map_err(|e| format!("{}", e))?;
+        fs::write(dir.join(RNG_FILE), rng).map_err(|e| format!("{}", e))
+    }
+
+    /// `load` loads a `Seq2Seq` from the directory `dir`. The optimizer state and the rng
+    /// are initialized if the directory has none, as in an exported model.
+    fn load(dir: &Path) -> Result<Seq2Seq> {
+        let config = Config::load(dir)?;
+        let vocabulary = Vocabulary::load(dir)?;
+        let variables = Variables::load(dir)?;
+
+        let bpe = if dir.join(BPE_FILE).exists() {
+            Some(Bpe::load(dir)?)
+        } else {
+            None
+        };
+
+        let optimizer = if dir.join(OPTIMIZER_FILE).exists() {
+            Some(OptimizerState::load(dir)?)
+        } else {
+            None
+        };
+
+        let rng = if dir.join(RNG_FILE).exists() {
+            let contents = fs::read(dir.join(RNG_FILE)).map_err(|e| format!("{}", e))?;
+            serde_json::from_slice(&contents).map_err(|e| format!("{}", e))?
+        } else {
+            StreamRng::new(Seeds::new(config.train.seed).dropout)
+        };
+
+        Seq2Seq::from_variables(config, vocabulary, variables, optimizer, rng, bpe)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{top_k, Seq2Seq};
+    use crate::bpe::{Bpe, BPE_FILE};
+    use crate::config::{Architecture, Config, Optimizer, Tokenization};
+    use crate::lr_schedule::LearningRateSchedule;
+    use crate::metadata::{Metadata, MetadataEncoding, MetadataFeature, MetadataOptions};
+    use crate::multi_task::{TargetConditioning, LONG_TARGET_TOKEN};
+    use crate::seed::Seeds;
+    use crate::summarizer::{evaluate, Batch, DecodeOptions, Summarizer, SummaryMode, TrainStepOptions};
+    use crate::vocabulary::Vocabulary;
+    use std::env;
+    use std::fs;
+
+    fn tokens(s: &str) -> Vec<String> {
+        s.split_whitespace().map(ToOwned::to_owned).collect()
+    }
+
+    fn config(size: usize, dropout: f64) -> Config {
+        let mut config = Config::tifu_short();
+        config.model.architecture = Architecture::Rnn;
+        config.model.embedding_size = size;
+        config.model.dropout = dropout;
+        config.train.optimizer = Optimizer::default();
+        config.train.learning_rate_schedule = LearningRateSchedule::Constant { learning_rate: 0.05 };
+        config
+    }
+
+    fn batch() -> Batch {
+        Batch {
+            ids: vec!["a".to_string(), "b".to_string()],
+            modes: vec![SummaryMode::Short; 2],
+            sources: vec![tokens("my cat ate my homework"), tokens("i broke the build today")],
+            summaries: vec![tokens("cat ate homework"), tokens("broke build")],
+            metadata: Vec::new(),
+        }
+    }
+
+    fn copy_batch() -> Batch {
+        Batch {
+            ids: vec!["a".to_string(), "b".to_string()],
+            modes: vec![SummaryMode::Short; 2],
+            sources: vec![tokens("my cat gustavo ate my homework"), tokens("my dog zucchini ate my homework")],
+            summaries: vec![tokens("gustavo ate homework"), tokens("zucchini ate homework")],
+            metadata: Vec::new(),
+        }
+    }
+
+    fn model(size: usize, dropout: f64) -> Seq2Seq {
+        let batch = batch();
+        let texts = batch.sources.iter().chain(batch.summaries.iter()).map(Vec::as_slice);
+        let vocabulary = Vocabulary::build(texts, 100, 1);
+        Seq2Seq::new(&config(size, dropout), vocabulary, None, &Seeds::new(0)).unwrap()
+    }
+
+    fn copy_model(size: usize) -> Seq2Seq {
+        let batch = copy_batch();
+        let texts = batch.sources.iter().chain(batch.summaries.iter()).map(Vec::as_slice);
+        let vocabulary = Vocabulary::build(texts, 100, 3);
+        let mut config = config(size, 0.0);
+        config.model.copy = true;
+        Seq2Seq::new(&config, vocabulary, None, &Seeds::new(0)).unwrap()
+    }
+
+    fn check_gradients(model: &Seq2Seq, mode: SummaryMode, metadata: Option<&Metadata>, source: &[String], summary: &[String]) {
+        let condition = model.condition(mode, metadata);
+        let copy = model.copy_example(source);
+        let source = model.source_ids(source);
+        let target = model.target_ids(summary, copy.as_ref());
+        let loss = |model: &Seq2Seq| model.example_loss(&condition, &source, copy.as_ref(), &target, None, None).unwrap();
+
+        let mut grads = model.variables.zero_gradients();
+        model.example_loss(&condition, &source, copy.as_ref(), &target, None, Some((&mut grads, 1.0))).unwrap();
+
+        let eps = 1e-2;
+        for (idx, grad) in grads.iter().enumerate() {
+            for i in (0..grad.len()).step_by(grad.len() / 5 + 1) {
+                let mut plus = model.clone();
+                plus.variables.get_mut(idx).data[i] += eps;
+                let mut minus = model.clone();
+                minus.variables.get_mut(idx).data[i] -= eps;
+
+                let numeric = (loss(&plus) - loss(&minus)) / (2.0 * f64::from(eps));
+                let analytic = f64::from(grad[i]);
+                assert!((numeric - analytic).abs() < 1e-2 + 0.05 * analytic.abs(),
+                    "{}[{}]: numeric {} != analytic {}", model.variables.names()[idx], i, numeric, analytic);
+            }
+        }
+    }
+
+    #[test]
+    fn test_seq2seq_top_k() {
+        assert_eq!(top_k(&[0.1, 0.5, 0.2, 0.9], 2), vec![3, 1]);
+        assert_eq!(top_k(&[0.1, 0.5], 3), vec![1, 0]);
+    }
+
+    #[test]
+    fn test_seq2seq_gradients() {
+        check_gradients(&model(4, 0.0), SummaryMode::Short, None, &tokens("my cat ate"), &tokens("cat ate"));
+
+        let model = copy_model(4);
+        assert_eq!(model.name(), "pointer_generator");
+        check_gradients(&model, SummaryMode::Short, None, &tokens("my cat gustavo ate"), &tokens("gustavo ate my homework"));
+    }
+
+    #[test]
+    fn test_seq2seq_train_decode() {
+        let batch = batch();
+        let mut model = model(16, 0.1);
+        let options = TrainStepOptions { learning_rate: 0.05, max_grad_norm: 5.0 };
+
+        let first = model.train_step(&batch, &options).unwrap();
+        assert_eq!(first.tokens, 7);
+        assert!(first.grad_norm > 0.0);
+
+        let mut last = first;
+        for _ in 0..100 {
+            last = model.train_step(&batch, &options).unwrap();
+        }
+        assert!(last.loss < first.loss / 4.0, "{} >= {} / 4", last.loss, first.loss);
+        assert!(model.loss(&batch).unwrap().loss < first.loss / 4.0);
+        assert_eq!(model.loss(&batch).unwrap().tokens, 7);
+
+        let hypotheses = model.decode(&batch, &DecodeOptions::default()).unwrap();
+        assert_eq!(hypotheses[0].tokens, batch.summaries[0]);
+        assert_eq!(hypotheses[1].tokens, batch.summaries[1]);
+
+        let options = DecodeOptions { attention: true, ..DecodeOptions::default() };
+        let hypotheses = model.decode(&batch, &options).unwrap();
+        assert_eq!(hypotheses[1].tokens, batch.summaries[1]);
+        assert_eq!(hypotheses[1].attention.len(), 1);
+        assert_eq!(hypotheses[1].attention[0].dilation, 1);
+        assert_eq!(hypotheses[1].attention[0].weights.len(), batch.summaries[1].len());
+        assert!(hypotheses[1].attention[0].weights.iter().all(|row| row.len() == batch.sources[1].len()));
+
+        let greedy = DecodeOptions { beam_size: 1, ..DecodeOptions::default() };
+        assert_eq!(evaluate(&model, std::iter::once(&batch), &greedy).unwrap().f1, 1.0);
+        assert!(model.decode(&batch, &DecodeOptions { beam_size: 0, ..greedy }).is_err());
+    }
+
+    #[test]
+    fn test_seq2seq_copy() {
+        let batch = copy_batch();
+        let mut model = copy_model(16);
+        let options = TrainStepOptions { learning_rate: 0.05, max_grad_norm: 5.0 };
+
+        for _ in 0..100 {
+            model.train_step(&batch, &options).unwrap();
+        }
+
+        let hypotheses = model.decode(&batch, &DecodeOptions::default()).unwrap();
+        assert_eq!(hypotheses[0].tokens, batch.summaries[0]);
+        assert_eq!(hypotheses[1].tokens, batch.summaries[1]);
+
+        let mut config = model.config.clone();
+        config.model.copy = false;
+        assert!(Seq2Seq::from_variables(config, model.vocabulary.clone(), model.variables.clone(), None, model.rng, None).is_err());
+    }
+
+    #[test]
+    fn test_seq2seq_mmn() {
+        let batch = copy_batch();
+        let texts = batch.sources.iter().chain(batch.summaries.iter()).map(Vec::as_slice);
+        let vocabulary = Vocabulary::build(texts, 100, 3);
+        let mut config = config(4, 0.0);
+        config.model.architecture = Architecture::Mmn;
+        config.model.layers = 3;
+        config.model.dilation_rates = vec![1, 2, 4];
+        config.model.memory_levels = 2;
+        config.model.copy = true;
+
+        let model = Seq2Seq::new(&config, vocabulary.clone(), None, &Seeds::new(0)).unwrap();
+        assert_eq!(model.name(), "mmn");
+        assert!(model.variables.index("encoder/conv2/weights").is_ok());
+        assert!(model.variables.index("encoder/input").is_err());
+        check_gradients(&model, SummaryMode::Short, None, &tokens("my cat gustavo ate"), &tokens("gustavo ate my homework"));
+
+        config.model.embedding_size = 16;
+        let mut model = Seq2Seq::new(&config, vocabulary, None, &Seeds::new(0)).unwrap();
+        let options = TrainStepOptions { learning_rate: 0.05, max_grad_norm: 5.0 };
+        for _ in 0..100 {
+            model.train_step(&batch, &options).unwrap();
+        }
+
+        let hypotheses = model.decode(&batch, &DecodeOptions::default()).unwrap();
+        assert_eq!(hypotheses[0].tokens, batch.summaries[0]);
+        assert_eq!(hypotheses[1].tokens, batch.summaries[1]);
+        assert!(hypotheses[0].attention.is_empty());
+
+        let options = DecodeOptions { attention: true, ..DecodeOptions::default() };
+        let attention = &model.decode(&batch, &options).unwrap()[0].attention;
+        assert_eq!(attention.iter().map(|level| level.dilation).collect::<Vec<usize>>(), vec![2, 4]);
+        assert!(attention.iter().all(|level| level.weights.len() == batch.summaries[0].len()));
+        assert!(attention.iter().all(|level| level.weights.iter().all(|row| row.len() == batch.sources[0].len())));
+
+        let mut config = model.config.clone();
+        config.model.memory_levels = 3;
+        assert!(Seq2Seq::from_variables(config.clone(), model.vocabulary.clone(), model.variables.clone(), None, model.rng, None).is_err());
+        config.model.memory_levels = 2;
+        config.model.kernel_size = 5;
+        assert!(Seq2Seq::from_variables(config.clone(), model.vocabulary.clone(), model.variables.clone(), None, model.rng, None).is_err());
+        config.model.architecture = Architecture::Rnn;
+        assert!(Seq2Seq::from_variables(config, model.vocabulary.clone(), model.variables.clone(), None, model.rng, None).is_err());
+    }
+
+    #[test]
+    fn test_seq2seq_target_conditioning() {
+        let source = tokens("my cat ate my homework today");
+        let batch = Batch {
+            ids: vec!["a".to_string(), "a".to_string()],
+            modes: vec![SummaryMode::Short, SummaryMode::Long],
+            sources: vec![source.clone(), source.clone()],
+            summaries: vec![tokens("cat ate"), tokens("my cat ate my homework")],
+            metadata: Vec::new(),
+        };
+        let vocabulary = Vocabulary::build(batch.sources.iter().map(Vec::as_slice), 100, 1);
+        let options = TrainStepOptions { learning_rate: 0.05, max_grad_norm: 5.0 };
+
+        for conditioning in [TargetConditioning::Token, TargetConditioning::Heads].iter() {
+            let mut config = config(16, 0.0);
+            config.model.target_conditioning = Some(*conditioning);
+            let mut model = Seq2Seq::new(&config, vocabulary.clone(), None, &Seeds::new(0)).unwrap();
+            assert_eq!(model.vocabulary.contains(LONG_TARGET_TOKEN), *conditioning == TargetConditioning::Token);
+            check_gradients(&model, SummaryMode::Long, None, &source, &batch.summaries[1]);
+
+            for _ in 0..100 {
+                model.train_step(&batch, &options).unwrap();
+            }
+
+            let hypotheses = model.decode(&batch, &DecodeOptions::default()).unwrap();
+            assert_eq!(hypotheses[0].tokens, batch.summaries[0]);
+            assert_eq!(hypotheses[1].tokens, batch.summaries[1]);
+
+            if *conditioning == TargetConditioning::Heads {
+                config.model.target_conditioning = None;
+                assert!(Seq2Seq::from_variables(config, model.vocabulary.clone(), model.variables.clone(), None, model.rng, None).is_err());
+            }
+        }
+    }
+
+    #[test]
+    fn test_seq2seq_metadata() {
+        let source = tokens("my cat ate my homework today");
+        let popular = Metadata { score: 1_000, ..Metadata::new() };
+        let batch = Batch {
+            ids: vec!["a".to_string(), "b".to_string()],
+            modes: vec![SummaryMode::Short; 2],
+            sources: vec![source.clone(), source.clone()],
+            summaries: vec![tokens("cat ate"), tokens("my homework")],
+            metadata: vec![Some(Metadata::new()), Some(popular.clone())],
+        };
+        let vocabulary = Vocabulary::build(batch.sources.iter().map(Vec::as_slice), 100, 1);
+        let options = TrainStepOptions { learning_rate: 0.05, max_grad_norm: 5.0 };
+
+        for encoding in [MetadataEncoding::Tokens, MetadataEncoding::Embeddings].iter() {
+            let mut config = config(16, 0.0);
+            let metadata = MetadataOptions { features: vec![MetadataFeature::Score], encoding: *encoding };
+            config.model.metadata = Some(metadata.clone());
+            let mut model = Seq2Seq::new(&config, vocabulary.clone(), None, &Seeds::new(0)).unwrap();
+            assert_eq!(model.vocabulary.contains(&metadata.tokens(&popular)[0]), *encoding == MetadataEncoding::Tokens);
+            check_gradients(&model, SummaryMode::Short, Some(&popular), &source, &batch.summaries[1]);
+
+            for _ in 0..100 {
+                model.train_step(&batch, &options).unwrap();
+            }
+
+            let hypotheses = model.decode(&batch, &DecodeOptions::default()).unwrap();
+            assert_eq!(hypotheses[0].tokens, batch.summaries[0]);
+            assert_eq!(hypotheses[1].tokens, batch.summaries[1]);
+
+            let mut invalid = batch.clone();
+            invalid.metadata.pop();
+            assert!(model.decode(&invalid, &DecodeOptions::default()).is_err());
+
+            if *encoding == MetadataEncoding::Embeddings {
+                config.model.metadata = None;
+                assert!(Seq2Seq::from_variables(config, model.vocabulary.clone(), model.variables.clone(), None, model.rng, None).is_err());
+            }
+        }
+    }
+
+    #[test]
+    fn test_seq2seq_save_load() {
+        let batch = batch();
+        let mut model = model(8, 0.2);
+        model.train_step(&batch, &TrainStepOptions::default()).unwrap();
+
+        let mut dir = env::temp_dir();
+        dir.push("mmn_test_seq2seq_save_load");
+        model.save(&dir).unwrap();
+
+        let mut loaded = Seq2Seq::load(&dir).unwrap();
+        assert_eq!(loaded, model);
+
+        let options = TrainStepOptions::default();
+        assert_eq!(loaded.train_step(&batch, &options).unwrap(), model.train_step(&batch, &options).unwrap());
+        assert_eq!(loaded, model);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_seq2seq_bpe() {
+        let batch = batch();
+        let texts: Vec<&[String]> = batch.sources.iter().chain(batch.summaries.iter()).map(Vec::as_slice).collect();
+        let bpe = Bpe::train(texts.iter().cloned(), 5, 1);
+        let subwords = batch.encode_bpe(&bpe);
+        let vocabulary = Vocabulary::build(subwords.sources.iter().chain(subwords.summaries.iter()).map(Vec::as_slice), 100, 1);
+
+        let mut config = config(16, 0.0);
+        assert!(Seq2Seq::new(&config, vocabulary.clone(), Some(bpe.clone()), &Seeds::new(0)).is_err());
+        config.model.tokenization = Tokenization::Bpe { merges: 5 };
+        assert!(Seq2Seq::new(&config, vocabulary.clone(), None, &Seeds::new(0)).is_err());
+
+        let mut model = Seq2Seq::new(&config, vocabulary, Some(bpe), &Seeds::new(0)).unwrap();
+        let options = TrainStepOptions { learning_rate: 0.05, max_grad_norm: 5.0 };
+        let first = model.train_step(&batch, &options).unwrap();
+        assert!(first.tokens > 7);
+        for _ in 0..100 {
+            model.train_step(&batch, &options).unwrap();
+        }
+
+        let options = DecodeOptions { attention: true, ..DecodeOptions::default() };
+        let hypotheses = model.decode(&batch, &options).unwrap();
+        assert_eq!(hypotheses[0].tokens, batch.summaries[0]);
+        let weights = &hypotheses[0].attention[0].weights;
+        assert_eq!(weights.len(), batch.summaries[0].len());
+        assert!(weights.iter().all(|row| row.len() == batch.sources[0].len()));
+        assert!(weights.iter().all(|row| row.iter().sum::<f32>() <= 1.0 + 1e-3));
+
+        let mut dir = env::temp_dir();
+        dir.push("mmn_test_seq2seq_bpe");
+        model.save(&dir).unwrap();
+        assert!(dir.join(BPE_FILE).exists());
+        assert_eq!(Seq2Seq::load(&dir).unwrap(), model);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
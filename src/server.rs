@@ -0,0 +1,574 @@
+use serde::{Serialize, Deserialize};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Arc;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+use crate::result::{panic_message, Result};
+use crate::summarizer::{Batch, DecodeOptions, SummarizeResponse, Summarizer, SummaryMode};
+use crate::tokenizer::tokenize;
+use crate::metadata::Metadata;
+
+/// `SummarizeRequest` is the body of a `POST /summarize` request.
+#[derive(Clone, Default, PartialEq, Debug, Serialize, Deserialize)]
+pub struct SummarizeRequest {
+    pub text: String,
+    #[serde(default)]
+    pub mode: SummaryMode,
+    /// `beam_size` is the beam size of the decoding, or the summarizer default if missing.
+    #[serde(default)]
+    pub beam_size: Option<usize>,
+    /// `metadata` are the post metadata of the text, if known.
+    #[serde(default)]
+    pub metadata: Option<Metadata>,
+}
+
+impl SummarizeRequest {
+    /// `validate` returns an error if the `SummarizeRequest` is invalid.
+    pub fn validate(&self) -> Result<()> {
+        if self.text.trim().is_empty() {
+            return Err("empty text".to_string());
+        }
+
+        if self.beam_size == Some(0) {
+            return Err("invalid beam_size: 0".to_string());
+        }
+
+        Ok(())
+    }
+}
+
+/// `BatchRequest` is the body of a `POST /summarize/batch` request.
+#[derive(Clone, Default, PartialEq, Debug, Serialize, Deserialize)]
+pub struct BatchRequest {
+    pub requests: Vec<SummarizeRequest>,
+}
+
+/// `BatchResponse` is the body of a `POST /summarize/batch` response.
+#[derive(Clone, Default, PartialEq, Debug, Serialize, Deserialize)]
+pub struct BatchResponse {
+    pub responses: Vec<SummarizeResponse>,
+}
+
+/// `ErrorResponse` is the body of an error response.
+#[derive(Clone, Default, PartialEq, Debug, Serialize, Deserialize)]
+pub struct ErrorResponse {
+    pub error: String,
+}
+
+/// `ServerOptions` are the options of a `Server`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct ServerOptions {
+    /// `max_batch_size` is the maximum number of requests summarized together.
+    pub max_batch_size: usize,
+    /// `max_batch_delay_ms` is how long the first request of a batch waits for others.
+    pub max_batch_delay_ms: u64,
+    /// `max_body_size` is the maximum size in bytes of a request body.
+    pub max_body_size: usize,
+    /// `read_timeout_ms` is how long reading a request may block. Zero disables the timeout.
+    pub read_timeout_ms: u64,
+    /// `write_timeout_ms` is how long writing a response may block. Zero disables the timeout.
+    pub write_timeout_ms: u64,
+}
+
+impl ServerOptions {
+    /// `new` creates a new `ServerOptions`.
+    pub fn new() -> ServerOptions {
+        ServerOptions::default()
+    }
+}
+
+impl Default for ServerOptions {
+    fn default() -> ServerOptions {
+        ServerOptions {
+            max_batch_size: 16,
+            max_batch_delay_ms: 10,
+            max_body_size: 1 << 20,
+            read_timeout_ms: 10_000,
+            write_timeout_ms: 10_000,
+        }
+    }
+}
+
+/// `Job` is a request waiting to be batched, with the channel its response is sent to.
+struct Job {
+    request: SummarizeRequest,
+    response: Sender<Result<SummarizeResponse>>,
+}
+
+/// `Response` is an HTTP response with a json body.
+struct Response {
+    status: u16,
+    body: String,
+}
+
+impl Response {
+    /// `ok` creates a 200 `Response` with the json of `body`.
+    fn ok<T: Serialize>(body: &T) -> Response {
+        match serde_json::to_string(body) {
+            Ok(body) => Response { status: 200, body },
+            Err(e) => Response::error(500, &format!("{}", e)),
+        }
+    }
+
+    /// `error` creates a `Response` with `status` and an `ErrorResponse` body.
+    fn error(status: u16, error: &str) -> Response {
+        let body = ErrorResponse { error: error.to_string() };
+
+        Response {
+            status,
+            body: serde_json::to_string(&body).unwrap_or_default(),
+        }
+    }
+
+    /// `reason` returns the reason phrase of the `Response` status.
+    fn reason(&self) -> &'static str {
+        match self.status {
+            200 => "OK",
+            400 => "Bad Request",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            413 => "Payload Too Large",
+            _ => "Internal Server Error",
+        }
+    }
+
+    /// `write` writes the `Response` to `stream`.
+    fn write<W: Write>(&self, mut stream: W) -> Result<()> {
+        write!(stream,
+            "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            self.status,
+            self.reason(),
+            self.body.len(),
+            self.body)
+            .map_err(|e| format!("{}", e))?;
+
+        stream.flush().map_err(|e| format!("{}", e))
+    }
+}
+
+/// `summarize` summarizes the raw texts of `requests` with `summarizer`, decoding the
+/// requests of each beam size together, with the other `options`.
+pub fn summarize<S: Summarizer>(summarizer: &S, requests: &[SummarizeRequest], options: &DecodeOptions) -> Result<Vec<SummarizeResponse>> {
+    let beam_size = |request: &SummarizeRequest| request.beam_size.unwrap_or(options.beam_size);
+    let mut beam_sizes: Vec<usize> = requests.iter().map(beam_size).collect();
+    beam_sizes.sort_unstable();
+    beam_sizes.dedup();
+
+    let mut responses = vec![SummarizeResponse::default(); requests.len()];
+
+    for size in beam_sizes {
+        let indices: Vec<usize> = (0..requests.len()).filter(|idx| beam_size(&requests[*idx]) == size).collect();
+        let batch = Batch {
+            ids: indices.iter().map(|idx| idx.to_string()).collect(),
+            modes: indices.iter().map(|idx| requests[*idx].mode).collect(),
+            sources: indices.iter().map(|idx| tokenize(&requests[*idx].text)).collect(),
+            summaries: vec![Vec::new(); indices.len()],
+            metadata: indices.iter().map(|idx| requests[*idx].metadata.to_owned()).collect(),
+        };
+
+        let hypotheses = summarizer.decode(&batch, &DecodeOptions { beam_size: size, ..*options })?;
+        if hypotheses.len() != indices.len() {
+            return Err(format!("expected {} summaries, found {}", indices.len(), hypotheses.len()));
+        }
+
+        for (idx, hypothesis) in indices.into_iter().zip(hypotheses) {
+            responses[idx] = SummarizeResponse {
+                summary: hypothesis.tokens.join(" "),
+                score: hypothesis.score,
+            };
+        }
+    }
+
+    Ok(responses)
+}
+
+/// `batch` runs the batching loop: it groups the jobs received within `max_batch_delay_ms`
+/// of the first one, up to `max_batch_size`, and summarizes them with a single call. A panic
+/// of `summarize` fails the jobs of its batch only.
+fn batch<F>(jobs: Receiver<Job>, options: ServerOptions, summarize: F)
+    where F: Fn(&[SummarizeRequest]) -> Result<Vec<SummarizeResponse>>
+{
+    let delay = Duration::from_millis(options.max_batch_delay_ms);
+
+    while let Ok(job) = jobs.recv() {
+        let deadline = Instant::now() + delay;
+        let mut batch = vec![job];
+
+        while batch.len() < options.max_batch_size {
+            let now = Instant::now();
+            if now >= deadline {
+                break;
+            }
+
+            match jobs.recv_timeout(deadline - now) {
+                Ok(job) => batch.push(job),
+                Err(_) => break,
+            }
+        }
+
+        let requests: Vec<SummarizeRequest> = batch.iter().map(|job| job.request.clone()).collect();
+
+        let responses = panic::catch_unwind(AssertUnwindSafe(|| summarize(&requests)))
+            .unwrap_or_else(|payload| Err(format!("summarize panicked: {}", panic_message(&*payload))));
+
+        match responses {
+            Ok(ref responses) if responses.len() == batch.len() => {
+                for (job, response) in batch.into_iter().zip(responses.iter()) {
+                    let _ = job.response.send(Ok(response.clone()));
+                }
+            },
+            Ok(responses) => {
+                let e = format!("expected {} summaries, found {}", batch.len(), responses.len());
+                for job in batch {
+                    let _ = job.response.send(Err(e.clone()));
+                }
+            },
+            Err(e) => {
+                for job in batch {
+                    let _ = job.response.send(Err(e.clone()));
+                }
+            },
+        }
+    }
+}
+
+/// `summarize_all` sends the `requests` to the batching loop and waits for their responses.
+/// The requests are sent one by one so that they can be batched with concurrent ones.
+fn summarize_all(jobs: &Sender<Job>, requests: Vec<SummarizeRequest>) -> Result<Vec<SummarizeResponse>> {
+    let mut receivers = Vec::with_capacity(requests.len());
+
+    for request in requests {
+        let (response, receiver) = mpsc::channel();
+        jobs.send(Job { request, response }).map_err(|e| format!("{}", e))?;
+        receivers.push(receiver);
+    }
+
+    receivers
+        .into_iter()
+        .map(|receiver| receiver.recv().map_err(|e| format!("{}", e))?)
+        .collect()
+}
+
+/// `read_request` reads the method, the path and the body of an HTTP request from `stream`.
+/// It returns an error `Response` if the request is malformed or too large.
+fn read_request<R: Read>(stream: R, max_body_size: usize) -> std::result::Result<(String, String, Vec<u8>), Response> {
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+
+    reader.read_line(&mut line).map_err(|e| Response::error(400, &format!("{}", e)))?;
+
+    let mut parts = line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_string();
+    let path = parts.next().unwrap_or_default().to_string();
+
+    if method.is_empty() || path.is_empty() {
+        return Err(Response::error(400, "invalid request line"));
+    }
+
+    let mut content_length = 0;
+
+    loop {
+        line.clear();
+        reader.read_line(&mut line).map_err(|e| Response::error(400, &format!("{}", e)))?;
+
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+
+        if let Some((name, value)) = header.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                content_length = value
+                    .trim()
+                    .parse()
+                    .map_err(|_| Response::error(400, "invalid content-length"))?;
+            }
+        }
+    }
+
+    if content_length > max_body_size {
+        return Err(Response::error(413, "request body too large"));
+    }
+
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body).map_err(|e| Response::error(400, &format!("{}", e)))?;
+
+    Ok((method, path, body))
+}
+
+/// `parse_body` parses the json `body` of a request.
+fn parse_body<'a, T: Deserialize<'a>>(body: &'a [u8]) -> std::result::Result<T, Response> {
+    serde_json::from_slice(body).map_err(|e| Response::error(400, &format!("{}", e)))
+}
+
+/// `route` returns the `Response` to the request with `method`, `path` and `body`.
+fn route(jobs: &Sender<Job>, method: &str, path: &str, body: &[u8]) -> std::result::Result<Response, Response> {
+    match (method, path) {
+        ("GET", "/health") => Ok(Response::ok(&serde_json::json!({ "status": "ok" }))),
+        ("POST", "/summarize") => {
+            let request: SummarizeRequest = parse_body(body)?;
+            request.validate().map_err(|e| Response::error(400, &e))?;
+
+            let mut responses = summarize_all(jobs, vec![request]).map_err(|e| Response::error(500, &e))?;
+            Ok(Response::ok(&responses.remove(0)))
+        },
+        ("POST", "/summarize/batch") => {
+            let batch: BatchRequest = parse_body(body)?;
+            for (i, request) in batch.requests.iter().enumerate() {
+                request.validate().map_err(|e| Response::error(400, &format!("request {}: {}", i, e)))?;
+            }
+
+            let responses = summarize_all(jobs, batch.requests).map_err(|e| Response::error(500, &e))?;
+            Ok(Response::ok(&BatchResponse { responses }))
+        },
+        (_, "/health") | (_, "/summarize") | (_, "/summarize/batch") => {
+            Err(Response::error(405, &format!("method not allowed: {}", method)))
+        },
+        _ => Err(Response::error(404, &format!("not found: {}", path))),
+    }
+}
+
+/// `timeout` returns the timeout of `ms` milliseconds, none if zero.
+fn timeout(ms: u64) -> Option<Duration> {
+    if ms == 0 {
+        None
+    } else {
+        Some(Duration::from_millis(ms))
+    }
+}
+
+/// `handle` answers the HTTP request on `stream`.
+fn handle(jobs: &Sender<Job>, mut stream: TcpStream, options: &ServerOptions) -> Result<()> {
+    stream.set_read_timeout(timeout(options.read_timeout_ms)).map_err(|e| format!("{}", e))?;
+    stream.set_write_timeout(timeout(options.write_timeout_ms)).map_err(|e| format!("{}", e))?;
+
+    let response = read_request(&mut stream, options.max_body_size)
+        .and_then(|(method, path, body)| route(jobs, &method, &path, &body))
+        .unwrap_or_else(|response| response);
+
+    response.write(&mut stream)
+}
+
+/// `Server` is a local HTTP server summarizing texts with json endpoints:
+/// `GET /health`, `POST /summarize` and `POST /summarize/batch`.
+pub struct Server {
+    listener: TcpListener,
+    options: ServerOptions,
+    jobs: Sender<Job>,
+}
+
+impl Server {
+    /// `bind` creates a new `Server` listening on `addr`. Concurrent requests are batched
+    /// and summarized by `summarize`, which returns one `SummarizeResponse` per request.
+    pub fn bind<A, F>(addr: A, options: ServerOptions, summarize: F) -> Result<Server>
+        where A: ToSocketAddrs,
+              F: Fn(&[SummarizeRequest]) -> Result<Vec<SummarizeResponse>> + Send + 'static
+    {
+        if options.max_batch_size == 0 {
+            return Err("invalid max_batch_size: 0".to_string());
+        }
+
+        let listener = TcpListener::bind(addr).map_err(|e| format!("{}", e))?;
+        let (jobs, receiver) = mpsc::channel();
+
+        thread::spawn(move || batch(receiver, options, summarize));
+
+        Ok(Server {
+            listener,
+            options,
+            jobs,
+        })
+    }
+
+    /// `local_addr` returns the address the `Server` is listening on.
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        self.listener.local_addr().map_err(|e| format!("{}", e))
+    }
+
+    /// `run` answers the incoming connections, each in its own thread, until the listener fails.
+    /// The errors of the connections, as a client gone or timed out, are passed to `on_error`.
+    pub fn run<E>(self, on_error: E) -> Result<()>
+        where E: Fn(String) + Send + Sync + 'static
+    {
+        let on_error = Arc::new(on_error);
+
+        for stream in self.listener.incoming() {
+            let stream = stream.map_err(|e| format!("{}", e))?;
+            let jobs = self.jobs.clone();
+            let options = self.options;
+            let on_error = on_error.clone();
+
+            thread::spawn(move || {
+                if let Err(e) = handle(&jobs, stream, &options) {
+                    on_error(e);
+                }
+            });
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{summarize, Server, ServerOptions, SummarizeRequest, SummarizeResponse, SummaryMode};
+    use crate::lead_summarizer::LeadSummarizer;
+    use crate::summarizer::{Batch, DecodeOptions, Summarizer, TrainStepOptions};
+    use std::io::{Read, Write};
+    use std::net::{SocketAddr, TcpStream};
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::thread;
+
+    fn request(addr: SocketAddr, method: &str, path: &str, body: &str) -> (u16, serde_json::Value) {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(stream, "{} {} HTTP/1.1\r\nHost: localhost\r\nContent-Length: {}\r\n\r\n{}",
+            method, path, body.len(), body).unwrap();
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+
+        let status = response[9..12].parse().unwrap();
+        let body = response.split("\r\n\r\n").nth(1).unwrap();
+        (status, serde_json::from_str(body).unwrap())
+    }
+
+    #[test]
+    fn test_server_endpoints() {
+        let max_batch = Arc::new(AtomicUsize::new(0));
+        let seen = max_batch.clone();
+
+        let options = ServerOptions {
+            max_batch_size: 8,
+            max_batch_delay_ms: 200,
+            ..ServerOptions::default()
+        };
+
+        let server = Server::bind("127.0.0.1:0", options, move |requests: &[SummarizeRequest]| {
+            seen.fetch_max(requests.len(), Ordering::SeqCst);
+
+            Ok(requests
+                .iter()
+                .map(|r| {
+                    let words = if r.mode == SummaryMode::Short { 1 } else { 2 };
+                    SummarizeResponse {
+                        summary: r.text.split_whitespace().take(words).collect::<Vec<_>>().join(" "),
+                        score: r.beam_size.unwrap_or(1) as f32,
+                    }
+                })
+                .collect())
+        }).unwrap();
+
+        let addr = server.local_addr().unwrap();
+        thread::spawn(move || server.run(|_| {}));
+
+        let (status, body) = request(addr, "GET", "/health", "");
+        assert_eq!(status, 200);
+        assert_eq!(body["status"], "ok");
+
+        let (status, body) = request(addr, "POST", "/summarize",
+            r#"{"text": "tifu by testing", "mode": "long", "beam_size": 4}"#);
+        assert_eq!(status, 200);
+        assert_eq!(body["summary"], "tifu by");
+        assert_eq!(body["score"], 4.0);
+
+        let (status, body) = request(addr, "POST", "/summarize/batch",
+            r#"{"requests": [{"text": "a b"}, {"text": "c d", "mode": "long"}]}"#);
+        assert_eq!(status, 200);
+        assert_eq!(body["responses"][0]["summary"], "a");
+        assert_eq!(body["responses"][1]["summary"], "c d");
+        assert!(max_batch.load(Ordering::SeqCst) >= 2);
+
+        assert_eq!(request(addr, "POST", "/summarize", r#"{"text": " "}"#).0, 400);
+        assert_eq!(request(addr, "POST", "/summarize", r#"{"text": "a", "beam_size": 0}"#).0, 400);
+        assert_eq!(request(addr, "POST", "/summarize", "{").0, 400);
+        assert_eq!(request(addr, "GET", "/summarize", "").0, 405);
+        assert_eq!(request(addr, "GET", "/missing", "").0, 404);
+    }
+
+    #[test]
+    fn test_server_batches_concurrent_requests() {
+        let max_batch = Arc::new(AtomicUsize::new(0));
+        let seen = max_batch.clone();
+
+        let options = ServerOptions {
+            max_batch_size: 4,
+            max_batch_delay_ms: 500,
+            ..ServerOptions::default()
+        };
+
+        let server = Server::bind("127.0.0.1:0", options, move |requests: &[SummarizeRequest]| {
+            seen.fetch_max(requests.len(), Ordering::SeqCst);
+            Ok(requests.iter().map(|_| SummarizeResponse::default()).collect())
+        }).unwrap();
+
+        let addr = server.local_addr().unwrap();
+        thread::spawn(move || server.run(|_| {}));
+
+        let clients: Vec<_> = (0..4)
+            .map(|i| thread::spawn(move || {
+                request(addr, "POST", "/summarize", &format!(r#"{{"text": "text {}"}}"#, i)).0
+            }))
+            .collect();
+
+        for client in clients {
+            assert_eq!(client.join().unwrap(), 200);
+        }
+
+        assert!(max_batch.load(Ordering::SeqCst) > 1);
+        assert!(max_batch.load(Ordering::SeqCst) <= 4);
+    }
+
+    #[test]
+    fn test_server_failures() {
+        let options = ServerOptions {
+            max_batch_delay_ms: 0,
+            read_timeout_ms: 100,
+            ..ServerOptions::default()
+        };
+
+        let server = Server::bind("127.0.0.1:0", options, |requests: &[SummarizeRequest]| {
+            if requests[0].text == "panic" {
+                panic!("bad input");
+            }
+            Ok(requests.iter().map(|_| SummarizeResponse::default()).collect())
+        }).unwrap();
+
+        let addr = server.local_addr().unwrap();
+        thread::spawn(move || server.run(|_| {}));
+
+        let (status, body) = request(addr, "POST", "/summarize", r#"{"text": "panic"}"#);
+        assert_eq!(status, 500);
+        assert_eq!(body["error"], "summarize panicked: bad input");
+        assert_eq!(request(addr, "POST", "/summarize", r#"{"text": "ok"}"#).0, 200);
+
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(stream, "POST /summarize HTTP/1.1\r\nContent-Length: 10\r\n\r\n{{").unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 400"));
+    }
+
+    #[test]
+    fn test_server_summarize() {
+        let mut lead = LeadSummarizer::new();
+        let mut batch = Batch::new();
+        batch.ids = vec!["a".to_string()];
+        batch.modes = vec![SummaryMode::Short];
+        batch.sources = vec![Vec::new()];
+        batch.summaries = vec![vec!["x".to_string(), "y".to_string()]];
+        lead.train_step(&batch, &TrainStepOptions::default()).unwrap();
+
+        let requests = vec![
+            SummarizeRequest { text: "I broke the build, again!".to_string(), ..SummarizeRequest::default() },
+            SummarizeRequest { text: "My cat ate it.".to_string(), beam_size: Some(2), ..SummarizeRequest::default() },
+        ];
+
+        let responses = summarize(&lead, &requests, &DecodeOptions::default()).unwrap();
+        assert_eq!(responses[0].summary, "i broke");
+        assert_eq!(responses[1].summary, "my cat");
+    }
+}
@@ -1,5 +1,8 @@
 use serde::{Serialize, Deserialize};
 use crate::raw_data_entry::RawDataEntry;
+use crate::data_entry::{DataEntry, Provenance};
+use crate::metadata::Metadata;
+use crate::sentence::split_sentences;
 
 /// ShortDataEntry is a struct representing an entry in the Short TIFU dataset.
 #[derive(Clone, Default, PartialEq, PartialOrd, Debug, Serialize, Deserialize)]
@@ -9,6 +12,15 @@ pub struct ShortDataEntry {
     pub summary_tokenized: Vec<String>,
     pub source: String,
     pub source_tokenized: Vec<String>,
+    /// `source_sentences` are the source tokens split in sentences.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub source_sentences: Vec<Vec<String>>,
+    /// `metadata` are the post metadata of the entry, if known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<Metadata>,
+    /// `provenance` records the post and the fields the entry was derived from, if known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provenance: Option<Provenance>,
 }
 
 impl ShortDataEntry {
@@ -16,22 +28,74 @@ impl ShortDataEntry {
     pub fn new() -> ShortDataEntry {
         ShortDataEntry::default()
     }
+}
 
-    /// `from_raw` creates a `ShortDataEntry` from a `RawDataEntry`.
-    pub fn from_raw(rde: &RawDataEntry) -> ShortDataEntry {
+impl From<&RawDataEntry> for ShortDataEntry {
+    fn from(rde: &RawDataEntry) -> ShortDataEntry {
         ShortDataEntry {
             id: rde.id.to_owned(),
             summary: rde.trimmed_title.to_owned(),
             summary_tokenized: rde.trimmed_title_tokenized.to_owned(),
             source: rde.selftext_without_tldr.to_owned(),
             source_tokenized: rde.selftext_without_tldr_tokenized.to_owned(),
+            source_sentences: Vec::new(),
+            metadata: Some(Metadata::from_raw(rde)),
+            provenance: Some(Provenance::from_raw(rde, "selftext_without_tldr", "trimmed_title")),
         }
     }
 }
 
+impl DataEntry for ShortDataEntry {
+    /// `from_raw` creates a `ShortDataEntry` from a `RawDataEntry`.
+    fn from_raw(rde: &RawDataEntry) -> ShortDataEntry {
+        ShortDataEntry::from(rde)
+    }
+
+    /// `id` returns the id of the `ShortDataEntry`.
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// `source` returns the source text of the `ShortDataEntry`.
+    fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// `source_mut` returns a mutable reference to the source text of the `ShortDataEntry`.
+    fn source_mut(&mut self) -> &mut String {
+        &mut self.source
+    }
+
+    /// `source_tokenized` returns the source tokens of the `ShortDataEntry`.
+    fn source_tokenized(&self) -> &[String] {
+        &self.source_tokenized
+    }
+
+    /// `source_tokenized_mut` returns a mutable reference to the source tokens of the `ShortDataEntry`.
+    fn source_tokenized_mut(&mut self) -> &mut Vec<String> {
+        &mut self.source_tokenized
+    }
+
+    /// `summary_tokenized` returns the summary tokens of the `ShortDataEntry`.
+    fn summary_tokenized(&self) -> &[String] {
+        &self.summary_tokenized
+    }
+
+    /// `source_sentences_mut` returns a mutable reference to the source sentences of the `ShortDataEntry`.
+    fn source_sentences_mut(&mut self) -> Option<&mut Vec<Vec<String>>> {
+        Some(&mut self.source_sentences)
+    }
+
+    /// `split_source_sentences` splits the source tokens of the `ShortDataEntry` in sentences.
+    fn split_source_sentences(&mut self) {
+        self.source_sentences = split_sentences(&self.source, &self.source_tokenized);
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::ShortDataEntry;
+    use crate::data_entry::DataEntry;
     use crate::raw_data_entries::RawDataEntries;
 
     #[test]
@@ -39,12 +103,17 @@ mod test {
         let count = 10;
         let rds = RawDataEntries::from_tifu_dataset_file(count).unwrap();
         for rd in rds {
-            let sd = ShortDataEntry::from_raw(&rd);
+            let mut sd = ShortDataEntry::from_raw(&rd);
             assert_eq!(&sd.id, &rd.id);
             assert_eq!(&sd.summary, &rd.trimmed_title);
             assert_eq!(&sd.summary_tokenized, &rd.trimmed_title_tokenized);
             assert_eq!(&sd.source, &rd.selftext_without_tldr);
             assert_eq!(&sd.source_tokenized, &rd.selftext_without_tldr_tokenized);
+            assert!(sd.source_sentences.is_empty());
+            sd.split_source_sentences();
+            assert_eq!(sd.source_sentences.concat(), rd.selftext_without_tldr_tokenized);
+            assert_eq!(sd.metadata.map(|m| m.score), Some(rd.score));
+            assert_eq!(sd.provenance.map(|p| p.permalink), Some(rd.permalink.to_owned()));
         }
     }
 }
@@ -0,0 +1,185 @@
+use serde::{Serialize, Deserialize};
+use crate::result::Result;
+use crate::hash::Hasher;
+use crate::seed::splitmix64;
+use crate::data_entry::DataEntry;
+use crate::data_entries::DataEntries;
+
+/// `Split` is the dataset split an entry is assigned to.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Split {
+    Train,
+    Validation,
+    Test,
+}
+
+/// `SplitOptions` are the fractions of the entries assigned to the validation and test splits.
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub struct SplitOptions {
+    pub validation: f64,
+    pub test: f64,
+}
+
+impl SplitOptions {
+    /// `new` creates a new `SplitOptions`.
+    pub fn new() -> SplitOptions {
+        SplitOptions::default()
+    }
+
+    /// `validate` returns an error if the fractions are invalid.
+    pub fn validate(&self) -> Result<()> {
+        if self.validation < 0.0 || self.test < 0.0 || self.validation + self.test >= 1.0 {
+            return Err("invalid split fractions".to_string());
+        }
+
+        Ok(())
+    }
+
+    /// `assign` returns the `Split` of the entry with id `id`. The assignment only depends
+    /// on `id` and `seed`, so it does not change when the dataset is reordered or extended.
+    pub fn assign(&self, id: &str, seed: u64) -> Split {
+        let mut hasher = Hasher::new();
+        hasher.update(&seed.to_le_bytes());
+        hasher.update(id.as_bytes());
+
+        let u = (splitmix64(hasher.finish_u64()) >> 11) as f64 / (1u64 << 53) as f64;
+
+        if u < self.validation {
+            Split::Validation
+        } else if u < self.validation + self.test {
+            Split::Test
+        } else {
+            Split::Train
+        }
+    }
+}
+
+impl Default for SplitOptions {
+    fn default() -> SplitOptions {
+        SplitOptions {
+            validation: 0.05,
+            test: 0.05,
+        }
+    }
+}
+
+/// `SplitSizes` are the number of entries of every split.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct SplitSizes {
+    pub train: usize,
+    pub validation: usize,
+    pub test: usize,
+}
+
+/// `Splits` are the train, validation and test splits of a `DataEntries`.
+#[derive(Clone, Default, PartialEq, Debug)]
+pub struct Splits<T> {
+    pub train: DataEntries<T>,
+    pub validation: DataEntries<T>,
+    pub test: DataEntries<T>,
+}
+
+impl<T: DataEntry> Splits<T> {
+    /// `new` assigns the `entries` to the splits with `options` and `seed`. The augmented
+    /// entries follow their original entry and are dropped outside of the train split, so
+    /// that no augmented text leaks into evaluation.
+    pub fn new(entries: DataEntries<T>, options: &SplitOptions, seed: u64) -> Result<Splits<T>> {
+        options.validate()?;
+
+        let mut splits = Splits {
+            train: DataEntries::new(),
+            validation: DataEntries::new(),
+            test: DataEntries::new(),
+        };
+
+        for entry in entries {
+            let id = entry.augmented_from().unwrap_or_else(|| entry.id());
+
+            match options.assign(id, seed) {
+                Split::Train => splits.train.push(entry),
+                _ if entry.augmented_from().is_some() => {},
+                Split::Validation => splits.validation.push(entry),
+                Split::Test => splits.test.push(entry),
+            }
+        }
+
+        Ok(splits)
+    }
+
+    /// `sizes` returns the `SplitSizes` of the `Splits`.
+    pub fn sizes(&self) -> SplitSizes {
+        SplitSizes {
+            train: self.train.len(),
+            validation: self.validation.len(),
+            test: self.test.len(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Splits, SplitOptions};
+    use crate::data_entries::DataEntries;
+    use crate::short_data_entry::ShortDataEntry;
+    use crate::long_data_entry::LongDataEntry;
+
+    fn entries(count: usize) -> DataEntries<ShortDataEntry> {
+        (0..count)
+            .map(|i| {
+                let mut entry = ShortDataEntry::new();
+                entry.id = format!("id{}", i);
+                entry
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_split_assign() {
+        let options = SplitOptions { validation: 0.1, test: 0.2 };
+        assert!(options.validate().is_ok());
+        assert!(SplitOptions { validation: 0.5, test: 0.5 }.validate().is_err());
+
+        let splits = Splits::new(entries(10_000), &options, 7).unwrap();
+        let sizes = splits.sizes();
+        assert_eq!(sizes.train + sizes.validation + sizes.test, 10_000);
+        assert!(sizes.validation > 800 && sizes.validation < 1200);
+        assert!(sizes.test > 1800 && sizes.test < 2200);
+
+        assert_eq!(Splits::new(entries(10_000), &options, 7).unwrap(), splits);
+        assert_ne!(Splits::new(entries(10_000), &options, 8).unwrap(), splits);
+
+        let mut shuffled = entries(10_000);
+        shuffled.shuffle(1);
+        let mut reordered = Splits::new(shuffled, &options, 7).unwrap();
+        reordered.validation.sort_by_key(|e| e.id.to_owned());
+        let mut validation = splits.validation.clone();
+        validation.sort_by_key(|e| e.id.to_owned());
+        assert_eq!(reordered.validation, validation);
+    }
+
+    #[test]
+    fn test_split_augmented() {
+        let options = SplitOptions { validation: 0.3, test: 0.3 };
+        let entries: DataEntries<LongDataEntry> = (0..1000)
+            .flat_map(|i| {
+                let mut entry = LongDataEntry::new();
+                entry.id = format!("id{}", i);
+
+                let mut augmented = entry.clone();
+                augmented.id = format!("id{}#aug", i);
+                augmented.augmented_from = Some(entry.id.to_owned());
+
+                vec![entry, augmented]
+            })
+            .collect();
+
+        let splits = Splits::new(entries, &options, 3).unwrap();
+        let originals = splits.train.iter().filter(|e| e.augmented_from.is_none()).count();
+        assert_eq!(splits.train.len(), 2 * originals);
+
+        for entry in splits.validation.iter().chain(splits.test.iter()) {
+            assert!(entry.augmented_from.is_none());
+        }
+    }
+}
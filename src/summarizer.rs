@@ -0,0 +1,246 @@
+use serde::{Serialize, Deserialize};
+use std::path::Path;
+use crate::result::Result;
+use crate::short_data_entry::ShortDataEntry;
+use crate::long_data_entry::LongDataEntry;
+use crate::rouge::{mean_rouge_l, RougeScore};
+use crate::bpe::Bpe;
+use crate::metadata::Metadata;
+use crate::attention::AttentionLevel;
+
+/// `SummaryMode` is the kind of summary to generate.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SummaryMode {
+    /// `Short` generates a title-like summary.
+    Short,
+    /// `Long` generates a tl;dr-like summary.
+    Long,
+}
+
+impl Default for SummaryMode {
+    fn default() -> SummaryMode {
+        SummaryMode::Short
+    }
+}
+
+/// `Batch` is a batch of tokenized sources and reference summaries shared by all the
+/// `Summarizer`s, whatever the dataset the entries come from.
+#[derive(Clone, Default, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Batch {
+    pub ids: Vec<String>,
+    /// `modes` are the kinds of the reference summaries.
+    pub modes: Vec<SummaryMode>,
+    pub sources: Vec<Vec<String>>,
+    /// `summaries` are the reference summaries, empty when an entry has none.
+    pub summaries: Vec<Vec<String>>,
+    /// `metadata` are the post metadata of the entries, empty when none are known.
+    #[serde(default)]
+    pub metadata: Vec<Option<Metadata>>,
+}
+
+impl Batch {
+    /// `new` creates a new `Batch`.
+    pub fn new() -> Batch {
+        Batch::default()
+    }
+
+    /// `len` returns the number of entries of the `Batch`.
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    /// `is_empty` returns if the `Batch` is empty.
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+
+    /// `encode_bpe` returns the `Batch` with the sources and the summaries split in subwords by `bpe`.
+    pub fn encode_bpe(&self, bpe: &Bpe) -> Batch {
+        Batch {
+            ids: self.ids.clone(),
+            modes: self.modes.clone(),
+            sources: self.sources.iter().map(|s| bpe.encode(s)).collect(),
+            summaries: self.summaries.iter().map(|s| bpe.encode(s)).collect(),
+            metadata: self.metadata.clone(),
+        }
+    }
+}
+
+impl From<&[ShortDataEntry]> for Batch {
+    fn from(entries: &[ShortDataEntry]) -> Batch {
+        Batch {
+            ids: entries.iter().map(|e| e.id.to_owned()).collect(),
+            modes: vec![SummaryMode::Short; entries.len()],
+            sources: entries.iter().map(|e| e.source_tokenized.to_owned()).collect(),
+            summaries: entries.iter().map(|e| e.summary_tokenized.to_owned()).collect(),
+            metadata: entries.iter().map(|e| e.metadata.to_owned()).collect(),
+        }
+    }
+}
+
+impl From<&[LongDataEntry]> for Batch {
+    fn from(entries: &[LongDataEntry]) -> Batch {
+        Batch {
+            ids: entries.iter().map(|e| e.id.to_owned()).collect(),
+            modes: vec![SummaryMode::Long; entries.len()],
+            sources: entries.iter().map(|e| e.source_tokenized.to_owned()).collect(),
+            summaries: entries.iter().map(|e| e.summary_tokenized.to_owned().unwrap_or_default()).collect(),
+            metadata: entries.iter().map(|e| e.metadata.to_owned()).collect(),
+        }
+    }
+}
+
+/// `DecodeOptions` are the options of `Summarizer::decode`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct DecodeOptions {
+    pub beam_size: usize,
+    pub max_len: usize,
+    /// `attention` records the attention weights of the decoded summaries.
+    #[serde(default)]
+    pub attention: bool,
+}
+
+impl DecodeOptions {
+    /// `new` creates a new `DecodeOptions`.
+    pub fn new() -> DecodeOptions {
+        DecodeOptions::default()
+    }
+}
+
+impl Default for DecodeOptions {
+    fn default() -> DecodeOptions {
+        DecodeOptions {
+            beam_size: 4,
+            max_len: 100,
+            attention: false,
+        }
+    }
+}
+
+/// `Hypothesis` is a decoded summary with its score.
+#[derive(Clone, Default, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Hypothesis {
+    pub tokens: Vec<String>,
+    pub score: f32,
+    /// `attention` are the attention weights of every memory level over the source tokens seen
+    /// by the summarizer, one row per summary token, recorded with the `attention` decode option.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attention: Vec<AttentionLevel>,
+}
+
+/// `SummarizeResponse` is the summary of a text, as answered by the server and written by
+/// the batch prediction.
+#[derive(Clone, Default, PartialEq, Debug, Serialize, Deserialize)]
+pub struct SummarizeResponse {
+    pub summary: String,
+    pub score: f32,
+}
+
+/// `TrainStepOptions` are the options of `Summarizer::train_step`.
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub struct TrainStepOptions {
+    pub learning_rate: f64,
+    /// `max_grad_norm` is the global norm the gradients are clipped to. Zero disables clipping.
+    pub max_grad_norm: f64,
+}
+
+impl TrainStepOptions {
+    /// `new` creates a new `TrainStepOptions`.
+    pub fn new() -> TrainStepOptions {
+        TrainStepOptions::default()
+    }
+}
+
+impl Default for TrainStepOptions {
+    fn default() -> TrainStepOptions {
+        TrainStepOptions {
+            learning_rate: 1e-3,
+            max_grad_norm: 0.0,
+        }
+    }
+}
+
+/// `TrainStepStats` are the statistics of a training step.
+#[derive(Clone, Copy, Default, PartialEq, Debug, Serialize, Deserialize)]
+pub struct TrainStepStats {
+    /// `loss` is the mean loss per target token.
+    pub loss: f64,
+    /// `grad_norm` is the global norm of the gradients before clipping.
+    pub grad_norm: f64,
+    /// `tokens` is the number of target tokens of the step.
+    pub tokens: usize,
+}
+
+/// `Summarizer` is a summarization model trained and evaluated on `Batch`es.
+pub trait Summarizer: Sized {
+    /// `name` returns the name of the `Summarizer`.
+    fn name(&self) -> &str;
+
+    /// `train_step` trains the `Summarizer` on `batch` and returns the step statistics.
+    fn train_step(&mut self, batch: &Batch, options: &TrainStepOptions) -> Result<TrainStepStats>;
+
+    /// `loss` returns the loss of `batch` as `train_step` does, without updating the
+    /// `Summarizer`.
+    fn loss(&self, batch: &Batch) -> Result<TrainStepStats>;
+
+    /// `decode` returns a `Hypothesis` per entry of `batch`.
+    fn decode(&self, batch: &Batch, options: &DecodeOptions) -> Result<Vec<Hypothesis>>;
+
+    /// `save` saves the `Summarizer` in the directory `dir`.
+    fn save(&self, dir: &Path) -> Result<()>;
+
+    /// `load` loads a `Summarizer` from the directory `dir`.
+    fn load(dir: &Path) -> Result<Self>;
+}
+
+/// `evaluate` returns the mean ROUGE-L of the summaries decoded by `summarizer` on `batches`
+/// against the reference summaries.
+pub fn evaluate<'a, S, I>(summarizer: &S, batches: I, options: &DecodeOptions) -> Result<RougeScore>
+    where S: Summarizer,
+          I: IntoIterator<Item = &'a Batch>
+{
+    let mut candidates = Vec::new();
+    let mut references = Vec::new();
+
+    for batch in batches {
+        let hypotheses = summarizer.decode(batch, options)?;
+        if hypotheses.len() != batch.len() {
+            return Err(format!("{}: expected {} hypotheses, found {}", summarizer.name(), batch.len(), hypotheses.len()));
+        }
+
+        candidates.extend(hypotheses.into_iter().map(|h| h.tokens));
+        references.extend(batch.summaries.iter().cloned());
+    }
+
+    Ok(mean_rouge_l(&candidates, &references))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Batch, SummaryMode};
+    use crate::short_data_entry::ShortDataEntry;
+    use crate::long_data_entry::LongDataEntry;
+
+    #[test]
+    fn test_summarizer_batch_from_entries() {
+        let mut short = ShortDataEntry::new();
+        short.id = "a".to_string();
+        short.source_tokenized = vec!["source".to_string()];
+        short.summary_tokenized = vec!["summary".to_string()];
+
+        let batch = Batch::from(&[short][..]);
+        assert_eq!(batch.len(), 1);
+        assert_eq!(batch.summaries[0], vec!["summary".to_string()]);
+        assert_eq!(batch.metadata, vec![None]);
+
+        let mut long = LongDataEntry::new();
+        long.id = "b".to_string();
+        long.source_tokenized = vec!["source".to_string()];
+
+        let batch = Batch::from(&[long][..]);
+        assert_eq!(batch.ids, vec!["b".to_string()]);
+        assert_eq!(batch.modes, vec![SummaryMode::Long]);
+        assert!(batch.summaries[0].is_empty());
+    }
+}
@@ -0,0 +1,49 @@
+/// `CLITICS` are the contraction suffixes split from the words, as in the dataset.
+const CLITICS: [&str; 7] = ["n't", "'s", "'m", "'re", "'ve", "'ll", "'d"];
+
+/// `is_word_char` returns if `c` is part of a word.
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '\''
+}
+
+/// `tokenize` splits `text` in tokens as the `selftext_without_tldr_tokenized` field of the
+/// dataset: lowercase words without punctuation, with the contractions split as in "do n't".
+pub fn tokenize(text: &str) -> Vec<String> {
+    let text = text.to_lowercase().replace('’', "'");
+    let mut tokens = Vec::new();
+
+    for word in text.split(|c| !is_word_char(c)) {
+        let word = word.trim_matches('\'');
+        if word.is_empty() {
+            continue;
+        }
+
+        match CLITICS.iter().find(|clitic| word.len() > clitic.len() && word.ends_with(*clitic)) {
+            Some(clitic) => {
+                let (stem, clitic) = word.split_at(word.len() - clitic.len());
+                tokens.push(stem.to_owned());
+                tokens.push(clitic.to_owned());
+            },
+            None => tokens.push(word.to_owned()),
+        }
+    }
+
+    tokens
+}
+
+#[cfg(test)]
+mod test {
+    use super::tokenize;
+
+    #[test]
+    fn test_tokenizer_tokenize() {
+        let text = "I don't multitask very well, so I forgot... It's the 'best' part — I’m 100% sure!";
+        let tokens: Vec<String> = "i do n't multitask very well so i forgot it 's the best part i 'm 100 sure"
+            .split(' ')
+            .map(ToOwned::to_owned)
+            .collect();
+        assert_eq!(tokenize(text), tokens);
+
+        assert!(tokenize(" ... ' ").is_empty());
+    }
+}
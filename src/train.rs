@@ -0,0 +1,811 @@
+use serde::{Serialize, Deserialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use crate::result::Result;
+use crate::config::{Config, Tokenization};
+use crate::bpe::Bpe;
+use crate::checkpoint::{checkpoint_path, last_checkpoint_path, TrainState};
+use crate::early_stopping::{EarlyStopping, Validation};
+use crate::data_entry::DataEntry;
+use crate::data_entries::DataEntries;
+use crate::data_loader::{DataLoader, DataLoaderOptions, LastBatch};
+use crate::curriculum::{curriculum_batches, LengthBuckets};
+use crate::manifest::RunManifest;
+use crate::metrics::{MetricsWriter, StepMetrics};
+use crate::model::Model;
+use crate::multi_task::{evaluate_multi_task, mix_batches, MultiTaskOptions};
+use crate::seed::{derive_seed, Seeds};
+use crate::split::{SplitOptions, SplitSizes, Splits};
+use crate::summarizer::{evaluate, Batch, DecodeOptions, Summarizer, SummaryMode, TrainStepOptions, TrainStepStats};
+use crate::vocabulary::Vocabulary;
+
+/// `MODEL_DIR` is the name of the directory of the final model in a run directory.
+pub const MODEL_DIR: &str = "model";
+
+/// `MULTI_TASK_STREAM` is the stream of the seed mixing the multi-task batches of an epoch.
+const MULTI_TASK_STREAM: u64 = 1;
+
+/// `TrainOptions` are the options of a training run besides its `Config`.
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub struct TrainOptions {
+    /// `split` are the fractions of the entries held out for validation and test.
+    pub split: SplitOptions,
+    /// `log_every` is the number of steps between two metrics records.
+    pub log_every: u64,
+    /// `decode` are the decode options of the validation summaries.
+    pub decode: DecodeOptions,
+    /// `checkpoint_every` is the number of steps between two saves of the last checkpoint,
+    /// from which the run can be resumed. Zero disables the periodic checkpoints.
+    pub checkpoint_every: u64,
+}
+
+impl TrainOptions {
+    /// `new` creates a new `TrainOptions`.
+    pub fn new() -> TrainOptions {
+        TrainOptions::default()
+    }
+}
+
+impl Default for TrainOptions {
+    fn default() -> TrainOptions {
+        TrainOptions {
+            split: SplitOptions::default(),
+            log_every: 100,
+            decode: DecodeOptions { beam_size: 1, ..DecodeOptions::default() },
+            checkpoint_every: 1000,
+        }
+    }
+}
+
+/// `TrainSummary` is the outcome of a training run.
+#[derive(Clone, Default, PartialEq, Debug, Serialize, Deserialize)]
+pub struct TrainSummary {
+    pub global_step: u64,
+    pub epoch: u64,
+    /// `loss` is the mean loss per token of the last epoch.
+    pub loss: f64,
+    pub split_sizes: SplitSizes,
+    /// `stopped` is true if the run was stopped early.
+    pub stopped: bool,
+    /// `best_checkpoint` is the best validated checkpoint, with early stopping.
+    pub best_checkpoint: Option<PathBuf>,
+}
+
+/// `StepWindow` accumulates the stats of the steps between two metrics records.
+struct StepWindow {
+    steps: u64,
+    loss: f64,
+    grad_norm: f64,
+    tokens: usize,
+    start: Instant,
+}
+
+impl StepWindow {
+    /// `new` creates a new empty `StepWindow` starting now.
+    fn new() -> StepWindow {
+        StepWindow {
+            steps: 0,
+            loss: 0.0,
+            grad_norm: 0.0,
+            tokens: 0,
+            start: Instant::now(),
+        }
+    }
+
+    /// `add` adds the `stats` of a step.
+    fn add(&mut self, stats: &TrainStepStats) {
+        self.steps += 1;
+        self.loss += stats.loss * stats.tokens as f64;
+        self.grad_norm += stats.grad_norm;
+        self.tokens += stats.tokens;
+    }
+
+    /// `metrics` returns the `StepMetrics` of the window ending at `step`.
+    fn metrics(&self, step: u64, learning_rate: f64) -> StepMetrics {
+        let secs = self.start.elapsed().as_secs_f64();
+
+        StepMetrics {
+            step,
+            loss: self.loss / self.tokens.max(1) as f64,
+            learning_rate,
+            grad_norm: self.grad_norm / self.steps.max(1) as f64,
+            tokens_per_sec: if secs > 0.0 { self.tokens as f64 / secs } else { 0.0 },
+            ..StepMetrics::default()
+        }
+    }
+}
+
+/// `Collate` is the type of the collate function of the `DataLoader` of a `Trainer`.
+type Collate<T> = fn(Vec<T>) -> Result<Batch>;
+
+/// `collate` converts the entries of a batch of the `DataLoader` of a `Trainer`.
+fn collate<T>(entries: Vec<T>) -> Result<Batch>
+    where for<'a> Batch: From<&'a [T]>
+{
+    Ok(Batch::from(entries.as_slice()))
+}
+
+/// `Trainer` trains a `Model` on the train split of entries of type `T`, following the
+/// learning rate schedule, the length buckets and the curriculum of its `Config`.
+pub struct Trainer<T> {
+    pub config: Config,
+    pub options: TrainOptions,
+    pub seeds: Seeds,
+    pub model: Model,
+    /// `validation` is the validation split of the entries.
+    pub validation: DataEntries<T>,
+    pub split_sizes: SplitSizes,
+    pub global_step: u64,
+    pub epoch: u64,
+    /// `epoch_position` is the number of entries of the current epoch already consumed.
+    pub epoch_position: usize,
+    /// `early_stopping` tracks the validations, with the `early_stopping` option of the config.
+    pub early_stopping: Option<EarlyStopping>,
+    vocabulary_hash: String,
+    loader: DataLoader<DataEntries<T>, Collate<T>>,
+}
+
+impl<T> Trainer<T>
+    where T: DataEntry + Send + Sync + 'static,
+          for<'a> Batch: From<&'a [T]>
+{
+    /// `new` creates a new `Trainer` of a model of `config`: the `entries` are split with the
+    /// seed of the config, the train split is extended with the augmented entries of the
+    /// `augmentation` option of the config and the vocabulary is built from it.
+    pub fn new(config: Config, entries: DataEntries<T>, options: TrainOptions) -> Result<Trainer<T>> {
+        config.validate()?;
+
+        if options.log_every == 0 {
+            return Err("invalid log_every: 0".to_string());
+        }
+
+        let seeds = Seeds::new(config.train.seed);
+        let splits = Splits::new(entries, &options.split, seeds.split)?;
+        let split_sizes = splits.sizes();
+
+        if splits.train.is_empty() {
+            return Err("invalid dataset: the train split is empty".to_string());
+        }
+
+        if config.train.early_stopping.is_some() && splits.validation.is_empty() {
+            return Err("invalid dataset: early stopping needs a validation split".to_string());
+        }
+
+        let mut train = splits.train;
+        if let Some(ref augmentation) = config.train.augmentation {
+            let augmenter = augmentation.augmenter()?;
+            let augmented: Vec<T> = train.iter().flat_map(|entry| entry.augment(&augmenter)).collect();
+            train.extend(augmented);
+        }
+
+        let texts = || {
+            train
+                .iter()
+                .flat_map(|entry| vec![entry.source_tokenized(), entry.summary_tokenized()])
+        };
+        let (size, min_count) = (config.model.vocabulary_size, config.model.min_token_count);
+
+        let (vocabulary, bpe) = match config.model.tokenization {
+            Tokenization::Word => (Vocabulary::build(texts(), size, min_count), None),
+            Tokenization::Bpe { merges } => {
+                let bpe = Bpe::train(texts(), merges, min_count);
+                let subwords: Vec<Vec<String>> = texts().map(|text| bpe.encode(text)).collect();
+                (Vocabulary::build(subwords.iter().map(Vec::as_slice), size, min_count), Some(bpe))
+            },
+        };
+        let vocabulary_hash = vocabulary.hash();
+        let model = Model::new(&config, vocabulary, bpe, &seeds)?;
+
+        let loader_options = DataLoaderOptions {
+            batch_size: config.train.batch_size,
+            seed: seeds.shuffle,
+            shuffle: true,
+            last_batch: LastBatch::Keep,
+            prefetch: 2,
+        };
+        let loader = DataLoader::new(train, loader_options, collate as Collate<T>)?;
+
+        let early_stopping = config.train.early_stopping.map(EarlyStopping::new);
+
+        Ok(Trainer {
+            config,
+            options,
+            seeds,
+            model,
+            validation: splits.validation,
+            split_sizes,
+            global_step: 0,
+            epoch: 0,
+            epoch_position: 0,
+            early_stopping,
+            vocabulary_hash,
+            loader,
+        })
+    }
+
+    /// `state` returns the `TrainState` of the run at the current step.
+    pub fn state(&self) -> Result<TrainState> {
+        Ok(TrainState {
+            global_step: self.global_step,
+            epoch: self.epoch,
+            epoch_position: self.epoch_position,
+            seed: self.config.train.seed,
+            vocabulary_hash: self.vocabulary_hash.to_owned(),
+            config_hash: self.config.hash()?,
+            learning_rate_schedule: self.config.train.learning_rate_schedule,
+            early_stopping: self.early_stopping.clone(),
+        })
+    }
+
+    /// `manifest` returns the `RunManifest` of the run, with its config, seeds, vocabulary hash
+    /// and split sizes. The dataset hash is left to the caller, who knows the dataset file.
+    pub fn manifest(&self) -> Result<RunManifest> {
+        let mut manifest = RunManifest::new("train", self.config.train.seed);
+        manifest.set_config(&self.config)?;
+        manifest.vocabulary_hash = Some(self.vocabulary_hash.to_owned());
+        manifest.split_sizes = self.split_sizes;
+        Ok(manifest)
+    }
+
+    /// `resume` restores the model, with its optimizer state and dropout rng, and the training
+    /// state saved in the checkpoint directory `dir`, so that `train` continues the run from the
+    /// checkpoint step. The checkpoint must have been saved with the same config and vocabulary.
+    pub fn resume<P: AsRef<Path>>(&mut self, dir: P) -> Result<()> {
+        let state = TrainState::load(&dir)?;
+        state.check_resume(&self.vocabulary_hash, &self.config.hash()?)?;
+
+        self.model = Model::load(dir.as_ref())?;
+        self.global_step = state.global_step;
+        self.epoch = state.epoch;
+        self.epoch_position = state.epoch_position;
+        self.early_stopping = state.early_stopping;
+        Ok(())
+    }
+
+    /// `save_last` saves the model and the training state in the last checkpoint directory of
+    /// the run directory `dir`, replacing the previous one only once fully written.
+    fn save_last(&self, dir: &Path) -> Result<()> {
+        let path = last_checkpoint_path(dir);
+        let tmp_path = path.with_extension("tmp");
+
+        if tmp_path.exists() {
+            fs::remove_dir_all(&tmp_path).map_err(|e| format!("{}", e))?;
+        }
+
+        self.model.save(&tmp_path)?;
+        self.state()?.save(&tmp_path)?;
+
+        if path.exists() {
+            fs::remove_dir_all(&path).map_err(|e| format!("{}", e))?;
+        }
+
+        fs::rename(&tmp_path, &path).map_err(|e| format!("{}", e))
+    }
+
+    /// `validate` returns the loss per token and the ROUGE-L F1 of the model on the
+    /// validation split. With the multi-task training, the ROUGE-L of every summary kind is
+    /// returned too, and the ROUGE-L F1 is their mean.
+    pub fn validate(&self) -> Result<Validation> {
+        let batches: Vec<Batch> = self
+            .validation
+            .chunks(self.config.train.batch_size)
+            .map(Batch::from)
+            .collect();
+
+        let (mut loss, mut tokens) = (0.0, 0);
+        for batch in batches.iter() {
+            let stats = self.model.loss(batch)?;
+            loss += stats.loss * stats.tokens as f64;
+            tokens += stats.tokens;
+        }
+
+        let (rouge_l, multi_task) = match self.config.train.multi_task {
+            Some(_) => {
+                let scores = evaluate_multi_task(&self.model, batches.iter(), &self.options.decode)?;
+                (scores.f1(), Some(scores))
+            },
+            None => (evaluate(&self.model, batches.iter(), &self.options.decode)?.f1, None),
+        };
+
+        Ok(Validation {
+            step: self.global_step,
+            loss: loss / tokens.max(1) as f64,
+            rouge_l,
+            multi_task,
+        })
+    }
+
+    /// `checkpoint` saves the model in the checkpoint directory of the current step of the run
+    /// directory `dir` and validates it, writing the validation with the metrics of `window`.
+    /// The checkpoints no longer among the best ones are removed, the kept one gets the training
+    /// state. It returns if the run must stop.
+    fn checkpoint(&mut self, dir: &Path, metrics: &mut MetricsWriter, window: &StepMetrics) -> Result<bool> {
+        let path = checkpoint_path(dir, self.global_step);
+        self.model.save(&path)?;
+
+        let validation = self.validate()?;
+        metrics.write(&StepMetrics {
+            validation_loss: Some(validation.loss),
+            validation_rouge_l: Some(validation.rouge_l),
+            validation_rouge_l_short: validation.multi_task.map(|scores| scores.short.f1),
+            validation_rouge_l_long: validation.multi_task.map(|scores| scores.long.f1),
+            ..*window
+        })?;
+        metrics.flush()?;
+
+        let update = match self.early_stopping {
+            Some(ref mut early_stopping) => early_stopping.update(validation, path.to_owned()),
+            None => return Ok(false),
+        };
+
+        for evicted in update.evicted.iter().filter(|path| path.exists()) {
+            fs::remove_dir_all(evicted).map_err(|e| format!("{}", e))?;
+        }
+
+        if update.keep {
+            self.state()?.save(&path)?;
+        }
+
+        Ok(update.stop)
+    }
+
+    /// `mixed_batch_indices` returns the entry indices of every batch of `epoch` mixing the
+    /// title and tl;dr entries with `options`.
+    fn mixed_batch_indices(&self, epoch: u64, options: &MultiTaskOptions) -> Result<Vec<Vec<usize>>> {
+        let (short, long): (Vec<usize>, Vec<usize>) = (0..self.loader.dataset().len())
+            .partition(|idx| self.loader.dataset()[*idx].mode() == SummaryMode::Short);
+
+        let mut rng = Seeds::rng(derive_seed(self.seeds.epoch_shuffle(epoch), MULTI_TASK_STREAM));
+        let batches = mix_batches(short.len(), long.len(), options, self.config.train.batch_size, &mut rng)?;
+
+        Ok(batches
+            .into_iter()
+            .map(|tasks| {
+                tasks
+                    .into_iter()
+                    .map(|task| match task.mode {
+                        SummaryMode::Short => short[task.idx],
+                        SummaryMode::Long => long[task.idx],
+                    })
+                    .collect()
+            })
+            .collect())
+    }
+
+    /// `train` runs the remaining epochs, writing the metrics every `log_every` steps and at
+    /// the end, and the final model in the run directory `dir`. The batches of every epoch
+    /// group the sources by length bucket, or mix the title and tl;dr entries at the ratio of
+    /// the multi-task training, and only the sources admitted by the curriculum at the step of
+    /// their batch are used. With early stopping, the model is checkpointed and validated
+    /// every `validate_every` steps, and the run stops when the validation metric stops
+    /// improving. A resumed run skips the entries of its epoch consumed before the
+    /// checkpoint.
+    pub fn train<P: AsRef<Path>>(&mut self, dir: P) -> Result<TrainSummary> {
+        let dir = dir.as_ref();
+        let train = self.config.train.clone();
+        let buckets = LengthBuckets::new(train.length_buckets.clone())?;
+        let lengths: Vec<usize> = self
+            .loader
+            .dataset()
+            .iter()
+            .map(|entry| entry.source_tokenized().len())
+            .collect();
+
+        let mut metrics = MetricsWriter::new(dir)?;
+        let mut window = StepWindow::new();
+        let mut loss = 0.0;
+        let mut stopped = false;
+
+        while self.epoch < train.epochs && !stopped {
+            let mut indices = match train.multi_task {
+                Some(ref multi_task) => self.mixed_batch_indices(self.epoch, multi_task)?,
+                None => self.loader.bucketed_batch_indices(self.epoch, &lengths, usize::MAX, &buckets)?,
+            };
+
+            let (position, mut consumed) = (self.epoch_position, 0);
+            let skipped = indices
+                .iter()
+                .take_while(|batch| {
+                    consumed += batch.len();
+                    consumed <= position
+                })
+                .count();
+            indices.drain(..skipped);
+
+            let (consumed, indices): (Vec<usize>, Vec<Vec<usize>>) =
+                curriculum_batches(indices, &lengths, train.curriculum.as_ref(), self.global_step, self.epoch)?
+                    .into_iter()
+                    .unzip();
+            let (mut epoch_loss, mut epoch_tokens) = (0.0, 0);
+
+            for (batch, consumed) in self.loader.batches(indices).zip(consumed) {
+                let batch = batch?;
+                let learning_rate = train.learning_rate_schedule.learning_rate(self.global_step);
+                let options = TrainStepOptions {
+                    learning_rate,
+                    max_grad_norm: train.max_grad_norm,
+                };
+
+                let stats = self.model.train_step(&batch, &options)?;
+                self.global_step += 1;
+                self.epoch_position += consumed;
+                epoch_loss += stats.loss * stats.tokens as f64;
+                epoch_tokens += stats.tokens;
+                window.add(&stats);
+
+                let validate = self
+                    .early_stopping
+                    .as_ref()
+                    .map(|early_stopping| early_stopping.should_validate(self.global_step))
+                    .unwrap_or(false);
+
+                if validate {
+                    stopped = self.checkpoint(dir, &mut metrics, &window.metrics(self.global_step, learning_rate))?;
+                    window = StepWindow::new();
+                } else if self.global_step % self.options.log_every == 0 {
+                    metrics.write(&window.metrics(self.global_step, learning_rate))?;
+                    metrics.flush()?;
+                    window = StepWindow::new();
+                }
+
+                let checkpoint_every = self.options.checkpoint_every;
+                if checkpoint_every > 0 && self.global_step % checkpoint_every == 0 {
+                    self.save_last(dir)?;
+                }
+
+                if stopped {
+                    break;
+                }
+            }
+
+            loss = epoch_loss / epoch_tokens.max(1) as f64;
+            if !stopped {
+                self.epoch += 1;
+                self.epoch_position = 0;
+            }
+        }
+
+        if window.steps > 0 {
+            let learning_rate = train.learning_rate_schedule.learning_rate(self.global_step - 1);
+            metrics.write(&window.metrics(self.global_step, learning_rate))?;
+        }
+
+        metrics.flush()?;
+        self.model.save(&dir.join(MODEL_DIR))?;
+
+        Ok(TrainSummary {
+            global_step: self.global_step,
+            epoch: self.epoch,
+            loss,
+            split_sizes: self.split_sizes,
+            stopped,
+            best_checkpoint: self
+                .early_stopping
+                .as_ref()
+                .and_then(|early_stopping| early_stopping.best().first())
+                .map(|(_, path)| path.to_owned()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{TrainOptions, Trainer, MODEL_DIR};
+    use crate::checkpoint::{checkpoint_path, last_checkpoint_path, TrainState, CHECKPOINTS_DIR};
+    use crate::augmentation::{AugmentationOptions, Technique, TechniqueOptions};
+    use crate::config::{Architecture, Config};
+    use crate::curriculum::{Curriculum, CurriculumSchedule, CurriculumStage, CurriculumUnit};
+    use crate::early_stopping::{EarlyStoppingOptions, Metric};
+    use crate::data_entry::DataEntry;
+    use crate::data_entries::DataEntries;
+    use crate::lr_schedule::LearningRateSchedule;
+    use crate::metrics::{StepMetrics, CSV_FILE, JSONL_FILE};
+    use crate::model::Model;
+    use crate::multi_task::{MultiTaskEntries, MultiTaskOptions, TargetConditioning, TaskEntry};
+    use crate::short_data_entry::ShortDataEntry;
+    use crate::long_data_entry::LongDataEntry;
+    use crate::split::SplitOptions;
+    use crate::summarizer::{Summarizer, SummaryMode};
+    use std::env;
+    use std::fs;
+
+    fn entries(count: usize) -> DataEntries<ShortDataEntry> {
+        let words = ["cat", "dog", "ate", "my", "homework", "broke", "the", "build", "today", "again"];
+
+        (0..count)
+            .map(|i| {
+                let mut entry = ShortDataEntry::new();
+                entry.id = format!("id{}", i);
+                entry.source_tokenized = (0..4 + i % 5).map(|j| words[(i + j) % words.len()].to_string()).collect();
+                entry.summary_tokenized = entry.source_tokenized[..2].to_vec();
+                entry
+            })
+            .collect()
+    }
+
+    fn config() -> Config {
+        let mut config = Config::tifu_short();
+        config.model.embedding_size = 8;
+        config.model.min_token_count = 1;
+        config.train.batch_size = 4;
+        config.train.epochs = 2;
+        config.train.length_buckets = vec![6];
+        config.train.learning_rate_schedule = LearningRateSchedule::Constant { learning_rate: 0.01 };
+        config
+    }
+
+    #[test]
+    fn test_train_trainer() {
+        let mut dir = env::temp_dir();
+        dir.push("mmn_test_train_trainer");
+
+        let options = TrainOptions {
+            split: SplitOptions { validation: 0.2, test: 0.0 },
+            log_every: 2,
+            ..TrainOptions::default()
+        };
+        let mut trainer = Trainer::new(config(), entries(40), options).unwrap();
+        let sizes = trainer.split_sizes;
+        assert_eq!(sizes.train + sizes.validation, 40);
+
+        let manifest = trainer.manifest().unwrap();
+        assert_eq!(manifest.split_sizes, sizes);
+        assert_eq!(manifest.seeds, trainer.seeds);
+        assert_eq!(manifest.vocabulary_hash, Some(trainer.state().unwrap().vocabulary_hash));
+
+        let summary = trainer.train(&dir).unwrap();
+        assert_eq!(summary.epoch, 2);
+        assert!(summary.global_step >= 2 * ((sizes.train as u64 + 3) / 4));
+        assert!(summary.loss > 0.0);
+
+        let csv = fs::read_to_string(dir.join(CSV_FILE)).unwrap();
+        assert_eq!(csv.lines().count() as u64, 1 + (summary.global_step + 1) / 2);
+        assert_eq!(Model::load(&dir.join(MODEL_DIR)).unwrap(), trainer.model);
+        assert_eq!(trainer.model.name(), "mmn");
+
+        assert!(trainer.train(&dir).is_ok());
+        assert_eq!(trainer.global_step, summary.global_step);
+
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(Trainer::new(config(), DataEntries::<ShortDataEntry>::new(), options).is_err());
+    }
+
+    #[test]
+    fn test_train_early_stopping() {
+        let mut dir = env::temp_dir();
+        dir.push("mmn_test_train_early_stopping");
+
+        let options = TrainOptions {
+            split: SplitOptions { validation: 0.2, test: 0.0 },
+            ..TrainOptions::default()
+        };
+
+        let mut config = config();
+        config.train.early_stopping = Some(EarlyStoppingOptions {
+            validate_every: 2,
+            metric: Metric::Loss,
+            keep_best: 2,
+            patience: 100,
+        });
+
+        let mut trainer = Trainer::new(config.clone(), entries(40), options).unwrap();
+        let validation = trainer.validate().unwrap();
+        assert!(validation.loss > 0.0);
+
+        let summary = trainer.train(&dir).unwrap();
+        assert!(!summary.stopped);
+        assert!(summary.best_checkpoint.as_ref().unwrap().exists());
+        assert!(trainer.validate().unwrap().loss < validation.loss);
+
+        let checkpoints = fs::read_dir(dir.join(CHECKPOINTS_DIR)).unwrap().count();
+        assert_eq!(checkpoints, 2);
+        let csv = fs::read_to_string(dir.join(CSV_FILE)).unwrap();
+        let validated = csv.lines().filter(|l| l.split(',').nth(6).map(|v| !v.is_empty()).unwrap_or(false));
+        assert_eq!(validated.count() as u64, 1 + summary.global_step / 2);
+        fs::remove_dir_all(&dir).unwrap();
+
+        config.model.architecture = Architecture::Lead;
+        config.train.early_stopping = Some(EarlyStoppingOptions {
+            validate_every: 1,
+            metric: Metric::RougeL,
+            keep_best: 1,
+            patience: 1,
+        });
+
+        let mut trainer = Trainer::new(config.clone(), entries(40), options).unwrap();
+        let summary = trainer.train(&dir).unwrap();
+        assert!(summary.stopped);
+        assert_eq!(summary.global_step, 2);
+        assert_eq!(summary.best_checkpoint, Some(checkpoint_path(&dir, 1)));
+        fs::remove_dir_all(&dir).unwrap();
+
+        let options = TrainOptions { split: SplitOptions { validation: 0.0, test: 0.0 }, ..options };
+        assert!(Trainer::new(config, entries(40), options).is_err());
+    }
+
+    #[test]
+    fn test_train_resume() {
+        let mut dir = env::temp_dir();
+        dir.push("mmn_test_train_resume");
+        let resumed_dir = dir.join("resumed");
+
+        let options = TrainOptions {
+            split: SplitOptions { validation: 0.2, test: 0.0 },
+            checkpoint_every: 5,
+            ..TrainOptions::default()
+        };
+
+        let mut trainer = Trainer::new(config(), entries(40), options).unwrap();
+        let summary = trainer.train(&dir).unwrap();
+
+        let last = last_checkpoint_path(&dir);
+        let state = TrainState::load(&last).unwrap();
+        assert_eq!(summary.global_step, 16);
+        assert_eq!((state.global_step, state.epoch, state.epoch_position), (15, 1, 26));
+
+        let mut resumed = Trainer::new(config(), entries(40), options).unwrap();
+        resumed.resume(&last).unwrap();
+        assert_eq!(resumed.global_step, state.global_step);
+
+        let resumed_summary = resumed.train(&resumed_dir).unwrap();
+        assert_eq!(resumed_summary.global_step, summary.global_step);
+        assert_eq!(resumed.model, trainer.model);
+
+        let mut config = config();
+        config.train.seed += 1;
+        let mut other = Trainer::new(config, entries(40), options).unwrap();
+        assert!(other.resume(&last).is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_train_curriculum_mid_epoch() {
+        let mut dir = env::temp_dir();
+        dir.push("mmn_test_train_curriculum_mid_epoch");
+
+        let options = TrainOptions {
+            split: SplitOptions { validation: 0.2, test: 0.0 },
+            ..TrainOptions::default()
+        };
+        let curriculum = |stages: Vec<CurriculumStage>| {
+            let mut config = config();
+            config.train.epochs = 1;
+            config.train.length_buckets = vec![];
+            config.train.curriculum = Some(Curriculum {
+                unit: CurriculumUnit::Step,
+                schedule: CurriculumSchedule::Stages { stages },
+            });
+            config
+        };
+        let train = |config: Config| {
+            let mut trainer = Trainer::new(config, entries(40), options).unwrap();
+            let summary = trainer.train(&dir).unwrap();
+            fs::remove_dir_all(&dir).unwrap();
+            (summary.global_step, trainer.model)
+        };
+
+        let short = train(curriculum(vec![CurriculumStage { from: 0, max_len: 5 }]));
+        let full = train(curriculum(vec![CurriculumStage { from: 0, max_len: 100 }]));
+        let crossing = train(curriculum(vec![
+            CurriculumStage { from: 0, max_len: 5 },
+            CurriculumStage { from: 2, max_len: 100 },
+        ]));
+
+        assert!(crossing.0 <= full.0);
+        assert!(crossing.1 != short.1);
+        assert!(crossing.1 != full.1);
+    }
+
+    #[test]
+    fn test_train_augmentation() {
+        let entries: DataEntries<LongDataEntry> = entries(40)
+            .iter()
+            .map(|entry| {
+                let mut long = LongDataEntry::new();
+                long.id = entry.id.to_owned();
+                long.source_tokenized = entry.source_tokenized.to_owned();
+                long.source = long.source_tokenized.join(" ");
+                long.summary_tokenized = Some(entry.summary_tokenized.to_owned());
+                long.summary = long.summary_tokenized.as_ref().map(|summary| summary.join(" "));
+                long
+            })
+            .collect();
+
+        let mut config = config();
+        config.train.mode = SummaryMode::Long;
+        config.train.augmentation = Some(AugmentationOptions {
+            techniques: vec![TechniqueOptions::new(Technique::TokenSwap, 0.5, 1)],
+            neighbours: None,
+        });
+        let options = TrainOptions {
+            split: SplitOptions { validation: 0.2, test: 0.0 },
+            ..TrainOptions::default()
+        };
+
+        let trainer = Trainer::new(config.clone(), entries.clone(), options).unwrap();
+        let train = trainer.loader.dataset();
+        let augmented = train.iter().filter(|entry| entry.augmented_from.is_some()).count();
+        assert!(augmented > 0);
+        assert_eq!(train.len(), trainer.split_sizes.train + augmented);
+        assert!(trainer.validation.iter().all(|entry| entry.augmented_from.is_none()));
+
+        let mut plain_config = config.clone();
+        plain_config.train.augmentation = None;
+        let plain = Trainer::new(plain_config, entries.clone(), options).unwrap();
+        assert_eq!(plain.loader.dataset().len(), plain.split_sizes.train);
+        assert_eq!(plain.validation, trainer.validation);
+
+        config.train.mode = SummaryMode::Short;
+        assert!(Trainer::new(config, entries, options).is_err());
+    }
+
+    #[test]
+    fn test_train_multi_task() {
+        let mut dir = env::temp_dir();
+        dir.push("mmn_test_train_multi_task");
+
+        let short = entries(40);
+        let long: DataEntries<LongDataEntry> = short
+            .iter()
+            .filter(|entry| entry.source_tokenized.len() > 5)
+            .map(|entry| {
+                let mut long = LongDataEntry::new();
+                long.id = entry.id.to_owned();
+                long.source_tokenized = entry.source_tokenized.to_owned();
+                long.summary_tokenized = Some(entry.source_tokenized[1..5].to_vec());
+                long.summary = long.summary_tokenized.as_ref().map(|summary| summary.join(" "));
+                long
+            })
+            .collect();
+        let long_len = long.len();
+        let tasks = MultiTaskEntries { short, long }.into_tasks();
+
+        let mut config = config();
+        config.model.target_conditioning = Some(TargetConditioning::Token);
+        config.train.multi_task = Some(MultiTaskOptions { short_ratio: 0.75 });
+        config.train.early_stopping = Some(EarlyStoppingOptions {
+            validate_every: 2,
+            metric: Metric::RougeL,
+            keep_best: 1,
+            patience: 100,
+        });
+        let options = TrainOptions {
+            split: SplitOptions { validation: 0.2, test: 0.0 },
+            ..TrainOptions::default()
+        };
+
+        let mut trainer = Trainer::new(config.clone(), tasks.clone(), options).unwrap();
+        let validation = trainer.validate().unwrap();
+        let scores = validation.multi_task.unwrap();
+        assert_eq!(validation.rouge_l, (scores.short.f1 + scores.long.f1) / 2.0);
+
+        let ids = |entries: &DataEntries<TaskEntry>, mode| {
+            let mut ids: Vec<String> = entries.iter().filter(|e| e.mode() == mode).map(|e| e.id().to_owned()).collect();
+            ids.sort();
+            ids
+        };
+        let validation_ids = ids(&trainer.validation, SummaryMode::Short);
+        assert!(ids(&trainer.validation, SummaryMode::Long).iter().all(|id| validation_ids.contains(id)));
+        assert_eq!(trainer.validation.len() + trainer.loader.dataset().len(), 40 + long_len);
+
+        let batches = trainer.mixed_batch_indices(0, &MultiTaskOptions { short_ratio: 0.75 }).unwrap();
+        let first: Vec<SummaryMode> = batches[0].iter().map(|idx| trainer.loader.dataset()[*idx].mode()).collect();
+        assert_eq!(first.iter().filter(|mode| **mode == SummaryMode::Short).count(), 3);
+        assert_eq!(batches.iter().map(Vec::len).sum::<usize>(), trainer.loader.dataset().len());
+
+        let summary = trainer.train(&dir).unwrap();
+        assert!(summary.global_step > 0);
+
+        let jsonl = fs::read_to_string(dir.join(JSONL_FILE)).unwrap();
+        let metrics: Vec<StepMetrics> = jsonl.lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+        let validated: Vec<&StepMetrics> = metrics.iter().filter(|m| m.validation_rouge_l.is_some()).collect();
+        assert!(!validated.is_empty());
+        assert!(validated.iter().all(|m| m.validation_rouge_l_short.is_some() && m.validation_rouge_l_long.is_some()));
+        fs::remove_dir_all(&dir).unwrap();
+
+        config.model.target_conditioning = None;
+        assert!(Trainer::new(config, tasks, options).is_err());
+    }
+}
@@ -0,0 +1,296 @@
+use serde::{Serialize, Deserialize};
+use serde_json::{self, Value};
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+use crate::result::Result;
+use crate::parse_options::ParseOptions;
+use crate::raw_data_entry::RawDataEntry;
+
+/// `ValidationErrorKind` is the kind of a `ValidationError`.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Serialize, Deserialize)]
+pub enum ValidationErrorKind {
+    /// `Encoding` is an error of a line that is not valid UTF-8.
+    Encoding,
+    /// `Json` is an error of a line that is not valid json.
+    Json,
+    /// `Parse` is an error of a line that does not follow the dataset schema.
+    Parse,
+    /// `Semantic` is an error of an entry that breaks a dataset invariant.
+    Semantic,
+}
+
+/// `ValidationError` is an error found in a line of a dataset file.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct ValidationError {
+    pub line: usize,
+    pub id: Option<String>,
+    pub kind: ValidationErrorKind,
+    pub field: Option<String>,
+    pub reason: String,
+}
+
+impl ValidationError {
+    /// `new` creates a new `ValidationError`.
+    pub fn new(line: usize, id: Option<String>, kind: ValidationErrorKind, field: Option<&str>, reason: &str) -> ValidationError {
+        ValidationError {
+            line,
+            id,
+            kind,
+            field: field.map(ToOwned::to_owned),
+            reason: reason.to_owned(),
+        }
+    }
+}
+
+/// `ValidationReport` is the result of the validation of a dataset file.
+#[derive(Clone, Default, PartialEq, Debug, Serialize, Deserialize)]
+pub struct ValidationReport {
+    pub lines: usize,
+    pub valid_entries: usize,
+    pub warnings: usize,
+    pub errors: Vec<ValidationError>,
+}
+
+impl ValidationReport {
+    /// `new` creates a new `ValidationReport`.
+    pub fn new() -> ValidationReport {
+        ValidationReport::default()
+    }
+
+    /// `invalid_entries` returns the number of lines with at least one error.
+    pub fn invalid_entries(&self) -> usize {
+        self.lines - self.valid_entries
+    }
+
+    /// `is_valid` returns if no errors were found.
+    pub fn is_valid(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    /// `error_counts` returns the number of errors by kind and field.
+    pub fn error_counts(&self) -> BTreeMap<(ValidationErrorKind, String), usize> {
+        let mut counts = BTreeMap::new();
+
+        for error in self.errors.iter() {
+            let field = error.field.to_owned().unwrap_or_else(|| "-".to_string());
+            *counts.entry((error.kind, field)).or_insert(0) += 1;
+        }
+
+        counts
+    }
+
+    /// `write_errors` writes the errors to `writer` as json lines.
+    pub fn write_errors<W: Write>(&self, mut writer: W) -> Result<()> {
+        for error in self.errors.iter() {
+            let line = serde_json::to_string(error).map_err(|e| format!("{}", e))?;
+            writeln!(writer, "{}", line).map_err(|e| format!("{}", e))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for ValidationReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "lines:           {}", self.lines)?;
+        writeln!(f, "valid entries:   {}", self.valid_entries)?;
+        writeln!(f, "invalid entries: {}", self.invalid_entries())?;
+        writeln!(f, "warnings:        {}", self.warnings)?;
+        writeln!(f, "errors:          {}", self.errors.len())?;
+
+        let counts = self.error_counts();
+        if !counts.is_empty() {
+            writeln!(f)?;
+            writeln!(f, "{:<10} {:<34} {:>8}", "kind", "field", "errors")?;
+            writeln!(f, "{:<10} {:<34} {:>8}", "-".repeat(10), "-".repeat(34), "-".repeat(8))?;
+
+            for ((kind, field), count) in counts.iter() {
+                writeln!(f, "{:<10} {:<34} {:>8}", format!("{:?}", kind), field, count)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// `parse_error_field` returns the field named in an error returned by `RawDataEntry::from_json_value_with_options`.
+fn parse_error_field(error: &str) -> Option<&str> {
+    let rest = error.strip_prefix("invalid ")?;
+    rest.find(" field").map(|i| &rest[..i])
+}
+
+/// `semantic_errors` returns the errors of the dataset invariants broken by `entry`.
+pub fn semantic_errors(line: usize, entry: &RawDataEntry) -> Vec<ValidationError> {
+    let mut errors = Vec::new();
+    let id = Some(entry.id.to_owned());
+
+    let mut push = |field: &str, reason: &str| {
+        errors.push(ValidationError::new(line, id.to_owned(), ValidationErrorKind::Semantic, Some(field), reason));
+    };
+
+    if entry.trimmed_title_tokenized.is_empty() {
+        push("trimmed_title_tokenized", "empty tokenized trimmed title");
+    }
+
+    if entry.selftext_without_tldr_tokenized.is_empty() {
+        push("selftext_without_tldr_tokenized", "empty tokenized source");
+    }
+
+    match (&entry.tldr, &entry.tldr_tokenized) {
+        (Some(_), None) => push("tldr_tokenized", "tldr present without tldr_tokenized"),
+        (None, Some(_)) => push("tldr", "tldr_tokenized present without tldr"),
+        _ => {},
+    }
+
+    if let Some(tldr) = entry.tldr.as_ref().map(|t| t.trim().to_lowercase()) {
+        if !tldr.is_empty() && entry.selftext_without_tldr.to_lowercase().contains(&tldr) {
+            push("selftext_without_tldr", "selftext_without_tldr contains the tldr");
+        }
+    }
+
+    errors
+}
+
+/// `validate_line` validates the json `bytes` of the line number `line`, returning the parsed
+/// entry and the number of parse warnings if the line could be parsed.
+fn validate_line(line: usize, bytes: &[u8], options: &ParseOptions, errors: &mut Vec<ValidationError>) -> Option<(RawDataEntry, usize)> {
+    let s = match std::str::from_utf8(bytes) {
+        Ok(s) => s,
+        Err(e) => {
+            errors.push(ValidationError::new(line, None, ValidationErrorKind::Encoding, None, &format!("{}", e)));
+            return None;
+        },
+    };
+
+    let value: Value = match serde_json::from_str(s) {
+        Ok(value) => value,
+        Err(e) => {
+            errors.push(ValidationError::new(line, None, ValidationErrorKind::Json, None, &format!("{}", e)));
+            return None;
+        },
+    };
+
+    let id = value["id"].as_str().map(ToOwned::to_owned);
+
+    match RawDataEntry::from_json_value_with_options(&value, options) {
+        Ok((entry, warnings)) => Some((entry, warnings.len())),
+        Err(e) => {
+            errors.push(ValidationError::new(line, id, ValidationErrorKind::Parse, parse_error_field(&e), &e));
+            None
+        },
+    }
+}
+
+/// `validate_dataset_file` scans the whole dataset file at `path`, collecting every error.
+pub fn validate_dataset_file<P: AsRef<Path>>(path: P, options: &ParseOptions) -> Result<ValidationReport> {
+    let file = File::open(&path).map_err(|e| format!("{}", e))?;
+    let mut reader = BufReader::new(file);
+    let mut report = ValidationReport::new();
+    let mut ids = HashSet::new();
+    let mut buf = Vec::new();
+
+    loop {
+        buf.clear();
+        let size = reader.read_until(b'\n', &mut buf).map_err(|e| format!("{}", e))?;
+        if size == 0 {
+            break;
+        }
+
+        while buf.last() == Some(&b'\n') || buf.last() == Some(&b'\r') {
+            buf.pop();
+        }
+
+        report.lines += 1;
+        let line = report.lines;
+        let errors_len = report.errors.len();
+
+        if let Some((entry, warnings)) = validate_line(line, &buf, options, &mut report.errors) {
+            report.warnings += warnings;
+            report.errors.extend(semantic_errors(line, &entry));
+
+            if !ids.insert(entry.id.to_owned()) {
+                report.errors.push(ValidationError::new(line, Some(entry.id.to_owned()), ValidationErrorKind::Semantic, Some("id"), "duplicate id"));
+            }
+        }
+
+        if report.errors.len() == errors_len {
+            report.valid_entries += 1;
+        }
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{validate_dataset_file, semantic_errors, ValidationErrorKind};
+    use crate::parse_options::ParseOptions;
+    use crate::raw_data_entry::RawDataEntry;
+    use std::env;
+    use std::fs::{self, File};
+    use std::io::Write;
+
+    fn valid_entry(id: &str) -> RawDataEntry {
+        let mut entry = RawDataEntry::new();
+        entry.id = id.to_string();
+        entry.trimmed_title_tokenized = vec!["title".to_string()];
+        entry.selftext_without_tldr = "some text".to_string();
+        entry.selftext_without_tldr_tokenized = vec!["some".to_string(), "text".to_string()];
+        entry
+    }
+
+    #[test]
+    fn test_validation_semantic_errors() {
+        let entry = valid_entry("a");
+        assert!(semantic_errors(1, &entry).is_empty());
+
+        let mut entry = valid_entry("b");
+        entry.trimmed_title_tokenized.clear();
+        entry.tldr = Some("Some Text".to_string());
+        let errors = semantic_errors(2, &entry);
+        assert_eq!(errors.len(), 3);
+        assert!(errors.iter().all(|e| e.line == 2 && e.id == Some("b".to_string())));
+        assert!(errors.iter().any(|e| e.field == Some("tldr_tokenized".to_string())));
+        assert!(errors.iter().any(|e| e.field == Some("selftext_without_tldr".to_string())));
+    }
+
+    #[test]
+    fn test_validation_validate_dataset_file() {
+        let mut path = env::temp_dir();
+        path.push("mmn_test_validation_validate_dataset_file.json");
+
+        let mut file = File::create(&path).unwrap();
+        writeln!(file, "{}", valid_entry("a").to_json_string().unwrap()).unwrap();
+        writeln!(file, "{{\"id\": \"b\", \"selftext_without_tldr_tokenized\": []}}").unwrap();
+        writeln!(file, "{{ not json").unwrap();
+        file.write_all(b"\xff\xfe\n").unwrap();
+        writeln!(file, "{}", valid_entry("a").to_json_string().unwrap()).unwrap();
+        writeln!(file, "{}", valid_entry("c").to_json_string().unwrap()).unwrap();
+
+        let report = validate_dataset_file(&path, &ParseOptions::strict()).unwrap();
+        assert_eq!(report.lines, 6);
+        assert_eq!(report.valid_entries, 2);
+        assert_eq!(report.invalid_entries(), 4);
+        assert!(!report.is_valid());
+
+        let kinds: Vec<(usize, ValidationErrorKind)> = report.errors.iter().map(|e| (e.line, e.kind)).collect();
+        assert_eq!(kinds, vec![
+            (2, ValidationErrorKind::Parse),
+            (3, ValidationErrorKind::Json),
+            (4, ValidationErrorKind::Encoding),
+            (5, ValidationErrorKind::Semantic),
+        ]);
+        assert_eq!(report.errors[0].id, Some("b".to_string()));
+        assert_eq!(report.errors[0].field, Some("title_tokenized".to_string()));
+
+        let mut errors = Vec::new();
+        report.write_errors(&mut errors).unwrap();
+        assert_eq!(String::from_utf8(errors).unwrap().lines().count(), 4);
+        assert!(format!("{}", report).contains("title_tokenized"));
+
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,327 @@
+use rand::Rng;
+use std::fs;
+use std::path::Path;
+use crate::result::Result;
+
+/// `VARIABLES_FILE` is the name of the model variables file in a model directory.
+pub const VARIABLES_FILE: &str = "variables.bin";
+
+/// `MAGIC` are the first bytes of a variables file.
+const MAGIC: &[u8; 4] = b"MMNV";
+/// `VERSION` is the version of the variables file format.
+const VERSION: u32 = 1;
+
+/// `Tensor` is a dense row-major tensor of `f32`s.
+#[derive(Clone, Default, PartialEq, Debug)]
+pub struct Tensor {
+    pub shape: Vec<usize>,
+    pub data: Vec<f32>,
+}
+
+impl Tensor {
+    /// `zeros` creates a new `Tensor` of `shape` filled with zeros.
+    pub fn zeros(shape: &[usize]) -> Tensor {
+        Tensor {
+            shape: shape.to_vec(),
+            data: vec![0.0; shape.iter().product()],
+        }
+    }
+
+    /// `uniform` creates a new `Tensor` of `shape` sampled uniformly in `[-scale, scale)`.
+    pub fn uniform<R: Rng>(shape: &[usize], scale: f32, rng: &mut R) -> Tensor {
+        let len = shape.iter().product();
+
+        Tensor {
+            shape: shape.to_vec(),
+            data: (0..len).map(|_| rng.gen_range(-scale, scale)).collect(),
+        }
+    }
+
+    /// `len` returns the number of elements of the `Tensor`.
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    /// `is_empty` returns if the `Tensor` has no elements.
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// `cols` returns the size of the last dimension of the `Tensor`.
+    pub fn cols(&self) -> usize {
+        self.shape.last().cloned().unwrap_or(1)
+    }
+
+    /// `row` returns the row `idx` of a matrix.
+    pub fn row(&self, idx: usize) -> &[f32] {
+        let cols = self.cols();
+        &self.data[idx * cols..(idx + 1) * cols]
+    }
+
+    /// `matvec` returns the product of the matrix by `x`.
+    pub fn matvec(&self, x: &[f32]) -> Vec<f32> {
+        self.data
+            .chunks(self.cols())
+            .map(|row| dot(row, x))
+            .collect()
+    }
+
+    /// `matvec_transposed` returns the product of the transposed matrix by `y`.
+    pub fn matvec_transposed(&self, y: &[f32]) -> Vec<f32> {
+        let mut x = vec![0.0; self.cols()];
+
+        for (row, y) in self.data.chunks(self.cols()).zip(y.iter()) {
+            if *y != 0.0 {
+                add_scaled(&mut x, row, *y);
+            }
+        }
+
+        x
+    }
+}
+
+/// `dot` returns the dot product of `a` and `b`.
+pub fn dot(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b.iter()).map(|(a, b)| a * b).sum()
+}
+
+/// `add_scaled` adds `x` scaled by `scale` to `y`.
+pub fn add_scaled(y: &mut [f32], x: &[f32], scale: f32) {
+    for (y, x) in y.iter_mut().zip(x.iter()) {
+        *y += scale * x;
+    }
+}
+
+/// `add_outer` adds the outer product of `y` and `x` to the row-major matrix `grad`.
+pub fn add_outer(grad: &mut [f32], y: &[f32], x: &[f32]) {
+    for (row, y) in grad.chunks_mut(x.len()).zip(y.iter()) {
+        if *y != 0.0 {
+            add_scaled(row, x, *y);
+        }
+    }
+}
+
+/// `Variables` are the named tensors of a model, in insertion order. The gradients of the
+/// `Variables` are a `Vec<f32>` per tensor in the same order.
+#[derive(Clone, Default, PartialEq, Debug)]
+pub struct Variables {
+    names: Vec<String>,
+    tensors: Vec<Tensor>,
+}
+
+impl Variables {
+    /// `new` creates a new empty `Variables`.
+    pub fn new() -> Variables {
+        Variables::default()
+    }
+
+    /// `len` returns the number of tensors of the `Variables`.
+    pub fn len(&self) -> usize {
+        self.tensors.len()
+    }
+
+    /// `is_empty` returns if the `Variables` have no tensors.
+    pub fn is_empty(&self) -> bool {
+        self.tensors.is_empty()
+    }
+
+    /// `names` returns the names of the tensors.
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    /// `insert` adds the tensor `name` and returns its index.
+    pub fn insert(&mut self, name: &str, tensor: Tensor) -> Result<usize> {
+        if self.names.iter().any(|n| n == name) {
+            return Err(format!("duplicate variable: {}", name));
+        }
+
+        self.names.push(name.to_owned());
+        self.tensors.push(tensor);
+        Ok(self.tensors.len() - 1)
+    }
+
+    /// `index` returns the index of the tensor `name`.
+    pub fn index(&self, name: &str) -> Result<usize> {
+        self.names
+            .iter()
+            .position(|n| n == name)
+            .ok_or_else(|| format!("missing variable: {}", name))
+    }
+
+    /// `get` returns the tensor at `idx`.
+    pub fn get(&self, idx: usize) -> &Tensor {
+        &self.tensors[idx]
+    }
+
+    /// `get_mut` returns a mutable reference to the tensor at `idx`.
+    pub fn get_mut(&mut self, idx: usize) -> &mut Tensor {
+        &mut self.tensors[idx]
+    }
+
+    /// `zero_gradients` returns zero gradients of the `Variables`.
+    pub fn zero_gradients(&self) -> Vec<Vec<f32>> {
+        self.tensors.iter().map(|t| vec![0.0; t.len()]).collect()
+    }
+
+    /// `parameters` returns the number of scalar parameters.
+    pub fn parameters(&self) -> usize {
+        self.tensors.iter().map(Tensor::len).sum()
+    }
+
+    /// `to_bytes` encodes the `Variables` in the little-endian variables file format.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(12 + 4 * self.parameters());
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&VERSION.to_le_bytes());
+        bytes.extend_from_slice(&(self.tensors.len() as u32).to_le_bytes());
+
+        for (name, tensor) in self.names.iter().zip(self.tensors.iter()) {
+            bytes.extend_from_slice(&(name.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(name.as_bytes());
+            bytes.extend_from_slice(&(tensor.shape.len() as u32).to_le_bytes());
+
+            for dim in tensor.shape.iter() {
+                bytes.extend_from_slice(&(*dim as u64).to_le_bytes());
+            }
+
+            for v in tensor.data.iter() {
+                bytes.extend_from_slice(&v.to_le_bytes());
+            }
+        }
+
+        bytes
+    }
+
+    /// `from_bytes` decodes `Variables` encoded by `to_bytes`.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Variables> {
+        let mut reader = ByteReader { bytes, pos: 0 };
+
+        if reader.take(4)? != MAGIC {
+            return Err("invalid variables file".to_string());
+        }
+
+        let version = reader.u32()?;
+        if version != VERSION {
+            return Err(format!("unsupported variables file version: {}", version));
+        }
+
+        let mut variables = Variables::new();
+
+        for _ in 0..reader.u32()? {
+            let len = reader.u32()? as usize;
+            let name = String::from_utf8(reader.take(len)?.to_vec()).map_err(|e| format!("{}", e))?;
+
+            let rank = reader.u32()? as usize;
+            let mut shape = Vec::with_capacity(rank);
+            for _ in 0..rank {
+                shape.push(reader.u64()? as usize);
+            }
+
+            let len = shape.iter().product::<usize>();
+            let data = reader
+                .take(4 * len)?
+                .chunks(4)
+                .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                .collect();
+
+            variables.insert(&name, Tensor { shape, data })?;
+        }
+
+        if reader.pos != bytes.len() {
+            return Err("invalid variables file: trailing bytes".to_string());
+        }
+
+        Ok(variables)
+    }
+
+    /// `save_file` saves the `Variables` in the file at `path`.
+    pub fn save_file<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        fs::write(path, self.to_bytes()).map_err(|e| format!("{}", e))
+    }
+
+    /// `load_file` loads `Variables` from the file at `path`.
+    pub fn load_file<P: AsRef<Path>>(path: P) -> Result<Variables> {
+        let bytes = fs::read(path).map_err(|e| format!("{}", e))?;
+        Variables::from_bytes(&bytes)
+    }
+
+    /// `save` saves the `Variables` in the directory `dir`.
+    pub fn save<P: AsRef<Path>>(&self, dir: P) -> Result<()> {
+        fs::create_dir_all(&dir).map_err(|e| format!("{}", e))?;
+        self.save_file(dir.as_ref().join(VARIABLES_FILE))
+    }
+
+    /// `load` loads `Variables` from the directory `dir`.
+    pub fn load<P: AsRef<Path>>(dir: P) -> Result<Variables> {
+        Variables::load_file(dir.as_ref().join(VARIABLES_FILE))
+    }
+}
+
+/// `ByteReader` reads the fields of a variables file.
+struct ByteReader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> ByteReader<'a> {
+    /// `take` returns the next `len` bytes.
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        let end = self.pos.checked_add(len).filter(|end| *end <= self.bytes.len())
+            .ok_or_else(|| "invalid variables file: truncated".to_string())?;
+        let bytes = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    /// `u32` returns the next little-endian `u32`.
+    fn u32(&mut self) -> Result<u32> {
+        let b = self.take(4)?;
+        Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    /// `u64` returns the next little-endian `u64`.
+    fn u64(&mut self) -> Result<u64> {
+        let b = self.take(8)?;
+        let mut buf = [0u8; 8];
+        buf.copy_from_slice(b);
+        Ok(u64::from_le_bytes(buf))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{add_outer, Tensor, Variables};
+    use crate::seed::StreamRng;
+
+    #[test]
+    fn test_variables_linear_algebra() {
+        let w = Tensor { shape: vec![2, 3], data: vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0] };
+        assert_eq!(w.matvec(&[1.0, 0.0, -1.0]), vec![-2.0, -2.0]);
+        assert_eq!(w.matvec_transposed(&[1.0, -1.0]), vec![-3.0, -3.0, -3.0]);
+        assert_eq!(w.row(1), &[4.0, 5.0, 6.0]);
+
+        let mut grad = vec![0.0; 6];
+        add_outer(&mut grad, &[1.0, 2.0], &[1.0, 0.0, 3.0]);
+        assert_eq!(grad, vec![1.0, 0.0, 3.0, 2.0, 0.0, 6.0]);
+    }
+
+    #[test]
+    fn test_variables_bytes() {
+        let mut rng = StreamRng::new(0);
+        let mut variables = Variables::new();
+        variables.insert("embedding", Tensor::uniform(&[4, 3], 0.1, &mut rng)).unwrap();
+        variables.insert("bias", Tensor::zeros(&[3])).unwrap();
+        assert!(variables.insert("bias", Tensor::zeros(&[1])).is_err());
+
+        assert_eq!(variables.index("bias").unwrap(), 1);
+        assert!(variables.index("missing").is_err());
+        assert_eq!(variables.parameters(), 15);
+        assert!(variables.get(0).data.iter().all(|v| v.abs() < 0.1));
+
+        let bytes = variables.to_bytes();
+        assert_eq!(Variables::from_bytes(&bytes).unwrap(), variables);
+        assert!(Variables::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        assert!(Variables::from_bytes(b"nope").is_err());
+    }
+}
@@ -0,0 +1,226 @@
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use crate::result::Result;
+use crate::hash::Hasher;
+
+/// `PAD_TOKEN` is the padding token.
+pub const PAD_TOKEN: &str = "<pad>";
+/// `UNK_TOKEN` is the token of the out-of-vocabulary tokens.
+pub const UNK_TOKEN: &str = "<unk>";
+/// `BOS_TOKEN` is the token starting a summary.
+pub const BOS_TOKEN: &str = "<s>";
+/// `EOS_TOKEN` is the token ending a summary.
+pub const EOS_TOKEN: &str = "</s>";
+
+/// `PAD_ID` is the id of `PAD_TOKEN`.
+pub const PAD_ID: usize = 0;
+/// `UNK_ID` is the id of `UNK_TOKEN`.
+pub const UNK_ID: usize = 1;
+/// `BOS_ID` is the id of `BOS_TOKEN`.
+pub const BOS_ID: usize = 2;
+/// `EOS_ID` is the id of `EOS_TOKEN`.
+pub const EOS_ID: usize = 3;
+
+/// `SPECIAL_TOKENS` are the tokens every `Vocabulary` starts with, in id order.
+pub const SPECIAL_TOKENS: [&str; 4] = [PAD_TOKEN, UNK_TOKEN, BOS_TOKEN, EOS_TOKEN];
+
+/// `VOCABULARY_FILE` is the name of the vocabulary file in a model directory.
+pub const VOCABULARY_FILE: &str = "vocabulary.json";
+
+/// `Vocabulary` maps the tokens to the ids of the model.
+#[derive(Clone, Default, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(from = "VocabularyTokens", into = "VocabularyTokens")]
+pub struct Vocabulary {
+    tokens: Vec<String>,
+    ids: HashMap<String, usize>,
+}
+
+/// `VocabularyTokens` is the serialized form of a `Vocabulary`.
+#[derive(Clone, Default, PartialEq, Debug, Serialize, Deserialize)]
+struct VocabularyTokens {
+    tokens: Vec<String>,
+}
+
+impl From<VocabularyTokens> for Vocabulary {
+    fn from(v: VocabularyTokens) -> Vocabulary {
+        Vocabulary::from_tokens(v.tokens)
+    }
+}
+
+impl From<Vocabulary> for VocabularyTokens {
+    fn from(v: Vocabulary) -> VocabularyTokens {
+        VocabularyTokens { tokens: v.tokens }
+    }
+}
+
+impl Vocabulary {
+    /// `new` creates a new `Vocabulary` with only the special tokens.
+    pub fn new() -> Vocabulary {
+        Vocabulary::from_tokens(Vec::new())
+    }
+
+    /// `from_tokens` creates a new `Vocabulary` with the special tokens followed by `tokens`.
+    /// Duplicated tokens keep their first id.
+    pub fn from_tokens(tokens: Vec<String>) -> Vocabulary {
+        let mut vocabulary = Vocabulary::default();
+
+        for token in SPECIAL_TOKENS.iter().map(|t| t.to_string()).chain(tokens) {
+            if !vocabulary.ids.contains_key(&token) {
+                vocabulary.ids.insert(token.to_owned(), vocabulary.tokens.len());
+                vocabulary.tokens.push(token);
+            }
+        }
+
+        vocabulary
+    }
+
+    /// `build` creates a new `Vocabulary` from the token sequences `texts`, keeping the
+    /// tokens appearing at least `min_count` times, most frequent first, up to `max_size`
+    /// tokens including the special ones.
+    pub fn build<'a, I>(texts: I, max_size: usize, min_count: usize) -> Vocabulary
+        where I: IntoIterator<Item = &'a [String]>
+    {
+        let mut counts: HashMap<&str, usize> = HashMap::new();
+
+        for text in texts {
+            for token in text.iter() {
+                *counts.entry(token.as_str()).or_insert(0) += 1;
+            }
+        }
+
+        let mut counts: Vec<(&str, usize)> = counts
+            .into_iter()
+            .filter(|(token, count)| *count >= min_count && !SPECIAL_TOKENS.contains(token))
+            .collect();
+        counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+
+        let tokens = counts
+            .into_iter()
+            .take(max_size.saturating_sub(SPECIAL_TOKENS.len()))
+            .map(|(token, _)| token.to_owned())
+            .collect();
+
+        Vocabulary::from_tokens(tokens)
+    }
+
+    /// `extend` adds the `tokens` missing from the `Vocabulary` after its tokens.
+    pub fn extend<'a, I: IntoIterator<Item = &'a str>>(&mut self, tokens: I) {
+        for token in tokens {
+            if !self.ids.contains_key(token) {
+                self.ids.insert(token.to_owned(), self.tokens.len());
+                self.tokens.push(token.to_owned());
+            }
+        }
+    }
+
+    /// `len` returns the number of tokens of the `Vocabulary`.
+    pub fn len(&self) -> usize {
+        self.tokens.len()
+    }
+
+    /// `is_empty` returns if the `Vocabulary` has no tokens.
+    pub fn is_empty(&self) -> bool {
+        self.tokens.is_empty()
+    }
+
+    /// `contains` returns if `token` is in the `Vocabulary`.
+    pub fn contains(&self, token: &str) -> bool {
+        self.ids.contains_key(token)
+    }
+
+    /// `id` returns the id of `token`, or `UNK_ID` if it is out of the `Vocabulary`.
+    pub fn id(&self, token: &str) -> usize {
+        self.ids.get(token).cloned().unwrap_or(UNK_ID)
+    }
+
+    /// `token` returns the token of `id`, if any.
+    pub fn token(&self, id: usize) -> Option<&str> {
+        self.tokens.get(id).map(String::as_str)
+    }
+
+    /// `encode` returns the ids of `tokens`.
+    pub fn encode(&self, tokens: &[String]) -> Vec<usize> {
+        tokens.iter().map(|token| self.id(token)).collect()
+    }
+
+    /// `decode` returns the tokens of `ids`, stopping at `EOS_ID` and skipping the padding.
+    pub fn decode(&self, ids: &[usize]) -> Vec<String> {
+        ids.iter()
+            .take_while(|id| **id != EOS_ID)
+            .filter(|id| **id != PAD_ID && **id != BOS_ID)
+            .map(|id| self.token(*id).unwrap_or(UNK_TOKEN).to_owned())
+            .collect()
+    }
+
+    /// `hash` returns the hash of the `Vocabulary`, as stored in the checkpoints.
+    pub fn hash(&self) -> String {
+        let mut hasher = Hasher::new();
+
+        for token in self.tokens.iter() {
+            hasher.update(token.as_bytes());
+            hasher.update(b"\n");
+        }
+
+        hasher.finish()
+    }
+
+    /// `save` saves the `Vocabulary` in the directory `dir`.
+    pub fn save<P: AsRef<Path>>(&self, dir: P) -> Result<()> {
+        fs::create_dir_all(&dir).map_err(|e| format!("{}", e))?;
+        let contents = serde_json::to_vec(self).map_err(|e| format!("{}", e))?;
+        fs::write(dir.as_ref().join(VOCABULARY_FILE), contents).map_err(|e| format!("{}", e))
+    }
+
+    /// `load` loads a `Vocabulary` from the directory `dir`.
+    pub fn load<P: AsRef<Path>>(dir: P) -> Result<Vocabulary> {
+        let contents = fs::read(dir.as_ref().join(VOCABULARY_FILE)).map_err(|e| format!("{}", e))?;
+        serde_json::from_slice(&contents).map_err(|e| format!("{}", e))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Vocabulary, EOS_ID, UNK_ID};
+    use std::env;
+    use std::fs;
+
+    fn tokens(s: &str) -> Vec<String> {
+        s.split_whitespace().map(ToOwned::to_owned).collect()
+    }
+
+    #[test]
+    fn test_vocabulary_build() {
+        let texts = [tokens("a b c a b a"), tokens("d a <unk>")];
+        let vocabulary = Vocabulary::build(texts.iter().map(Vec::as_slice), 6, 2);
+
+        assert_eq!(vocabulary.len(), 6);
+        assert_eq!(vocabulary.token(4), Some("a"));
+        assert_eq!(vocabulary.token(5), Some("b"));
+        assert!(!vocabulary.contains("c"));
+
+        let ids = vocabulary.encode(&tokens("b c a"));
+        assert_eq!(ids, vec![5, UNK_ID, 4]);
+
+        let mut ids = ids;
+        ids.extend_from_slice(&[EOS_ID, 4]);
+        assert_eq!(vocabulary.decode(&ids), tokens("b <unk> a"));
+    }
+
+    #[test]
+    fn test_vocabulary_save_load() {
+        let vocabulary = Vocabulary::from_tokens(tokens("x y z"));
+
+        let mut dir = env::temp_dir();
+        dir.push("mmn_test_vocabulary_save_load");
+        vocabulary.save(&dir).unwrap();
+
+        let loaded = Vocabulary::load(&dir).unwrap();
+        assert_eq!(loaded, vocabulary);
+        assert_eq!(loaded.hash(), vocabulary.hash());
+        assert_ne!(Vocabulary::new().hash(), vocabulary.hash());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}